@@ -1,64 +1,127 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{diagnostics::Diagnostic, semantic::SymbolTable};
+
 // program is a vector of classes
 #[derive(Debug)]
 pub struct Program {
     pub classes: Vec<Class>,
+    pub delegates: Vec<Delegate>, // the delegate types, declared in or out of the classes
+    pub symbols: SymbolTable,     // what every name refers to
+    pub diagnostics: Vec<Diagnostic>, // problems found while translating
 }
 
-// a class is a vector of methods
+// a class is a vector of fields and methods
 #[derive(Debug)]
 pub struct Class {
     pub name: String,
+    pub fields: Vec<Field>,
     pub methods: Vec<Method>,
     pub uses_input: bool,
 }
 
+// a delegate type, delegate int BinaryOp(int a, int b)
+#[derive(Debug, Clone)]
+pub struct Delegate {
+    pub name: String,
+    pub return_type: Type,
+    pub parameters: Vec<Parameter>,
+}
+
+// a field of a class, static int count = 5
+#[derive(Debug)]
+pub struct Field {
+    pub modifiers: Vec<Modifier>, // modifiers, such as private, static, const
+    pub variable: Variable,       // the type and name of the field
+    pub value: Option<Expression>, // the initial value
+}
+
 // here it gets a bit more complicated
 #[derive(Debug)]
 pub struct Method {
-    pub name: String,              // method's name
-    pub return_type: Type,         // the return type
-    pub modifiers: Vec<Modifier>,  // modifiers, such as public, private
-    pub parameters: Vec<Variable>, // parameters of the method
-    pub body: Vec<Statement>,      // body, a vector of statements
+    pub name: String,               // method's name
+    pub return_type: Type,          // the return type
+    pub modifiers: Vec<Modifier>,   // modifiers, such as public, private
+    pub parameters: Vec<Parameter>, // parameters of the method
+    pub body: Vec<Statement>,       // body, a vector of statements
+}
+
+// a parameter of a method, int x = 5 or params int[] xs
+#[derive(Debug, Clone)]
+pub struct Parameter {
+    pub variable: Variable,                // the type and name of the parameter
+    pub modifiers: Vec<ParameterModifier>, // ref, out, in, this
+    pub default: Option<Expression>,       // the value of an optional parameter
+    pub params: bool,                      // params int[] xs, takes any number of arguments
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterModifier {
+    Ref,
+    Out,
+    In,
+    This, // the target of an extension method
+    Unknown,
 }
 
 // return and variable types
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Void,
     Int,
+    Long,
+    Short,
+    Byte,  // unsigned in C#, 0 to 255
+    SByte, // signed byte
+    UInt,
+    ULong,
+    UShort,
+    Decimal,
     Bool,
     String,
+    Char,
     Float,
     Double,
+    Named(String),              // user defined or library type, such as a class name
+    Nullable(Box<Type>),        // int? or string?, the type that can also be null
+    Array(Box<Type>),           // int[], the arrays with one dimension
+    Holder(Box<Type>),          // a variable passed by ref, kept in a generated IntRef or Ref<T>
+    Generic(String, Vec<Type>), // a type with type arguments, such as Func<int, bool>
     Unknown,
 }
 
 // method modifiers
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Modifier {
     Public,
     Private,
     Static,
+    Protected,
+    Const,    // a constant, static final in java
+    Readonly, // final in java
+    Event,    // an event, a list of listeners in java
     Unknown,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Variable {
-    pub typ: Type,    // type of the variable
-    pub name: String, // name of variable
+    pub typ: Type,      // type of the variable
+    pub name: String,   // name of variable
+    pub implicit: bool, // declared with var, the type is inferred from the value
+    pub constant: bool, // declared with const, the value is known when compiling
 }
 
 // we can have multiple statements in a code block
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Statement {
     VariableDeclaration {
         variable: Variable,        // the variable, containing type and name
         value: Option<Expression>, // the value, which can be a literal, or a boolean expression
     },
     Assignment {
-        target: String,    // target of the assignment
-        value: Expression, // value, a generic expression
+        target: Expression,               // target of the assignment, variable or member
+        operator: Option<BinaryOperator>, // compound operator, the + in +=, None for =
+        value: Expression,                // value, a generic expression
     },
     If {
         condition: Expression,     // if condition
@@ -77,12 +140,35 @@ pub enum Statement {
         condition: Expression, // the while condition
         body: Vec<Statement>,  // body, a vec of statements
     },
+    Checked {
+        checked: bool,        // false for unchecked { }
+        body: Vec<Statement>, // the statements checked for overflow
+    },
+    TryCatch {
+        body: Vec<Statement>,    // the statements that can throw
+        exception: Variable,     // the caught exception, NumberFormatException e
+        handler: Vec<Statement>, // the statements run when it is thrown
+    },
+    Block(Vec<Statement>), // a block of its own, { ... }, also made by the lowering to keep a temporary
+    Throw(Expression),     // a throw, throw new OverflowException()
     Return(Option<Expression>), // the return of a function
-    Expression(Expression),     // a generic expression, handled as a statement
+    Expression(Expression), // a generic expression, handled as a statement
 }
 
-#[derive(Debug)]
-pub enum Expression {
+// identifies an expression, the type table and the symbol table find the
+// expressions by it, and it stays the same when the lowering moves them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(u32);
+
+// an expression, with the id of the node
+#[derive(Debug, Clone)]
+pub struct Expression {
+    pub id: NodeId,
+    pub kind: ExpressionKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExpressionKind {
     Literal(Literal), // literals, values as int, float, double, string
     Variable(String), // variable name
     BinaryExpression {
@@ -99,41 +185,235 @@ pub enum Expression {
         operator: UnaryOperator, // operator
     },
     Call {
-        function: String,           // function name
+        callee: Box<Expression>,    // called function, a name or a member access
         arguments: Vec<Expression>, // vector of arguments
     },
+    MemberAccess {
+        target: Box<Expression>, // the object or class being accessed
+        name: String,            // name of the accessed member
+    },
+    ElementAccess {
+        target: Box<Expression>, // the array or collection, the xs of xs[i]
+        index: Box<Expression>,  // the index or key
+    },
+    ConditionalAccess {
+        target: Box<Expression>, // the object that may be null, the x of x?.Invoke()
+        name: String,            // name of the accessed member
+    },
+    ObjectCreation {
+        typ: Type,                  // the created class
+        arguments: Vec<Expression>, // constructor arguments
+    },
+    // new List<int> { 1, 2 } adds the elements to the created collection,
+    // new Dictionary<string, int> { ["a"] = 1 } writes them through its indexer
+    CollectionInitializer {
+        creation: Box<Expression>,      // the ObjectCreation, new List<int>()
+        elements: Vec<Vec<Expression>>, // the arguments of Add, 1 or { "a", 1 }, or the index and value
+        indexed: bool,                  // the elements are written through the indexer, ["a"] = 1
+    },
+    // new { p.Name, Total = 5 }, its members with their names
+    AnonymousObject(Vec<(String, Expression)>),
+    Assignment {
+        target: Box<Expression>, // the assigned variable or field
+        value: Box<Expression>,  // the new value, which is also the value of the expression
+    },
+    This,                                     // this
+    Base,                                     // base, becomes super in java
+    ParenthesizedExpression(Box<Expression>), // expression between parentheses, (a + b)
+    ConditionalExpression {
+        condition: Box<Expression>,   // condition ? ...
+        consequence: Box<Expression>, // value when the condition is true
+        alternative: Box<Expression>, // value when the condition is false
+    },
+    CastExpression {
+        typ: Type,              // the type we cast to
+        value: Box<Expression>, // the value being cast
+    },
+    IsExpression {
+        left: Box<Expression>, // value being tested
+        typ: Type,             // type tested against, x is int
+    },
+    AsExpression {
+        left: Box<Expression>, // value being converted
+        typ: Type,             // target type, x as string
+    },
+    CheckedExpression {
+        checked: bool,          // false for unchecked(...)
+        value: Box<Expression>, // the value checked for overflow
+    },
+    TypeOfExpression(Type), // typeof(T)
+    NamedArgument {
+        name: String,           // the parameter the argument goes to, the x in x: 5
+        value: Box<Expression>, // the passed value
+    },
+    RefArgument {
+        modifier: ParameterModifier, // ref, out or in
+        value: Box<Expression>,      // the passed variable, or the declared one
+    },
+    DeclarationExpression(Variable), // the out var n of a call, declared on the spot
+    InterpolatedString(Vec<InterpolationPart>), // $"Hello {name}", also used for composite formats
+    Lambda {
+        parameters: Vec<Variable>, // the parameters, without a type when it is inferred
+        body: LambdaBody,          // a single expression or a block
+    },
+    MethodReference {
+        target: Box<Expression>, // the class or object of the method, Program or this
+        name: String,            // the referenced method, Square in Program::Square
+    },
 }
 
-#[derive(Debug)]
+// the body of a lambda, x => x * 2 or x => { return x * 2; }
+#[derive(Debug, Clone)]
+pub enum LambdaBody {
+    Expression(Box<Expression>),
+    Block(Vec<Statement>),
+}
+
+// the pieces of an interpolated string, in order
+#[derive(Debug, Clone)]
+pub enum InterpolationPart {
+    Text(String), // plain text, already unescaped
+    Value {
+        value: Expression,      // the interpolated expression
+        alignment: Option<i32>, // minimum width, negative means left aligned
+        format: Option<String>, // .NET format specifier, such as D3 or F2
+    },
+}
+
+impl Expression {
+    /// Creates an expression, with an id no other expression has
+    pub fn new(kind: ExpressionKind) -> Expression {
+        static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+        Expression {
+            id: NodeId(NEXT_ID.fetch_add(1, Ordering::Relaxed)),
+            kind,
+        }
+    }
+
+    /// Flattens variables and member accesses into a dotted name,
+    /// such as Console.WriteLine, returns None for any other expression
+    pub fn path(&self) -> Option<String> {
+        match &self.kind {
+            ExpressionKind::Variable(name) => Some(name.clone()),
+            ExpressionKind::This => Some("this".to_string()),
+            ExpressionKind::Base => Some("base".to_string()),
+            ExpressionKind::MemberAccess { target, name } => {
+                Some(format!("{}.{}", target.path()?, name))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the direct sub-expressions, in source order
+    pub fn children_mut(&mut self) -> Vec<&mut Expression> {
+        match &mut self.kind {
+            ExpressionKind::BinaryExpression { left, right, .. } => vec![&mut **left, &mut **right],
+            ExpressionKind::PrefixUnaryExpression { right, .. } => vec![&mut **right],
+            ExpressionKind::PostfixUnaryExpression { left, .. } => vec![&mut **left],
+            ExpressionKind::Call { callee, arguments } => {
+                let mut children = vec![&mut **callee];
+                children.extend(arguments.iter_mut());
+                children
+            }
+            ExpressionKind::MemberAccess { target, .. }
+            | ExpressionKind::ConditionalAccess { target, .. }
+            | ExpressionKind::MethodReference { target, .. } => vec![&mut **target],
+            ExpressionKind::ElementAccess { target, index } => vec![&mut **target, &mut **index],
+            ExpressionKind::Assignment { target, value } => vec![&mut **target, &mut **value],
+            // the statements of a block lambda are walked like any other block
+            ExpressionKind::Lambda { body, .. } => match body {
+                LambdaBody::Expression(body) => vec![&mut **body],
+                LambdaBody::Block(_) => Vec::new(),
+            },
+            ExpressionKind::ObjectCreation { arguments, .. } => arguments.iter_mut().collect(),
+            ExpressionKind::CollectionInitializer {
+                creation, elements, ..
+            } => {
+                let mut children = vec![&mut **creation];
+                children.extend(elements.iter_mut().flatten());
+                children
+            }
+            ExpressionKind::AnonymousObject(members) => {
+                members.iter_mut().map(|(_, value)| value).collect()
+            }
+            ExpressionKind::ParenthesizedExpression(inner) => vec![&mut **inner],
+            ExpressionKind::ConditionalExpression {
+                condition,
+                consequence,
+                alternative,
+            } => vec![&mut **condition, &mut **consequence, &mut **alternative],
+            ExpressionKind::CastExpression { value, .. }
+            | ExpressionKind::CheckedExpression { value, .. }
+            | ExpressionKind::NamedArgument { value, .. }
+            | ExpressionKind::RefArgument { value, .. } => vec![&mut **value],
+            ExpressionKind::IsExpression { left, .. }
+            | ExpressionKind::AsExpression { left, .. } => {
+                vec![&mut **left]
+            }
+            ExpressionKind::InterpolatedString(parts) => parts
+                .iter_mut()
+                .filter_map(|part| match part {
+                    InterpolationPart::Value { value, .. } => Some(value),
+                    InterpolationPart::Text(_) => None,
+                })
+                .collect(),
+            ExpressionKind::Literal(_)
+            | ExpressionKind::Variable(_)
+            | ExpressionKind::This
+            | ExpressionKind::Base
+            | ExpressionKind::TypeOfExpression(_)
+            | ExpressionKind::DeclarationExpression(_) => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum Literal {
     // literals represent values, such as int, float, string
     Int(i32),
+    Long(i64),  // 5L, or a value too big for int
+    UInt(u32),  // 5u
+    ULong(u64), // 5ul
     Bool(bool),
-    String(String),
-    Float(f32),
-    Double(f64),
+    String(String),     // the unescaped value, without quotes
+    Utf8String(String), // "text"u8, a byte sequence
+    Char(char),
+    Float(String),   // the source spelling, such as 2.5f or 1e10f
+    Double(String),  // the source spelling, such as 3.14, 1e10 or 5.0d
+    Decimal(String), // the value without the m suffix, such as 1.5
+    Null,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum BinaryOperator {
-    Add, // +
-    Sub, // -
-    Mul, // *
-    Div, // /
-    Eq,  // ==
-    Ne,  // !=
-    Lt,  // <
-    Gt,  // >
-    Le,  // <=
-    Ge,  // >=
-    And, // &&
-    Or,  // ||
+    Add,                // +
+    Sub,                // -
+    Mul,                // *
+    Div,                // /
+    Mod,                // %
+    Eq,                 // ==
+    Ne,                 // !=
+    Lt,                 // <
+    Gt,                 // >
+    Le,                 // <=
+    Ge,                 // >=
+    And,                // &&
+    Or,                 // ||
+    BitAnd,             // &
+    BitOr,              // |
+    BitXor,             // ^
+    LeftShift,          // <<
+    RightShift,         // >>
+    UnsignedRightShift, // >>>
+    Coalesce,           // ??
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum UnaryOperator {
-    Not,  // !
-    Neg,  // - (negative)
-    UAdd, // ++
-    USub, // --
+    Not,    // !
+    Neg,    // - (negative)
+    Plus,   // + (positive)
+    BitNot, // ~
+    UAdd,   // ++
+    USub,   // --
 }
@@ -1,8 +1,27 @@
-use crate::{ast::*, parser_cs::*};
+use crate::{
+    ast::*, lowering::lower_program, options::Options, parser_cs::*, semantic::analyze_program,
+};
 use tree_sitter::Node;
 
 pub fn build_program(root: Node, source: &str) -> Program {
+    build_program_with_options(root, source, &Options::default())
+}
+
+/// Same as build_program, with the compiler switches of the project
+pub fn build_program_with_options(root: Node, source: &str, options: &Options) -> Program {
     let mut classes = Vec::new();
     find_classes(root, source, &mut classes);
-    Program { classes }
+    let mut delegates = Vec::new();
+    find_delegates(root, source, &mut delegates);
+    let mut program = Program {
+        classes,
+        delegates,
+        symbols: Default::default(),
+        diagnostics: Vec::new(),
+    };
+    // find the types the C# code leaves out
+    analyze_program(&mut program);
+    // rewrite the C# constructs java does not have
+    lower_program(&mut program, options);
+    program
 }
@@ -0,0 +1,41 @@
+use std::fmt;
+
+/// How bad a reported problem is
+#[derive(Debug, Clone, PartialEq)]
+pub enum Severity {
+    Warning, // the translation may behave differently
+    Error,   // the C# code itself is wrong, or cannot be translated
+}
+
+/// A problem found in the C# code, reported without stopping the translation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {}", severity, self.message)
+    }
+}
@@ -1,8 +1,33 @@
-use crate::ast::*;
+use std::collections::BTreeSet;
+
+use crate::{
+    ast::*,
+    lowering::is_pure,
+    mappings::{MethodMapping, TemplatePart, template_parts},
+    options::Options,
+    semantic::{
+        TypeTable,
+        collections::{collection, collection_import, simple_name},
+        scopes::is_integral,
+        typecheck::check_types,
+    },
+};
 
 pub struct JavaGenerator {
     indent: usize,
     output: String,
+    imports: BTreeSet<String>, // imports needed by the generated code
+    options: Options,
+    types: TypeTable, // the C# types of the expressions, for the translations depending on them
+    holders: BTreeSet<String>, // holder classes of the ref and out variables, IntRef or Ref
+    interfaces: BTreeSet<String>, // functional interfaces java lacks, such as Function3
+    helpers: BTreeSet<String>, // helper classes of the mapped calls, such as DotNetMath
+}
+
+impl Default for JavaGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl JavaGenerator {
@@ -10,12 +35,26 @@ impl JavaGenerator {
         Self {
             indent: 0,
             output: String::new(),
+            imports: BTreeSet::new(),
+            options: Options::default(),
+            types: TypeTable::default(),
+            holders: BTreeSet::new(),
+            interfaces: BTreeSet::new(),
+            helpers: BTreeSet::new(),
         }
     }
 
     /// Calling this function will continue calling child functions until the program is complete
     pub fn generate(program: &Program) -> String {
+        JavaGenerator::generate_with_options(program, &Options::default())
+    }
+
+    /// Same as generate, with the options of the project
+    pub fn generate_with_options(program: &Program, options: &Options) -> String {
         let mut generator = JavaGenerator::new();
+        generator.options = options.clone();
+        // the types are those of the lowered program, the one being generated
+        generator.types = check_types(program).0;
         // call the create_program
         generator.create_program(program);
         // return the output
@@ -24,21 +63,49 @@ impl JavaGenerator {
 
     /// This function will iterate through a program's classes and call further creator functions
     pub fn create_program(&mut self, program: &Program) {
+        // the delegates are interfaces, used by the classes
+        for delegate in &program.delegates {
+            self.create_delegate(delegate);
+        }
         for class in &program.classes {
             self.create_class(class);
         }
+        // the holders used by the ref and out variables come after the classes,
+        // and so do the interfaces of the functions with many parameters
+        for holder in std::mem::take(&mut self.holders) {
+            self.create_holder_class(&holder);
+        }
+        for interface in std::mem::take(&mut self.interfaces) {
+            self.create_functional_interface(&interface);
+        }
+        for helper in std::mem::take(&mut self.helpers) {
+            self.create_helper_class(&helper);
+        }
+
+        // the imports are only known at the end, place them on top of the file
+        let imports: String = self
+            .imports
+            .iter()
+            .map(|import| format!("import {};\n", import))
+            .collect();
+        self.output.insert_str(0, &imports);
     }
 
     /// This function itereates through a class's methods and call further creator functions
     pub fn create_class(&mut self, class: &Class) {
         // begin creating actual lines of code
         if class.uses_input {
-            self.create_line("import java.util.Scanner;");
+            self.imports.insert("java.util.Scanner".to_string());
         }
         self.create_line(&format!("class {} {{", class.name));
         // indent
         self.indent += 1;
 
+        // the fields come first
+        for field in &class.fields {
+            self.create_field(field);
+        }
+
         // iterate methods, create them
         for method in &class.methods {
             self.create_method(method, class.uses_input);
@@ -50,24 +117,114 @@ impl JavaGenerator {
         self.create_line("}");
     }
 
+    /// This function creates a holder class, the box of a ref or out variable.
+    /// IntRef holds an int, Ref<T> holds any object
+    fn create_holder_class(&mut self, holder: &str) {
+        let (class, typ) = match holder.strip_suffix("Ref") {
+            Some("") | None => ("Ref<T>".to_string(), "T".to_string()),
+            Some(primitive) => (holder.to_string(), primitive.to_lowercase()),
+        };
+        self.create_line(&format!("class {} {{", class));
+        self.indent += 1;
+        self.create_line(&format!("{} value;", typ));
+        self.create_line(&format!("{}({} value) {{", holder, typ));
+        self.indent += 1;
+        self.create_line("this.value = value;");
+        self.indent -= 1;
+        self.create_line("}");
+        self.indent -= 1;
+        self.create_line("}");
+    }
+
+    /// This function writes a helper class of the mappings, with its imports
+    fn create_helper_class(&mut self, name: &str) {
+        let helper = self
+            .options
+            .mappings
+            .helper(name)
+            .unwrap_or_else(|| panic!("Unknown helper class `{}` in the mappings", name))
+            .clone();
+        self.imports.extend(helper.imports);
+        for line in helper.java.lines() {
+            self.create_line(line);
+        }
+    }
+
+    /// This function creates the interface of a delegate type, a functional
+    /// interface with a single invoke method
+    pub fn create_delegate(&mut self, delegate: &Delegate) {
+        let return_type = self.create_declared_type(&delegate.return_type);
+        let parameters = self.create_parameters(&delegate.parameters);
+        self.create_line("@FunctionalInterface");
+        self.create_line(&format!("interface {} {{", delegate.name));
+        self.indent += 1;
+        self.create_line(&format!("{} invoke({});", return_type, parameters));
+        self.indent -= 1;
+        self.create_line("}");
+    }
+
+    /// This function creates a functional interface java does not have,
+    /// Function3 takes three arguments, Consumer3 is the same without a result
+    fn create_functional_interface(&mut self, interface: &str) {
+        let digits = interface.trim_start_matches(|c: char| c.is_alphabetic());
+        let count: usize = digits.parse().expect("Expected the number of parameters");
+        let returns = interface.starts_with("Function");
+        let mut type_parameters: Vec<String> = (1..=count).map(|i| format!("T{}", i)).collect();
+        let parameters: Vec<String> = (1..=count).map(|i| format!("T{} t{}", i, i)).collect();
+        let (return_type, method) = if returns {
+            type_parameters.push("R".to_string());
+            ("R", "apply")
+        } else {
+            ("void", "accept")
+        };
+        self.create_line("@FunctionalInterface");
+        self.create_line(&format!(
+            "interface {}<{}> {{",
+            interface,
+            type_parameters.join(", ")
+        ));
+        self.indent += 1;
+        self.create_line(&format!(
+            "{} {}({});",
+            return_type,
+            method,
+            parameters.join(", ")
+        ));
+        self.indent -= 1;
+        self.create_line("}");
+    }
+
+    /// This function creates a field of a class, MODIFIERS TYPE NAME = VALUE
+    pub fn create_field(&mut self, field: &Field) {
+        let mut line = format!(
+            "{}{} {}",
+            java_modifier(&field.modifiers),
+            self.create_declared_type(&field.variable.typ),
+            field.variable.name
+        );
+        if let Some(value) = &field.value {
+            line.push_str(" = ");
+            line.push_str(&self.create_expression(value));
+        }
+        line.push(';');
+        self.create_line(&line);
+    }
+
     /// This function creates a method's body
     pub fn create_method(&mut self, method: &Method, input: bool) {
         let modifiers = java_modifier(&method.modifiers);
-        let return_type = java_type(&method.return_type);
-        let parameters = java_parameters(&method.parameters);
+        let return_type = self.create_declared_type(&method.return_type);
+        let parameters = self.create_parameters(&method.parameters);
         self.create_line(&format!(
             "{}{} {}({}) {{",
             modifiers,
             return_type,
-            match method.name.as_ref() {
-                "Main" => "main".to_string(),
-                _ => method.name.clone(),
-            },
+            java_method_name(&method.name),
             match method.name.as_ref() {
                 "Main" =>
                     "String[] args".to_string()
                         // add a space if we have more arguments
-                        + if parameters.len() > 0 { ", " } else { "" }
+                        + if !parameters.is_empty() { ", " } else { "" }
                         + &parameters,
                 _ => parameters,
             }
@@ -89,14 +246,18 @@ impl JavaGenerator {
     pub fn create_line(&mut self, line: &str) {
         self.output.push_str(&"    ".repeat(self.indent));
         self.output.push_str(line);
-        self.output.push_str("\n");
+        self.output.push('\n');
     }
 
     fn create_statement(&mut self, stmt: &Statement) {
         match stmt {
             // create a variable declaration statement, TYPE VAR = VALUE
             Statement::VariableDeclaration { variable, value } => {
-                let mut line = format!("{} {}", java_type(&variable.typ), variable.name);
+                let mut line = format!("{} {}", self.create_variable_type(variable), variable.name);
+                // a const local is final, java takes it as a constant too
+                if variable.constant {
+                    line.insert_str(0, "final ");
+                }
 
                 if let Some(expr) = value {
                     line.push_str(" = ");
@@ -107,15 +268,20 @@ impl JavaGenerator {
                 self.create_line(&line);
             }
             // assignment statement, VAR = VALUE
-            Statement::Assignment { target, value } => {
-                let value_str: String = self.create_expression(value);
-                self.create_line(&format!("{} = {};", target, value_str));
+            Statement::Assignment { .. } => {
+                let line = self.create_inline_statement(stmt);
+                self.create_line(&format!("{};", line));
             }
             // generic expression
             Statement::Expression(expr) => {
                 let expr_str = self.create_expression(expr);
                 self.create_line(&format!("{};", expr_str));
             }
+            // throw statement, the exception classes are mapped to the java ones
+            Statement::Throw(expr) => {
+                let expr_str = self.create_expression(expr);
+                self.create_line(&format!("throw {};", expr_str));
+            }
             // return statement, can be with parameter or not
             Statement::Return(expr) => {
                 if let Some(e) = expr {
@@ -154,6 +320,40 @@ impl JavaGenerator {
                     self.create_line("}");
                 }
             }
+            // try/catch statement, only created by the lowering of TryParse
+            // try { } catch (NumberFormatException e) { }
+            Statement::TryCatch {
+                body,
+                exception,
+                handler,
+            } => {
+                self.create_line("try {");
+                self.indent += 1;
+                for statement in body {
+                    self.create_statement(statement);
+                }
+                self.indent -= 1;
+                self.create_line("}");
+                let exception_type = self.create_type(&exception.typ);
+                self.create_line(&format!("catch ({} {}) {{", exception_type, exception.name));
+                self.indent += 1;
+                for statement in handler {
+                    self.create_statement(statement);
+                }
+                self.indent -= 1;
+                self.create_line("}");
+            }
+            // java has no checked blocks, the lowering already added the
+            // overflow checks, a plain block keeps the scope of the variables
+            Statement::Checked { body, .. } | Statement::Block(body) => {
+                self.create_line("{");
+                self.indent += 1;
+                for statement in body {
+                    self.create_statement(statement);
+                }
+                self.indent -= 1;
+                self.create_line("}");
+            }
             // while statement
             Statement::While { condition, body } => {
                 // create the condition
@@ -211,7 +411,7 @@ impl JavaGenerator {
             // inline needed for variable declaration
             Statement::VariableDeclaration { variable, value } => {
                 // type and variable name is mandatory
-                let mut out = format!("{} {}", java_type(&variable.typ), variable.name);
+                let mut out = format!("{} {}", self.create_variable_type(variable), variable.name);
                 // the initial value is optional
                 if let Some(expr) = value {
                     out.push_str(" = ");
@@ -221,9 +421,17 @@ impl JavaGenerator {
                 out
             }
 
-            Statement::Assignment { target, value } => {
-                format!("{} = {}", target, self.create_expression(value))
-            }
+            // compound assignments keep their operator, a += b
+            Statement::Assignment {
+                target,
+                operator,
+                value,
+            } => format!(
+                "{} {}= {}",
+                self.create_expression(target),
+                operator.as_ref().map(java_binary_operator).unwrap_or(""),
+                self.create_expression(value)
+            ),
             // generic expression
             Statement::Expression(expr) => self.create_expression(expr),
 
@@ -233,82 +441,574 @@ impl JavaGenerator {
 
     /// This function turns expressions into strings
     pub fn create_expression(&mut self, expr: &Expression) -> String {
-        match expr {
+        match &expr.kind {
             // the literals can safely be converted to strings using to_string
-            Expression::Literal(lit) => match lit {
+            ExpressionKind::Literal(lit) => match lit {
                 Literal::Int(n) => n.to_string(),
+                Literal::Long(n) => n.to_string() + "L",
+                // java has no unsigned literals, big values are written
+                // in hexadecimal, which keeps the bits
+                Literal::UInt(n) if *n > i32::MAX as u32 => format!("0x{:X}", n),
+                Literal::UInt(n) => n.to_string(),
+                Literal::ULong(n) if *n > i64::MAX as u64 => format!("0x{:X}L", n),
+                Literal::ULong(n) => n.to_string() + "L",
                 Literal::Bool(b) => b.to_string(),
-                // strings already have the quote symbols, there is no need to add them here
-                Literal::String(s) => format!("{}", s),
-                Literal::Float(f) => f.to_string() + "f",
-                Literal::Double(d) => d.to_string() + "d",
+                Literal::Null => "null".to_string(),
+                // strings are escaped again, multi-line ones become text blocks
+                Literal::String(s) => java_string_or_text_block(s),
+                // java has no utf8 literals, encode the string instead
+                Literal::Utf8String(s) => format!(
+                    "{}.getBytes(java.nio.charset.StandardCharsets.UTF_8)",
+                    java_string_literal(s)
+                ),
+                Literal::Char(c) => java_char_literal(*c),
+                // real literals keep their spelling
+                Literal::Float(f) => f.clone(),
+                Literal::Double(d) => d.clone(),
+                // a string keeps every digit of the decimal
+                Literal::Decimal(d) => {
+                    let typ = self.create_type(&Type::Decimal);
+                    format!("new {}(\"{}\")", typ, d)
+                }
             },
-            // just return the variable name
-            Expression::Variable(name) => name.clone(),
+            // just return the variable name, java classes may need an import
+            ExpressionKind::Variable(name) => {
+                if let Some(import) = java_class_import(name) {
+                    self.imports.insert(import.to_string());
+                }
+                name.clone()
+            }
 
-            Expression::BinaryExpression {
+            ExpressionKind::BinaryExpression {
                 left,
                 operator,
                 right,
-            } => format!(
-                // use this function to create the left and right expressions
+            } => {
+                // a bool in a string is written True or False, like C# does
+                let is_concatenation =
+                    *operator == BinaryOperator::Add && self.types.get(expr) == Some(&Type::String);
+                let mut create_side = |side: &Expression| match self.create_bool_text(side) {
+                    Some(text) if is_concatenation => format!("({})", text),
+                    _ => self.create_expression(side),
+                };
+                let (left, right) = (create_side(left), create_side(right));
                 // use the helper function to obtain the operator as a string
-                "{} {} {}",
-                self.create_expression(left),
-                java_binary_operator(operator),
-                self.create_expression(right)
-            ),
+                format!("{} {} {}", left, java_binary_operator(operator), right)
+            }
 
-            Expression::PrefixUnaryExpression { operator, right } => format!(
+            ExpressionKind::PrefixUnaryExpression { operator, right } => format!(
                 // similar to binary expression, but we use the unary operator helper
                 "{}{}",
                 java_unary_operator(operator),
-                self.create_expression(right)
+                self.create_operand(right)
             ),
 
-            Expression::PostfixUnaryExpression { left, operator } => format!(
+            ExpressionKind::PostfixUnaryExpression { left, operator } => format!(
                 // same as prefix, different order
                 "{}{}",
                 self.create_expression(left),
                 java_unary_operator(operator)
             ),
 
-            Expression::Call {
-                function,
-                arguments,
-            } => {
-                match function.as_str() {
-                    // user output
-                    "Console.WriteLine" => {
+            ExpressionKind::Call { callee, arguments } => {
+                match callee.path().as_deref() {
+                    // the calls of the mappings file, Console.WriteLine(x) is System.out.println(x)
+                    Some(path)
+                        if let Some(mapping) = self.options.mappings.method(
+                            path,
+                            &self.argument_types(arguments),
+                            self.options.java_version,
+                        ) && let Some(text) =
+                            self.create_mapped_call(&mapping.clone(), arguments) =>
+                    {
+                        text
+                    }
+
+                    // true.ToString() is True in C#
+                    _ if arguments.is_empty()
+                        && let ExpressionKind::MemberAccess { target, name } = &callee.kind
+                        && name == "ToString"
+                        && let Some(text) = self.create_bool_text(target) =>
+                    {
+                        format!("({})", text)
+                    }
+
+                    // anything else
+                    _ => {
                         let args = arguments
                             .iter()
                             .map(|arg| self.create_expression(arg))
                             .collect::<Vec<_>>()
                             .join(", ");
-                        format!("System.out.println({})", args)
+                        format!("{}({})", self.create_callee(callee), args)
                     }
+                }
+            }
 
-                    // user input, change the flag
-                    "Console.ReadLine" => "scanner.nextLine()".to_string(),
+            // the length of an array is a field in java
+            ExpressionKind::MemberAccess { target, name }
+                if name == "Length" && matches!(self.types.get(target), Some(Type::Array(_))) =>
+            {
+                format!("{}.length", self.create_target(target))
+            }
 
-                    // read and parse
-                    "int.Parse" => "scanner.nextInt()".to_string(),
+            // the properties of the mappings file, Environment.NewLine
+            ExpressionKind::MemberAccess { .. }
+                if let Some(path) = expr.path()
+                    && let Some(mapping) = self.options.mappings.property(&path) =>
+            {
+                self.imports.extend(mapping.imports.iter().cloned());
+                mapping.java.clone()
+            }
 
-                    "double.Parse" => "scanner.nextDouble()".to_string(),
+            ExpressionKind::MemberAccess { target, name } => {
+                format!("{}.{}", self.create_target(target), name)
+            }
 
-                    "bool.Parse" => "scanner.nextBoolean()".to_string(),
+            // the lowering turns the collection indexers into calls, only the arrays are left
+            ExpressionKind::ElementAccess { target, index } => {
+                format!(
+                    "{}[{}]",
+                    self.create_target(target),
+                    self.create_expression(index)
+                )
+            }
 
-                    // anything else
-                    _ => {
-                        let args = arguments
-                            .iter()
-                            .map(|arg| self.create_expression(arg))
-                            .collect::<Vec<_>>()
-                            .join(", ");
-                        format!("{}({})", function, args)
+            // the overflow checks are already added by the lowering
+            ExpressionKind::CheckedExpression { value, .. } => self.create_expression(value),
+
+            // java has no named arguments, the lowering puts them in order,
+            // the ref arguments are already holders
+            ExpressionKind::NamedArgument { value, .. }
+            | ExpressionKind::RefArgument { value, .. } => self.create_expression(value),
+
+            // the lowering declares the out variables before the call
+            ExpressionKind::DeclarationExpression(variable) => variable.name.clone(),
+
+            ExpressionKind::ObjectCreation { typ, arguments } => {
+                let typ_str = self.create_type(typ);
+                let args = arguments
+                    .iter()
+                    .map(|arg| self.create_expression(arg))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("new {}({})", typ_str, args)
+            }
+
+            // new { p.Name } becomes new Object() { final String Name = p.Name; },
+            // var keeps the anonymous class so its fields can be read
+            ExpressionKind::AnonymousObject(members) => {
+                let fields: Vec<String> = members
+                    .iter()
+                    .map(|(name, value)| {
+                        let typ = match self.types.get(value).cloned() {
+                            Some(typ) => self.create_type(&typ),
+                            None => "Object".to_string(),
+                        };
+                        format!(
+                            "final {} {} = {};",
+                            typ,
+                            name,
+                            self.create_expression(value)
+                        )
+                    })
+                    .collect();
+                format!("new Object() {{ {} }}", fields.join(" "))
+            }
+
+            // an assignment used as a value, only created by the lowering of ++d,
+            // kept between parentheses since its operator binds the weakest
+            ExpressionKind::Assignment { target, value } => format!(
+                "({} = {})",
+                self.create_expression(target),
+                self.create_expression(value)
+            ),
+
+            ExpressionKind::This => "this".to_string(),
+
+            ExpressionKind::Base => "super".to_string(),
+
+            ExpressionKind::ParenthesizedExpression(inner) => {
+                format!("({})", self.create_expression(inner))
+            }
+
+            ExpressionKind::ConditionalExpression {
+                condition,
+                consequence,
+                alternative,
+            } => format!(
+                // the ternary operator is the same in java
+                "{} ? {} : {}",
+                self.create_expression(condition),
+                self.create_expression(consequence),
+                self.create_expression(alternative)
+            ),
+
+            // numeric and reference casts share the syntax
+            ExpressionKind::CastExpression { typ, value } => {
+                let typ_str = self.create_type(typ);
+                format!("({}) {}", typ_str, self.create_expression(value))
+            }
+
+            // instanceof only works with reference types, use the wrapper classes
+            ExpressionKind::IsExpression { left, typ } => format!(
+                "{} instanceof {}",
+                self.create_expression(left),
+                self.create_boxed_type(typ)
+            ),
+
+            // `as` yields null instead of throwing, so we check before casting
+            ExpressionKind::AsExpression { left, typ } if is_pure(left) => {
+                let left_str = self.create_expression(left);
+                let typ_str = self.create_boxed_type(typ);
+                format!(
+                    "({} instanceof {} ? ({}) {} : null)",
+                    left_str, typ_str, typ_str, left_str
+                )
+            }
+            // a call is evaluated once, through an Optional
+            ExpressionKind::AsExpression { left, typ } => {
+                self.imports.insert("java.util.Optional".to_string());
+                let left_str = self.create_expression(left);
+                let typ_str = self.create_boxed_type(typ);
+                format!(
+                    "Optional.ofNullable({}).filter({}.class::isInstance).map({}.class::cast).orElse(null)",
+                    left_str, typ_str, typ_str
+                )
+            }
+
+            ExpressionKind::TypeOfExpression(typ) => format!("{}.class", self.create_type(typ)),
+
+            ExpressionKind::InterpolatedString(parts) => self.create_interpolated_string(parts),
+            // x => x * 2 becomes x -> x * 2, java infers the parameter types
+            ExpressionKind::Lambda { parameters, body } => {
+                let names: Vec<&str> = parameters
+                    .iter()
+                    .map(|parameter| parameter.name.as_str())
+                    .collect();
+                let parameters = match names.as_slice() {
+                    [name] => name.to_string(),
+                    _ => format!("({})", names.join(", ")),
+                };
+                let body = match body {
+                    LambdaBody::Expression(body) => self.create_expression(body),
+                    LambdaBody::Block(statements) => self.create_lambda_block(statements),
+                };
+                format!("{} -> {}", parameters, body)
+            }
+            // Program::square, this::square
+            ExpressionKind::MethodReference { target, name } => {
+                format!(
+                    "{}::{}",
+                    self.create_expression(target),
+                    java_method_name(name)
+                )
+            }
+            // the lowering turns x?.M() into an if or x == null ? null : x.M()
+            ExpressionKind::ConditionalAccess { name, .. } => {
+                panic!("?.{} should have been lowered", name)
+            }
+            // the lowering turns new List<int> { 1, 2 } into a creation from a list
+            ExpressionKind::CollectionInitializer { .. } => {
+                panic!("collection initializers should have been lowered")
+            }
+        }
+    }
+
+    /// This function creates an interpolated string, plain values become
+    /// a string concatenation, formatted values become a String.format call
+    fn create_interpolated_string(&mut self, parts: &[InterpolationPart]) -> String {
+        let formatted = parts.iter().any(|part| {
+            matches!(part, InterpolationPart::Value { alignment, format, .. }
+                if alignment.is_some() || format.is_some())
+        });
+
+        if formatted {
+            let mut format_string = String::new();
+            let mut args = Vec::new();
+            for part in parts {
+                match part {
+                    // % is special for the java formatter
+                    InterpolationPart::Text(text) => {
+                        format_string.push_str(&text.replace('%', "%%"))
+                    }
+                    InterpolationPart::Value {
+                        value,
+                        alignment,
+                        format,
+                    } => {
+                        let operand = match self.create_bool_text(value) {
+                            Some(text) => format!("({})", text),
+                            None => self.create_operand(value),
+                        };
+                        let typ = self.types.get(value).cloned();
+                        let (specifier, arg) = java_format_specifier(
+                            *alignment,
+                            format.as_deref(),
+                            operand,
+                            typ.as_ref(),
+                        );
+                        format_string.push_str(&specifier);
+                        args.push(arg);
+                    }
+                }
+            }
+            return format!(
+                "String.format({}, {})",
+                java_string_literal(&format_string),
+                args.join(", ")
+            );
+        }
+
+        let mut pieces: Vec<String> = Vec::new();
+        for part in parts {
+            match part {
+                InterpolationPart::Text(text) => pieces.push(java_string_literal(text)),
+                InterpolationPart::Value { value, .. } => {
+                    let piece = match self.create_bool_text(value) {
+                        Some(text) => format!("({})", text),
+                        None => self.create_operand(value),
+                    };
+                    pieces.push(piece)
+                }
+            }
+        }
+        // make sure the concatenation starts with a string, so that
+        // $"{a}{b}" does not become an addition of two numbers
+        let starts_with_text = matches!(parts.first(), Some(InterpolationPart::Text(_)));
+        let second_is_text = matches!(parts.get(1), Some(InterpolationPart::Text(_)));
+        if !starts_with_text && !second_is_text {
+            pieces.insert(0, "\"\"".to_string());
+        }
+        pieces.join(" + ")
+    }
+
+    /// This function writes a bool the way C# prints it, java would print true
+    /// instead of True. Returns None for the other types
+    fn create_bool_text(&mut self, expr: &Expression) -> Option<String> {
+        if self.types.get(expr) != Some(&Type::Bool) {
+            return None;
+        }
+        Some(format!(
+            "{} ? \"True\" : \"False\"",
+            self.create_operand(expr)
+        ))
+    }
+
+    /// This function fills the template of a mapped call with its arguments.
+    /// Returns None when the template needs more arguments than the call has
+    fn create_mapped_call(
+        &mut self,
+        mapping: &MethodMapping,
+        arguments: &[Expression],
+    ) -> Option<String> {
+        // int.Parse(Console.ReadLine()) reads the number with the scanner
+        if let Some(input) = &mapping.input
+            && match arguments {
+                [] => true,
+                [argument] => {
+                    matches!(&argument.kind, ExpressionKind::Call { callee, .. } if callee.path().as_deref() == Some("Console.ReadLine"))
+                }
+                _ => false,
+            }
+        {
+            return Some(input.clone());
+        }
+        // the lowering reported the template using more arguments than the call has,
+        // the call is written as is
+        if mapping.missing_argument(arguments.len()).is_some() {
+            return None;
+        }
+        let parts = template_parts(&mapping.java).expect("Invalid mapping template");
+        let mut text = String::new();
+        for (position, part) in parts.iter().enumerate() {
+            match part {
+                TemplatePart::Text(part) => text.push_str(part),
+                TemplatePart::Arguments => {
+                    let arguments = arguments
+                        .iter()
+                        .map(|arg| self.create_mapped_argument(mapping, arg))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    text.push_str(&arguments);
+                }
+                // a literal is trimmed right away
+                TemplatePart::Argument { index, trim: true } => match &arguments[*index] {
+                    Expression {
+                        kind: ExpressionKind::Literal(Literal::String(literal)),
+                        ..
+                    } => text.push_str(&java_string_literal(literal.trim())),
+                    argument => {
+                        let argument = self.create_target(argument);
+                        text.push_str(&format!("{}.trim()", argument));
+                    }
+                },
+                TemplatePart::Argument { index, trim: false } => {
+                    let argument = &arguments[*index];
+                    // an argument alone between commas or parentheses needs no parentheses,
+                    // (a + b).foo() and -(a + b) do
+                    let opens = position == 0
+                        || matches!(&parts[position - 1], TemplatePart::Text(text) if text.trim_end().ends_with(['(', ',']));
+                    let closes = match parts.get(position + 1) {
+                        None => true,
+                        Some(TemplatePart::Text(text)) => text.trim_start().starts_with([')', ',']),
+                        _ => false,
+                    };
+                    let argument = match parts.get(position + 1) {
+                        Some(TemplatePart::Text(text)) if text.starts_with('.') => {
+                            self.create_target(argument)
+                        }
+                        _ if opens && closes => self.create_mapped_argument(mapping, argument),
+                        _ => self.create_operand(argument),
+                    };
+                    text.push_str(&argument);
+                }
+            }
+        }
+        self.imports.extend(mapping.imports.iter().cloned());
+        self.helpers.extend(mapping.helpers.iter().cloned());
+        Some(text)
+    }
+
+    /// This function obtains the C# types of the arguments of a call, for the
+    /// mappings of its overloads
+    fn argument_types(&self, arguments: &[Expression]) -> Vec<Option<Type>> {
+        arguments
+            .iter()
+            .map(|argument| self.types.get(argument).cloned())
+            .collect()
+    }
+
+    /// This function creates an argument of a mapped call, the printed ones
+    /// write their bools the way C# does
+    fn create_mapped_argument(&mut self, mapping: &MethodMapping, argument: &Expression) -> String {
+        match mapping.printed {
+            true => self
+                .create_bool_text(argument)
+                .unwrap_or_else(|| self.create_expression(argument)),
+            false => self.create_expression(argument),
+        }
+    }
+
+    /// This function creates the block of a lambda, its statements are indented
+    /// one level deeper than the line the lambda is on
+    fn create_lambda_block(&mut self, statements: &[Statement]) -> String {
+        let output = std::mem::take(&mut self.output);
+        self.indent += 1;
+        for statement in statements {
+            self.create_statement(statement);
+        }
+        self.indent -= 1;
+        let block = std::mem::replace(&mut self.output, output);
+        format!("{{\n{}{}}}", block, "    ".repeat(self.indent))
+    }
+
+    /// This function creates an expression meant to be used as an operand,
+    /// adding parentheses around the ones with a lower precedence
+    fn create_operand(&mut self, expr: &Expression) -> String {
+        let expr_str = self.create_expression(expr);
+        match &expr.kind {
+            ExpressionKind::BinaryExpression { .. }
+            | ExpressionKind::ConditionalExpression { .. }
+            | ExpressionKind::IsExpression { .. } => format!("({})", expr_str),
+            _ => expr_str,
+        }
+    }
+
+    /// This function generates the object of a member access, casts and
+    /// unary expressions also need parentheses there, ((int) x).foo
+    fn create_target(&mut self, expr: &Expression) -> String {
+        match &expr.kind {
+            ExpressionKind::CastExpression { .. }
+            | ExpressionKind::PrefixUnaryExpression { .. } => {
+                format!("({})", self.create_expression(expr))
+            }
+            _ => self.create_operand(expr),
+        }
+    }
+
+    /// This function turns a type into its java name, remembering the import it needs
+    fn create_type(&mut self, typ: &Type) -> String {
+        if let Type::Holder(inner) = typ {
+            // Ref<BigDecimal> needs the import of BigDecimal
+            self.create_type(inner);
+            let holder = java_holder_type(inner);
+            let class = holder.split('<').next().unwrap_or(&holder);
+            self.holders.insert(class.to_string());
+        }
+        if let Type::Generic(name, arguments) = typ {
+            for argument in arguments {
+                self.create_type(argument);
+            }
+            let class = java_generic_class(name, arguments.len());
+            if let Some(import) = java_class_import(&class) {
+                self.imports.insert(import.to_string());
+            }
+            // Function3 and Consumer3 are generated
+            if class.ends_with(|c: char| c.is_ascii_digit()) {
+                self.interfaces.insert(class);
+            }
+        }
+        if let Some(import) = java_type_import(typ) {
+            self.imports.insert(import.to_string());
+        }
+        java_type(typ)
+    }
+
+    /// Same as create_type, for the type of a declaration. The nullable
+    /// types are marked with @Nullable, if the option is set
+    fn create_declared_type(&mut self, typ: &Type) -> String {
+        let typ_str = self.create_type(typ);
+        if self.options.nullable_annotations && matches!(typ, Type::Nullable(_)) {
+            self.imports
+                .insert("org.jspecify.annotations.Nullable".to_string());
+            return format!("@Nullable {}", typ_str);
+        }
+        typ_str
+    }
+
+    /// This function creates the type of a local variable, var is kept
+    /// if the targeted java has it
+    fn create_variable_type(&mut self, variable: &Variable) -> String {
+        if variable.implicit && self.options.java_version >= 10 {
+            return "var".to_string();
+        }
+        self.create_declared_type(&variable.typ)
+    }
+
+    /// Same as create_type, but for the wrapper class of the type
+    fn create_boxed_type(&mut self, typ: &Type) -> String {
+        self.create_type(typ);
+        java_boxed_type(typ)
+    }
+
+    /// This function creates a method's parameter list
+    fn create_parameters(&mut self, parameters: &[Parameter]) -> String {
+        parameters
+            .iter()
+            .map(|parameter| {
+                let variable = &parameter.variable;
+                match &variable.typ {
+                    // params int[] xs becomes the varargs int... xs
+                    Type::Array(element) if parameter.params => {
+                        format!("{}... {}", self.create_type(element), variable.name)
                     }
+                    typ => format!("{} {}", self.create_declared_type(typ), variable.name),
                 }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// This function creates the called part of a call expression,
+    /// methods follow the java naming convention
+    fn create_callee(&mut self, callee: &Expression) -> String {
+        match &callee.kind {
+            ExpressionKind::Variable(name) => java_method_name(name),
+            ExpressionKind::MemberAccess { target, name } => {
+                format!("{}.{}", self.create_target(target), java_method_name(name))
             }
+            _ => self.create_expression(callee),
         }
     }
 }
@@ -321,6 +1021,7 @@ fn java_binary_operator(op: &BinaryOperator) -> &'static str {
         BinaryOperator::Sub => "-",
         BinaryOperator::Mul => "*",
         BinaryOperator::Div => "/",
+        BinaryOperator::Mod => "%",
         BinaryOperator::Eq => "==",
         BinaryOperator::Ne => "!=",
         BinaryOperator::Lt => "<",
@@ -329,6 +1030,13 @@ fn java_binary_operator(op: &BinaryOperator) -> &'static str {
         BinaryOperator::Ge => ">=",
         BinaryOperator::And => "&&",
         BinaryOperator::Or => "||",
+        BinaryOperator::BitAnd => "&",
+        BinaryOperator::BitOr => "|",
+        BinaryOperator::BitXor => "^",
+        BinaryOperator::LeftShift => "<<",
+        BinaryOperator::RightShift => ">>",
+        BinaryOperator::UnsignedRightShift => ">>>",
+        BinaryOperator::Coalesce => panic!("?? should have been lowered"),
     }
 }
 
@@ -338,11 +1046,198 @@ fn java_unary_operator(op: &UnaryOperator) -> &'static str {
     match op {
         UnaryOperator::Not => "!",
         UnaryOperator::Neg => "-",
+        UnaryOperator::Plus => "+",
+        UnaryOperator::BitNot => "~",
         UnaryOperator::UAdd => "++",
         UnaryOperator::USub => "--",
     }
 }
 
+/// Helper function for creating a java string literal, escaping the text
+pub fn java_string_literal(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            // other control characters use unicode escapes
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Helper function for creating either a string literal or,
+/// when the text spans multiple lines, a java text block
+pub fn java_string_or_text_block(text: &str) -> String {
+    if !text.trim_end_matches('\n').contains('\n') {
+        return java_string_literal(text);
+    }
+
+    let mut out = String::from("\"\"\"\n");
+    let lines: Vec<&str> = text.split('\n').collect();
+    for (i, line) in lines.iter().enumerate() {
+        let mut escaped = line
+            .replace('\\', "\\\\")
+            .replace("\"\"\"", "\"\"\\\"")
+            .replace('\r', "\\r")
+            .replace('\t', "\\t");
+        // java strips trailing spaces from text blocks, keep the last one escaped
+        if escaped.ends_with(' ') {
+            escaped.pop();
+            escaped.push_str("\\s");
+        }
+        out.push_str(&escaped);
+        if i + 1 < lines.len() {
+            out.push('\n');
+        } else if !line.is_empty() {
+            // no line break after the last line
+            out.push_str("\\\n");
+        }
+    }
+    // the closing quotes stay on column 0, so no indentation is stripped
+    out.push_str("\"\"\"");
+    out
+}
+
+/// Helper function for creating a java char literal, escaping the character
+pub fn java_char_literal(c: char) -> String {
+    match c {
+        '\'' => "'\\''".to_string(),
+        '"' => "'\"'".to_string(),
+        // the rest of the escapes are the same as for strings
+        _ => {
+            let escaped = java_string_literal(&c.to_string());
+            format!("'{}'", &escaped[1..escaped.len() - 1])
+        }
+    }
+}
+
+/// Helper function for translating a .NET format item into a java.util.Formatter specifier.
+/// Returns the specifier and the argument, adjusted where java needs a different value.
+/// The floating point conversions take a BigDecimal as it is, the other numbers as doubles
+pub fn java_format_specifier(
+    alignment: Option<i32>,
+    format: Option<&str>,
+    arg: String,
+    typ: Option<&Type>,
+) -> (String, String) {
+    // negative alignments are left aligned
+    let left = if alignment.is_some_and(|a| a < 0) {
+        "-"
+    } else {
+        ""
+    };
+    let width = alignment
+        .map(|a| a.unsigned_abs().to_string())
+        .unwrap_or_default();
+
+    // the format is a letter followed by an optional precision, such as D3 or F2
+    let format = format.unwrap_or("").trim();
+    let mut chars = format.chars();
+    let kind = chars.next();
+    let precision = chars.as_str().parse::<usize>().ok();
+    let decimal = typ == Some(&Type::Decimal);
+    let number = |arg: String| {
+        if decimal {
+            arg
+        } else {
+            format!("(double) {}", arg)
+        }
+    };
+
+    match kind {
+        // default formatting
+        None | Some('G' | 'g') if precision.is_none() => (format!("%{}{}s", left, width), arg),
+        // D pads with zeros up to the precision, X does the same in hexadecimal
+        Some(c @ ('D' | 'd' | 'X' | 'x')) => {
+            let conversion = match c {
+                'X' => 'X',
+                'x' => 'x',
+                _ => 'd',
+            };
+            match (precision, alignment) {
+                (None, _) => (format!("%{}{}{}", left, width, conversion), arg),
+                (Some(p), None) => (format!("%0{}{}", p, conversion), arg),
+                // java cannot zero pad and align in one go, format twice
+                (Some(p), Some(_)) => (
+                    format!("%{}{}s", left, width),
+                    format!("String.format(\"%0{}{}\", {})", p, conversion, arg),
+                ),
+            }
+        }
+        // fixed point, .NET defaults to 2 decimals
+        Some('F' | 'f') => (
+            format!("%{}{}.{}f", left, width, precision.unwrap_or(2)),
+            number(arg),
+        ),
+        // number, fixed point with group separators
+        Some('N' | 'n') => (
+            format!("%{},{}.{}f", left, width, precision.unwrap_or(2)),
+            number(arg),
+        ),
+        // scientific notation, .NET defaults to 6 decimals
+        Some(c @ ('E' | 'e')) => (
+            format!("%{}{}.{}{}", left, width, precision.unwrap_or(6), c),
+            number(arg),
+        ),
+        // percent, the value is multiplied by 100
+        Some('P' | 'p') => (
+            format!("%{}{}.{}f%%", left, width, precision.unwrap_or(2)),
+            if decimal {
+                format!("{}.multiply(BigDecimal.valueOf(100))", arg)
+            } else {
+                format!("(double) {} * 100", arg)
+            },
+        ),
+        // custom numeric formats, such as 0.00 or #,##0.0
+        _ if !format.is_empty() && format.chars().all(|c| "0#.,".contains(c)) => {
+            let (integer, decimals) = format.split_once('.').unwrap_or((format, ""));
+            let grouping = if integer.contains(',') { "," } else { "" };
+            // an integer without decimals stays an integer
+            let (conversion, arg) = match typ {
+                Some(typ) if is_integral(typ) && decimals.is_empty() => ("d".to_string(), arg),
+                _ => (format!(".{}f", decimals.len()), number(arg)),
+            };
+            // the zeros before the point are the least digits, 00000 pads 42 to 00042
+            let digits = integer.chars().filter(|&c| c == '0').count();
+            let padded = match decimals.len() {
+                0 => digits,
+                n => digits + 1 + n,
+            };
+            match (digits > 1, alignment) {
+                (false, _) => (format!("%{}{}{}{}", left, grouping, width, conversion), arg),
+                (true, None) => (format!("%{}0{}{}", grouping, padded, conversion), arg),
+                // java cannot zero pad and align in one go, format twice
+                (true, Some(_)) => (
+                    format!("%{}{}s", left, width),
+                    format!(
+                        "String.format(\"%{}0{}{}\", {})",
+                        grouping, padded, conversion, arg
+                    ),
+                ),
+            }
+        }
+        // anything else is printed as is
+        _ => (format!("%{}{}s", left, width), arg),
+    }
+}
+
+/// Helper function for turning C# PascalCase method names
+/// into java camelCase names, Main becomes main
+pub fn java_method_name(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
 /// Helper function for parsing modifiers
 pub fn java_modifier(modifiers: &Vec<Modifier>) -> String {
     let mut out = String::new();
@@ -351,6 +1246,10 @@ pub fn java_modifier(modifiers: &Vec<Modifier>) -> String {
             Modifier::Public => out.push_str("public "),
             Modifier::Private => out.push_str("private "),
             Modifier::Static => out.push_str("static "),
+            Modifier::Protected => out.push_str("protected "),
+            // a constant belongs to the class
+            Modifier::Const => out.push_str("static final "),
+            Modifier::Readonly => out.push_str("final "),
             _ => (),
         }
     }
@@ -362,26 +1261,131 @@ pub fn java_type(typ: &Type) -> String {
     match typ {
         Type::Void => "void".to_string(),
         Type::Int => "int".to_string(),
+        Type::Long => "long".to_string(),
+        Type::Short => "short".to_string(),
+        // java only has signed types, the unsigned ones keep their size
+        Type::Byte | Type::SByte => "byte".to_string(),
+        Type::UShort => "short".to_string(),
+        Type::UInt => "int".to_string(),
+        Type::ULong => "long".to_string(),
+        Type::Decimal => "BigDecimal".to_string(),
         Type::Bool => "boolean".to_string(),
         Type::String => "String".to_string(),
+        Type::Char => "char".to_string(),
         Type::Float => "float".to_string(),
         Type::Double => "double".to_string(),
+        Type::Named(name) => java_class_name(name).to_string(),
+        // int? can only be stored in the wrapper class
+        Type::Nullable(inner) => java_boxed_type(inner),
+        Type::Array(element) => format!("{}[]", java_type(element)),
+        Type::Holder(inner) => java_holder_type(inner),
+        // the type arguments can only be classes, Function<Integer, Boolean>
+        Type::Generic(name, arguments) => {
+            let arguments: Vec<String> = arguments.iter().map(java_boxed_type).collect();
+            format!(
+                "{}<{}>",
+                java_generic_class(name, arguments.len()),
+                arguments.join(", ")
+            )
+        }
         Type::Unknown => "Object".to_string(),
     }
 }
 
-/// Helper function for parsing parameters
-pub fn java_parameters(parameters: &Vec<Variable>) -> String {
-    let mut out = String::new();
-    for parameter in parameters {
-        out.push_str(&java_type(&parameter.typ));
-        out.push(' ');
-        out.push_str(&parameter.name);
-        out.push_str(", ");
+/// Helper function for obtaining the holder of a variable passed by ref, the
+/// primitives have their own holder, IntRef, so that they are never boxed
+pub fn java_holder_type(typ: &Type) -> String {
+    match java_type(typ).as_str() {
+        primitive @ ("int" | "long" | "short" | "byte" | "boolean" | "char" | "float"
+        | "double") => {
+            format!("{}{}Ref", primitive[..1].to_uppercase(), &primitive[1..])
+        }
+        _ => format!("Ref<{}>", java_boxed_type(typ)),
     }
-    if !parameters.is_empty() {
-        out.pop();
-        out.pop();
+}
+
+/// Helper function for obtaining the java class of a generic type with the given
+/// number of type arguments. The delegates become the interfaces of java.util.function,
+/// Func<int, bool> is a Function, Func<bool> is a Supplier
+pub fn java_generic_class(name: &str, count: usize) -> String {
+    match (name, count) {
+        ("Func", 1) => "Supplier".to_string(),
+        ("Func", 2) => "Function".to_string(),
+        ("Func", 3) => "BiFunction".to_string(),
+        ("Func", _) => format!("Function{}", count - 1),
+        ("Action", 1) => "Consumer".to_string(),
+        ("Action", 2) => "BiConsumer".to_string(),
+        ("Action", _) => format!("Consumer{}", count),
+        // the sequences of LINQ, a query becomes a list
+        ("IEnumerable" | "ICollection" | "IReadOnlyCollection", _) => "Collection".to_string(),
+        ("IReadOnlyList", _) => "List".to_string(),
+        // List<int> is a java List, made by an ArrayList
+        _ => match collection(name) {
+            Some(collection) => simple_name(collection.interface).to_string(),
+            None => name.to_string(),
+        },
+    }
+}
+
+/// Helper function for obtaining the import a type needs, if any
+pub fn java_type_import(typ: &Type) -> Option<&'static str> {
+    java_class_import(&java_type(typ))
+}
+
+/// Helper function for obtaining the java class standing in for a .NET one
+pub fn java_class_name(name: &str) -> &str {
+    match name {
+        "OverflowException" | "System.OverflowException" => "ArithmeticException",
+        // a dictionary read of a missing key, see the collections lowering
+        "KeyNotFoundException" => "NoSuchElementException",
+        // the listeners of an EventHandler take any object
+        "EventArgs" | "System.EventArgs" => "Object",
+        // the Action without parameters
+        "Action" => "Runnable",
+        _ => name,
+    }
+}
+
+/// Helper function for obtaining the import of a java class, if it is not in java.lang
+pub fn java_class_import(name: &str) -> Option<&'static str> {
+    match name {
+        "BigDecimal" => Some("java.math.BigDecimal"),
+        "MathContext" => Some("java.math.MathContext"),
+        "RoundingMode" => Some("java.math.RoundingMode"),
+        "Objects" => Some("java.util.Objects"),
+        "Optional" => Some("java.util.Optional"),
+        "Collection" => Some("java.util.Collection"),
+        "Arrays" => Some("java.util.Arrays"),
+        "NoSuchElementException" => Some("java.util.NoSuchElementException"),
+        "Collections" => Some("java.util.Collections"),
+        "Locale" => Some("java.util.Locale"),
+        "Pattern" => Some("java.util.regex.Pattern"),
+        "Comparator" => Some("java.util.Comparator"),
+        "Collectors" => Some("java.util.stream.Collectors"),
+        "Stream" => Some("java.util.stream.Stream"),
+        "Function" => Some("java.util.function.Function"),
+        "BiFunction" => Some("java.util.function.BiFunction"),
+        "Supplier" => Some("java.util.function.Supplier"),
+        "Consumer" => Some("java.util.function.Consumer"),
+        "BiConsumer" => Some("java.util.function.BiConsumer"),
+        "Predicate" => Some("java.util.function.Predicate"),
+        // the collections and the classes they are made with
+        _ => collection_import(name),
+    }
+}
+
+/// Helper function for obtaining the wrapper class of a type,
+/// needed wherever java does not accept primitives
+pub fn java_boxed_type(typ: &Type) -> String {
+    match typ {
+        Type::Int | Type::UInt => "Integer".to_string(),
+        Type::Long | Type::ULong => "Long".to_string(),
+        Type::Short | Type::UShort => "Short".to_string(),
+        Type::Byte | Type::SByte => "Byte".to_string(),
+        Type::Bool => "Boolean".to_string(),
+        Type::Char => "Character".to_string(),
+        Type::Float => "Float".to_string(),
+        Type::Double => "Double".to_string(),
+        _ => java_type(typ),
     }
-    out
 }
@@ -1,4 +1,9 @@
 pub mod ast;
 pub mod builder_java;
+pub mod diagnostics;
 pub mod generator_java;
+pub mod lowering;
+pub mod mappings;
+pub mod options;
 pub mod parser_cs;
+pub mod semantic;
@@ -0,0 +1,562 @@
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+use crate::semantic::typecheck::cs_type_name;
+
+use super::unsigned::{mask, widen};
+use super::{
+    Lowering, Scopes, hoist_target, lower_children, lower_methods, lower_statement_children,
+    lower_statements, promote, static_call, take_expression,
+};
+
+/// Adds the overflow checks of C# checked code. Java always wraps around, so inside
+/// checked { } and checked(...) the int and long arithmetic goes through Math.addExact
+/// and friends, which throw an ArithmeticException just like the OverflowException of C#.
+/// The small and uint types are computed wider and converted back with a check.
+/// With the /checked option the whole file starts out checked, unchecked turns it off
+pub fn lower_checked(program: &mut Program, checked: bool) {
+    let mut pass = CheckedLowering {
+        scopes: Scopes::default(),
+        checked,
+        diagnostics: Vec::new(),
+    };
+    lower_methods(&mut pass, program);
+    program.diagnostics.extend(pass.diagnostics);
+}
+
+struct CheckedLowering {
+    scopes: Scopes,
+    checked: bool, // whether the code being lowered is checked
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Lowering for CheckedLowering {
+    fn scopes(&mut self) -> &mut Scopes {
+        &mut self.scopes
+    }
+
+    fn lower_expression(&mut self, expr: &mut Expression) {
+        match &mut expr.kind {
+            ExpressionKind::CheckedExpression { checked, value } => {
+                let outer = self.checked;
+                self.checked = *checked;
+                self.lower_expression(value);
+                self.checked = outer;
+
+                // java has no such expression, only the value remains
+                let value = take_expression(value);
+                *expr = match value.kind {
+                    ExpressionKind::Literal(_)
+                    | ExpressionKind::Variable(_)
+                    | ExpressionKind::Call { .. }
+                    | ExpressionKind::MemberAccess { .. }
+                    | ExpressionKind::ParenthesizedExpression(_) => value,
+                    _ => Expression::new(ExpressionKind::ParenthesizedExpression(Box::new(value))),
+                };
+            }
+            ExpressionKind::BinaryExpression {
+                left,
+                operator,
+                right,
+            } if self.checked && exact_method(operator).is_some() => {
+                let (left_type, right_type) = self.scopes.operand_types(left, right);
+                let promoted = match (&left_type, &right_type) {
+                    (Some(left), Some(right)) => promote(left, right),
+                    _ => None,
+                };
+                lower_children(self, expr);
+                let ExpressionKind::BinaryExpression {
+                    left,
+                    operator,
+                    right,
+                } = &mut expr.kind
+                else {
+                    return;
+                };
+                let method = exact_method(operator).expect("Expected an exact operator");
+                match promoted {
+                    // a + b becomes Math.addExact(a, b)
+                    Some(Type::Int | Type::Long) => {
+                        let arguments = vec![take_expression(left), take_expression(right)];
+                        *expr = static_call("Math", method, arguments);
+                    }
+                    // the uints are added as longs, Convert.ToUInt32 throws when the sum
+                    // does not fit: Convert.ToUInt32(Math.addExact(toUnsignedLong(a), ...))
+                    Some(Type::UInt) => {
+                        let mut left = take_expression(left);
+                        let mut right = take_expression(right);
+                        for (side, typ) in [(&mut left, &left_type), (&mut right, &right_type)] {
+                            if let Some(typ) = typ {
+                                widen(side, typ, &Type::Long);
+                            }
+                        }
+                        let sum = static_call("Math", method, vec![left, right]);
+                        *expr = static_call("Convert", "ToUInt32", vec![sum]);
+                    }
+                    Some(Type::ULong) => {
+                        let what = format!("`{}` of two ulongs", cs_operator(operator));
+                        self.unchecked(&what);
+                    }
+                    _ => {}
+                }
+            }
+            ExpressionKind::PrefixUnaryExpression {
+                operator: UnaryOperator::Neg,
+                right,
+            } if self.checked => {
+                let typ = self.scopes.type_of(right);
+                lower_children(self, expr);
+                if is_exact_type(typ.as_ref())
+                    && let ExpressionKind::PrefixUnaryExpression { right, .. } = &mut expr.kind
+                {
+                    let value = take_expression(right);
+                    *expr = static_call("Math", "negateExact", vec![value]);
+                }
+            }
+            // ++x inside an expression becomes (x = Math.incrementExact(x)), x++ takes
+            // one off again, which cannot overflow
+            ExpressionKind::PrefixUnaryExpression {
+                operator: operator @ (UnaryOperator::UAdd | UnaryOperator::USub),
+                right: target,
+            }
+            | ExpressionKind::PostfixUnaryExpression {
+                operator: operator @ (UnaryOperator::UAdd | UnaryOperator::USub),
+                left: target,
+            } if self.checked => {
+                let typ = self.scopes.type_of(target);
+                let prefix = matches!(&mut expr.kind, ExpressionKind::PrefixUnaryExpression { .. });
+                let (operator, target) = match &mut expr.kind {
+                    ExpressionKind::PrefixUnaryExpression { operator, right } => (operator, right),
+                    ExpressionKind::PostfixUnaryExpression { operator, left } => (operator, left),
+                    _ => return,
+                };
+                let operator = operator.clone();
+                self.lower_expression(target);
+                let exact = is_exact_type(typ.as_ref());
+                if !exact || !self.scopes.is_plain_target(target) {
+                    if let Some(typ) = typ.filter(is_integral_type) {
+                        let what = format!(
+                            "`{}` of a `{}` inside an expression",
+                            java_step(&operator),
+                            cs_type_name(&typ)
+                        );
+                        self.unchecked(&what);
+                    }
+                    return;
+                }
+
+                let target = take_expression(target);
+                let method = exact_step_method(&operator);
+                let value = static_call("Math", method, vec![target.clone()]);
+                let assignment = Expression::new(ExpressionKind::Assignment {
+                    target: Box::new(target),
+                    value: Box::new(value),
+                });
+                *expr = if prefix {
+                    assignment
+                } else {
+                    let undo = match operator {
+                        UnaryOperator::UAdd => BinaryOperator::Sub,
+                        _ => BinaryOperator::Add,
+                    };
+                    Expression::new(ExpressionKind::ParenthesizedExpression(Box::new(
+                        Expression::new(ExpressionKind::BinaryExpression {
+                            left: Box::new(assignment),
+                            operator: undo,
+                            right: Box::new(Expression::new(ExpressionKind::Literal(
+                                Literal::Int(1),
+                            ))),
+                        }),
+                    )))
+                };
+            }
+            ExpressionKind::CastExpression { typ, value } if self.checked => {
+                let value_type = self.scopes.type_of(value);
+                let typ = typ.clone();
+                lower_children(self, expr);
+                let (
+                    Expression {
+                        kind: ExpressionKind::CastExpression { value, .. },
+                        ..
+                    },
+                    Some(value_type),
+                ) = (&mut *expr, value_type)
+                else {
+                    return;
+                };
+                // the value is an argument now, (short) (a + b) needs no parentheses
+                let value = match take_expression(value) {
+                    Expression {
+                        kind: ExpressionKind::ParenthesizedExpression(inner),
+                        ..
+                    } => *inner,
+                    value => value,
+                };
+                match self.checked_cast(value, &value_type, &typ) {
+                    Ok(checked) => *expr = checked,
+                    Err(value) => {
+                        if let ExpressionKind::CastExpression { value: cast, .. } = &mut expr.kind {
+                            **cast = parenthesized(value);
+                        }
+                    }
+                }
+            }
+            _ => lower_children(self, expr),
+        }
+    }
+
+    fn lower_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::Checked { checked, body } => {
+                let outer = self.checked;
+                self.checked = *checked;
+                lower_statements(self, body);
+                self.checked = outer;
+            }
+            // catch (OverflowException) has no variable, java needs one
+            Statement::TryCatch { exception, .. } if exception.name.is_empty() => {
+                exception.name = self.scopes.fresh_name("e");
+                self.scopes.names.insert(exception.name.clone());
+                lower_statement_children(self, statement);
+            }
+            Statement::Assignment {
+                target,
+                operator: Some(operator),
+                value,
+            } if self.checked && exact_method(operator).is_some() => {
+                let target_type = self.scopes.type_of(target);
+                let (_, value_type) = self.scopes.operand_types(target, value);
+                let Some(plan) = self.compound_plan(target_type.as_ref(), value_type.as_ref())
+                else {
+                    if let Some(typ) = target_type.filter(is_integral_type) {
+                        let what =
+                            format!("`{}=` on a `{}`", cs_operator(operator), cs_type_name(&typ));
+                        self.unchecked(&what);
+                    }
+                    return lower_statement_children(self, statement);
+                };
+                // the target is read and written, Get().V += 1 keeps Get() in a variable
+                if hoist_target(&mut self.scopes, statement) {
+                    return lower_statement_children(self, statement);
+                }
+                lower_statement_children(self, statement);
+
+                let Statement::Assignment {
+                    target,
+                    operator: assignment_operator,
+                    value,
+                } = statement
+                else {
+                    return;
+                };
+                let Some(method) = assignment_operator.take().as_ref().and_then(exact_method)
+                else {
+                    return;
+                };
+                let target_type = target_type.expect("Expected a target type");
+                let assigned = value;
+                let mut value = take_expression(assigned);
+                *assigned = match plan {
+                    // total += x becomes total = Math.addExact(total, x)
+                    Plan::Exact => static_call("Math", method, vec![target.clone(), value]),
+                    // b += 10 becomes b = Convert.ToByte(Math.addExact((b & 255), 10))
+                    Plan::Narrow(convert) => {
+                        let current = read(target.clone(), &target_type);
+                        match value_type {
+                            Some(Type::UInt) => widen(&mut value, &Type::UInt, &Type::Long),
+                            // an uint target widened it already
+                            Some(typ) if target_type != Type::UInt => mask(&mut value, Some(&typ)),
+                            _ => {}
+                        }
+                        let result = static_call("Math", method, vec![current, value]);
+                        static_call("Convert", convert, vec![result])
+                    }
+                };
+            }
+            // i++ on its own line becomes i = Math.incrementExact(i),
+            // b++ becomes b = Convert.ToByte((b & 255) + 1)
+            Statement::Expression(
+                Expression {
+                    kind:
+                        ExpressionKind::PostfixUnaryExpression {
+                            left: target,
+                            operator,
+                        },
+                    ..
+                }
+                | Expression {
+                    kind:
+                        ExpressionKind::PrefixUnaryExpression {
+                            operator,
+                            right: target,
+                        },
+                    ..
+                },
+            ) if self.checked && matches!(operator, UnaryOperator::UAdd | UnaryOperator::USub) => {
+                let Some(typ) = self.scopes.type_of(target) else {
+                    return lower_statement_children(self, statement);
+                };
+                let convert = narrowing_method(&typ);
+                if !is_exact_type(Some(&typ)) && convert.is_none() {
+                    if is_integral_type(&typ) {
+                        let what =
+                            format!("`{}` of a `{}`", java_step(operator), cs_type_name(&typ));
+                        self.unchecked(&what);
+                    }
+                    // only the target, the ++ itself is reported
+                    return self.lower_expression(target);
+                }
+                if hoist_target(&mut self.scopes, statement) {
+                    return lower_statement_children(self, statement);
+                }
+
+                let Statement::Expression(
+                    Expression {
+                        kind:
+                            ExpressionKind::PostfixUnaryExpression {
+                                left: target,
+                                operator,
+                            },
+                        ..
+                    }
+                    | Expression {
+                        kind:
+                            ExpressionKind::PrefixUnaryExpression {
+                                operator,
+                                right: target,
+                            },
+                        ..
+                    },
+                ) = statement
+                else {
+                    return;
+                };
+                let operator = operator.clone();
+                let mut target = take_expression(target);
+                self.lower_expression(&mut target);
+                let value = match convert {
+                    Some(convert) => {
+                        let step = Expression::new(ExpressionKind::BinaryExpression {
+                            left: Box::new(read(target.clone(), &typ)),
+                            operator: match operator {
+                                UnaryOperator::UAdd => BinaryOperator::Add,
+                                _ => BinaryOperator::Sub,
+                            },
+                            right: Box::new(Expression::new(ExpressionKind::Literal(
+                                Literal::Int(1),
+                            ))),
+                        });
+                        static_call("Convert", convert, vec![step])
+                    }
+                    None => {
+                        let method = exact_step_method(&operator);
+                        static_call("Math", method, vec![target.clone()])
+                    }
+                };
+                *statement = Statement::Assignment {
+                    target,
+                    operator: None,
+                    value,
+                };
+            }
+            _ => lower_statement_children(self, statement),
+        }
+    }
+}
+
+/// How a checked compound assignment is computed
+enum Plan {
+    Exact,                // in the type of the target, with Math.addExact
+    Narrow(&'static str), // in a wider type, converted back with the Convert method
+}
+
+impl CheckedLowering {
+    /// Decides how a compound assignment is checked, None when java cannot check it
+    fn compound_plan(&self, target: Option<&Type>, value: Option<&Type>) -> Option<Plan> {
+        let (target, value) = (target?, value?);
+        // b += 1.5 and u += ul are computed in types with no exact methods
+        if !is_integral_type(value) || *value == Type::ULong {
+            return None;
+        }
+        match promote(target, value)? {
+            Type::Int | Type::Long if target == &Type::Int || target == &Type::Long => {
+                Some(Plan::Exact)
+            }
+            Type::Int | Type::Long | Type::UInt => narrowing_method(target).map(Plan::Narrow),
+            _ => None,
+        }
+    }
+
+    /// Rewrites a cast of checked code into a call that throws when the value does not
+    /// fit, (int) l becomes Math.toIntExact(l) and (short) i becomes Convert.ToInt16(i).
+    /// A double is truncated first, (int) d becomes Math.toIntExact(Convert.ToInt64(Math.Truncate(d))).
+    /// Gives the value back when the cast cannot overflow, or java cannot check it
+    fn checked_cast(
+        &mut self,
+        value: Expression,
+        from: &Type,
+        to: &Type,
+    ) -> Result<Expression, Expression> {
+        if !can_overflow(from, to) {
+            return Err(value);
+        }
+        let (value, from) = match from {
+            Type::Float | Type::Double if *to != Type::ULong => {
+                let truncated = static_call("Math", "Truncate", vec![value]);
+                (
+                    static_call("Convert", "ToInt64", vec![truncated]),
+                    Type::Long,
+                )
+            }
+            // the uint keeps its value in a long, (int) u becomes Math.toIntExact(...)
+            Type::UInt => (
+                static_call("Integer", "toUnsignedLong", vec![value]),
+                Type::Long,
+            ),
+            // the bytes and ushorts are read without their sign
+            Type::Byte | Type::UShort => {
+                let mut value = value;
+                mask(&mut value, Some(from));
+                (value, Type::Int)
+            }
+            Type::ULong | Type::Float | Type::Double => {
+                return Err(self.unchecked_cast(value, from, to));
+            }
+            _ => (value, from.clone()),
+        };
+        match to {
+            Type::Long => Ok(value),
+            Type::Int if from == Type::Long => Ok(static_call("Math", "toIntExact", vec![value])),
+            _ => match narrowing_method(to) {
+                Some(method) => Ok(static_call("Convert", method, vec![value])),
+                None => Err(self.unchecked_cast(value, &from, to)),
+            },
+        }
+    }
+
+    /// Reports a cast that is left unchecked, the value is kept as is
+    fn unchecked_cast(&mut self, value: Expression, from: &Type, to: &Type) -> Expression {
+        let what = format!(
+            "cast from `{}` to `{}`",
+            cs_type_name(from),
+            cs_type_name(to)
+        );
+        self.unchecked(&what);
+        value
+    }
+
+    /// Reports an operation of checked code that java cannot check
+    fn unchecked(&mut self, what: &str) {
+        self.diagnostics.push(Diagnostic::warning(format!(
+            "the {} in {} is not checked for overflow, java wraps around",
+            what, self.scopes.method
+        )));
+    }
+}
+
+/// Helper function that reads a small or uint target in a wider type, without its sign,
+/// (b & 255) or Integer.toUnsignedLong(u)
+fn read(mut target: Expression, typ: &Type) -> Expression {
+    match typ {
+        Type::UInt => widen(&mut target, typ, &Type::Long),
+        _ => mask(&mut target, Some(typ)),
+    }
+    target
+}
+
+/// Helper function that puts a value back in parentheses, when it is not a single term
+fn parenthesized(value: Expression) -> Expression {
+    match value.kind {
+        ExpressionKind::Literal(_)
+        | ExpressionKind::Variable(_)
+        | ExpressionKind::Call { .. }
+        | ExpressionKind::MemberAccess { .. } => value,
+        _ => Expression::new(ExpressionKind::ParenthesizedExpression(Box::new(value))),
+    }
+}
+
+/// Helper function that checks for the types Math has exact methods for
+fn is_exact_type(typ: Option<&Type>) -> bool {
+    matches!(typ, Some(Type::Int | Type::Long))
+}
+
+/// Helper function for the integer types, whose arithmetic can overflow
+fn is_integral_type(typ: &Type) -> bool {
+    integral_range(typ).is_some()
+}
+
+/// Helper function for the values an integer type holds
+fn integral_range(typ: &Type) -> Option<(i128, i128)> {
+    let range = match typ {
+        Type::SByte => (i8::MIN.into(), i8::MAX.into()),
+        Type::Byte => (0, u8::MAX.into()),
+        Type::Short => (i16::MIN.into(), i16::MAX.into()),
+        Type::UShort | Type::Char => (0, u16::MAX.into()),
+        Type::Int => (i32::MIN.into(), i32::MAX.into()),
+        Type::UInt => (0, u32::MAX.into()),
+        Type::Long => (i64::MIN.into(), i64::MAX.into()),
+        Type::ULong => (0, u64::MAX.into()),
+        _ => return None,
+    };
+    Some(range)
+}
+
+/// Checks whether a cast to an integer type can lose the value, (byte) i can,
+/// (long) i cannot. A real number can always be too large
+fn can_overflow(from: &Type, to: &Type) -> bool {
+    match (integral_range(from), integral_range(to)) {
+        (Some((from_min, from_max)), Some((to_min, to_max))) => {
+            from_min < to_min || from_max > to_max
+        }
+        (None, Some(_)) => matches!(from, Type::Float | Type::Double),
+        _ => false,
+    }
+}
+
+/// Helper function that obtains the Convert method which checks the conversion
+/// of an int or a long into a smaller type, the DotNetMath helper implements them
+fn narrowing_method(typ: &Type) -> Option<&'static str> {
+    match typ {
+        Type::Short => Some("ToInt16"),
+        Type::UShort => Some("ToUInt16"),
+        Type::Byte => Some("ToByte"),
+        Type::SByte => Some("ToSByte"),
+        Type::UInt => Some("ToUInt32"),
+        Type::ULong => Some("ToUInt64"),
+        _ => None,
+    }
+}
+
+/// Helper function that obtains the Math method checking an operator
+fn exact_method(operator: &BinaryOperator) -> Option<&'static str> {
+    match operator {
+        BinaryOperator::Add => Some("addExact"),
+        BinaryOperator::Sub => Some("subtractExact"),
+        BinaryOperator::Mul => Some("multiplyExact"),
+        _ => None,
+    }
+}
+
+/// Helper function that obtains the Math method of ++ and --
+fn exact_step_method(operator: &UnaryOperator) -> &'static str {
+    match operator {
+        UnaryOperator::UAdd => "incrementExact",
+        _ => "decrementExact",
+    }
+}
+
+/// Helper function for the text of ++ and -- in the messages
+fn java_step(operator: &UnaryOperator) -> &'static str {
+    match operator {
+        UnaryOperator::UAdd => "++",
+        _ => "--",
+    }
+}
+
+/// Helper function for the text of the checked operators in the messages
+fn cs_operator(operator: &BinaryOperator) -> &'static str {
+    match operator {
+        BinaryOperator::Add => "+",
+        BinaryOperator::Sub => "-",
+        _ => "*",
+    }
+}
@@ -0,0 +1,428 @@
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+use crate::semantic::collections::{
+    Collection, collection, collection_member, collection_type, simple_name,
+};
+use crate::semantic::typecheck::cs_type_name;
+
+use super::{
+    Lowering, Scopes, duplicate_key, hoist_target, lower_children, lower_methods,
+    lower_statement_children, method_call, static_call, take_expression,
+};
+
+/// Translates the members of the collections with the table of semantic::collections,
+/// xs.Count becomes xs.size(), xs[i] becomes xs.get(i), ages[name] = 5 becomes
+/// ages.put(name, 5) and new List<int>() becomes new ArrayList<Integer>().
+/// A dictionary throws for a missing or a repeated key, like the C# one
+pub fn lower_collections(program: &mut Program) {
+    let mut pass = CollectionLowering::default();
+    lower_methods(&mut pass, program);
+    program.diagnostics.extend(pass.diagnostics);
+}
+
+#[derive(Default)]
+struct CollectionLowering {
+    scopes: Scopes,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Lowering for CollectionLowering {
+    fn scopes(&mut self) -> &mut Scopes {
+        &mut self.scopes
+    }
+
+    fn lower_statement(&mut self, statement: &mut Statement) {
+        // the read and the write of ages[Key()] += 1 both need the key
+        if let Statement::Assignment {
+            target:
+                Expression {
+                    kind: ExpressionKind::ElementAccess { target, .. },
+                    ..
+                },
+            operator: Some(_),
+            ..
+        } = statement
+            && self.indexer(target).is_some()
+            && hoist_target(&mut self.scopes, statement)
+        {
+            return lower_statement_children(self, statement);
+        }
+
+        // xs[i] = v and xs[i] += v write through the indexer, ages.put(name, v)
+        if let Statement::Assignment {
+            target,
+            operator,
+            value,
+        } = statement
+            && let ExpressionKind::ElementAccess { target, index } = &mut target.kind
+            && let Some((get, set)) = self.indexer(target)
+        {
+            self.lower_expression(target);
+            self.lower_expression(index);
+            self.lower_expression(value);
+            let mut value = take_expression(value);
+            if let Some(operator) = operator.take() {
+                let current = self.read((**target).clone(), (**index).clone(), get);
+                value = Expression::new(ExpressionKind::BinaryExpression {
+                    left: Box::new(current),
+                    operator,
+                    right: Box::new(value),
+                });
+            }
+            let arguments = vec![take_expression(index), value];
+            *statement =
+                Statement::Expression(method_call(take_expression(target), set, arguments));
+            return;
+        }
+
+        // counts[word]++ adds one through the indexer
+        if let Statement::Expression(
+            Expression {
+                kind:
+                    ExpressionKind::PrefixUnaryExpression {
+                        operator: UnaryOperator::UAdd | UnaryOperator::USub,
+                        right: operand,
+                    },
+                ..
+            }
+            | Expression {
+                kind:
+                    ExpressionKind::PostfixUnaryExpression {
+                        left: operand,
+                        operator: UnaryOperator::UAdd | UnaryOperator::USub,
+                    },
+                ..
+            },
+        ) = statement
+            && let ExpressionKind::ElementAccess { target, .. } = &operand.kind
+            && self.indexer(target).is_some()
+            && hoist_target(&mut self.scopes, statement)
+        {
+            return lower_statement_children(self, statement);
+        }
+        if let Statement::Expression(
+            Expression {
+                kind:
+                    ExpressionKind::PrefixUnaryExpression {
+                        operator: operator @ (UnaryOperator::UAdd | UnaryOperator::USub),
+                        right: operand,
+                    },
+                ..
+            }
+            | Expression {
+                kind:
+                    ExpressionKind::PostfixUnaryExpression {
+                        left: operand,
+                        operator: operator @ (UnaryOperator::UAdd | UnaryOperator::USub),
+                    },
+                ..
+            },
+        ) = statement
+            && let ExpressionKind::ElementAccess { target, index } = &mut operand.kind
+            && let Some((get, set)) = self.indexer(target)
+        {
+            let operator = match operator {
+                UnaryOperator::UAdd => BinaryOperator::Add,
+                _ => BinaryOperator::Sub,
+            };
+            self.lower_expression(target);
+            self.lower_expression(index);
+            let current = self.read((**target).clone(), (**index).clone(), get);
+            let value = Expression::new(ExpressionKind::BinaryExpression {
+                left: Box::new(current),
+                operator,
+                right: Box::new(Expression::new(ExpressionKind::Literal(Literal::Int(1)))),
+            });
+            let arguments = vec![take_expression(index), value];
+            *statement =
+                Statement::Expression(method_call(take_expression(target), set, arguments));
+            return;
+        }
+        lower_statement_children(self, statement);
+    }
+
+    fn lower_expression(&mut self, expr: &mut Expression) {
+        match &mut expr.kind {
+            // xs[i] reads through the indexer, xs.get(i)
+            ExpressionKind::ElementAccess { target, .. } => {
+                let indexer = self.indexer(target);
+                lower_children(self, expr);
+                if let Some((get, _)) = indexer
+                    && let ExpressionKind::ElementAccess { target, index } = &mut expr.kind
+                {
+                    *expr = self.read(take_expression(target), take_expression(index), get);
+                }
+            }
+            // xs.Count is a method in java, xs.size()
+            ExpressionKind::MemberAccess { target, name } => {
+                let member = self
+                    .collection(target)
+                    .and_then(|(collection, _)| collection_member(collection, name, true));
+                lower_children(self, expr);
+                if let Some(member) = member
+                    && let ExpressionKind::MemberAccess { target, .. } = &mut expr.kind
+                {
+                    *expr = method_call(take_expression(target), member.java, Vec::new());
+                }
+            }
+            // xs.Add(x) becomes xs.add(x), the name of the method is not a property
+            ExpressionKind::Call { callee, arguments } => {
+                let ExpressionKind::MemberAccess { target, name } = &mut callee.kind else {
+                    lower_children(self, expr);
+                    return;
+                };
+                let found = self
+                    .collection(target)
+                    .and_then(|(collection, type_arguments)| {
+                        collection_member(collection, name, false)
+                            .map(|member| (member, type_arguments))
+                    });
+                self.lower_expression(target);
+                for argument in arguments.iter_mut() {
+                    self.lower_expression(argument);
+                }
+                let Some((member, type_arguments)) = found else {
+                    return;
+                };
+                *name = member.java.to_string();
+                // a repeated key throws, ages.merge(name, 5, (a, b) -> { throw ... })
+                if member.java == "merge" {
+                    arguments.push(duplicate_key(&self.scopes));
+                }
+                // List<int>.remove(5) would remove at the index 5, the boxed 5 is removed
+                if member.boxed
+                    && let Some(element) = type_arguments.first()
+                    && is_primitive(element)
+                {
+                    for argument in arguments.iter_mut() {
+                        *argument = Expression::new(ExpressionKind::CastExpression {
+                            typ: Type::Nullable(Box::new(element.clone())),
+                            value: Box::new(take_expression(argument)),
+                        });
+                    }
+                }
+            }
+            // new List<int>() creates an ArrayList
+            ExpressionKind::ObjectCreation { typ, .. } => {
+                if let Type::Generic(name, _) = typ
+                    && let Some(collection) = collection(name)
+                {
+                    *name = simple_name(collection.class).to_string();
+                }
+                lower_children(self, expr);
+            }
+            ExpressionKind::CollectionInitializer { .. } => {
+                *expr = self.initializer(take_expression(expr));
+                self.lower_expression(expr);
+            }
+            _ => lower_children(self, expr),
+        }
+    }
+}
+
+impl CollectionLowering {
+    /// Finds the collection an expression is, with its type arguments
+    fn collection(&self, expr: &Expression) -> Option<(&'static Collection, Vec<Type>)> {
+        let typ = self.scopes.type_of(expr)?;
+        let (collection, arguments) = collection_type(&typ)?;
+        Some((collection, arguments.to_vec()))
+    }
+
+    /// Finds the java methods of the indexer of a collection, get and set for a list
+    fn indexer(&self, expr: &Expression) -> Option<(&'static str, &'static str)> {
+        self.collection(expr)?.0.indexer
+    }
+
+    /// Reads an element through the indexer, xs.get(i). A dictionary throws for a
+    /// missing key where get gives null, ages.computeIfAbsent(name, key -> { throw ... })
+    fn read(&self, target: Expression, index: Expression, get: &str) -> Expression {
+        if self
+            .collection(&target)
+            .is_none_or(|(collection, _)| collection.interface != "java.util.Map")
+        {
+            return method_call(target, get, vec![index]);
+        }
+        let key = self.scopes.fresh_name("key");
+        let message = [
+            ExpressionKind::Literal(Literal::String("The given key '".to_string())),
+            ExpressionKind::Variable(key.clone()),
+            ExpressionKind::Literal(Literal::String(
+                "' was not present in the dictionary.".to_string(),
+            )),
+        ]
+        .into_iter()
+        .map(Expression::new)
+        .reduce(|left, right| {
+            Expression::new(ExpressionKind::BinaryExpression {
+                left: Box::new(left),
+                operator: BinaryOperator::Add,
+                right: Box::new(right),
+            })
+        });
+        let exception = Expression::new(ExpressionKind::ObjectCreation {
+            typ: Type::Named("KeyNotFoundException".to_string()),
+            arguments: message.into_iter().collect(),
+        });
+        let missing = Expression::new(ExpressionKind::Lambda {
+            parameters: vec![Variable {
+                typ: Type::Unknown,
+                name: key,
+                implicit: true,
+                constant: false,
+            }],
+            body: LambdaBody::Block(vec![Statement::Throw(exception)]),
+        });
+        method_call(target, "computeIfAbsent", vec![index, missing])
+    }
+
+    /// Creates a collection with its elements. new List<int> { 1, 2 } becomes
+    /// new ArrayList<Integer>(Arrays.asList(1, 2)), a dictionary collects its pairs
+    /// with the merge function of Add or of the indexer, new Dictionary<string, int>
+    /// { ["a"] = 1 } becomes Stream.of(new SimpleImmutableEntry<>("a", 1))
+    /// .collect(Collectors.toMap(Entry::getKey, Entry::getValue, (a, b) -> b, LinkedHashMap::new))
+    fn initializer(&mut self, expr: Expression) -> Expression {
+        let ExpressionKind::CollectionInitializer {
+            creation,
+            elements,
+            indexed,
+        } = expr.kind
+        else {
+            return expr;
+        };
+        let found = self.collection(&creation);
+        let ExpressionKind::ObjectCreation { typ, arguments } = creation.kind else {
+            return *creation;
+        };
+        if elements.is_empty() {
+            return Expression::new(ExpressionKind::ObjectCreation { typ, arguments });
+        }
+        let (collection, type_arguments) = match found {
+            Some((collection, type_arguments))
+                if collection.interface != "java.util.Queue"
+                    && collection.interface != "java.util.Deque" =>
+            {
+                (collection, type_arguments)
+            }
+            _ => {
+                self.diagnostics.push(Diagnostic::error(format!(
+                    "the collection initializer of `{}` in {} is not supported",
+                    cs_type_name(&typ),
+                    self.scopes.method
+                )));
+                return Expression::new(ExpressionKind::ObjectCreation { typ, arguments });
+            }
+        };
+        // the capacity makes no difference, the elements of another collection would
+        if arguments
+            .iter()
+            .any(|argument| self.scopes.type_of(argument) != Some(Type::Int))
+        {
+            self.diagnostics.push(Diagnostic::error(format!(
+                "the collection initializer in {} cannot add to a copied collection, add its elements after creating it",
+                self.scopes.method
+            )));
+        }
+        // the elements are boxed by their own type, Arrays.asList(1) is no List<Long>
+        let elements: Vec<Vec<Expression>> = elements
+            .into_iter()
+            .map(|values| {
+                values
+                    .into_iter()
+                    .zip(&type_arguments)
+                    .map(|(value, typ)| self.convert(value, typ))
+                    .collect()
+            })
+            .collect();
+
+        if collection.interface != "java.util.Map" {
+            let values = elements.into_iter().flatten().collect();
+            return Expression::new(ExpressionKind::ObjectCreation {
+                typ,
+                arguments: vec![static_call("Arrays", "asList", values)],
+            });
+        }
+        let pairs = elements
+            .into_iter()
+            .map(|pair| {
+                Expression::new(ExpressionKind::ObjectCreation {
+                    typ: Type::Generic("KeyValuePair".to_string(), Vec::new()),
+                    arguments: pair,
+                })
+            })
+            .collect();
+        let entry = |name: &str| {
+            Expression::new(ExpressionKind::MethodReference {
+                target: Box::new(Expression::new(ExpressionKind::Variable(
+                    "Entry".to_string(),
+                ))),
+                name: name.to_string(),
+            })
+        };
+        // the indexer keeps the last value of a key, Add throws
+        let merge = if indexed {
+            let (first, second) = (self.scopes.fresh_name("a"), self.scopes.fresh_name("b"));
+            let parameter = |name: &str| Variable {
+                typ: Type::Unknown,
+                name: name.to_string(),
+                implicit: true,
+                constant: false,
+            };
+            Expression::new(ExpressionKind::Lambda {
+                parameters: vec![parameter(&first), parameter(&second)],
+                body: LambdaBody::Expression(Box::new(Expression::new(ExpressionKind::Variable(
+                    second,
+                )))),
+            })
+        } else {
+            duplicate_key(&self.scopes)
+        };
+        let map = Expression::new(ExpressionKind::MethodReference {
+            target: Box::new(Expression::new(ExpressionKind::Variable(
+                simple_name(collection.class).to_string(),
+            ))),
+            name: "new".to_string(),
+        });
+        let collector = static_call(
+            "Collectors",
+            "toMap",
+            vec![entry("getKey"), entry("getValue"), merge, map],
+        );
+        method_call(
+            static_call("Stream", "of", pairs),
+            "collect",
+            vec![collector],
+        )
+    }
+
+    /// Casts an element to the primitive type of its collection, when it has another type
+    fn convert(&self, value: Expression, typ: &Type) -> Expression {
+        match self.scopes.type_of(&value) {
+            Some(found) if is_primitive(typ) && found != *typ => {
+                Expression::new(ExpressionKind::CastExpression {
+                    typ: typ.clone(),
+                    value: Box::new(value),
+                })
+            }
+            _ => value,
+        }
+    }
+}
+
+/// Helper function that checks if a type is a java primitive, boxed in a collection
+fn is_primitive(typ: &Type) -> bool {
+    matches!(
+        typ,
+        Type::Int
+            | Type::UInt
+            | Type::Long
+            | Type::ULong
+            | Type::Short
+            | Type::UShort
+            | Type::Byte
+            | Type::SByte
+            | Type::Char
+            | Type::Float
+            | Type::Double
+            | Type::Bool
+    )
+}
@@ -0,0 +1,521 @@
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+
+use super::unsigned::{to_unsigned_string, widen};
+use super::{
+    Lowering, Scopes, hoist_target, lower_children, lower_methods, lower_statement_children,
+    method_call, promote, static_call, static_field, take_expression,
+};
+
+/// The precision of a division, C# decimals keep up to 29 significant digits
+const DECIMAL_PRECISION: i32 = 29;
+
+/// Translates the C# decimal type into java.math.BigDecimal. Java has no operators
+/// for it, so arithmetic and comparisons become method calls, the values mixed
+/// with decimals are converted with BigDecimal.valueOf
+pub fn lower_decimal(program: &mut Program) {
+    let mut pass = DecimalLowering::default();
+    lower_methods(&mut pass, program);
+    program.diagnostics.extend(pass.diagnostics);
+}
+
+#[derive(Default)]
+struct DecimalLowering {
+    scopes: Scopes,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Lowering for DecimalLowering {
+    fn scopes(&mut self) -> &mut Scopes {
+        &mut self.scopes
+    }
+
+    fn lower_expression(&mut self, expr: &mut Expression) {
+        match &mut expr.kind {
+            ExpressionKind::BinaryExpression { left, right, .. } => {
+                // the types are computed before the children are rewritten
+                let (left_type, right_type) = self.scopes.operand_types(left, right);
+                lower_children(self, expr);
+                self.lower_binary(expr, left_type, right_type);
+            }
+            ExpressionKind::PrefixUnaryExpression { operator, right } => {
+                let is_decimal = self.scopes.type_of(right) == Some(Type::Decimal);
+                let operator = operator.clone();
+                lower_children(self, expr);
+                if is_decimal
+                    && let ExpressionKind::PrefixUnaryExpression { right, .. } = &mut expr.kind
+                {
+                    let value = take_expression(right);
+                    match operator {
+                        UnaryOperator::Neg => *expr = method_call(value, "negate", vec![]),
+                        UnaryOperator::Plus => *expr = value,
+                        // ++d gives the new value, (d = d.add(BigDecimal.ONE))
+                        UnaryOperator::UAdd | UnaryOperator::USub => {
+                            match self.increment(value, &operator) {
+                                Ok(incremented) => *expr = incremented,
+                                Err(value) => **right = value,
+                            }
+                        }
+                        // ~d, the type check reported it
+                        _ => **right = value,
+                    }
+                }
+            }
+            // d++ gives the old value, which is the new one minus one,
+            // (d = d.add(BigDecimal.ONE)).subtract(BigDecimal.ONE)
+            ExpressionKind::PostfixUnaryExpression { left, operator }
+                if matches!(operator, UnaryOperator::UAdd | UnaryOperator::USub)
+                    && self.scopes.type_of(left) == Some(Type::Decimal) =>
+            {
+                let operator = operator.clone();
+                lower_children(self, expr);
+                if let ExpressionKind::PostfixUnaryExpression { left, .. } = &mut expr.kind {
+                    let undo = match operator {
+                        UnaryOperator::UAdd => UnaryOperator::USub,
+                        _ => UnaryOperator::UAdd,
+                    };
+                    match self.increment(take_expression(left), &operator) {
+                        Ok(incremented) => {
+                            *expr = method_call(incremented, step_method(&undo), vec![one()])
+                        }
+                        Err(value) => **left = value,
+                    }
+                }
+            }
+            ExpressionKind::CastExpression { typ, value } => {
+                let value_type = self.scopes.type_of(value);
+                let typ = typ.clone();
+                lower_children(self, expr);
+                let ExpressionKind::CastExpression { value, .. } = &mut expr.kind else {
+                    return;
+                };
+                if typ == Type::Decimal {
+                    // (decimal) x becomes BigDecimal.valueOf(x)
+                    let mut value = take_expression(value);
+                    to_decimal(&mut value, value_type.as_ref());
+                    *expr = value;
+                } else if value_type == Some(Type::Decimal) {
+                    // (int) d becomes d.intValue()
+                    lower_decimal_cast(expr, &typ);
+                }
+            }
+            ExpressionKind::Call { callee, arguments } => {
+                let path = callee.path();
+                let argument_types: Vec<Option<Type>> = arguments
+                    .iter()
+                    .map(|argument| self.scopes.type_of(argument))
+                    .collect();
+                // Twice(5) passes a decimal to Twice(decimal d)
+                let parameter_types: Vec<Option<Type>> = match self
+                    .scopes
+                    .called_method(callee, arguments)
+                {
+                    Some(method) => (0..arguments.len())
+                        .map(|index| method.parameters.get(index).map(|p| p.variable.typ.clone()))
+                        .collect(),
+                    None => Vec::new(),
+                };
+                // d.ToString()
+                let target_type = match &callee.kind {
+                    ExpressionKind::MemberAccess { target, name } if name == "ToString" => {
+                        self.scopes.type_of(target)
+                    }
+                    _ => None,
+                };
+                lower_children(self, expr);
+                let ExpressionKind::Call { callee, arguments } = &mut expr.kind else {
+                    return;
+                };
+                for (index, argument) in arguments.iter_mut().enumerate() {
+                    if let Some(Some(Type::Decimal)) = parameter_types.get(index) {
+                        to_decimal(argument, argument_types[index].as_ref());
+                    }
+                }
+
+                if target_type == Some(Type::Decimal) && arguments.is_empty() {
+                    // the plain string never switches to the 1E-7 notation, just like C#
+                    if let ExpressionKind::MemberAccess { name, .. } = &mut callee.kind {
+                        *name = "toPlainString".to_string();
+                    }
+                    return;
+                }
+
+                let is_decimal = argument_types.first() == Some(&Some(Type::Decimal));
+                match path.as_deref() {
+                    // decimal.Parse(s) becomes new BigDecimal(s)
+                    Some("decimal.Parse") => {
+                        *expr = Expression::new(ExpressionKind::ObjectCreation {
+                            typ: Type::Decimal,
+                            arguments: std::mem::take(arguments),
+                        });
+                    }
+                    Some("Math.Round" | "decimal.Round") if is_decimal => {
+                        *expr = lower_round(std::mem::take(arguments));
+                    }
+                    Some("Math.Abs") if is_decimal => {
+                        let value = take_expression(&mut arguments[0]);
+                        *expr = method_call(value, "abs", vec![]);
+                    }
+                    Some(path @ ("Math.Max" | "Math.Min")) if arguments.len() == 2 => {
+                        let (Some(left_type), Some(right_type)) =
+                            (&argument_types[0], &argument_types[1])
+                        else {
+                            return;
+                        };
+                        if promote(left_type, right_type) != Some(Type::Decimal) {
+                            return;
+                        }
+                        // Math.Max(a, b) becomes a.max(b)
+                        let mut right = arguments.pop().unwrap();
+                        let mut left = arguments.pop().unwrap();
+                        to_decimal(&mut left, Some(left_type));
+                        to_decimal(&mut right, Some(right_type));
+                        let method = if path == "Math.Max" { "max" } else { "min" };
+                        *expr = method_call(left, method, vec![right]);
+                    }
+                    _ => {}
+                }
+            }
+            _ => lower_children(self, expr),
+        }
+    }
+
+    fn lower_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::VariableDeclaration {
+                variable,
+                value: Some(value),
+            } => {
+                let value_type = self.scopes.type_of(value);
+                let is_decimal = variable.typ == Type::Decimal;
+                lower_statement_children(self, statement);
+                if is_decimal
+                    && let Statement::VariableDeclaration {
+                        value: Some(value), ..
+                    } = statement
+                {
+                    to_decimal(value, value_type.as_ref());
+                }
+            }
+            Statement::Assignment {
+                target,
+                operator,
+                value,
+            } => {
+                let target_type = self.scopes.type_of(target);
+                let (_, value_type) = self.scopes.operand_types(target, value);
+                let operator = operator.clone();
+                // the target is read and written, Get().Price += x keeps Get() in a variable
+                if operator.is_some()
+                    && target_type == Some(Type::Decimal)
+                    && hoist_target(&mut self.scopes, statement)
+                {
+                    return lower_statement_children(self, statement);
+                }
+                lower_statement_children(self, statement);
+
+                let Statement::Assignment {
+                    target,
+                    operator: assignment_operator,
+                    value,
+                } = statement
+                else {
+                    return;
+                };
+                if target_type != Some(Type::Decimal) {
+                    return;
+                }
+                match operator {
+                    // total += x becomes total = total.add(x)
+                    Some(op) => {
+                        let mut binary = Expression::new(ExpressionKind::BinaryExpression {
+                            left: Box::new(target.clone()),
+                            operator: op,
+                            right: Box::new(take_expression(value)),
+                        });
+                        self.lower_binary(&mut binary, target_type, value_type);
+                        *value = binary;
+                        *assignment_operator = None;
+                    }
+                    None => to_decimal(value, value_type.as_ref()),
+                }
+            }
+            Statement::Return(Some(value)) => {
+                let value_type = self.scopes.type_of(value);
+                let is_decimal = self.scopes.return_type == Some(Type::Decimal);
+                lower_statement_children(self, statement);
+                if is_decimal && let Statement::Return(Some(value)) = statement {
+                    to_decimal(value, value_type.as_ref());
+                }
+            }
+            // d++ on its own line becomes d = d.add(BigDecimal.ONE)
+            Statement::Expression(
+                Expression {
+                    kind:
+                        ExpressionKind::PostfixUnaryExpression {
+                            left: target,
+                            operator,
+                        },
+                    ..
+                }
+                | Expression {
+                    kind:
+                        ExpressionKind::PrefixUnaryExpression {
+                            operator,
+                            right: target,
+                        },
+                    ..
+                },
+            ) if matches!(operator, UnaryOperator::UAdd | UnaryOperator::USub)
+                && self.scopes.type_of(target) == Some(Type::Decimal) =>
+            {
+                if hoist_target(&mut self.scopes, statement) {
+                    return lower_statement_children(self, statement);
+                }
+                let Statement::Expression(
+                    Expression {
+                        kind:
+                            ExpressionKind::PostfixUnaryExpression {
+                                left: target,
+                                operator,
+                            },
+                        ..
+                    }
+                    | Expression {
+                        kind:
+                            ExpressionKind::PrefixUnaryExpression {
+                                operator,
+                                right: target,
+                            },
+                        ..
+                    },
+                ) = statement
+                else {
+                    return;
+                };
+                let operator = operator.clone();
+                let mut target = take_expression(target);
+                self.lower_expression(&mut target);
+                let value = method_call(target.clone(), step_method(&operator), vec![one()]);
+                *statement = Statement::Assignment {
+                    target,
+                    operator: None,
+                    value,
+                };
+            }
+            _ => lower_statement_children(self, statement),
+        }
+    }
+}
+
+impl DecimalLowering {
+    /// Adds one to a decimal inside an expression, (d = d.add(BigDecimal.ONE)). The target
+    /// is read twice, it has to be a variable, a field or an array element without side
+    /// effects, prices[0]++ on a list would need a get and a set in one expression.
+    /// Gives the target back when it is none of them
+    fn increment(
+        &mut self,
+        target: Expression,
+        operator: &UnaryOperator,
+    ) -> Result<Expression, Expression> {
+        if !self.scopes.is_plain_target(&target) {
+            self.diagnostics.push(Diagnostic::error(format!(
+                "the decimal {} inside an expression in {} can only change a variable, a field or an array element, put it in a statement of its own",
+                if *operator == UnaryOperator::UAdd { "++" } else { "--" },
+                self.scopes.method
+            )));
+            return Err(target);
+        }
+        let value = method_call(target.clone(), step_method(operator), vec![one()]);
+        Ok(Expression::new(ExpressionKind::Assignment {
+            target: Box::new(target),
+            value: Box::new(value),
+        }))
+    }
+
+    /// Rewrites a binary expression whose children are already lowered,
+    /// when it works on decimals
+    fn lower_binary(
+        &mut self,
+        expr: &mut Expression,
+        left_type: Option<Type>,
+        right_type: Option<Type>,
+    ) {
+        let ExpressionKind::BinaryExpression {
+            left,
+            operator,
+            right,
+        } = &mut expr.kind
+        else {
+            return;
+        };
+        let (Some(left_type), Some(right_type)) = (left_type, right_type) else {
+            return;
+        };
+        if promote(&left_type, &right_type) != Some(Type::Decimal) {
+            return;
+        }
+
+        let comparison = match operator {
+            BinaryOperator::Eq
+            | BinaryOperator::Ne
+            | BinaryOperator::Lt
+            | BinaryOperator::Gt
+            | BinaryOperator::Le
+            | BinaryOperator::Ge => Some(operator.clone()),
+            _ => None,
+        };
+        let method = match operator {
+            BinaryOperator::Add => "add",
+            BinaryOperator::Sub => "subtract",
+            BinaryOperator::Mul => "multiply",
+            BinaryOperator::Div => "divide",
+            BinaryOperator::Mod => "remainder",
+            // equals also compares the scale, 1.0m == 1.00m is true in C#
+            _ if comparison.is_some() => "compareTo",
+            // d & 1 and d && e, the type check reported them
+            _ => return,
+        };
+
+        let mut left = take_expression(left);
+        let mut right = take_expression(right);
+        to_decimal(&mut left, Some(&left_type));
+        to_decimal(&mut right, Some(&right_type));
+
+        let mut arguments = vec![right];
+        // an exact division could never end for 10m / 3
+        if method == "divide" {
+            arguments.push(Expression::new(ExpressionKind::ObjectCreation {
+                typ: Type::Named("MathContext".to_string()),
+                arguments: vec![
+                    Expression::new(ExpressionKind::Literal(Literal::Int(DECIMAL_PRECISION))),
+                    static_field("RoundingMode", "HALF_EVEN"),
+                ],
+            }));
+        }
+        let call = method_call(left, method, arguments);
+
+        *expr = match comparison {
+            // a < b becomes a.compareTo(b) < 0
+            Some(operator) => Expression::new(ExpressionKind::BinaryExpression {
+                left: Box::new(call),
+                operator,
+                right: Box::new(Expression::new(ExpressionKind::Literal(Literal::Int(0)))),
+            }),
+            None => call,
+        };
+    }
+}
+
+/// Helper function for the method of ++ and --, add and subtract
+fn step_method(operator: &UnaryOperator) -> &'static str {
+    match operator {
+        UnaryOperator::UAdd => "add",
+        _ => "subtract",
+    }
+}
+
+/// Helper function for BigDecimal.ONE
+fn one() -> Expression {
+    static_field("BigDecimal", "ONE")
+}
+
+/// Rewrites the cast of a decimal to another number, (int) d becomes d.intValue()
+fn lower_decimal_cast(expr: &mut Expression, typ: &Type) {
+    let ExpressionKind::CastExpression { value, .. } = &mut expr.kind else {
+        return;
+    };
+    let method = match typ {
+        Type::Int | Type::UInt | Type::Char => "intValue",
+        Type::Long | Type::ULong => "longValue",
+        Type::Short | Type::UShort => "shortValue",
+        Type::Byte | Type::SByte => "byteValue",
+        Type::Float => "floatValue",
+        Type::Double => "doubleValue",
+        _ => return,
+    };
+    let value = method_call(take_expression(value), method, vec![]);
+    *expr = match typ {
+        // there is no charValue, the int still needs the cast
+        Type::Char => Expression::new(ExpressionKind::CastExpression {
+            typ: Type::Char,
+            value: Box::new(value),
+        }),
+        _ => value,
+    };
+}
+
+/// Rewrites Math.Round(d, digits, mode) into d.setScale(digits, RoundingMode.X),
+/// the arguments are already lowered
+fn lower_round(mut arguments: Vec<Expression>) -> Expression {
+    let mut digits = Expression::new(ExpressionKind::Literal(Literal::Int(0)));
+    // C# rounds to the even neighbour, unless told otherwise
+    let mut mode = "HALF_EVEN";
+    for argument in arguments.drain(1..) {
+        match argument.path().as_deref() {
+            Some("MidpointRounding.ToEven") => mode = "HALF_EVEN",
+            Some("MidpointRounding.AwayFromZero") => mode = "HALF_UP",
+            Some("MidpointRounding.ToZero") => mode = "DOWN",
+            Some("MidpointRounding.ToNegativeInfinity") => mode = "FLOOR",
+            Some("MidpointRounding.ToPositiveInfinity") => mode = "CEILING",
+            _ => digits = argument,
+        }
+    }
+    let value = arguments.pop().expect("Math.Round needs a value");
+    method_call(
+        value,
+        "setScale",
+        vec![digits, static_field("RoundingMode", mode)],
+    )
+}
+
+/// Converts a value into a BigDecimal, if it is some other kind of number
+fn to_decimal(expr: &mut Expression, typ: Option<&Type>) {
+    // the common constants already exist
+    if let ExpressionKind::Literal(Literal::Int(n)) = &mut expr.kind {
+        let name = match n {
+            0 => Some("ZERO"),
+            1 => Some("ONE"),
+            10 => Some("TEN"),
+            _ => None,
+        };
+        if let Some(name) = name {
+            *expr = static_field("BigDecimal", name);
+            return;
+        }
+    }
+
+    let Some(typ) = typ else {
+        return;
+    };
+    // the parentheses are not needed inside a call
+    if let ExpressionKind::ParenthesizedExpression(inner) = &mut expr.kind {
+        *expr = take_expression(inner);
+    }
+    match typ {
+        // a ulong may not fit in a long, it goes through its string
+        Type::ULong => {
+            to_unsigned_string(expr, typ);
+            let value = take_expression(expr);
+            *expr = Expression::new(ExpressionKind::ObjectCreation {
+                typ: Type::Decimal,
+                arguments: vec![value],
+            });
+        }
+        Type::Int
+        | Type::Long
+        | Type::Short
+        | Type::Byte
+        | Type::SByte
+        | Type::UInt
+        | Type::UShort
+        | Type::Char
+        | Type::Float
+        | Type::Double => {
+            widen(expr, typ, &Type::Decimal);
+            let value = take_expression(expr);
+            *expr = static_call("BigDecimal", "valueOf", vec![value]);
+        }
+        _ => {}
+    }
+}
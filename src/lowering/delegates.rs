@@ -0,0 +1,190 @@
+use crate::ast::*;
+use crate::semantic::{
+    functional::{function_type, functional_method},
+    symbols::SymbolKind,
+};
+
+use super::{
+    Lowering, Scopes, lower_children, lower_methods, lower_statement_children, method_call,
+    take_expression,
+};
+
+/// Translates the delegates into the java functional interfaces. Calling a delegate,
+/// f(x) or f.Invoke(x), calls the method of its interface, f.apply(x) for a Function.
+/// A method group becomes a method reference, Square becomes Program::square
+/// and Console.WriteLine becomes System.out::println
+pub fn lower_delegates(program: &mut Program) {
+    let mut pass = DelegateLowering::default();
+    lower_methods(&mut pass, program);
+}
+
+#[derive(Default)]
+struct DelegateLowering {
+    scopes: Scopes,
+}
+
+impl Lowering for DelegateLowering {
+    fn scopes(&mut self) -> &mut Scopes {
+        &mut self.scopes
+    }
+
+    fn lower_statement(&mut self, statement: &mut Statement) {
+        // Console.WriteLine is a method group only where a delegate is expected
+        match statement {
+            Statement::VariableDeclaration {
+                variable,
+                value: Some(value),
+            } => self.member_group(value, &variable.typ),
+            Statement::Assignment {
+                target,
+                operator: None,
+                value,
+            } => {
+                if let Some(typ) = self.scopes.type_of(target) {
+                    self.member_group(value, &typ);
+                }
+            }
+            Statement::Return(Some(value)) => {
+                if let Some(typ) = self.scopes.return_type.clone() {
+                    self.member_group(value, &typ);
+                }
+            }
+            _ => {}
+        }
+        lower_statement_children(self, statement);
+    }
+
+    fn lower_expression(&mut self, expr: &mut Expression) {
+        // Apply(Square, 5) passes the method Square
+        if self.is_method_group(expr) {
+            if let ExpressionKind::Variable(name) = &mut expr.kind {
+                let name = std::mem::take(name);
+                *expr = self.method_reference(None, name);
+            }
+            return;
+        }
+        match &mut expr.kind {
+            ExpressionKind::Call { callee, arguments } => {
+                for (index, argument) in arguments.iter_mut().enumerate() {
+                    if let Some(typ) = self.scopes.parameter_type(callee, index) {
+                        self.member_group(argument, &typ);
+                    }
+                }
+                // f(x) and f.Invoke(x) become f.apply(x)
+                let delegate = match &callee.kind {
+                    ExpressionKind::MemberAccess { target, name } if name == "Invoke" => {
+                        self.scopes.type_of(target).map(|typ| (typ, true))
+                    }
+                    ExpressionKind::Variable(name) => {
+                        self.scopes.lookup(name).cloned().map(|typ| (typ, false))
+                    }
+                    _ => None,
+                }
+                .filter(|(typ, _)| function_type(typ, &self.scopes.delegates).is_some());
+
+                // the name of a called method is not a method group
+                if !matches!(callee.kind, ExpressionKind::Variable(_)) {
+                    self.lower_expression(callee);
+                }
+                for argument in arguments.iter_mut() {
+                    self.lower_expression(argument);
+                }
+
+                if let Some((typ, invoke)) = delegate {
+                    let target = match take_expression(callee) {
+                        Expression {
+                            kind: ExpressionKind::MemberAccess { target, .. },
+                            ..
+                        } if invoke => *target,
+                        callee => callee,
+                    };
+                    let arguments = std::mem::take(arguments);
+                    *expr = method_call(target, functional_method(&typ), arguments);
+                }
+            }
+            _ => lower_children(self, expr),
+        }
+    }
+}
+
+impl DelegateLowering {
+    /// Checks if a name is a method of the class and not a variable, Square in Apply(Square, 5)
+    fn is_method_group(&self, expr: &Expression) -> bool {
+        let ExpressionKind::Variable(name) = &expr.kind else {
+            return false;
+        };
+        self.scopes.methods.contains_key(name)
+            && match self.scopes.declaration(expr) {
+                Some(symbol) => symbol.kind == SymbolKind::Method,
+                None => self.scopes.lookup(name).is_none(),
+            }
+    }
+
+    /// Turns a member access given to a delegate into a method reference,
+    /// this.Square or Math.Abs. Only the methods we know of are turned
+    fn member_group(&self, value: &mut Expression, typ: &Type) {
+        if function_type(typ, &self.scopes.delegates).is_none() {
+            return;
+        }
+        let path = value.path();
+        let ExpressionKind::MemberAccess { target, name } = &mut value.kind else {
+            return;
+        };
+        let reference = match (path.as_deref(), &target.kind) {
+            // the console is System.out in java
+            (Some("Console.WriteLine"), _) => Expression::new(ExpressionKind::MethodReference {
+                target: Box::new(Expression::new(ExpressionKind::Variable(
+                    "System.out".to_string(),
+                ))),
+                name: "println".to_string(),
+            }),
+            (Some("Console.Write"), _) => Expression::new(ExpressionKind::MethodReference {
+                target: Box::new(Expression::new(ExpressionKind::Variable(
+                    "System.out".to_string(),
+                ))),
+                name: "print".to_string(),
+            }),
+            // this.Square or Program.Square
+            (_, ExpressionKind::This) if self.scopes.methods.contains_key(name.as_str()) => {
+                self.method_reference(Some(Expression::new(ExpressionKind::This)), name.clone())
+            }
+            (_, ExpressionKind::Variable(class))
+                if *class == self.scopes.class
+                    && self.scopes.methods.contains_key(name.as_str()) =>
+            {
+                self.method_reference(None, name.clone())
+            }
+            // the static methods of the library, Math.Abs becomes Math::abs
+            (_, ExpressionKind::Variable(class))
+                if self.scopes.is_class(target)
+                    && class.starts_with(|c: char| c.is_ascii_uppercase()) =>
+            {
+                Expression::new(ExpressionKind::MethodReference {
+                    target: target.clone(),
+                    name: name.clone(),
+                })
+            }
+            _ => return,
+        };
+        *value = reference;
+    }
+
+    /// Creates the reference to a method of the class, Program::square for a
+    /// static method and this::square for the others
+    fn method_reference(&self, target: Option<Expression>, name: String) -> Expression {
+        let is_static = self
+            .scopes
+            .methods
+            .get(&name)
+            .is_some_and(|candidates| candidates.iter().all(|candidate| candidate.is_static));
+        let target = if is_static {
+            Expression::new(ExpressionKind::Variable(self.scopes.class.clone()))
+        } else {
+            target.unwrap_or(Expression::new(ExpressionKind::This))
+        };
+        Expression::new(ExpressionKind::MethodReference {
+            target: Box::new(target),
+            name,
+        })
+    }
+}
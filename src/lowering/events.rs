@@ -0,0 +1,565 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+
+use super::{
+    Lowering, Scopes, is_pure, lower_children, lower_methods, lower_statement_children,
+    method_call, take_expression,
+};
+use crate::semantic::{functional::function_type, symbols::SymbolKind};
+
+/// Translates the events into lists of listeners. An event Action<string> Clicked
+/// becomes a clickedListeners list with the addClickedListener and
+/// removeClickedListener methods, Clicked += f subscribes with addClickedListener(f)
+/// and raising the event, Clicked?.Invoke(x), calls every listener of a copy of the list.
+/// A method group is kept in a field, Clicked -= OnClick removes the object Clicked += OnClick
+/// added. var handler = Clicked copies the list, raising handler calls the copied listeners.
+/// The other x?.M() calls become if (x != null) x.M(), or x == null ? null : x.M()
+/// when their value is used
+pub fn lower_events(program: &mut Program) {
+    // any class can subscribe to the events of another one
+    let events = program
+        .classes
+        .iter()
+        .map(|class| {
+            let events = class
+                .fields
+                .iter()
+                .filter(|field| is_event(field))
+                .map(|field| field.variable.clone())
+                .collect();
+            (class.name.clone(), events)
+        })
+        .collect();
+    let mut pass = EventLowering {
+        events,
+        ..Default::default()
+    };
+    lower_methods(&mut pass, program);
+    program.diagnostics.extend(pass.diagnostics);
+
+    for class in &mut program.classes {
+        create_listener_lists(class);
+        let (cached, others): (Vec<CachedListener>, _) = std::mem::take(&mut pass.cached)
+            .into_iter()
+            .partition(|cached| cached.class == class.name);
+        pass.cached = others;
+        class
+            .fields
+            .extend(cached.into_iter().map(|cached| cached.field));
+    }
+}
+
+#[derive(Default)]
+struct EventLowering {
+    scopes: Scopes,
+    events: HashMap<String, Vec<Variable>>, // the events of every class
+    copies: HashSet<String>,                // the locals copying the listeners of an event
+    cached: Vec<CachedListener>,            // the fields keeping the method groups
+    diagnostics: Vec<Diagnostic>,
+}
+
+// a method group given to an event, kept in a field so it can be removed again,
+// private final Consumer<String> onClickListener = this::onClick
+struct CachedListener {
+    class: String,  // the class of the method and of the field
+    method: String, // the method of the group, OnClick
+    field: Field,   // the field, with the type of the event
+}
+
+// an event used by the code, b.Clicked is the Clicked event of the object b
+struct EventAccess {
+    owner: Option<Expression>, // the object or class of the event, None inside its class
+    event: Variable,           // the type and name of the event
+    copy: bool,                // a local with a copy of the listeners, var handler = Clicked
+}
+
+impl EventAccess {
+    /// The list of listeners, clickedListeners or b.clickedListeners
+    fn listeners(&self) -> Expression {
+        if self.copy {
+            return Expression::new(ExpressionKind::Variable(self.event.name.clone()));
+        }
+        let name = listeners_name(&self.event.name);
+        match &self.owner {
+            Some(owner) => Expression::new(ExpressionKind::MemberAccess {
+                target: Box::new(owner.clone()),
+                name,
+            }),
+            None => Expression::new(ExpressionKind::Variable(name)),
+        }
+    }
+
+    /// Adds or removes a listener, b.AddClickedListener(f) or handler.add(f) for a copy
+    fn subscribe(self, adding: bool, listener: Expression) -> Expression {
+        if self.copy {
+            let method = if adding { "add" } else { "remove" };
+            return method_call(self.listeners(), method, vec![listener]);
+        }
+        let method = match adding {
+            true => add_listener_name(&self.event.name),
+            false => remove_listener_name(&self.event.name),
+        };
+        let callee = match self.owner {
+            Some(owner) => ExpressionKind::MemberAccess {
+                target: Box::new(owner),
+                name: method,
+            },
+            None => ExpressionKind::Variable(method),
+        };
+        Expression::new(ExpressionKind::Call {
+            callee: Box::new(Expression::new(callee)),
+            arguments: vec![listener],
+        })
+    }
+}
+
+impl Lowering for EventLowering {
+    fn scopes(&mut self) -> &mut Scopes {
+        &mut self.scopes
+    }
+
+    fn lower_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            // Clicked += f subscribes, Clicked -= f unsubscribes
+            Statement::Assignment {
+                target,
+                operator: Some(operator @ (BinaryOperator::Add | BinaryOperator::Sub)),
+                value,
+            } => {
+                if let Some(access) = self.event_access(target) {
+                    let adding = *operator == BinaryOperator::Add;
+                    if let Some(cached) = self.cached_listener(value, &access.event.typ) {
+                        *value = cached;
+                    } else if !adding && !self.is_stored(value) {
+                        self.diagnostics.push(Diagnostic::warning(format!(
+                            "removing a lambda or the method of another object from the event `{}` in {} does nothing in java, \
+                             every method reference is a new object, keep the listener in a variable",
+                            access.event.name, self.scopes.method
+                        )));
+                    }
+                    let value = take_expression(value);
+                    *statement = Statement::Expression(access.subscribe(adding, value));
+                }
+            }
+            // var handler = Clicked copies the listeners, like the delegate it reads
+            Statement::VariableDeclaration {
+                variable,
+                value: Some(value),
+            } => {
+                if let Some(access) = self.event_access(value)
+                    && !access.copy
+                {
+                    let typ = access.event.typ.clone();
+                    self.copies.insert(variable.name.clone());
+                    variable.typ = Type::Generic("List".to_string(), vec![typ]);
+                    *value = Expression::new(ExpressionKind::ObjectCreation {
+                        typ: Type::Generic("ArrayList".to_string(), Vec::new()),
+                        arguments: vec![access.listeners()],
+                    });
+                }
+            }
+            // x?.M() as a statement only runs when x is not null
+            Statement::Expression(Expression {
+                kind: ExpressionKind::Call { callee, arguments },
+                ..
+            }) => {
+                if let ExpressionKind::ConditionalAccess { target, name } = &callee.kind
+                    && target.path().is_some()
+                    && self.event_access(target).is_none()
+                {
+                    let condition = Expression::new(ExpressionKind::BinaryExpression {
+                        left: target.clone(),
+                        operator: BinaryOperator::Ne,
+                        right: Box::new(Expression::new(ExpressionKind::Literal(Literal::Null))),
+                    });
+                    let call = method_call((**target).clone(), name, std::mem::take(arguments));
+                    *statement = Statement::If {
+                        condition,
+                        then_body: vec![Statement::Expression(call)],
+                        else_body: Vec::new(),
+                    };
+                }
+            }
+            _ => {}
+        }
+        lower_statement_children(self, statement);
+    }
+
+    fn lower_expression(&mut self, expr: &mut Expression) {
+        match &mut expr.kind {
+            // the member of x?.M() is not a value of its own
+            ExpressionKind::Call { callee, arguments }
+                if matches!(callee.kind, ExpressionKind::ConditionalAccess { .. }) =>
+            {
+                if let ExpressionKind::ConditionalAccess { target, .. } = &mut callee.kind {
+                    self.lower_expression(target);
+                }
+                for argument in arguments.iter_mut() {
+                    self.lower_expression(argument);
+                }
+            }
+            _ => lower_children(self, expr),
+        }
+        match &mut expr.kind {
+            // Clicked(x), Clicked.Invoke(x) and Clicked?.Invoke(x) call every listener
+            ExpressionKind::Call { callee, arguments } => {
+                let raised = match &callee.kind {
+                    ExpressionKind::MemberAccess { target, name }
+                    | ExpressionKind::ConditionalAccess { target, name }
+                        if name == "Invoke" =>
+                    {
+                        self.event_access(target)
+                    }
+                    _ => self.event_access(callee),
+                };
+                if let Some(access) = raised {
+                    let arguments = std::mem::take(arguments);
+                    *expr = self.raise(access, arguments);
+                } else if let ExpressionKind::ConditionalAccess { target, name } = &mut callee.kind
+                {
+                    // x?.M() is null when x is null
+                    let call = method_call((**target).clone(), name, std::mem::take(arguments));
+                    *expr = self.null_conditional(target, call);
+                }
+            }
+            // x?.Length is null when x is null
+            ExpressionKind::ConditionalAccess { target, name } => {
+                let access = Expression::new(ExpressionKind::MemberAccess {
+                    target: target.clone(),
+                    name: name.clone(),
+                });
+                *expr = self.null_conditional(target, access);
+            }
+            // Clicked != null checks for listeners
+            ExpressionKind::BinaryExpression {
+                left,
+                operator: operator @ (BinaryOperator::Eq | BinaryOperator::Ne),
+                right,
+            } if matches!(right.kind, ExpressionKind::Literal(Literal::Null)) => {
+                if let Some(access) = self.event_access(left) {
+                    let empty = method_call(access.listeners(), "isEmpty", Vec::new());
+                    *expr = match operator {
+                        BinaryOperator::Eq => empty,
+                        _ => Expression::new(ExpressionKind::PrefixUnaryExpression {
+                            operator: UnaryOperator::Not,
+                            right: Box::new(empty),
+                        }),
+                    };
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl EventLowering {
+    /// Finds the event an expression refers to, Clicked, this.Clicked or b.Clicked
+    fn event_access(&self, expr: &Expression) -> Option<EventAccess> {
+        let (owner, class, name) = match &expr.kind {
+            // a local holding a copy of the listeners, raised like the event
+            ExpressionKind::Variable(name)
+                if self.copies.contains(name)
+                    && let Some(Type::Generic(list, arguments)) = self.scopes.lookup(name)
+                    && list == "List"
+                    && arguments.len() == 1
+                    && function_type(&arguments[0], &self.scopes.delegates).is_some() =>
+            {
+                return Some(EventAccess {
+                    owner: None,
+                    event: Variable {
+                        typ: arguments[0].clone(),
+                        name: name.clone(),
+                        implicit: false,
+                        constant: false,
+                    },
+                    copy: true,
+                });
+            }
+            // a local or a parameter hides the event
+            ExpressionKind::Variable(_)
+                if self.scopes.declaration(expr).is_some_and(|symbol| {
+                    matches!(symbol.kind, SymbolKind::Local | SymbolKind::Parameter)
+                }) =>
+            {
+                return None;
+            }
+            ExpressionKind::Variable(name) => (None, self.scopes.class.clone(), name),
+            ExpressionKind::MemberAccess { target, name } => {
+                let class = match &target.kind {
+                    ExpressionKind::This => self.scopes.class.clone(),
+                    // a static event, Button.Clicked
+                    ExpressionKind::Variable(class) if self.scopes.is_class(target) => {
+                        class.clone()
+                    }
+                    _ => match self.scopes.type_of(target)? {
+                        Type::Named(class) => class,
+                        _ => return None,
+                    },
+                };
+                (Some((**target).clone()), class, name)
+            }
+            _ => return None,
+        };
+        let event = self.events.get(&class)?.iter().find(|e| e.name == *name)?;
+        Some(EventAccess {
+            owner,
+            event: event.clone(),
+            copy: false,
+        })
+    }
+
+    /// Gives null instead of the value when the target is null, x == null ? null : x.M().
+    /// The target is read twice, so it has to be free of side effects
+    fn null_conditional(&mut self, target: &Expression, value: Expression) -> Expression {
+        if !is_pure(target) {
+            self.diagnostics.push(Diagnostic::error(format!(
+                "the target of ?. is read twice in java, keep it in a variable in {}",
+                self.scopes.method
+            )));
+        }
+        let condition = Expression::new(ExpressionKind::BinaryExpression {
+            left: Box::new(target.clone()),
+            operator: BinaryOperator::Eq,
+            right: Box::new(Expression::new(ExpressionKind::Literal(Literal::Null))),
+        });
+        Expression::new(ExpressionKind::ParenthesizedExpression(Box::new(
+            Expression::new(ExpressionKind::ConditionalExpression {
+                condition: Box::new(condition),
+                consequence: Box::new(Expression::new(ExpressionKind::Literal(Literal::Null))),
+                alternative: Box::new(value),
+            }),
+        )))
+    }
+
+    /// Keeps a method group of the class given to an event in a field, java creates a new
+    /// object for every method reference. Returns the field, onClickListener
+    fn cached_listener(&mut self, value: &Expression, typ: &Type) -> Option<Expression> {
+        let method = match &value.kind {
+            ExpressionKind::Variable(name)
+                if self.scopes.methods.contains_key(name)
+                    && match self.scopes.declaration(value) {
+                        Some(symbol) => symbol.kind == SymbolKind::Method,
+                        None => self.scopes.lookup(name).is_none(),
+                    } =>
+            {
+                name
+            }
+            ExpressionKind::MemberAccess { target, name }
+                if matches!(target.kind, ExpressionKind::This)
+                    && self.scopes.methods.contains_key(name) =>
+            {
+                name
+            }
+            _ => return None,
+        };
+        let class = &self.scopes.class;
+        let is_static = self.scopes.methods[method]
+            .iter()
+            .all(|candidate| candidate.is_static);
+        let field = |name: &str| match is_static {
+            true => ExpressionKind::Variable(name.to_string()),
+            false => ExpressionKind::MemberAccess {
+                target: Box::new(Expression::new(ExpressionKind::This)),
+                name: name.to_string(),
+            },
+        };
+        if let Some(cached) = self.cached.iter().find(|cached| {
+            cached.class == *class && cached.method == *method && cached.field.variable.typ == *typ
+        }) {
+            return Some(Expression::new(field(&cached.field.variable.name)));
+        }
+
+        // the name cannot be taken by another field, onClickListener2
+        let taken = |name: &str| {
+            self.scopes
+                .fields
+                .get(class)
+                .is_some_and(|fields| fields.contains_key(name))
+                || self
+                    .cached
+                    .iter()
+                    .any(|cached| cached.class == *class && cached.field.variable.name == name)
+        };
+        let mut chars = method.chars();
+        let base = match chars.next() {
+            Some(first) => format!("{}{}Listener", first.to_lowercase(), chars.as_str()),
+            None => return None,
+        };
+        let mut name = base.clone();
+        let mut counter = 1;
+        while taken(&name) {
+            counter += 1;
+            name = format!("{}{}", base, counter);
+        }
+
+        let mut modifiers = vec![Modifier::Private];
+        let target = match is_static {
+            true => {
+                modifiers.push(Modifier::Static);
+                ExpressionKind::Variable(class.clone())
+            }
+            false => ExpressionKind::This,
+        };
+        modifiers.push(Modifier::Readonly);
+        self.cached.push(CachedListener {
+            class: class.clone(),
+            method: method.clone(),
+            field: Field {
+                modifiers,
+                variable: Variable {
+                    typ: typ.clone(),
+                    name: name.clone(),
+                    implicit: false,
+                    constant: false,
+                },
+                value: Some(Expression::new(ExpressionKind::MethodReference {
+                    target: Box::new(Expression::new(target)),
+                    name: method.clone(),
+                })),
+            },
+        });
+        Some(Expression::new(field(&name)))
+    }
+
+    /// Checks if a listener is kept in a variable, the same object can then be removed
+    fn is_stored(&self, value: &Expression) -> bool {
+        match &value.kind {
+            ExpressionKind::Variable(name) => self.scopes.lookup(name).is_some(),
+            ExpressionKind::MemberAccess { target, .. } => self.scopes.type_of(target).is_some(),
+            _ => false,
+        }
+    }
+
+    /// Calls every listener of an event, new ArrayList<>(clickedListeners).forEach(listener ->
+    /// listener(x)), the delegates pass then calls the method of the interface. A listener
+    /// can subscribe or unsubscribe while the event is raised, the copy stays the same
+    fn raise(&self, access: EventAccess, arguments: Vec<Expression>) -> Expression {
+        // the name of the lambda parameter cannot hide a local
+        let mut listener = "listener".to_string();
+        let mut counter = 1;
+        while self.scopes.lookup(&listener).is_some() {
+            counter += 1;
+            listener = format!("listener{}", counter);
+        }
+        let call = Expression::new(ExpressionKind::Call {
+            callee: Box::new(Expression::new(ExpressionKind::Variable(listener.clone()))),
+            arguments,
+        });
+        let lambda = Expression::new(ExpressionKind::Lambda {
+            parameters: vec![Variable {
+                typ: access.event.typ.clone(),
+                name: listener,
+                implicit: true,
+                constant: false,
+            }],
+            body: LambdaBody::Expression(Box::new(call)),
+        });
+        let listeners = match access.copy {
+            true => access.listeners(),
+            false => Expression::new(ExpressionKind::ObjectCreation {
+                typ: Type::Generic("ArrayList".to_string(), Vec::new()),
+                arguments: vec![access.listeners()],
+            }),
+        };
+        method_call(listeners, "forEach", vec![lambda])
+    }
+}
+
+/// Replaces every event of a class with a list of listeners and the methods
+/// that add and remove them, the methods keep the access of the event
+fn create_listener_lists(class: &mut Class) {
+    let mut methods = Vec::new();
+    for field in class.fields.iter_mut().filter(|field| is_event(field)) {
+        let name = field.variable.name.clone();
+        let typ = field.variable.typ.clone();
+        field
+            .modifiers
+            .retain(|modifier| !matches!(modifier, Modifier::Event));
+        let modifiers = field.modifiers.clone();
+
+        // private final List<Consumer<String>> clickedListeners = new ArrayList<>();
+        let mut list_modifiers = vec![Modifier::Private];
+        if modifiers
+            .iter()
+            .any(|modifier| matches!(modifier, Modifier::Static))
+        {
+            list_modifiers.push(Modifier::Static);
+        }
+        list_modifiers.push(Modifier::Readonly);
+        *field = Field {
+            modifiers: list_modifiers,
+            variable: Variable {
+                typ: Type::Generic("List".to_string(), vec![typ.clone()]),
+                name: listeners_name(&name),
+                implicit: false,
+                constant: false,
+            },
+            value: Some(Expression::new(ExpressionKind::ObjectCreation {
+                typ: Type::Generic("ArrayList".to_string(), Vec::new()),
+                arguments: Vec::new(),
+            })),
+        };
+
+        // addClickedListener(listener) { clickedListeners.add(listener); }
+        for (method, list_method) in [
+            (add_listener_name(&name), "add"),
+            (remove_listener_name(&name), "remove"),
+        ] {
+            let listener = Expression::new(ExpressionKind::Variable("listener".to_string()));
+            let list = Expression::new(ExpressionKind::Variable(listeners_name(&name)));
+            methods.push(Method {
+                name: method,
+                return_type: Type::Void,
+                modifiers: modifiers.clone(),
+                parameters: vec![Parameter {
+                    variable: Variable {
+                        typ: typ.clone(),
+                        name: "listener".to_string(),
+                        implicit: false,
+                        constant: false,
+                    },
+                    modifiers: Vec::new(),
+                    default: None,
+                    params: false,
+                }],
+                body: vec![Statement::Expression(method_call(
+                    list,
+                    list_method,
+                    vec![listener],
+                ))],
+            });
+        }
+    }
+    // the listener methods come before the others
+    methods.append(&mut class.methods);
+    class.methods = methods;
+}
+
+/// Helper function that checks if a field is an event
+fn is_event(field: &Field) -> bool {
+    field
+        .modifiers
+        .iter()
+        .any(|modifier| matches!(modifier, Modifier::Event))
+}
+
+/// Helper function that names the list of listeners of an event, clickedListeners
+fn listeners_name(event: &str) -> String {
+    let mut chars = event.chars();
+    match chars.next() {
+        Some(first) => format!("{}{}Listeners", first.to_lowercase(), chars.as_str()),
+        None => String::new(),
+    }
+}
+
+/// Helper function that names the method subscribing to an event, AddClickedListener
+fn add_listener_name(event: &str) -> String {
+    format!("Add{}Listener", event)
+}
+
+/// Helper function that names the method unsubscribing from an event, RemoveClickedListener
+fn remove_listener_name(event: &str) -> String {
+    format!("Remove{}Listener", event)
+}
@@ -0,0 +1,936 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+use crate::semantic::typecheck::children;
+
+use super::{
+    Scopes, class_fields, class_methods, declared_names, default_value, method_call, static_call,
+    take_expression, try_parse_type,
+};
+
+/// Translates the ref and out parameters, java passes everything by value.
+/// A variable passed by ref or out is kept in a holder, int x becomes
+/// IntRef x = new IntRef(0), its uses become x.value and the holder itself is
+/// passed, so the method can change it. A field or an array element is copied
+/// into a local holder and back after the call, a discarded out _ gets a holder
+/// nobody reads. The TryParse and TryGetValue calls have
+/// their own java idiom, they are hoisted before their statement:
+/// int.TryParse(s, out n) becomes a try/catch around Integer.parseInt and
+/// d.TryGetValue(k, out v) becomes d.containsKey(k) followed by d.get(k)
+pub fn lower_holders(program: &mut Program) {
+    let mut pass = HolderLowering::default();
+    pass.scopes.fields = class_fields(program);
+    for class in &mut program.classes {
+        // the types of the fields and of the parameters, for the copies and the discards
+        pass.scopes.methods = class_methods(class);
+        pass.scopes.class = class.name.clone();
+        for method in &mut class.methods {
+            pass.method = method.name.clone();
+            pass.scopes.names = declared_names(method);
+            pass.locals = method
+                .parameters
+                .iter()
+                .map(|parameter| {
+                    (
+                        parameter.variable.name.clone(),
+                        parameter.variable.typ.clone(),
+                    )
+                })
+                .collect();
+            pass.hoist_block(&mut method.body);
+
+            // the ref and out parameters, and the locals passed by ref or out
+            pass.holders = method
+                .parameters
+                .iter()
+                .filter(|parameter| is_by_ref(&parameter.modifiers))
+                .map(|parameter| parameter.variable.name.clone())
+                .collect();
+            pass.holders.extend(
+                passed_by_ref(&method.body)
+                    .into_iter()
+                    .filter(|name| pass.locals.contains_key(name)),
+            );
+            // a java lambda can only use the locals that are never set again,
+            // the others are shared with it through a holder too
+            let assigned = assigned(&method.body);
+            for name in captured(&method.body) {
+                if !assigned.contains(&name) || !pass.locals.contains_key(&name) {
+                    continue;
+                }
+                if method
+                    .parameters
+                    .iter()
+                    .any(|parameter| parameter.variable.name == name)
+                {
+                    pass.diagnostics.push(Diagnostic::error(format!(
+                        "the parameter `{}` is changed and used by a lambda in {}, java does not allow it",
+                        name, pass.method
+                    )));
+                } else {
+                    pass.holders.insert(name);
+                }
+            }
+
+            for parameter in &mut method.parameters {
+                if pass.holders.contains(&parameter.variable.name) {
+                    let typ = take_type(&mut parameter.variable.typ);
+                    parameter.variable.typ = Type::Holder(Box::new(typ));
+                }
+            }
+            pass.rewrite_block(&mut method.body);
+        }
+    }
+    program.diagnostics.extend(pass.diagnostics);
+}
+
+#[derive(Default)]
+struct HolderLowering {
+    diagnostics: Vec<Diagnostic>,
+    method: String,                // the method being lowered, for the messages
+    locals: HashMap<String, Type>, // the parameters and locals of the method
+    holders: HashSet<String>,      // the ones kept in a holder
+    scopes: Scopes,                // the names of the method, for the created variables
+    copied_back: Vec<Statement>,   // the fields set back from their holders after the statement
+    lambda_depth: usize, // inside the body of an expression lambda, which has no statements
+}
+
+impl HolderLowering {
+    /// Hoists the TryParse, TryGetValue and out var of a block before their statements
+    fn hoist_block(&mut self, statements: &mut Vec<Statement>) {
+        let outer = std::mem::take(&mut self.copied_back);
+        for mut statement in std::mem::take(statements) {
+            let mut prelude = Vec::new();
+            self.hoist_statement(&mut statement, &mut prelude);
+            statements.extend(prelude);
+            // Bump(ref field) sets the field after its statement, which has to run to its end
+            let copied_back = std::mem::take(&mut self.copied_back);
+            if !copied_back.is_empty()
+                && !matches!(
+                    statement,
+                    Statement::Expression(_)
+                        | Statement::VariableDeclaration { .. }
+                        | Statement::Assignment { .. }
+                )
+            {
+                self.diagnostics.push(Diagnostic::error(format!(
+                    "a field or an array element passed by ref in {} is set back after the statement, put the call in a statement of its own",
+                    self.method
+                )));
+            }
+            // int.TryParse(s, out n); on its own line only leaves its prelude
+            if !matches!(
+                statement,
+                Statement::Expression(Expression {
+                    kind: ExpressionKind::Variable(_),
+                    ..
+                })
+            ) {
+                statements.push(statement);
+            }
+            statements.extend(copied_back);
+        }
+        self.copied_back = outer;
+    }
+
+    fn hoist_statement(&mut self, statement: &mut Statement, prelude: &mut Vec<Statement>) {
+        match statement {
+            Statement::VariableDeclaration { variable, value } => {
+                if let Some(value) = value {
+                    self.hoist_expression(value, prelude);
+                }
+                self.locals
+                    .insert(variable.name.clone(), variable.typ.clone());
+            }
+            Statement::Assignment { value, .. } => self.hoist_expression(value, prelude),
+            Statement::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                self.hoist_expression(condition, prelude);
+                self.hoist_block(then_body);
+                self.hoist_block(else_body);
+            }
+            // for (i = 0; int.TryParse(lines[i], out n); i++) is the while loop below,
+            // with i = 0 before it and i++ at the end of its body
+            Statement::For {
+                initializer,
+                condition: Some(condition),
+                increment,
+                body,
+            } if has_hoisted_calls(condition)
+                && !matches!(
+                    initializer.as_deref(),
+                    Some(Statement::VariableDeclaration { .. })
+                ) =>
+            {
+                if let Some(initializer) = initializer.take() {
+                    prelude.push(*initializer);
+                }
+                let mut body = std::mem::take(body);
+                body.extend(increment.take().map(|increment| *increment));
+                let mut loop_statement = Statement::While {
+                    condition: take_expression(condition),
+                    body,
+                };
+                self.hoist_statement(&mut loop_statement, prelude);
+                *statement = loop_statement;
+            }
+            Statement::For {
+                initializer,
+                condition,
+                body,
+                ..
+            } => {
+                if let Some(initializer) = initializer
+                    && let Statement::VariableDeclaration { variable, .. } = &**initializer
+                {
+                    self.locals
+                        .insert(variable.name.clone(), variable.typ.clone());
+                }
+                if let Some(condition) = condition {
+                    self.check_loop_condition(condition);
+                }
+                self.hoist_block(body);
+            }
+            // while (int.TryParse(line, out n)) parses before the loop and again
+            // at the end of every iteration, C# has no continue to skip it
+            Statement::While { condition, body } => {
+                let mut hoisted = Vec::new();
+                self.hoist_expression(condition, &mut hoisted);
+                self.hoist_block(body);
+                body.extend(hoisted.iter().filter_map(repeated));
+                prelude.extend(hoisted);
+            }
+            Statement::Checked { body, .. } | Statement::Block(body) => self.hoist_block(body),
+            Statement::TryCatch { body, handler, .. } => {
+                self.hoist_block(body);
+                self.hoist_block(handler);
+            }
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    self.hoist_expression(value, prelude);
+                }
+            }
+            Statement::Expression(expr) | Statement::Throw(expr) => {
+                self.hoist_expression(expr, prelude)
+            }
+        }
+    }
+
+    /// Moves the out arguments of the calls in an expression into statements of the prelude
+    fn hoist_expression(&mut self, expr: &mut Expression, prelude: &mut Vec<Statement>) {
+        // the statements of a lambda get their own preludes
+        if let ExpressionKind::Lambda { parameters, body } = &mut expr.kind {
+            for parameter in parameters.iter() {
+                self.locals
+                    .insert(parameter.name.clone(), parameter.typ.clone());
+            }
+            match body {
+                LambdaBody::Expression(body) => {
+                    self.lambda_depth += 1;
+                    self.hoist_expression(body, prelude);
+                    self.lambda_depth -= 1;
+                }
+                LambdaBody::Block(statements) => {
+                    let depth = std::mem::take(&mut self.lambda_depth);
+                    self.hoist_block(statements);
+                    self.lambda_depth = depth;
+                }
+            }
+            return;
+        }
+        for child in expr.children_mut() {
+            self.hoist_expression(child, prelude);
+        }
+        let ExpressionKind::Call { callee, arguments } = &mut expr.kind else {
+            return;
+        };
+        let path = callee.path().unwrap_or_default();
+
+        // int.TryParse(s, out var n)
+        if let (
+            Some(typ),
+            [
+                text,
+                Expression {
+                    kind: ExpressionKind::RefArgument { value, .. },
+                    ..
+                },
+            ],
+        ) = (try_parse_type(&path), arguments.as_mut_slice())
+        {
+            let Some(name) = self.out_target(value, false, prelude) else {
+                self.unsupported_out(&path);
+                return;
+            };
+            let parsed = self.declare_fresh(&format!("{}Parsed", name));
+            prelude.push(Statement::VariableDeclaration {
+                variable: Variable {
+                    typ: Type::Bool,
+                    name: parsed.clone(),
+                    implicit: false,
+                    constant: false,
+                },
+                value: None,
+            });
+            // the catch variable only has to differ from the variables around it
+            let exception = self.scopes.fresh_name("e");
+            prelude.push(try_parse(
+                typ,
+                take_expression(text),
+                &name,
+                &parsed,
+                exception,
+            ));
+            *expr = Expression::new(ExpressionKind::Variable(parsed));
+            return;
+        }
+
+        // d.TryGetValue(k, out var v)
+        if let ExpressionKind::MemberAccess { target, name } = &mut callee.kind
+            && name == "TryGetValue"
+            && let [
+                key,
+                Expression {
+                    kind: ExpressionKind::RefArgument { value, .. },
+                    ..
+                },
+            ] = arguments.as_mut_slice()
+        {
+            let Some(name) = out_name(value) else {
+                self.unsupported_out(&path);
+                return;
+            };
+            let declared = match &value.kind {
+                ExpressionKind::DeclarationExpression(variable) => Some(variable.clone()),
+                _ => None,
+            };
+            let (dictionary, key) = (take_expression(target), take_expression(key));
+            let typ = declared
+                .as_ref()
+                .map(|variable| variable.typ.clone())
+                .or_else(|| self.locals.get(&name).cloned())
+                .unwrap_or(Type::Unknown);
+
+            let found = self.declare_fresh(&format!("{}Found", name));
+            prelude.push(Statement::VariableDeclaration {
+                variable: Variable {
+                    typ: Type::Bool,
+                    name: found.clone(),
+                    implicit: false,
+                    constant: false,
+                },
+                value: Some(method_call(
+                    dictionary.clone(),
+                    "containsKey",
+                    vec![key.clone()],
+                )),
+            });
+            // a missing int is 0 in C#, java would unbox a null
+            let lookup = match default_value(&typ) {
+                Some(default) => method_call(dictionary, "getOrDefault", vec![key, default]),
+                None => method_call(dictionary, "get", vec![key]),
+            };
+            prelude.push(match declared {
+                Some(variable) => {
+                    self.locals.insert(name, variable.typ.clone());
+                    Statement::VariableDeclaration {
+                        variable,
+                        value: Some(lookup),
+                    }
+                }
+                None => Statement::Assignment {
+                    target: Expression::new(ExpressionKind::Variable(name)),
+                    operator: None,
+                    value: lookup,
+                },
+            });
+            *expr = Expression::new(ExpressionKind::Variable(found));
+            return;
+        }
+
+        // Divide(a, b, out var q) declares q before the call
+        for (index, argument) in arguments.iter_mut().enumerate() {
+            let ExpressionKind::RefArgument { modifier, value } = &mut argument.kind else {
+                continue;
+            };
+            if *modifier == ParameterModifier::In {
+                continue;
+            }
+            if self.is_discard(value) {
+                let declared = match &value.kind {
+                    ExpressionKind::DeclarationExpression(variable) if !variable.implicit => {
+                        Some(variable.typ.clone())
+                    }
+                    _ => None,
+                };
+                match declared.or_else(|| self.scopes.parameter_type(callee, index)) {
+                    Some(typ) => *argument = discard_holder(typ),
+                    None => self.diagnostics.push(Diagnostic::error(format!(
+                        "the type of the discarded out argument of `{}` is unknown in {}, pass a variable",
+                        path, self.method
+                    ))),
+                }
+            } else if matches!(value.kind, ExpressionKind::DeclarationExpression(_)) {
+                self.out_target(value, true, prelude);
+            } else if !matches!(&value.kind, ExpressionKind::Variable(name) if self.locals.contains_key(name))
+            {
+                self.copy_argument(value, prelude);
+            }
+        }
+    }
+
+    /// Checks if an out argument is discarded, out _ when no variable is called _,
+    /// out var _ and out int _
+    fn is_discard(&self, value: &Expression) -> bool {
+        match &value.kind {
+            ExpressionKind::Variable(name) => name == "_" && !self.locals.contains_key(name),
+            ExpressionKind::DeclarationExpression(variable) => variable.name == "_",
+            _ => false,
+        }
+    }
+
+    /// Passes a field or an array element by ref through a local, which becomes a holder
+    /// like any other: Bump(ref calls) becomes int callsRef = calls; before the statement,
+    /// Bump(ref callsRef) and calls = callsRef; after it. The method sees the changes of
+    /// its own holder only, not of the field
+    fn copy_argument(&mut self, value: &mut Expression, prelude: &mut Vec<Statement>) {
+        // the holder reads and sets the argument a second time, from the statements around it
+        if self.lambda_depth > 0 {
+            return;
+        }
+        let mut scopes = self.scopes.clone();
+        scopes.push();
+        let fields = scopes
+            .fields
+            .get(&scopes.class)
+            .cloned()
+            .unwrap_or_default();
+        for (name, typ) in fields.into_iter().chain(self.locals.clone()) {
+            scopes.declare(&name, typ);
+        }
+        if !scopes.is_plain_target(value) {
+            return;
+        }
+        let Some(typ) = scopes.type_of(value) else {
+            return;
+        };
+        let base = match &value.kind {
+            ExpressionKind::Variable(name) | ExpressionKind::MemberAccess { name, .. } => {
+                let mut chars = name.chars();
+                match chars.next() {
+                    Some(first) => format!("{}{}Ref", first.to_lowercase(), chars.as_str()),
+                    None => return,
+                }
+            }
+            _ => "elementRef".to_string(),
+        };
+        let name = self.declare_fresh(&base);
+        self.locals.insert(name.clone(), typ.clone());
+        let argument = std::mem::replace(
+            value,
+            Expression::new(ExpressionKind::Variable(name.clone())),
+        );
+        prelude.push(Statement::VariableDeclaration {
+            variable: Variable {
+                typ,
+                name: name.clone(),
+                implicit: false,
+                constant: false,
+            },
+            value: Some(argument.clone()),
+        });
+        self.copied_back.push(Statement::Assignment {
+            target: argument,
+            operator: None,
+            value: Expression::new(ExpressionKind::Variable(name)),
+        });
+    }
+
+    /// Declares the variable of an out var before the statement, then passes
+    /// the variable itself. Returns the name of the variable
+    fn out_target(
+        &mut self,
+        value: &mut Expression,
+        initialize: bool,
+        prelude: &mut Vec<Statement>,
+    ) -> Option<String> {
+        if let ExpressionKind::DeclarationExpression(variable) = &mut value.kind {
+            let variable = Variable {
+                implicit: false,
+                constant: false,
+                ..variable.clone()
+            };
+            self.locals
+                .insert(variable.name.clone(), variable.typ.clone());
+            *value = Expression::new(ExpressionKind::Variable(variable.name.clone()));
+            // the method sets it, java wants it set before the call anyway
+            let value = initialize.then(|| {
+                default_value(&variable.typ)
+                    .unwrap_or(Expression::new(ExpressionKind::Literal(Literal::Null)))
+            });
+            prelude.push(Statement::VariableDeclaration { variable, value });
+        }
+        out_name(value)
+    }
+
+    /// The out variables of a loop condition would have to be set again on every
+    /// iteration, a for loop declaring its variable has to be rewritten by hand
+    fn check_loop_condition(&mut self, condition: &Expression) {
+        let mut calls = Vec::new();
+        hoisted_calls(condition, &mut calls);
+        for path in calls {
+            self.diagnostics.push(Diagnostic::error(format!(
+                "`{}` with an out argument cannot be translated in the loop condition of {}",
+                path, self.method
+            )));
+        }
+    }
+
+    /// Finds a name for a variable created in the method, and reserves it
+    fn declare_fresh(&mut self, base: &str) -> String {
+        let name = self.scopes.fresh_name(base);
+        self.scopes.names.insert(name.clone());
+        name
+    }
+
+    fn unsupported_out(&mut self, path: &str) {
+        self.diagnostics.push(Diagnostic::error(format!(
+            "the out argument of `{}` has to be a local variable in {}",
+            path, self.method
+        )));
+    }
+
+    /// Puts the holders in place of their variables, in a block
+    fn rewrite_block(&mut self, statements: &mut [Statement]) {
+        for statement in statements {
+            self.rewrite_statement(statement);
+        }
+    }
+
+    fn rewrite_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::VariableDeclaration { variable, value } => {
+                if let Some(value) = value {
+                    self.rewrite_expression(value);
+                }
+                // int x = 5 becomes IntRef x = new IntRef(5)
+                if self.holders.contains(&variable.name) {
+                    let typ = take_type(&mut variable.typ);
+                    let initial = value
+                        .take()
+                        .or_else(|| default_value(&typ))
+                        .unwrap_or(Expression::new(ExpressionKind::Literal(Literal::Null)));
+                    variable.typ = Type::Holder(Box::new(typ));
+                    variable.implicit = false;
+                    *value = Some(Expression::new(ExpressionKind::ObjectCreation {
+                        typ: variable.typ.clone(),
+                        arguments: vec![initial],
+                    }));
+                }
+            }
+            Statement::Assignment { target, value, .. } => {
+                self.rewrite_expression(target);
+                self.rewrite_expression(value);
+            }
+            Statement::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                self.rewrite_expression(condition);
+                self.rewrite_block(then_body);
+                self.rewrite_block(else_body);
+            }
+            Statement::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                if let Some(initializer) = initializer {
+                    self.rewrite_statement(initializer);
+                }
+                if let Some(condition) = condition {
+                    self.rewrite_expression(condition);
+                }
+                if let Some(increment) = increment {
+                    self.rewrite_statement(increment);
+                }
+                self.rewrite_block(body);
+            }
+            Statement::While { condition, body } => {
+                self.rewrite_expression(condition);
+                self.rewrite_block(body);
+            }
+            Statement::Checked { body, .. } | Statement::Block(body) => self.rewrite_block(body),
+            Statement::TryCatch { body, handler, .. } => {
+                self.rewrite_block(body);
+                self.rewrite_block(handler);
+            }
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    self.rewrite_expression(value);
+                }
+            }
+            Statement::Expression(expr) | Statement::Throw(expr) => self.rewrite_expression(expr),
+        }
+    }
+
+    fn rewrite_expression(&mut self, expr: &mut Expression) {
+        match &mut expr.kind {
+            // x becomes x.value
+            ExpressionKind::Variable(name) if self.holders.contains(name) => {
+                let holder = take_expression(expr);
+                *expr = Expression::new(ExpressionKind::MemberAccess {
+                    target: Box::new(holder),
+                    name: "value".to_string(),
+                });
+            }
+            // ref x passes the holder itself
+            ExpressionKind::RefArgument { modifier, value } => {
+                match &value.kind {
+                    ExpressionKind::Variable(name)
+                        if *modifier != ParameterModifier::In && self.holders.contains(name) => {}
+                    // in x is a read only copy, java passes it as it is
+                    _ if *modifier == ParameterModifier::In => self.rewrite_expression(value),
+                    // an out var left in a loop condition, reported already
+                    ExpressionKind::DeclarationExpression(_) => {}
+                    ExpressionKind::Variable(name) if self.locals.contains_key(name) => {}
+                    // the fields and array elements are copied into holders,
+                    // unless they are read with a call or in a loop condition
+                    _ => {
+                        self.diagnostics.push(Diagnostic::error(format!(
+                            "the ref argument in {} can only be a variable, a field or an array element read without calls, outside of loop conditions and lambda expressions",
+                            self.method
+                        )));
+                        self.rewrite_expression(value);
+                    }
+                }
+                *expr = take_expression(value);
+            }
+            // Swap(a, b), the name of the method is not a variable
+            ExpressionKind::Call { callee, arguments } => {
+                if !matches!(callee.kind, ExpressionKind::Variable(_)) {
+                    self.rewrite_expression(callee);
+                }
+                for argument in arguments {
+                    self.rewrite_expression(argument);
+                }
+            }
+            ExpressionKind::Lambda {
+                body: LambdaBody::Block(statements),
+                ..
+            } => self.rewrite_block(statements),
+            _ => {
+                for child in expr.children_mut() {
+                    self.rewrite_expression(child);
+                }
+            }
+        }
+    }
+}
+
+/// Helper function that builds the java form of int.TryParse(text, out n)
+fn try_parse(
+    typ: Type,
+    text: Expression,
+    name: &str,
+    parsed: &str,
+    exception: String,
+) -> Statement {
+    let assign = |target: &str, value: Expression| Statement::Assignment {
+        target: Expression::new(ExpressionKind::Variable(target.to_string())),
+        operator: None,
+        value,
+    };
+    // C# skips the surrounding white space, java does not
+    let text = method_call(text, "trim", Vec::new());
+    let parse = match typ {
+        Type::Int => static_call("Integer", "parseInt", vec![text]),
+        Type::Long => static_call("Long", "parseLong", vec![text]),
+        Type::Double => static_call("Double", "parseDouble", vec![text]),
+        // decimal.Parse is left to the decimal pass
+        _ => Expression::new(ExpressionKind::Call {
+            callee: Box::new(Expression::new(ExpressionKind::MemberAccess {
+                target: Box::new(Expression::new(ExpressionKind::Variable(
+                    "decimal".to_string(),
+                ))),
+                name: "Parse".to_string(),
+            })),
+            arguments: vec![text],
+        }),
+    };
+    let default = default_value(&typ).expect("Expected a number type");
+    Statement::TryCatch {
+        body: vec![
+            assign(name, parse),
+            assign(
+                parsed,
+                Expression::new(ExpressionKind::Literal(Literal::Bool(true))),
+            ),
+        ],
+        exception: Variable {
+            typ: Type::Named("NumberFormatException".to_string()),
+            name: exception,
+            implicit: false,
+            constant: false,
+        },
+        handler: vec![
+            assign(name, default),
+            assign(
+                parsed,
+                Expression::new(ExpressionKind::Literal(Literal::Bool(false))),
+            ),
+        ],
+    }
+}
+
+/// Helper function that creates the holder of a discarded out argument, new IntRef(0)
+fn discard_holder(typ: Type) -> Expression {
+    let initial =
+        default_value(&typ).unwrap_or(Expression::new(ExpressionKind::Literal(Literal::Null)));
+    Expression::new(ExpressionKind::ObjectCreation {
+        typ: Type::Holder(Box::new(typ)),
+        arguments: vec![initial],
+    })
+}
+
+/// Helper function that tells whether an expression has calls hoisted before their statement
+fn has_hoisted_calls(expr: &Expression) -> bool {
+    let mut calls = Vec::new();
+    hoisted_calls(expr, &mut calls);
+    !calls.is_empty()
+}
+
+/// Helper function that gives the statement of a prelude run again at the end of
+/// a loop iteration, the declared variables are only set
+fn repeated(statement: &Statement) -> Option<Statement> {
+    match statement {
+        Statement::VariableDeclaration {
+            variable,
+            value: Some(value),
+        } => Some(Statement::Assignment {
+            target: Expression::new(ExpressionKind::Variable(variable.name.clone())),
+            operator: None,
+            value: value.clone(),
+        }),
+        Statement::VariableDeclaration { value: None, .. } => None,
+        statement => Some(statement.clone()),
+    }
+}
+
+/// Helper function that collects the TryParse and TryGetValue calls and the out var
+/// declarations of an expression, they all need a statement of their own
+fn hoisted_calls(expr: &Expression, calls: &mut Vec<String>) {
+    for child in children(expr) {
+        hoisted_calls(child, calls);
+    }
+    if let ExpressionKind::Call { callee, arguments } = &expr.kind {
+        let path = callee.path().unwrap_or_default();
+        let is_special = try_parse_type(&path).is_some() || path.ends_with(".TryGetValue");
+        let declares = arguments.iter().any(|argument| {
+            matches!(&argument.kind, ExpressionKind::RefArgument { value, .. }
+                if matches!(value.kind, ExpressionKind::DeclarationExpression(_)))
+        });
+        if is_special || declares {
+            calls.push(path);
+        }
+    }
+}
+
+// a statement or an expression met while walking a block
+enum Node<'a> {
+    Statement(&'a Statement),
+    Expression(&'a Expression),
+}
+
+/// Helper function that calls visit on every statement and expression of a block,
+/// the nested blocks and the blocks of the lambdas included
+fn visit_block<'a>(statements: &'a [Statement], visit: &mut impl FnMut(Node<'a>)) {
+    for statement in statements {
+        visit(Node::Statement(statement));
+        match statement {
+            Statement::VariableDeclaration { value, .. } | Statement::Return(value) => {
+                if let Some(value) = value {
+                    visit_expression(value, visit);
+                }
+            }
+            Statement::Assignment { target, value, .. } => {
+                visit_expression(target, visit);
+                visit_expression(value, visit);
+            }
+            Statement::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                visit_expression(condition, visit);
+                visit_block(then_body, visit);
+                visit_block(else_body, visit);
+            }
+            Statement::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                if let Some(initializer) = initializer {
+                    visit_block(std::slice::from_ref(&**initializer), visit);
+                }
+                if let Some(condition) = condition {
+                    visit_expression(condition, visit);
+                }
+                if let Some(increment) = increment {
+                    visit_block(std::slice::from_ref(&**increment), visit);
+                }
+                visit_block(body, visit);
+            }
+            Statement::While { condition, body } => {
+                visit_expression(condition, visit);
+                visit_block(body, visit);
+            }
+            Statement::Checked { body, .. } | Statement::Block(body) => visit_block(body, visit),
+            Statement::TryCatch { body, handler, .. } => {
+                visit_block(body, visit);
+                visit_block(handler, visit);
+            }
+            Statement::Expression(expr) | Statement::Throw(expr) => visit_expression(expr, visit),
+        }
+    }
+}
+
+/// Same as visit_block, for an expression and its sub-expressions
+fn visit_expression<'a>(expr: &'a Expression, visit: &mut impl FnMut(Node<'a>)) {
+    visit(Node::Expression(expr));
+    if let ExpressionKind::Lambda {
+        body: LambdaBody::Block(statements),
+        ..
+    } = &expr.kind
+    {
+        visit_block(statements, visit);
+    }
+    for child in children(expr) {
+        visit_expression(child, visit);
+    }
+}
+
+/// Helper function that collects the variables passed by ref or out in a block
+fn passed_by_ref(statements: &[Statement]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    visit_block(statements, &mut |node| {
+        if let Node::Expression(Expression {
+            kind: ExpressionKind::RefArgument { modifier, value },
+            ..
+        }) = node
+            && *modifier != ParameterModifier::In
+            && let ExpressionKind::Variable(name) = &value.kind
+        {
+            names.insert(name.clone());
+        }
+    });
+    names
+}
+
+/// Helper function that collects the variables set again after their declaration
+fn assigned(statements: &[Statement]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    visit_block(statements, &mut |node| match node {
+        Node::Statement(Statement::Assignment {
+            target:
+                Expression {
+                    kind: ExpressionKind::Variable(name),
+                    ..
+                },
+            ..
+        }) => {
+            names.insert(name.clone());
+        }
+        Node::Expression(
+            Expression {
+                kind:
+                    ExpressionKind::PrefixUnaryExpression {
+                        operator: UnaryOperator::UAdd | UnaryOperator::USub,
+                        right: operand,
+                    },
+                ..
+            }
+            | Expression {
+                kind:
+                    ExpressionKind::PostfixUnaryExpression {
+                        operator: UnaryOperator::UAdd | UnaryOperator::USub,
+                        left: operand,
+                    },
+                ..
+            },
+        ) => {
+            if let ExpressionKind::Variable(name) = &operand.kind {
+                names.insert(name.clone());
+            }
+        }
+        _ => {}
+    });
+    names
+}
+
+/// Helper function that collects the variables the lambdas of a block use
+/// from around them
+fn captured(statements: &[Statement]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    visit_block(statements, &mut |node| {
+        let Node::Expression(Expression {
+            kind: ExpressionKind::Lambda { parameters, body },
+            ..
+        }) = node
+        else {
+            return;
+        };
+        let mut declared: HashSet<String> = parameters
+            .iter()
+            .map(|parameter| parameter.name.clone())
+            .collect();
+        let mut used = HashSet::new();
+        let mut collect = |node: Node| match node {
+            Node::Statement(Statement::VariableDeclaration { variable, .. }) => {
+                declared.insert(variable.name.clone());
+            }
+            Node::Expression(Expression {
+                kind: ExpressionKind::Variable(name),
+                ..
+            }) => {
+                used.insert(name.clone());
+            }
+            _ => {}
+        };
+        match body {
+            LambdaBody::Expression(body) => visit_expression(body, &mut collect),
+            LambdaBody::Block(statements) => visit_block(statements, &mut collect),
+        }
+        names.extend(used.into_iter().filter(|name| !declared.contains(name)));
+    });
+    names
+}
+
+/// Helper function that obtains the name of the variable an out argument sets
+fn out_name(value: &Expression) -> Option<String> {
+    match &value.kind {
+        ExpressionKind::Variable(name) => Some(name.clone()),
+        ExpressionKind::DeclarationExpression(variable) => Some(variable.name.clone()),
+        _ => None,
+    }
+}
+
+/// Helper function that tells whether a parameter is passed by reference, in is a copy
+fn is_by_ref(modifiers: &[ParameterModifier]) -> bool {
+    modifiers
+        .iter()
+        .any(|modifier| matches!(modifier, ParameterModifier::Ref | ParameterModifier::Out))
+}
+
+/// Helper function that takes a type out of a variable, so it can be wrapped
+fn take_type(typ: &mut Type) -> Type {
+    std::mem::replace(typ, Type::Unknown)
+}
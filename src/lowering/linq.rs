@@ -0,0 +1,587 @@
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+use crate::semantic::linq::{element_type, is_deferred, is_linq};
+
+use super::{
+    Lowering, Scopes, default_value, duplicate_key, lower_children, lower_methods,
+    lower_statement_children, method_call, static_call, take_expression,
+};
+
+/// Translates the LINQ methods into java streams, xs.Where(x => x > 0).ToList()
+/// becomes xs.stream().filter(x -> x > 0).collect(Collectors.toList()).
+/// A C# query only runs when it is enumerated, a java stream can only be used once,
+/// so a query kept in a variable, returned or passed to a method is collected into a list
+pub fn lower_linq(program: &mut Program) {
+    let mut pass = LinqLowering::default();
+    lower_methods(&mut pass, program);
+    program.diagnostics.extend(pass.diagnostics);
+}
+
+#[derive(Default)]
+struct LinqLowering {
+    scopes: Scopes,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Lowering for LinqLowering {
+    fn scopes(&mut self) -> &mut Scopes {
+        &mut self.scopes
+    }
+
+    fn lower_statement(&mut self, statement: &mut Statement) {
+        let query = match statement {
+            Statement::VariableDeclaration {
+                variable,
+                value: Some(value),
+            } if self.is_query(value) => {
+                // var evens = xs.Where(...) keeps a list of the results
+                if variable.implicit
+                    && let Some(element) =
+                        self.scopes.type_of(value).as_ref().and_then(element_type)
+                {
+                    variable.typ = Type::Generic("List".to_string(), vec![element]);
+                }
+                Some(format!("the query assigned to `{}`", variable.name))
+            }
+            Statement::Assignment {
+                target,
+                operator: None,
+                value,
+            } if self.is_query(value) => Some(format!(
+                "the query assigned to `{}`",
+                target.path().unwrap_or_default()
+            )),
+            Statement::Return(Some(value)) if self.is_query(value) => {
+                Some("the returned query".to_string())
+            }
+            _ => None,
+        };
+        if let Some(query) = &query {
+            self.deferred(query);
+        }
+        lower_statement_children(self, statement);
+
+        if query.is_some()
+            && let Statement::VariableDeclaration {
+                value: Some(value), ..
+            }
+            | Statement::Assignment { value, .. }
+            | Statement::Return(Some(value)) = statement
+        {
+            *value = to_list(take_expression(value));
+        }
+    }
+
+    fn lower_expression(&mut self, expr: &mut Expression) {
+        match &mut expr.kind {
+            // xs.Where(...), on a sequence
+            ExpressionKind::Call { .. } => self.lower_call(expr),
+            // the key of a group, g.Key
+            ExpressionKind::MemberAccess { target, name }
+                if name == "Key"
+                    && matches!(
+                        self.scopes.type_of(target),
+                        Some(Type::Generic(ref group, _)) if group == "IGrouping"
+                    ) =>
+            {
+                lower_children(self, expr);
+                if let ExpressionKind::MemberAccess { target, .. } = &mut expr.kind {
+                    *expr = method_call(take_expression(target), "getKey", Vec::new());
+                }
+            }
+            _ => lower_children(self, expr),
+        }
+    }
+}
+
+// a LINQ call, with what is known about it before its target is lowered
+struct Query {
+    typ: Type,                       // the C# type of the call
+    source: Type,                    // the C# type of the sequence it works on
+    stream: bool, // the sequence is itself a query, already a stream once lowered
+    sorts: usize, // the OrderBy and ThenBy calls right before it
+    flattened: Option<(bool, Type)>, // for SelectMany, whether its lambda gives a query and its type
+    numbers: Option<Type>, // the values of the elements or of the lambda, averaged by Average
+}
+
+impl LinqLowering {
+    /// Lowers a call, a LINQ method becomes stream calls and the queries
+    /// passed to a method are collected
+    fn lower_call(&mut self, expr: &mut Expression) {
+        let ExpressionKind::Call { callee, arguments } = &expr.kind else {
+            return;
+        };
+        let query = match &callee.kind {
+            ExpressionKind::MemberAccess { target, name } if is_linq(name) => self
+                .scopes
+                .type_of(target)
+                .filter(|typ| element_type(typ).is_some())
+                .map(|typ| Query {
+                    typ: self.scopes.type_of(expr).unwrap_or(Type::Unknown),
+                    numbers: match arguments.first() {
+                        Some(lambda) => element_type(&typ)
+                            .and_then(|element| self.scopes.lambda_type(lambda, &element)),
+                        None => element_type(&typ),
+                    },
+                    source: typ,
+                    stream: self.is_query(target),
+                    sorts: sort_depth(target),
+                    flattened: None,
+                }),
+            _ => None,
+        };
+        // the lambda of SelectMany gives the sequences to flatten
+        let query = query.map(|mut query| {
+            if let Some(Expression {
+                kind:
+                    ExpressionKind::Lambda {
+                        parameters,
+                        body: LambdaBody::Expression(body),
+                    },
+                ..
+            }) = arguments.first()
+                && let Some(element) = element_type(&query.source)
+            {
+                self.scopes.push();
+                for parameter in parameters {
+                    self.scopes.declare(&parameter.name, element.clone());
+                }
+                query.flattened = self
+                    .scopes
+                    .type_of(body)
+                    .map(|typ| (self.is_query(body), typ));
+                self.scopes.pop();
+            }
+            query
+        });
+        // Print(xs.Where(...)) gets the list of the results
+        let passed: Vec<usize> = match query {
+            Some(_) => Vec::new(),
+            None => (0..arguments.len())
+                .filter(|index| self.is_query(&arguments[*index]))
+                .collect(),
+        };
+        if !passed.is_empty() {
+            let callee = callee.path().unwrap_or_default();
+            self.deferred(&format!("the query passed to `{}`", callee));
+        }
+        lower_children(self, expr);
+
+        let ExpressionKind::Call { callee, arguments } = &mut expr.kind else {
+            return;
+        };
+        for index in passed {
+            arguments[index] = to_list(take_expression(&mut arguments[index]));
+        }
+        if let Some(query) = query
+            && let ExpressionKind::MemberAccess { target, name } = take_expression(callee).kind
+        {
+            let arguments = std::mem::take(arguments);
+            *expr = self.lower_query(query, *target, &name, arguments);
+        }
+    }
+
+    /// Checks if an expression is a deferred query, xs.Where(...) but not xs.Count()
+    fn is_query(&self, expr: &Expression) -> bool {
+        match &expr.kind {
+            ExpressionKind::ParenthesizedExpression(inner) => self.is_query(inner),
+            ExpressionKind::Call { callee, .. } => match &callee.kind {
+                ExpressionKind::MemberAccess { target, name } => {
+                    is_deferred(name)
+                        && self
+                            .scopes
+                            .type_of(target)
+                            .as_ref()
+                            .and_then(element_type)
+                            .is_some()
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Reports a query that java runs right away
+    fn deferred(&mut self, query: &str) {
+        self.diagnostics.push(Diagnostic::warning(format!(
+            "{} in {} runs right away in java, C# only runs it when it is enumerated",
+            query, self.scopes.method
+        )));
+    }
+
+    /// Builds the stream calls of a LINQ method, the target and arguments are already lowered
+    fn lower_query(
+        &mut self,
+        query: Query,
+        target: Expression,
+        method: &str,
+        arguments: Vec<Expression>,
+    ) -> Expression {
+        let source = match query.stream {
+            true => target,
+            false => stream(target, &query.source),
+        };
+        let mut arguments = arguments.into_iter();
+        let argument = arguments.next();
+        let second = arguments.next();
+        // First(x => x > 0) filters first
+        let filtered = |source: Expression| match &argument {
+            Some(predicate) => method_call(source, "filter", vec![predicate.clone()]),
+            None => source,
+        };
+        match method {
+            "Where" => method_call(source, "filter", argument.into_iter().collect()),
+            "Select" => {
+                if let Some(Expression {
+                    kind: ExpressionKind::Lambda { parameters, .. },
+                    ..
+                }) = &argument
+                    && parameters.len() == 2
+                {
+                    self.diagnostics.push(Diagnostic::error(format!(
+                        "the Select with an index in {} has no java counterpart",
+                        self.scopes.method
+                    )));
+                }
+                method_call(source, "map", argument.into_iter().collect())
+            }
+            // the lambda has to give a stream, x -> x.Children.stream()
+            "SelectMany" => {
+                let mut argument = argument.into_iter().collect::<Vec<_>>();
+                if let Some((false, typ)) = &query.flattened
+                    && let Some(Expression {
+                        kind:
+                            ExpressionKind::Lambda {
+                                body: LambdaBody::Expression(body),
+                                ..
+                            },
+                        ..
+                    }) = argument.first_mut()
+                {
+                    **body = stream(take_expression(body), typ);
+                }
+                method_call(source, "flatMap", argument)
+            }
+            "Distinct" => method_call(source, "distinct", Vec::new()),
+            "OrderBy" | "OrderByDescending" => {
+                let comparator = comparator(argument, method == "OrderByDescending");
+                method_call(source, "sorted", vec![comparator])
+            }
+            // the sort is stable, the secondary key is sorted first
+            "ThenBy" | "ThenByDescending" => {
+                let comparator = comparator(argument, method == "ThenByDescending");
+                sort_before(source, query.sorts, comparator)
+            }
+            // the groups keep the order of their first element, like in C#
+            "GroupBy" => {
+                let to_list = static_call("Collectors", "toList", Vec::new());
+                let downstream = match second {
+                    Some(element) => static_call("Collectors", "mapping", vec![element, to_list]),
+                    None => to_list,
+                };
+                let map = Expression::new(ExpressionKind::MethodReference {
+                    target: Box::new(Expression::new(ExpressionKind::Variable(
+                        "LinkedHashMap".to_string(),
+                    ))),
+                    name: "new".to_string(),
+                });
+                let grouping = static_call(
+                    "Collectors",
+                    "groupingBy",
+                    argument.into_iter().chain([map, downstream]).collect(),
+                );
+                let groups = method_call(source, "collect", vec![grouping]);
+                let entries = method_call(groups, "entrySet", Vec::new());
+                method_call(entries, "stream", Vec::new())
+            }
+            "Any" if argument.is_none() => {
+                let any = method_call(source, "findAny", Vec::new());
+                method_call(any, "isPresent", Vec::new())
+            }
+            "Any" => method_call(source, "anyMatch", argument.into_iter().collect()),
+            "All" => method_call(source, "allMatch", argument.into_iter().collect()),
+            "First" => {
+                let first = method_call(filtered(source), "findFirst", Vec::new());
+                method_call(first, "orElseThrow", Vec::new())
+            }
+            "FirstOrDefault" => {
+                let first = method_call(filtered(source), "findFirst", Vec::new());
+                let default = default_value(&query.typ)
+                    .unwrap_or(Expression::new(ExpressionKind::Literal(Literal::Null)));
+                method_call(first, "orElse", vec![default])
+            }
+            // count gives a long
+            "Count" => Expression::new(ExpressionKind::CastExpression {
+                typ: Type::Int,
+                value: Box::new(method_call(filtered(source), "count", Vec::new())),
+            }),
+            "Sum" => self.sum(source, argument, &query.typ),
+            "Average" => self.average(source, argument, query.numbers),
+            "Max" | "Min" => self.extreme(source, argument, method, &query.typ),
+            "ToList" => to_list(source),
+            // the entries keep their order like in C#, and a repeated key throws
+            "ToDictionary" => {
+                let value =
+                    second.unwrap_or_else(|| static_call("Function", "identity", Vec::new()));
+                let map = Expression::new(ExpressionKind::MethodReference {
+                    target: Box::new(Expression::new(ExpressionKind::Variable(
+                        "LinkedHashMap".to_string(),
+                    ))),
+                    name: "new".to_string(),
+                });
+                let collector = static_call(
+                    "Collectors",
+                    "toMap",
+                    argument
+                        .into_iter()
+                        .chain([value, duplicate_key(&self.scopes), map])
+                        .collect(),
+                );
+                method_call(source, "collect", vec![collector])
+            }
+            _ => unreachable!("{} is not a LINQ method", method),
+        }
+    }
+
+    /// Adds the numbers of a stream, on the primitive stream of their type,
+    /// the decimals are added one by one. Sum is checked in C#, the ints and
+    /// longs are added with Math.addExact
+    fn sum(&mut self, source: Expression, selector: Option<Expression>, typ: &Type) -> Expression {
+        let numbers = match typ {
+            Type::Decimal => {
+                let numbers = match selector {
+                    Some(selector) => method_call(source, "map", vec![selector]),
+                    None => source,
+                };
+                let add = Expression::new(ExpressionKind::MethodReference {
+                    target: Box::new(Expression::new(ExpressionKind::Variable(
+                        "BigDecimal".to_string(),
+                    ))),
+                    name: "add".to_string(),
+                });
+                let zero = Expression::new(ExpressionKind::MemberAccess {
+                    target: Box::new(Expression::new(ExpressionKind::Variable(
+                        "BigDecimal".to_string(),
+                    ))),
+                    name: "ZERO".to_string(),
+                });
+                return method_call(numbers, "reduce", vec![zero, add]);
+            }
+            Type::Int | Type::Long | Type::Double | Type::Float => numbers(source, selector, typ),
+            _ => self.unknown_numbers("Sum", source, selector),
+        };
+        match typ {
+            // reduce(0, Math::addExact) throws on an overflow
+            Type::Int | Type::Long => {
+                let add = Expression::new(ExpressionKind::MethodReference {
+                    target: Box::new(Expression::new(ExpressionKind::Variable(
+                        "Math".to_string(),
+                    ))),
+                    name: "addExact".to_string(),
+                });
+                let zero = Expression::new(ExpressionKind::Literal(Literal::Int(0)));
+                method_call(numbers, "reduce", vec![zero, add])
+            }
+            Type::Float => Expression::new(ExpressionKind::CastExpression {
+                typ: Type::Float,
+                value: Box::new(method_call(numbers, "sum", Vec::new())),
+            }),
+            _ => method_call(numbers, "sum", Vec::new()),
+        }
+    }
+
+    /// Averages the numbers of a stream, an empty one throws like in C#
+    fn average(
+        &mut self,
+        source: Expression,
+        selector: Option<Expression>,
+        typ: Option<Type>,
+    ) -> Expression {
+        let numbers = match &typ {
+            Some(number @ (Type::Int | Type::Long | Type::Double | Type::Float)) => {
+                numbers(source, selector, number)
+            }
+            Some(Type::Decimal) => {
+                self.diagnostics.push(Diagnostic::error(format!(
+                    "the Average of decimals in {} is not supported",
+                    self.scopes.method
+                )));
+                return method_call(source, "average", selector.into_iter().collect());
+            }
+            _ => self.unknown_numbers("Average", source, selector),
+        };
+        let average = method_call(
+            method_call(numbers, "average", Vec::new()),
+            "orElseThrow",
+            Vec::new(),
+        );
+        match typ {
+            Some(Type::Float) => Expression::new(ExpressionKind::CastExpression {
+                typ: Type::Float,
+                value: Box::new(average),
+            }),
+            _ => average,
+        }
+    }
+
+    /// Finds the largest or the smallest value of a stream with Max or Min, the
+    /// numbers on their primitive stream, the rest in their natural order. An
+    /// empty stream throws, except for the references which give null like in C#
+    fn extreme(
+        &mut self,
+        source: Expression,
+        selector: Option<Expression>,
+        method: &str,
+        typ: &Type,
+    ) -> Expression {
+        let name = method.to_lowercase();
+        let extreme = match typ {
+            Type::Int | Type::Long | Type::Double | Type::Float => {
+                method_call(numbers(source, selector, typ), &name, Vec::new())
+            }
+            _ => {
+                let values = match selector {
+                    Some(selector) => method_call(source, "map", vec![selector]),
+                    None => source,
+                };
+                let order = static_call("Comparator", "naturalOrder", Vec::new());
+                method_call(values, &name, vec![order])
+            }
+        };
+        match typ {
+            Type::Float => Expression::new(ExpressionKind::CastExpression {
+                typ: Type::Float,
+                value: Box::new(method_call(extreme, "orElseThrow", Vec::new())),
+            }),
+            Type::String | Type::Named(_) | Type::Generic(..) | Type::Array(_) => method_call(
+                extreme,
+                "orElse",
+                vec![Expression::new(ExpressionKind::Literal(Literal::Null))],
+            ),
+            _ => method_call(extreme, "orElseThrow", Vec::new()),
+        }
+    }
+
+    /// Reports the numbers of an unknown type, they are taken as doubles
+    fn unknown_numbers(
+        &mut self,
+        method: &str,
+        source: Expression,
+        selector: Option<Expression>,
+    ) -> Expression {
+        self.diagnostics.push(Diagnostic::error(format!(
+            "the type of the numbers added by {} in {} is not known",
+            method, self.scopes.method
+        )));
+        numbers(source, selector, &Type::Double)
+    }
+}
+
+/// Helper function that maps a stream to the primitive stream of its numbers,
+/// Sum() unboxes the elements with Integer::intValue
+fn numbers(source: Expression, selector: Option<Expression>, typ: &Type) -> Expression {
+    let (map, class, unboxing) = match typ {
+        Type::Int => ("mapToInt", "Integer", "intValue"),
+        Type::Long => ("mapToLong", "Long", "longValue"),
+        _ => ("mapToDouble", "Double", "doubleValue"),
+    };
+    let selector = selector.unwrap_or_else(|| {
+        Expression::new(ExpressionKind::MethodReference {
+            target: Box::new(Expression::new(ExpressionKind::Variable(class.to_string()))),
+            name: unboxing.to_string(),
+        })
+    });
+    method_call(source, map, vec![selector])
+}
+
+/// Helper function that starts the stream of a sequence, xs.stream() or
+/// Arrays.stream(xs), the arrays of numbers are boxed like the lists
+pub(super) fn stream(target: Expression, typ: &Type) -> Expression {
+    match typ {
+        Type::Array(element) => {
+            let stream = static_call("Arrays", "stream", vec![target]);
+            match **element {
+                Type::Int | Type::Long | Type::Double => method_call(stream, "boxed", Vec::new()),
+                _ => stream,
+            }
+        }
+        // a group is an entry of the map, its elements are the value
+        Type::Generic(name, _) if name == "IGrouping" => {
+            let elements = method_call(target, "getValue", Vec::new());
+            method_call(elements, "stream", Vec::new())
+        }
+        _ => method_call(target, "stream", Vec::new()),
+    }
+}
+
+/// Helper function that collects a stream into a list, which can be changed like a C# one
+fn to_list(stream: Expression) -> Expression {
+    let collector = static_call("Collectors", "toList", Vec::new());
+    method_call(stream, "collect", vec![collector])
+}
+
+/// Helper function that compares by a key, Comparator.comparing(x -> x.Age),
+/// in reverse order for the descending sorts
+fn comparator(key: Option<Expression>, descending: bool) -> Expression {
+    let mut arguments: Vec<Expression> = key.into_iter().collect();
+    if descending {
+        arguments.push(static_call("Comparator", "reverseOrder", Vec::new()));
+    }
+    static_call("Comparator", "comparing", arguments)
+}
+
+/// Helper function that counts the OrderBy and ThenBy calls ending a query,
+/// those a ThenBy refines
+fn sort_depth(expr: &Expression) -> usize {
+    let callee = match &expr.kind {
+        ExpressionKind::ParenthesizedExpression(inner) => return sort_depth(inner),
+        ExpressionKind::Call { callee, .. } => callee,
+        _ => return 0,
+    };
+    match &callee.kind {
+        ExpressionKind::MemberAccess { target, name } => match name.as_str() {
+            "ThenBy" | "ThenByDescending" => 1 + sort_depth(target),
+            "OrderBy" | "OrderByDescending" => 1,
+            _ => 0,
+        },
+        _ => 0,
+    }
+}
+
+/// Helper function that sorts a stream before its last sorts, OrderBy(a).ThenBy(b)
+/// becomes sorted(b).sorted(a), the stable sort by a keeps the order of b for equal keys
+fn sort_before(source: Expression, depth: usize, comparator: Expression) -> Expression {
+    match source {
+        Expression {
+            kind: ExpressionKind::ParenthesizedExpression(inner),
+            id,
+        } if depth > 0 => Expression {
+            id,
+            kind: ExpressionKind::ParenthesizedExpression(Box::new(sort_before(
+                *inner, depth, comparator,
+            ))),
+        },
+        Expression {
+            kind: ExpressionKind::Call { callee, arguments },
+            id,
+        } if depth > 0 => {
+            let ExpressionKind::MemberAccess { target, name } = callee.kind else {
+                unreachable!("a sort is a method call");
+            };
+            Expression {
+                id,
+                kind: ExpressionKind::Call {
+                    callee: Box::new(Expression {
+                        id: callee.id,
+                        kind: ExpressionKind::MemberAccess {
+                            target: Box::new(sort_before(*target, depth - 1, comparator)),
+                            name,
+                        },
+                    }),
+                    arguments,
+                },
+            }
+        }
+        source => method_call(source, "sorted", vec![comparator]),
+    }
+}
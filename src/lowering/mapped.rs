@@ -0,0 +1,59 @@
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+use crate::mappings::Mappings;
+
+use super::{Lowering, Scopes, lower_children, lower_methods};
+
+/// Reports the calls the mappings cannot write, a template using {1} for a call
+/// with one argument. It runs last, on the calls the generator writes
+pub fn check_mapped_calls(program: &mut Program, mappings: &Mappings, java_version: u32) {
+    let mut pass = MappedCalls {
+        scopes: Scopes::default(),
+        diagnostics: Vec::new(),
+        mappings,
+        java_version,
+    };
+    lower_methods(&mut pass, program);
+    program.diagnostics.extend(pass.diagnostics);
+}
+
+struct MappedCalls<'a> {
+    scopes: Scopes,
+    diagnostics: Vec<Diagnostic>,
+    mappings: &'a Mappings,
+    java_version: u32,
+}
+
+impl Lowering for MappedCalls<'_> {
+    fn scopes(&mut self) -> &mut Scopes {
+        &mut self.scopes
+    }
+
+    fn lower_expression(&mut self, expr: &mut Expression) {
+        if let ExpressionKind::Call { callee, arguments } = &expr.kind
+            && let Some(path) = callee.path()
+        {
+            let types: Vec<Option<Type>> = arguments
+                .iter()
+                .map(|argument| self.scopes.types.get(argument).cloned())
+                .collect();
+            // int.Parse() reads the input, it needs no argument
+            if let Some(mapping) = self.mappings.method(&path, &types, self.java_version)
+                && !(mapping.input.is_some() && arguments.is_empty())
+                && let Some(index) = mapping.missing_argument(arguments.len())
+            {
+                self.diagnostics.push(Diagnostic::error(format!(
+                    "the mapping of `{}` uses `{{{}}}`, the call in {} has {}",
+                    path,
+                    index,
+                    self.scopes.method,
+                    match arguments.len() {
+                        1 => "1 argument".to_string(),
+                        n => format!("{} arguments", n),
+                    }
+                )));
+            }
+        }
+        lower_children(self, expr);
+    }
+}
@@ -0,0 +1,532 @@
+use std::collections::HashSet;
+
+use crate::{
+    ast::*,
+    options::Options,
+    semantic::typecheck::{check_types, children},
+};
+
+pub use crate::semantic::scopes::{
+    Scopes, class_fields, class_methods, fits, is_integral, literal_type, promote, try_parse_type,
+    underlying_type,
+};
+
+mod checked;
+mod collections;
+mod decimal;
+mod delegates;
+mod events;
+mod holders;
+mod linq;
+mod mapped;
+mod nullable;
+mod overloads;
+mod parameters;
+mod strings;
+mod unsigned;
+
+/// Runs every lowering pass on the program, each pass rewrites
+/// C# constructs that java does not have into ones it does.
+/// The named arguments are put in order first and the optional parameters
+/// become overloads, the events become lists of listeners, then the ref and out
+/// variables become holders, the delegates become java interfaces and the LINQ
+/// calls become streams before the overloads are resolved, on the C# types of the arguments,
+/// then the string comparisons, while x.ToString() is still a string,
+/// then the decimal pass, it widens the unsigned values it needs itself,
+/// x.Value is only removed after the decimal and unsigned passes used its type,
+/// the overflow checks come after them, on the already masked values,
+/// and the collection members last, every pass before still sees xs[i] and xs.Count.
+/// The calls the mappings cannot write are reported on the lowered program
+pub fn lower_program(program: &mut Program, options: &Options) {
+    parameters::lower_parameters(program);
+    events::lower_events(program);
+    holders::lower_holders(program);
+    delegates::lower_delegates(program);
+    linq::lower_linq(program);
+    overloads::lower_overloads(program);
+    strings::lower_strings(program, options.java_version);
+    decimal::lower_decimal(program);
+    unsigned::lower_unsigned(program);
+    nullable::lower_nullable(program);
+    checked::lower_checked(program, options.checked);
+    collections::lower_collections(program);
+    mapped::check_mapped_calls(program, &options.mappings, options.java_version);
+}
+
+impl Scopes {
+    /// Finds a name for a created variable that no variable of the method uses,
+    /// numberParsed, or numberParsed2 when the C# code has a numberParsed already.
+    /// The fields of the class are avoided too, the variable would hide them.
+    /// The caller adds it to the names once it declares it
+    pub fn fresh_name(&self, base: &str) -> String {
+        let fields = self.fields.get(&self.class);
+        let taken = |name: &String| {
+            self.names.contains(name) || fields.is_some_and(|fields| fields.contains_key(name))
+        };
+        let mut name = base.to_string();
+        let mut count = 1;
+        while taken(&name) {
+            count += 1;
+            name = format!("{}{}", base, count);
+        }
+        name
+    }
+
+    /// Checks whether the target of ++ or of an assignment can be read and written
+    /// inside one expression, (x = x + 1): a variable, a field of one, or an array
+    /// element with a plain index. A list element needs a get and a set
+    pub fn is_plain_target(&self, expr: &Expression) -> bool {
+        match &expr.kind {
+            ExpressionKind::Variable(_) | ExpressionKind::MemberAccess { .. } => is_pure(expr),
+            ExpressionKind::ElementAccess { target, index } => {
+                matches!(self.type_of(target), Some(Type::Array(_)))
+                    && is_pure(target)
+                    && is_pure(index)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Helper function that obtains default(T), the value GetValueOrDefault falls back to
+/// and the one an out variable starts with. None for the types that default to null
+pub fn default_value(typ: &Type) -> Option<Expression> {
+    let value = match typ {
+        Type::Int | Type::UInt => Literal::Int(0),
+        Type::Long | Type::ULong => Literal::Long(0),
+        Type::Bool => Literal::Bool(false),
+        Type::Char => Literal::Char('\0'),
+        Type::Float => Literal::Float("0f".to_string()),
+        Type::Double => Literal::Double("0.0".to_string()),
+        Type::Decimal => return Some(static_field("BigDecimal", "ZERO")),
+        // the small types need a cast, Objects.requireNonNullElse takes two of the same
+        Type::Short | Type::Byte | Type::SByte | Type::UShort => {
+            return Some(Expression::new(ExpressionKind::CastExpression {
+                typ: typ.clone(),
+                value: Box::new(Expression::new(ExpressionKind::Literal(Literal::Int(0)))),
+            }));
+        }
+        _ => return None,
+    };
+    Some(Expression::new(ExpressionKind::Literal(value)))
+}
+
+/// A lowering pass, the functions below walk the methods and call it
+/// for every statement and expression
+pub trait Lowering {
+    /// The variable types, kept up to date while walking
+    fn scopes(&mut self) -> &mut Scopes;
+
+    /// Rewrites an expression, the pass decides when to lower the children,
+    /// usually after looking at their C# types
+    fn lower_expression(&mut self, expr: &mut Expression) {
+        lower_children(self, expr);
+    }
+
+    /// Rewrites a statement, same as for expressions
+    fn lower_statement(&mut self, statement: &mut Statement) {
+        lower_statement_children(self, statement);
+    }
+}
+
+/// Runs a lowering pass on every field initializer and method of the program
+pub fn lower_methods<L: Lowering>(pass: &mut L, program: &mut Program) {
+    // the table goes back to the program for the next pass
+    pass.scopes().symbols = std::mem::take(&mut program.symbols);
+    // the types are checked again, the previous passes changed the program
+    pass.scopes().types = check_types(program).0;
+    pass.scopes().delegates = program.delegates.clone();
+    pass.scopes().fields = class_fields(program);
+    for class in &mut program.classes {
+        // the methods of the class can be called from any of them
+        pass.scopes().methods = class_methods(class);
+        pass.scopes().class = class.name.clone();
+
+        // the fields are visible in every method
+        pass.scopes().push();
+        pass.scopes().return_type = None;
+        pass.scopes().method = class.name.clone();
+        for field in &mut class.fields {
+            // an initializer is lowered just like the one of a local
+            let mut declaration = Statement::VariableDeclaration {
+                variable: field.variable.clone(),
+                value: field.value.take(),
+            };
+            pass.lower_statement(&mut declaration);
+            if let Statement::VariableDeclaration { value, .. } = declaration {
+                field.value = value;
+            }
+        }
+
+        for method in &mut class.methods {
+            pass.scopes().return_type = Some(method.return_type.clone());
+            pass.scopes().method = method.name.clone();
+            pass.scopes().names = declared_names(method);
+            // the parameters are visible in the whole body
+            pass.scopes().push();
+            for parameter in &method.parameters {
+                pass.scopes()
+                    .declare(&parameter.variable.name, parameter.variable.typ.clone());
+            }
+            lower_statements(pass, &mut method.body);
+            pass.scopes().pop();
+        }
+        pass.scopes().pop();
+    }
+    program.symbols = std::mem::take(&mut pass.scopes().symbols);
+}
+
+/// Helper function that collects the names of the parameters and of every variable
+/// declared in a method, in the nested blocks and in the lambdas too
+pub fn declared_names(method: &Method) -> HashSet<String> {
+    fn statement_names(statement: &Statement, names: &mut HashSet<String>) {
+        let block = |statements: &[Statement], names: &mut HashSet<String>| {
+            for statement in statements {
+                statement_names(statement, names);
+            }
+        };
+        match statement {
+            Statement::VariableDeclaration { variable, value } => {
+                names.insert(variable.name.clone());
+                if let Some(value) = value {
+                    expression_names(value, names);
+                }
+            }
+            Statement::Assignment { target, value, .. } => {
+                expression_names(target, names);
+                expression_names(value, names);
+            }
+            Statement::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                expression_names(condition, names);
+                block(then_body, names);
+                block(else_body, names);
+            }
+            Statement::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                for statement in initializer.iter().chain(increment) {
+                    statement_names(statement, names);
+                }
+                if let Some(condition) = condition {
+                    expression_names(condition, names);
+                }
+                block(body, names);
+            }
+            Statement::While { condition, body } => {
+                expression_names(condition, names);
+                block(body, names);
+            }
+            Statement::Checked { body, .. } | Statement::Block(body) => block(body, names),
+            Statement::TryCatch {
+                body,
+                exception,
+                handler,
+            } => {
+                names.insert(exception.name.clone());
+                block(body, names);
+                block(handler, names);
+            }
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    expression_names(value, names);
+                }
+            }
+            Statement::Expression(expr) | Statement::Throw(expr) => expression_names(expr, names),
+        }
+    }
+    fn expression_names(expr: &Expression, names: &mut HashSet<String>) {
+        match &expr.kind {
+            // the out var n of a call
+            ExpressionKind::DeclarationExpression(variable) => {
+                names.insert(variable.name.clone());
+            }
+            ExpressionKind::Lambda { parameters, body } => {
+                names.extend(parameters.iter().map(|parameter| parameter.name.clone()));
+                if let LambdaBody::Block(statements) = body {
+                    for statement in statements {
+                        statement_names(statement, names);
+                    }
+                }
+            }
+            _ => {}
+        }
+        for child in children(expr) {
+            expression_names(child, names);
+        }
+    }
+
+    let mut names: HashSet<String> = method
+        .parameters
+        .iter()
+        .map(|parameter| parameter.variable.name.clone())
+        .collect();
+    for statement in &method.body {
+        statement_names(statement, &mut names);
+    }
+    names
+}
+
+/// Lowers a block of statements, in its own scope
+pub fn lower_statements<L: Lowering + ?Sized>(pass: &mut L, statements: &mut [Statement]) {
+    pass.scopes().push();
+    for statement in statements {
+        pass.lower_statement(statement);
+    }
+    pass.scopes().pop();
+}
+
+/// Lowers the expressions and blocks of a statement, declaring its variables
+pub fn lower_statement_children<L: Lowering + ?Sized>(pass: &mut L, statement: &mut Statement) {
+    match statement {
+        Statement::VariableDeclaration { variable, value } => {
+            // the value of a constant is computed before the lowering changes it
+            let constant = pass.scopes().declared_constant(variable, value.as_ref());
+            if let Some(value) = value {
+                pass.lower_expression(value);
+            }
+            // the variable is visible after its declaration
+            pass.scopes().declare(&variable.name, variable.typ.clone());
+            if let Some(constant) = constant {
+                pass.scopes().declare_constant(&variable.name, constant);
+            }
+        }
+        Statement::Assignment { target, value, .. } => {
+            pass.lower_expression(target);
+            pass.lower_expression(value);
+        }
+        Statement::If {
+            condition,
+            then_body,
+            else_body,
+        } => {
+            pass.lower_expression(condition);
+            lower_statements(pass, then_body);
+            lower_statements(pass, else_body);
+        }
+        Statement::For {
+            initializer,
+            condition,
+            increment,
+            body,
+        } => {
+            // the initializer's variable is only visible inside the loop
+            pass.scopes().push();
+            if let Some(initializer) = initializer {
+                pass.lower_statement(initializer);
+            }
+            if let Some(condition) = condition {
+                pass.lower_expression(condition);
+            }
+            if let Some(increment) = increment {
+                pass.lower_statement(increment);
+            }
+            lower_statements(pass, body);
+            pass.scopes().pop();
+        }
+        Statement::While { condition, body } => {
+            pass.lower_expression(condition);
+            lower_statements(pass, body);
+        }
+        Statement::Checked { body, .. } | Statement::Block(body) => lower_statements(pass, body),
+        Statement::TryCatch {
+            body,
+            exception,
+            handler,
+        } => {
+            lower_statements(pass, body);
+            pass.scopes().push();
+            pass.scopes()
+                .declare(&exception.name, exception.typ.clone());
+            lower_statements(pass, handler);
+            pass.scopes().pop();
+        }
+        Statement::Return(value) => {
+            if let Some(value) = value {
+                pass.lower_expression(value);
+            }
+        }
+        Statement::Expression(expr) | Statement::Throw(expr) => pass.lower_expression(expr),
+    }
+}
+
+/// Lowers the sub-expressions of an expression
+pub fn lower_children<L: Lowering + ?Sized>(pass: &mut L, expr: &mut Expression) {
+    // a lambda has its own scope, with its parameters
+    if let ExpressionKind::Lambda { parameters, body } = &mut expr.kind {
+        pass.scopes().push();
+        for parameter in parameters.iter() {
+            pass.scopes()
+                .declare(&parameter.name, parameter.typ.clone());
+        }
+        // the returns of a block are those of the lambda, not of the method
+        let return_type = pass.scopes().return_type.take();
+        match body {
+            LambdaBody::Expression(body) => pass.lower_expression(body),
+            LambdaBody::Block(statements) => lower_statements(pass, statements),
+        }
+        pass.scopes().return_type = return_type;
+        pass.scopes().pop();
+        return;
+    }
+    for child in expr.children_mut() {
+        pass.lower_expression(child);
+    }
+}
+
+/// Helper function that takes an expression out of the tree, so it can be
+/// moved into the rewritten one
+pub fn take_expression(expr: &mut Expression) -> Expression {
+    std::mem::replace(expr, Expression::new(ExpressionKind::This))
+}
+
+/// Helper function that creates a call to a static method, such as Integer.divideUnsigned(a, b)
+pub fn static_call(class: &str, method: &str, arguments: Vec<Expression>) -> Expression {
+    method_call(
+        Expression::new(ExpressionKind::Variable(class.to_string())),
+        method,
+        arguments,
+    )
+}
+
+/// Helper function that creates a call to a method of an object, such as a.add(b)
+pub fn method_call(target: Expression, method: &str, arguments: Vec<Expression>) -> Expression {
+    Expression::new(ExpressionKind::Call {
+        callee: Box::new(Expression::new(ExpressionKind::MemberAccess {
+            target: Box::new(target),
+            name: method.to_string(),
+        })),
+        arguments,
+    })
+}
+
+/// Helper function that creates an access to a static field, such as BigDecimal.ONE
+pub fn static_field(class: &str, name: &str) -> Expression {
+    Expression::new(ExpressionKind::MemberAccess {
+        target: Box::new(Expression::new(ExpressionKind::Variable(class.to_string()))),
+        name: name.to_string(),
+    })
+}
+
+/// Helper function for the merge function of toMap, which is only called for
+/// a repeated key, (a, b) -> { throw new IllegalStateException("Duplicate key"); }.
+/// Its parameters cannot hide the variables of the method
+pub fn duplicate_key(scopes: &Scopes) -> Expression {
+    let parameter = |name: &str| Variable {
+        typ: Type::Unknown,
+        name: name.to_string(),
+        implicit: true,
+        constant: false,
+    };
+    let exception = Expression::new(ExpressionKind::ObjectCreation {
+        typ: Type::Named("IllegalStateException".to_string()),
+        arguments: vec![Expression::new(ExpressionKind::Literal(Literal::String(
+            "Duplicate key".to_string(),
+        )))],
+    });
+    Expression::new(ExpressionKind::Lambda {
+        parameters: vec![
+            parameter(&scopes.fresh_name("a")),
+            parameter(&scopes.fresh_name("b")),
+        ],
+        body: LambdaBody::Block(vec![Statement::Throw(exception)]),
+    })
+}
+
+/// Keeps the receiver and the index of the target of an assignment or of ++ in
+/// variables, when reading them runs code, so that the rewritten statement reads
+/// them once: Get().Price += x becomes { var item = Get(); item.Price += x; }.
+/// The statement is put in a block after its declarations, returns false when
+/// nothing had to be kept
+pub fn hoist_target(scopes: &mut Scopes, statement: &mut Statement) -> bool {
+    let target = match statement {
+        Statement::Assignment { target, .. } => target,
+        Statement::Expression(
+            Expression {
+                kind: ExpressionKind::PostfixUnaryExpression { left: target, .. },
+                ..
+            }
+            | Expression {
+                kind: ExpressionKind::PrefixUnaryExpression { right: target, .. },
+                ..
+            },
+        ) => &mut **target,
+        _ => return false,
+    };
+    let parts = match &mut target.kind {
+        ExpressionKind::MemberAccess { target, .. } => vec![&mut **target],
+        ExpressionKind::ElementAccess { target, index } => vec![&mut **target, &mut **index],
+        _ => return false,
+    };
+
+    let mut prelude = Vec::new();
+    for (position, part) in parts.into_iter().enumerate() {
+        if is_pure(part) {
+            continue;
+        }
+        // the variable needs a type, the passes find the type of the target through it
+        let Some(typ) = scopes.type_of(part) else {
+            continue;
+        };
+        let base = match &typ {
+            _ if position == 1 => "index".to_string(),
+            // an Item is kept in item
+            Type::Named(class) => {
+                let mut chars = class.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_lowercase().chain(chars).collect())
+                    .unwrap_or_default()
+            }
+            _ => "target".to_string(),
+        };
+        let name = scopes.fresh_name(&base);
+        scopes.names.insert(name.clone());
+        let value = std::mem::replace(
+            part,
+            Expression::new(ExpressionKind::Variable(name.clone())),
+        );
+        prelude.push(Statement::VariableDeclaration {
+            variable: Variable {
+                typ,
+                name,
+                implicit: true,
+                constant: false,
+            },
+            value: Some(value),
+        });
+    }
+    if prelude.is_empty() {
+        return false;
+    }
+    prelude.push(std::mem::replace(statement, Statement::Block(Vec::new())));
+    *statement = Statement::Block(prelude);
+    true
+}
+
+/// Helper function that tells whether an expression can be evaluated twice, or in
+/// another order, without changing the program: the variables, literals and the
+/// fields read from them. A call or an assignment could run differently
+pub fn is_pure(expr: &Expression) -> bool {
+    match &expr.kind {
+        ExpressionKind::Literal(_)
+        | ExpressionKind::Variable(_)
+        | ExpressionKind::This
+        | ExpressionKind::Base
+        | ExpressionKind::TypeOfExpression(_) => true,
+        ExpressionKind::MemberAccess { target, .. }
+        | ExpressionKind::ParenthesizedExpression(target)
+        | ExpressionKind::CastExpression { value: target, .. }
+        | ExpressionKind::NamedArgument { value: target, .. } => is_pure(target),
+        ExpressionKind::PrefixUnaryExpression { operator, right } => {
+            !matches!(operator, UnaryOperator::UAdd | UnaryOperator::USub) && is_pure(right)
+        }
+        _ => false,
+    }
+}
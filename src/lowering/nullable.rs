@@ -0,0 +1,540 @@
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+
+use super::{
+    Lowering, Scopes, default_value, is_pure, lower_children, lower_methods,
+    lower_statement_children, method_call, static_call, static_field, take_expression,
+    underlying_type,
+};
+
+/// Translates the C# nullable value types, int? is stored in an Integer.
+/// x.HasValue becomes a null check, x.Value is unboxed by java itself, and the
+/// lifted operators check their nullable operands first, since java would throw.
+/// The check reads the operand a second time, a call is left unchecked.
+/// a ?? b becomes (a != null ? a : b)
+pub fn lower_nullable(program: &mut Program) {
+    let mut pass = NullableLowering::default();
+    lower_methods(&mut pass, program);
+    program.diagnostics.extend(pass.diagnostics);
+}
+
+#[derive(Default)]
+struct NullableLowering {
+    scopes: Scopes,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Lowering for NullableLowering {
+    fn scopes(&mut self) -> &mut Scopes {
+        &mut self.scopes
+    }
+
+    fn lower_expression(&mut self, expr: &mut Expression) {
+        match &mut expr.kind {
+            ExpressionKind::MemberAccess { target, name } => {
+                let is_nullable = is_nullable(self.scopes.type_of(target).as_ref());
+                let name = name.clone();
+                lower_children(self, expr);
+                let ExpressionKind::MemberAccess { target, .. } = &mut expr.kind else {
+                    return;
+                };
+                match name.as_str() {
+                    // x.Value becomes x
+                    "Value" if is_nullable => *expr = take_expression(target),
+                    // x.HasValue becomes x != null
+                    "HasValue" if is_nullable => {
+                        *expr = null_check(take_expression(target), BinaryOperator::Ne)
+                    }
+                    _ => {}
+                }
+            }
+            ExpressionKind::Call { callee, arguments } => {
+                // x.GetValueOrDefault(), with or without the default value
+                let inner = match &callee.kind {
+                    ExpressionKind::MemberAccess { target, name }
+                        if name == "GetValueOrDefault" =>
+                    {
+                        match self.scopes.type_of(target) {
+                            Some(Type::Nullable(inner)) => Some(*inner),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                let argument_type = arguments
+                    .first()
+                    .and_then(|argument| self.scopes.type_of(argument));
+                lower_children(self, expr);
+
+                let Some(inner) = inner else {
+                    return;
+                };
+                let ExpressionKind::Call { callee, arguments } = &mut expr.kind else {
+                    return;
+                };
+                let ExpressionKind::MemberAccess { target, .. } = &mut callee.kind else {
+                    return;
+                };
+                let fallback = match arguments.pop() {
+                    Some(mut value) => {
+                        let typ = Type::Nullable(Box::new(inner.clone()));
+                        self.convert(&mut value, argument_type.as_ref(), &typ);
+                        Some(value)
+                    }
+                    None => default_value(&inner),
+                };
+                // x.GetValueOrDefault() becomes Objects.requireNonNullElse(x, 0)
+                if let Some(fallback) = fallback {
+                    let target = take_expression(target);
+                    *expr = static_call("Objects", "requireNonNullElse", vec![target, fallback]);
+                }
+            }
+            ExpressionKind::BinaryExpression { left, right, .. } => {
+                // the types are computed before the children are rewritten
+                let left_type = self.scopes.type_of(left);
+                let right_type = self.scopes.type_of(right);
+                lower_children(self, expr);
+                if let ExpressionKind::BinaryExpression {
+                    operator: BinaryOperator::Coalesce,
+                    ..
+                } = &mut expr.kind
+                {
+                    return coalesce(expr);
+                }
+                self.lower_binary(expr, left_type, right_type);
+            }
+            ExpressionKind::PrefixUnaryExpression { right: operand, .. }
+            | ExpressionKind::PostfixUnaryExpression { left: operand, .. } => {
+                let typ = self.scopes.type_of(operand);
+                lower_children(self, expr);
+                if !is_nullable(typ.as_ref()) {
+                    return;
+                }
+                let (ExpressionKind::PrefixUnaryExpression { right: operand, .. }
+                | ExpressionKind::PostfixUnaryExpression { left: operand, .. }) = &mut expr.kind
+                else {
+                    return;
+                };
+                if !is_pure(operand) {
+                    self.unchecked();
+                    return;
+                }
+                // -x becomes (x == null ? null : -x), and x++ (x == null ? null : x++)
+                let check = null_check((**operand).clone(), BinaryOperator::Eq);
+                let value = take_expression(expr);
+                *expr = lifted(check, value);
+            }
+            _ => lower_children(self, expr),
+        }
+    }
+
+    fn lower_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            // x++ becomes if (x != null) { x++; }, a null stays null
+            Statement::Expression(
+                Expression {
+                    kind:
+                        ExpressionKind::PrefixUnaryExpression {
+                            operator: UnaryOperator::UAdd | UnaryOperator::USub,
+                            right: operand,
+                        },
+                    ..
+                }
+                | Expression {
+                    kind:
+                        ExpressionKind::PostfixUnaryExpression {
+                            left: operand,
+                            operator: UnaryOperator::UAdd | UnaryOperator::USub,
+                        },
+                    ..
+                },
+            ) if is_nullable(self.scopes.type_of(operand).as_ref()) => {
+                if !is_pure(operand) {
+                    self.unchecked();
+                    return;
+                }
+                let condition = null_check((**operand).clone(), BinaryOperator::Ne);
+                let increment = std::mem::replace(statement, Statement::Block(Vec::new()));
+                *statement = Statement::If {
+                    condition,
+                    then_body: vec![increment],
+                    else_body: Vec::new(),
+                };
+            }
+            // x += y becomes x = x + y, which is lifted like any other operator
+            Statement::Assignment {
+                target,
+                operator: Some(operator),
+                value,
+            } if is_nullable(self.scopes.type_of(target).as_ref()) => {
+                let sum = Expression::new(ExpressionKind::BinaryExpression {
+                    left: Box::new(target.clone()),
+                    operator: operator.clone(),
+                    right: Box::new(value.clone()),
+                });
+                // a short? would become an int?, the compound assignment is left as is
+                let target_type = self.scopes.type_of(target);
+                if !is_pure(target) || self.scopes.type_of(&sum) != target_type {
+                    self.unchecked();
+                    return lower_statement_children(self, statement);
+                }
+                *statement = Statement::Assignment {
+                    target: take_expression(target),
+                    operator: None,
+                    value: sum,
+                };
+                self.lower_statement(statement);
+            }
+            Statement::VariableDeclaration {
+                variable,
+                value: Some(value),
+            } => {
+                let value_type = self.scopes.type_of(value);
+                let typ = variable.typ.clone();
+                lower_statement_children(self, statement);
+                if let Statement::VariableDeclaration {
+                    value: Some(value), ..
+                } = statement
+                {
+                    self.convert(value, value_type.as_ref(), &typ);
+                }
+            }
+            Statement::Assignment {
+                target,
+                operator: None,
+                value,
+            } => {
+                let target_type = self.scopes.type_of(target);
+                let value_type = self.scopes.type_of(value);
+                lower_statement_children(self, statement);
+                if let Some(target_type) = target_type
+                    && let Statement::Assignment { value, .. } = statement
+                {
+                    self.convert(value, value_type.as_ref(), &target_type);
+                }
+            }
+            Statement::Return(Some(value)) => {
+                let value_type = self.scopes.type_of(value);
+                let return_type = self.scopes.return_type.clone();
+                lower_statement_children(self, statement);
+                if let Some(return_type) = return_type
+                    && let Statement::Return(Some(value)) = statement
+                {
+                    self.convert(value, value_type.as_ref(), &return_type);
+                }
+            }
+            _ => lower_statement_children(self, statement),
+        }
+    }
+}
+
+/// Helper function that tells whether a type is a nullable one
+fn is_nullable(typ: Option<&Type>) -> bool {
+    matches!(typ, Some(Type::Nullable(_)))
+}
+
+/// Helper function that creates the x == null or x != null expression
+fn null_check(value: Expression, operator: BinaryOperator) -> Expression {
+    Expression::new(ExpressionKind::BinaryExpression {
+        left: Box::new(value),
+        operator,
+        right: Box::new(Expression::new(ExpressionKind::Literal(Literal::Null))),
+    })
+}
+
+/// Puts the null checks in front of a comparison, (check && a < b)
+fn guard(expr: &mut Expression, check: Expression, joiner: BinaryOperator) {
+    let value = take_expression(expr);
+    *expr = Expression::new(ExpressionKind::ParenthesizedExpression(Box::new(
+        Expression::new(ExpressionKind::BinaryExpression {
+            left: Box::new(check),
+            operator: joiner,
+            right: Box::new(value),
+        }),
+    )));
+}
+
+/// Helper function that rewrites a ?? b, a variable is checked for null,
+/// (a != null ? a : b), a call is read once through an Optional,
+/// Optional.ofNullable(Next()).orElse(b), and orElseGet(() -> Compute()) computes b
+/// only for a null, like the ?? does
+fn coalesce(expr: &mut Expression) {
+    let ExpressionKind::BinaryExpression { left, right, .. } = &mut expr.kind else {
+        return;
+    };
+    let (left, right) = (take_expression(left), take_expression(right));
+    if is_pure(&left) {
+        let check = null_check(left.clone(), BinaryOperator::Ne);
+        *expr = Expression::new(ExpressionKind::ParenthesizedExpression(Box::new(
+            Expression::new(ExpressionKind::ConditionalExpression {
+                condition: Box::new(check),
+                consequence: Box::new(left),
+                alternative: Box::new(right),
+            }),
+        )));
+        return;
+    }
+    let optional = static_call("Optional", "ofNullable", vec![left]);
+    *expr = match is_pure(&right) {
+        true => method_call(optional, "orElse", vec![right]),
+        false => {
+            let supplier = Expression::new(ExpressionKind::Lambda {
+                parameters: Vec::new(),
+                body: LambdaBody::Expression(Box::new(right)),
+            });
+            method_call(optional, "orElseGet", vec![supplier])
+        }
+    };
+}
+
+/// Helper function that creates the (check ? null : value) expression of a lifted operator
+fn lifted(check: Expression, value: Expression) -> Expression {
+    Expression::new(ExpressionKind::ParenthesizedExpression(Box::new(
+        Expression::new(ExpressionKind::ConditionalExpression {
+            condition: Box::new(check),
+            consequence: Box::new(Expression::new(ExpressionKind::Literal(Literal::Null))),
+            alternative: Box::new(value),
+        }),
+    )))
+}
+
+impl NullableLowering {
+    /// Rewrites a binary expression with nullable operands, whose children are already lowered
+    fn lower_binary(
+        &mut self,
+        expr: &mut Expression,
+        left_type: Option<Type>,
+        right_type: Option<Type>,
+    ) {
+        let ExpressionKind::BinaryExpression {
+            left,
+            operator,
+            right,
+        } = &mut expr.kind
+        else {
+            return;
+        };
+        let is_bool = left_type.as_ref().map(underlying_type) == Some(&Type::Bool);
+        let left_nullable = is_nullable(left_type.as_ref());
+        let right_nullable = is_nullable(right_type.as_ref());
+        // x == null is already fine
+        let is_null =
+            |side: &Expression| matches!(&side.kind, ExpressionKind::Literal(Literal::Null));
+        if (!left_nullable && !right_nullable) || is_null(left) || is_null(right) {
+            return;
+        }
+        // the checks read the nullable operands twice, Next() + 1 would call Next() again
+        let impure = (left_nullable && !is_pure(left)) || (right_nullable && !is_pure(right));
+        let same_type =
+            left_type.as_ref().map(underlying_type) == right_type.as_ref().map(underlying_type);
+
+        // the null checks of the nullable operands
+        let checks = |check_operator: BinaryOperator, joiner: BinaryOperator| {
+            [(&**left, left_nullable), (&**right, right_nullable)]
+                .into_iter()
+                .filter(|(_, nullable)| *nullable)
+                .map(|(side, _)| null_check(side.clone(), check_operator.clone()))
+                .reduce(|a, b| {
+                    Expression::new(ExpressionKind::BinaryExpression {
+                        left: Box::new(a),
+                        operator: joiner.clone(),
+                        right: Box::new(b),
+                    })
+                })
+                .expect("Expected a nullable operand")
+        };
+
+        match operator {
+            // a null is printed as an empty string
+            BinaryOperator::Add
+                if left_type == Some(Type::String) || right_type == Some(Type::String) =>
+            {
+                for (side, nullable) in
+                    [(&mut **left, left_nullable), (&mut **right, right_nullable)]
+                {
+                    if nullable {
+                        let value = take_expression(side);
+                        *side = static_call(
+                            "Objects",
+                            "toString",
+                            vec![
+                                value,
+                                Expression::new(ExpressionKind::Literal(Literal::String(
+                                    String::new(),
+                                ))),
+                            ],
+                        );
+                    }
+                }
+            }
+            // a == b becomes Objects.equals(a, b), comparing the wrappers
+            // with == would compare the references
+            // Next() == 4 is compared once the same way
+            BinaryOperator::Eq | BinaryOperator::Ne
+                if (left_nullable && right_nullable && left_type == right_type)
+                    || (impure && same_type) =>
+            {
+                let negate = *operator == BinaryOperator::Ne;
+                let arguments = vec![take_expression(left), take_expression(right)];
+                let equals = static_call("Objects", "equals", arguments);
+                *expr = if negate {
+                    Expression::new(ExpressionKind::PrefixUnaryExpression {
+                        operator: UnaryOperator::Not,
+                        right: Box::new(equals),
+                    })
+                } else {
+                    equals
+                };
+            }
+            // && and || do not take a bool?
+            BinaryOperator::And | BinaryOperator::Or => {}
+            _ if impure => self.unchecked(),
+            // bool? has a three valued logic, a false decides the &, a true the |,
+            // a & b becomes (Objects.equals(a, false) || Objects.equals(b, false) ?
+            // Boolean.FALSE : a == null || b == null ? null : Boolean.TRUE)
+            BinaryOperator::BitAnd | BinaryOperator::BitOr if is_bool => {
+                let decisive = *operator == BinaryOperator::BitOr;
+                let (decided, other) = match decisive {
+                    true => ("TRUE", "FALSE"),
+                    false => ("FALSE", "TRUE"),
+                };
+                // a bool side decides by itself, flag & false is always false
+                let mut decides = Vec::new();
+                let mut settled = false;
+                for (side, nullable) in [(&**left, left_nullable), (&**right, right_nullable)] {
+                    match &side.kind {
+                        ExpressionKind::Literal(Literal::Bool(value)) if !nullable => {
+                            settled |= *value == decisive;
+                        }
+                        _ if !nullable && decisive => decides.push(side.clone()),
+                        _ if !nullable => {
+                            decides.push(Expression::new(ExpressionKind::PrefixUnaryExpression {
+                                operator: UnaryOperator::Not,
+                                right: Box::new(Expression::new(
+                                    ExpressionKind::ParenthesizedExpression(Box::new(side.clone())),
+                                )),
+                            }))
+                        }
+                        _ => {
+                            let value =
+                                Expression::new(ExpressionKind::Literal(Literal::Bool(decisive)));
+                            decides.push(static_call(
+                                "Objects",
+                                "equals",
+                                vec![side.clone(), value],
+                            ))
+                        }
+                    }
+                }
+                if settled {
+                    *expr = static_field("Boolean", decided);
+                    return;
+                }
+                let check = checks(BinaryOperator::Eq, BinaryOperator::Or);
+                let unknown = Expression::new(ExpressionKind::ConditionalExpression {
+                    condition: Box::new(check),
+                    consequence: Box::new(Expression::new(ExpressionKind::Literal(Literal::Null))),
+                    alternative: Box::new(static_field("Boolean", other)),
+                });
+                let decides = decides.into_iter().reduce(|a, b| {
+                    Expression::new(ExpressionKind::BinaryExpression {
+                        left: Box::new(a),
+                        operator: BinaryOperator::Or,
+                        right: Box::new(b),
+                    })
+                });
+                *expr = Expression::new(ExpressionKind::ParenthesizedExpression(Box::new(
+                    match decides {
+                        Some(decides) => Expression::new(ExpressionKind::ConditionalExpression {
+                            condition: Box::new(decides),
+                            consequence: Box::new(static_field("Boolean", decided)),
+                            alternative: Box::new(unknown),
+                        }),
+                        None => unknown,
+                    },
+                )));
+            }
+            // a == 5 becomes (a != null && a == 5)
+            BinaryOperator::Eq => {
+                let check = checks(BinaryOperator::Ne, BinaryOperator::And);
+                guard(expr, check, BinaryOperator::And);
+            }
+            // a != 5 becomes (a == null || a != 5)
+            BinaryOperator::Ne => {
+                let check = checks(BinaryOperator::Eq, BinaryOperator::Or);
+                guard(expr, check, BinaryOperator::Or);
+            }
+            // comparisons with a null are false
+            BinaryOperator::Lt | BinaryOperator::Gt | BinaryOperator::Le | BinaryOperator::Ge => {
+                let check = checks(BinaryOperator::Ne, BinaryOperator::And);
+                guard(expr, check, BinaryOperator::And);
+            }
+            // the rest give null for a null operand, a + b becomes (a == null || b == null ? null : a + b)
+            _ => {
+                let check = checks(BinaryOperator::Eq, BinaryOperator::Or);
+                let value = take_expression(expr);
+                *expr = lifted(check, value);
+            }
+        }
+    }
+
+    /// Converts a number stored into a nullable variable, java does not box
+    /// an int into a Long, so long? l = 5 needs a cast
+    fn convert(&mut self, expr: &mut Expression, from: Option<&Type>, to: &Type) {
+        let Type::Nullable(inner) = to else {
+            return;
+        };
+        let Some(from) = from else {
+            return;
+        };
+        let numeric = |typ: &Type| {
+            matches!(
+                typ,
+                Type::Int
+                    | Type::Long
+                    | Type::Short
+                    | Type::Byte
+                    | Type::SByte
+                    | Type::UInt
+                    | Type::ULong
+                    | Type::UShort
+                    | Type::Char
+                    | Type::Float
+                    | Type::Double
+            )
+        };
+        if from != &**inner && numeric(from) && numeric(inner) {
+            let value = take_expression(expr);
+            *expr = Expression::new(ExpressionKind::CastExpression {
+                typ: (**inner).clone(),
+                value: Box::new(value),
+            });
+        }
+        // double? d = a, for an int? a, becomes (a == null ? null : (double) a)
+        if let Type::Nullable(from) = from
+            && from != inner
+            && numeric(from)
+            && numeric(inner)
+        {
+            if !is_pure(expr) {
+                self.unchecked();
+                return;
+            }
+            let check = null_check(expr.clone(), BinaryOperator::Eq);
+            let value = take_expression(expr);
+            let cast = Expression::new(ExpressionKind::CastExpression {
+                typ: (**inner).clone(),
+                value: Box::new(value),
+            });
+            *expr = lifted(check, cast);
+        }
+    }
+
+    /// Reports a nullable operand read by a call, it is not checked for null
+    fn unchecked(&mut self) {
+        self.diagnostics.push(Diagnostic::warning(format!(
+            "a nullable value computed by a call in {} is not checked for null, java throws a NullPointerException on null",
+            self.scopes.method
+        )));
+    }
+}
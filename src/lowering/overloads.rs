@@ -0,0 +1,119 @@
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+use crate::semantic::overloads::{
+    Overload, Signature, bind, java_overload, resolve_overload, same_java_signature,
+};
+
+use super::{Lowering, Scopes, lower_children, lower_methods, take_expression};
+
+/// Makes java call the overload C# calls. Both pick the most specific candidate,
+/// but with different conversions, f(5) calls f(byte) in C# and nothing in java,
+/// f(5u) calls f(long) in C# and f(int) in java. When java would pick another
+/// overload the arguments are cast to the parameter types of the C# one.
+/// The ambiguous calls and the overloads java cannot tell apart are reported
+pub fn lower_overloads(program: &mut Program) {
+    let mut pass = OverloadLowering::default();
+    for class in &program.classes {
+        for (i, first) in class.methods.iter().enumerate() {
+            for second in &class.methods[i + 1..] {
+                let (first, second) = (Signature::of(first), Signature::of(second));
+                if same_java_signature(&first, &second) {
+                    pass.diagnostics.push(Diagnostic::error(format!(
+                        "the overloads {} and {} have the same java signature in {}",
+                        first, second, class.name
+                    )));
+                }
+            }
+        }
+    }
+    lower_methods(&mut pass, program);
+    program.diagnostics.extend(pass.diagnostics);
+}
+
+#[derive(Default)]
+struct OverloadLowering {
+    scopes: Scopes,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Lowering for OverloadLowering {
+    fn scopes(&mut self) -> &mut Scopes {
+        &mut self.scopes
+    }
+
+    fn lower_expression(&mut self, expr: &mut Expression) {
+        let ExpressionKind::Call { callee, arguments } = &mut expr.kind else {
+            lower_children(self, expr);
+            return;
+        };
+        // Compute(5) or this.Compute(5), with more than one Compute
+        let candidates = callee
+            .path()
+            .and_then(|path| {
+                let name = path.strip_prefix("this.").unwrap_or(&path).to_string();
+                self.scopes.methods.get(&name).cloned()
+            })
+            .filter(|candidates| candidates.len() > 1);
+        let types: Option<Vec<Type>> = arguments
+            .iter()
+            .map(|argument| self.scopes.type_of(argument))
+            .collect();
+        let (Some(candidates), Some(types)) = (candidates, types) else {
+            lower_children(self, expr);
+            return;
+        };
+
+        match resolve_overload(&candidates, arguments, &types) {
+            Overload::Resolved(chosen) => {
+                let binding = bind(&candidates[chosen], arguments, false)
+                    .or_else(|| bind(&candidates[chosen], arguments, true));
+                if java_overload(&candidates, arguments, &types) != Overload::Resolved(chosen)
+                    && let Some(binding) = binding
+                {
+                    // f(5) becomes f((byte) 5)
+                    for ((argument, typ), parameter) in
+                        arguments.iter_mut().zip(&types).zip(&binding.types)
+                    {
+                        let is_null =
+                            matches!(&mut argument.kind, ExpressionKind::Literal(Literal::Null));
+                        if typ != parameter || is_null {
+                            cast(argument, parameter);
+                        }
+                    }
+                }
+            }
+            Overload::Ambiguous(ambiguous) => {
+                let names: Vec<String> = ambiguous
+                    .iter()
+                    .map(|&index| candidates[index].to_string())
+                    .collect();
+                self.diagnostics.push(Diagnostic::error(format!(
+                    "the call to `{}` is ambiguous between {} in {}",
+                    candidates[0].name,
+                    names.join(" and "),
+                    self.scopes.method
+                )));
+            }
+            Overload::Inapplicable => {}
+        }
+        lower_children(self, expr);
+    }
+}
+
+/// Helper function that casts an argument to the type of its parameter
+fn cast(argument: &mut Expression, typ: &Type) {
+    let value = take_expression(argument);
+    // (long) (a + b), the cast binds tighter than the operators
+    let value = match value.kind {
+        ExpressionKind::Literal(_)
+        | ExpressionKind::Variable(_)
+        | ExpressionKind::Call { .. }
+        | ExpressionKind::MemberAccess { .. }
+        | ExpressionKind::ParenthesizedExpression(_) => value,
+        _ => Expression::new(ExpressionKind::ParenthesizedExpression(Box::new(value))),
+    };
+    *argument = Expression::new(ExpressionKind::CastExpression {
+        typ: typ.clone(),
+        value: Box::new(value),
+    });
+}
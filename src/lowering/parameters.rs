@@ -0,0 +1,209 @@
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+use crate::semantic::overloads::{
+    Overload, Signature, bind, resolve_overload, same_java_signature,
+};
+
+use super::{Lowering, Scopes, is_pure, lower_children, lower_methods};
+
+/// Translates the optional and named parameters, java has neither. The named
+/// arguments are put in the order of the parameters, the optional parameters
+/// skipped before them get their default value. Each optional parameter then
+/// becomes an overload calling the full method, F(int x, int y = 5) also gets
+/// F(int x) { return F(x, 5); }. The params parameters are already java varargs
+pub fn lower_parameters(program: &mut Program) {
+    let mut pass = ParameterLowering::default();
+    lower_methods(&mut pass, program);
+    program.diagnostics.extend(pass.diagnostics);
+
+    for class in &mut program.classes {
+        // the signatures java has, the written methods and the created overloads
+        let mut signatures: Vec<Signature> = class.methods.iter().map(Signature::of).collect();
+        let mut methods = Vec::new();
+        for mut method in std::mem::take(&mut class.methods) {
+            let overloads = optional_overloads(&method, &mut signatures);
+            // the overloads pass the defaults now
+            for parameter in &mut method.parameters {
+                parameter.default = None;
+            }
+            methods.push(method);
+            methods.extend(overloads);
+        }
+        class.methods = methods;
+    }
+}
+
+#[derive(Default)]
+struct ParameterLowering {
+    scopes: Scopes,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Lowering for ParameterLowering {
+    fn scopes(&mut self) -> &mut Scopes {
+        &mut self.scopes
+    }
+
+    fn lower_expression(&mut self, expr: &mut Expression) {
+        lower_children(self, expr);
+        let ExpressionKind::Call { callee, arguments } = &mut expr.kind else {
+            return;
+        };
+        let named =
+            |argument: &Expression| matches!(&argument.kind, ExpressionKind::NamedArgument { .. });
+        if !arguments.iter().any(named) {
+            return;
+        }
+        // only the methods of the class are known
+        let Some(candidates) = callee.path().and_then(|path| {
+            let name = path.strip_prefix("this.").unwrap_or(&path).to_string();
+            self.scopes.methods.get(&name).cloned()
+        }) else {
+            return;
+        };
+
+        let types: Option<Vec<Type>> = arguments
+            .iter()
+            .map(|argument| self.scopes.type_of(argument))
+            .collect();
+        let fitting: Vec<usize> = (0..candidates.len())
+            .filter(|&i| bind(&candidates[i], arguments, false).is_some())
+            .collect();
+        let chosen = match (types, fitting.as_slice()) {
+            (_, [only]) => Some(*only),
+            (Some(types), _) => match resolve_overload(&candidates, arguments, &types) {
+                Overload::Resolved(index) => Some(index),
+                _ => None,
+            },
+            _ => None,
+        };
+        let binding = chosen.and_then(|index| {
+            bind(&candidates[index], arguments, false)
+                .or_else(|| bind(&candidates[index], arguments, true))
+        });
+        let (Some(chosen), Some(binding)) = (chosen, binding) else {
+            self.diagnostics.push(Diagnostic::warning(format!(
+                "the named arguments of `{}` could not be matched to a method in {}, they are passed in order",
+                candidates[0].name, self.scopes.method
+            )));
+            return;
+        };
+
+        // java evaluates the arguments in the order of the parameters, a call
+        // moved before another one could see a different state
+        let reordered = (0..arguments.len()).any(|i| {
+            (i + 1..arguments.len()).any(|j| {
+                binding.positions[i] > binding.positions[j]
+                    && !(is_pure(&arguments[i]) && is_pure(&arguments[j]))
+            })
+        });
+        if reordered {
+            self.diagnostics.push(Diagnostic::warning(format!(
+                "the named arguments of `{}` in {} are evaluated in the order of the parameters, not in the written order",
+                candidates[chosen].name, self.scopes.method
+            )));
+        }
+
+        // F(y: 2, x: 1) becomes F(1, 2)
+        let parameters = &candidates[chosen].parameters;
+        let mut slots: Vec<Option<Expression>> = vec![None; parameters.len()];
+        let mut elements = Vec::new();
+        for (argument, position) in std::mem::take(arguments).into_iter().zip(binding.positions) {
+            let value = match argument {
+                Expression {
+                    kind: ExpressionKind::NamedArgument { value, .. },
+                    ..
+                } => *value,
+                argument => argument,
+            };
+            if binding.expanded && position == parameters.len() - 1 {
+                elements.push(value);
+            } else {
+                slots[position] = Some(value);
+            }
+        }
+        // the optional parameters left out before the last argument get their
+        // default value, the ones at the end are left to the overloads
+        let filled = slots
+            .iter()
+            .rposition(Option::is_some)
+            .map_or(0, |last| last + 1);
+        for (slot, parameter) in slots.iter_mut().zip(parameters).take(filled) {
+            if slot.is_none() {
+                *slot = parameter.default.clone();
+            }
+        }
+        arguments.extend(slots.into_iter().take(filled).flatten());
+        arguments.extend(elements);
+    }
+}
+
+/// Creates the overloads standing in for the optional parameters of a method,
+/// one for each number of optional arguments left out. An overload java already
+/// has, written by hand in C#, is not created again
+fn optional_overloads(method: &Method, signatures: &mut Vec<Signature>) -> Vec<Method> {
+    let parameters: Vec<&Parameter> = method
+        .parameters
+        .iter()
+        .filter(|parameter| !parameter.params)
+        .collect();
+    let Some(first_optional) = parameters
+        .iter()
+        .position(|parameter| parameter.default.is_some())
+    else {
+        return Vec::new();
+    };
+
+    let mut overloads: Vec<Method> = Vec::new();
+    for count in first_optional..parameters.len() {
+        let kept: Vec<Parameter> = parameters[..count]
+            .iter()
+            .map(|&parameter| Parameter {
+                default: None,
+                ..parameter.clone()
+            })
+            .collect();
+        let signature = Signature {
+            parameters: kept.clone(),
+            ..Signature::of(method)
+        };
+        if signatures
+            .iter()
+            .any(|other| same_java_signature(other, &signature))
+        {
+            continue;
+        }
+        signatures.push(signature);
+
+        // F(x) calls F(x, 5), with the defaults of the parameters left out
+        let mut arguments: Vec<Expression> = kept
+            .iter()
+            .map(|parameter| {
+                Expression::new(ExpressionKind::Variable(parameter.variable.name.clone()))
+            })
+            .collect();
+        arguments.extend(
+            parameters[count..]
+                .iter()
+                .filter_map(|parameter| parameter.default.clone()),
+        );
+        let call = Expression::new(ExpressionKind::Call {
+            callee: Box::new(Expression::new(ExpressionKind::Variable(
+                method.name.clone(),
+            ))),
+            arguments,
+        });
+        let statement = match method.return_type {
+            Type::Void => Statement::Expression(call),
+            _ => Statement::Return(Some(call)),
+        };
+        overloads.push(Method {
+            name: method.name.clone(),
+            return_type: method.return_type.clone(),
+            modifiers: method.modifiers.clone(),
+            parameters: kept,
+            body: vec![statement],
+        });
+    }
+    overloads
+}
@@ -0,0 +1,722 @@
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+use crate::semantic::linq::element_type;
+
+use super::{
+    Lowering, Scopes, is_pure, linq::stream, lower_children, lower_methods, method_call,
+    static_call, take_expression, underlying_type,
+};
+
+/// Translates the string comparisons. In C# == compares the characters of two strings,
+/// in java it compares the references, so a == b becomes Objects.equals(a, b).
+/// string.Compare and string.Equals become the methods of the java String,
+/// the ignore case comparisons become compareToIgnoreCase and equalsIgnoreCase,
+/// a bool chooses between them, (ignoreCase ? a.compareToIgnoreCase(b) : a.compareTo(b)).
+/// The other string members become their java counterparts, s.Length is s.length(),
+/// s[i] is s.charAt(i) and s.Substring(start, length) is s.substring(start, start + length).
+/// The rewrites reading a value twice only do it for the variables and literals,
+/// a call is read once through another java method
+pub fn lower_strings(program: &mut Program, java_version: u32) {
+    let mut pass = StringLowering {
+        java_version,
+        ..Default::default()
+    };
+    lower_methods(&mut pass, program);
+    program.diagnostics.extend(pass.diagnostics);
+}
+
+#[derive(Default)]
+struct StringLowering {
+    scopes: Scopes,
+    diagnostics: Vec<Diagnostic>,
+    java_version: u32, // strip, repeat and isBlank need java 11
+}
+
+impl Lowering for StringLowering {
+    fn scopes(&mut self) -> &mut Scopes {
+        &mut self.scopes
+    }
+
+    fn lower_expression(&mut self, expr: &mut Expression) {
+        match &mut expr.kind {
+            ExpressionKind::BinaryExpression {
+                left,
+                operator: operator @ (BinaryOperator::Eq | BinaryOperator::Ne),
+                right,
+            } => {
+                let negate = *operator == BinaryOperator::Ne;
+                let operator = if negate { "!=" } else { "==" };
+                let left_type = self.scopes.type_of(left);
+                let right_type = self.scopes.type_of(right);
+                // x == null stays a reference comparison
+                let is_null = |side: &Expression| {
+                    matches!(&side.kind, ExpressionKind::Literal(Literal::Null))
+                };
+                let is_string =
+                    |typ: &Option<Type>| typ.as_ref().map(underlying_type) == Some(&Type::String);
+                let is_unknown = |typ: &Option<Type>| matches!(typ, None | Some(Type::Unknown));
+                // an object == "x" compares the references in C# too
+                let compares_strings = is_string(&left_type) && is_string(&right_type);
+                let has_null = is_null(left) || is_null(right);
+                // a string compared with a value of an unknown type
+                let unknown_string = (is_string(&left_type) && right_type.is_none())
+                    || (is_string(&right_type) && left_type.is_none());
+
+                if !has_null
+                    && !compares_strings
+                    && ((is_unknown(&left_type) && is_unknown(&right_type)) || unknown_string)
+                {
+                    self.diagnostics.push(Diagnostic::warning(format!(
+                        "the operands of `{}` have unknown types in {}, java compares them by reference",
+                        operator, self.scopes.method
+                    )));
+                }
+                lower_children(self, expr);
+
+                if compares_strings
+                    && !has_null
+                    && let ExpressionKind::BinaryExpression { left, right, .. } = &mut expr.kind
+                {
+                    let arguments = vec![take_expression(left), take_expression(right)];
+                    *expr = negated(static_call("Objects", "equals", arguments), negate);
+                }
+            }
+            ExpressionKind::Call { callee, arguments } => {
+                let method = string_method(&self.scopes, callee, arguments);
+                // the chars and sequences passed to the methods, before they are lowered
+                let types: Vec<Option<Type>> = arguments
+                    .iter()
+                    .map(|argument| self.scopes.type_of(argument))
+                    .collect();
+                lower_children(self, expr);
+                let Some(method) = method else {
+                    return;
+                };
+                if let ExpressionKind::Call { callee, arguments } = take_expression(expr).kind {
+                    *expr = self.lower_string_member(method, *callee, arguments, &types);
+                }
+            }
+            // s.Length is a method in java
+            ExpressionKind::MemberAccess { target, name }
+                if name == "Length" && self.scopes.is_string(target) =>
+            {
+                lower_children(self, expr);
+                if let ExpressionKind::MemberAccess { target, .. } = &mut expr.kind {
+                    *expr = method_call(take_expression(target), "length", Vec::new());
+                }
+            }
+            ExpressionKind::MemberAccess { target, name }
+                if name == "Empty"
+                    && matches!(target.path().as_deref(), Some("string" | "String")) =>
+            {
+                *expr = Expression::new(ExpressionKind::Literal(Literal::String(String::new())));
+            }
+            // s[i] reads a char
+            ExpressionKind::ElementAccess { target, .. } if self.scopes.is_string(target) => {
+                lower_children(self, expr);
+                if let ExpressionKind::ElementAccess { target, index } = &mut expr.kind {
+                    let index = take_expression(index);
+                    *expr = method_call(take_expression(target), "charAt", vec![index]);
+                }
+            }
+            _ => lower_children(self, expr),
+        }
+    }
+}
+
+// the string methods with a java counterpart
+enum StringMethod {
+    Compare,                 // string.Compare(a, b), string.CompareOrdinal(a, b)
+    StaticEquals,            // string.Equals(a, b)
+    MemberEquals,            // a.Equals(b, comparison)
+    Renamed(&'static str),   // s.ToUpper() is s.toUpperCase(), the arguments stay
+    Invariant(&'static str), // s.ToUpperInvariant() is s.toUpperCase(Locale.ROOT)
+    Trim(&'static str),      // s.Trim(), s.TrimStart() and s.TrimEnd()
+    Substring,               // s.Substring(start, length)
+    Split,                   // s.Split(',')
+    Pad { left: bool },      // s.PadLeft(5, '0')
+    IsNullOrEmpty,           // string.IsNullOrEmpty(s)
+    IsNullOrWhiteSpace,      // string.IsNullOrWhiteSpace(s)
+    Join,                    // string.Join(", ", xs)
+    Concat,                  // string.Concat(a, b)
+}
+
+/// Finds out which string method a call is, None for the rest
+fn string_method(
+    scopes: &Scopes,
+    callee: &Expression,
+    arguments: &[Expression],
+) -> Option<StringMethod> {
+    match callee.path().as_deref() {
+        Some(
+            "string.Compare" | "String.Compare" | "string.CompareOrdinal" | "String.CompareOrdinal",
+        ) => Some(StringMethod::Compare),
+        // object.Equals(a, b) is the same for any object
+        Some("string.Equals" | "String.Equals" | "object.Equals" | "Object.Equals") => {
+            Some(StringMethod::StaticEquals)
+        }
+        Some("string.IsNullOrEmpty" | "String.IsNullOrEmpty") => Some(StringMethod::IsNullOrEmpty),
+        Some("string.IsNullOrWhiteSpace" | "String.IsNullOrWhiteSpace") => {
+            Some(StringMethod::IsNullOrWhiteSpace)
+        }
+        Some("string.Join" | "String.Join") => Some(StringMethod::Join),
+        Some("string.Concat" | "String.Concat") => Some(StringMethod::Concat),
+        _ => match &callee.kind {
+            ExpressionKind::MemberAccess { target, name } if scopes.is_string(target) => {
+                match name.as_str() {
+                    // a.Equals(b) alone is already the java equals
+                    "Equals" if arguments.len() == 2 => Some(StringMethod::MemberEquals),
+                    "ToUpper" => Some(StringMethod::Renamed("toUpperCase")),
+                    "ToLower" => Some(StringMethod::Renamed("toLowerCase")),
+                    "ToUpperInvariant" => Some(StringMethod::Invariant("toUpperCase")),
+                    "ToLowerInvariant" => Some(StringMethod::Invariant("toLowerCase")),
+                    "Contains" => Some(StringMethod::Renamed("contains")),
+                    "StartsWith" => Some(StringMethod::Renamed("startsWith")),
+                    "EndsWith" => Some(StringMethod::Renamed("endsWith")),
+                    "IndexOf" => Some(StringMethod::Renamed("indexOf")),
+                    "LastIndexOf" => Some(StringMethod::Renamed("lastIndexOf")),
+                    "Replace" => Some(StringMethod::Renamed("replace")),
+                    "ToCharArray" => Some(StringMethod::Renamed("toCharArray")),
+                    "Trim" => Some(StringMethod::Trim("Trim")),
+                    "TrimStart" => Some(StringMethod::Trim("TrimStart")),
+                    "TrimEnd" => Some(StringMethod::Trim("TrimEnd")),
+                    "Substring" => Some(StringMethod::Substring),
+                    "Split" => Some(StringMethod::Split),
+                    "PadLeft" => Some(StringMethod::Pad { left: true }),
+                    "PadRight" => Some(StringMethod::Pad { left: false }),
+                    _ => None,
+                }
+            }
+            _ => None,
+        },
+    }
+}
+
+impl StringLowering {
+    /// Rewrites a call of a string member or of string.Join and friends, the target
+    /// and arguments are already lowered, the types are those of the C# arguments
+    fn lower_string_member(
+        &mut self,
+        method: StringMethod,
+        callee: Expression,
+        arguments: Vec<Expression>,
+        types: &[Option<Type>],
+    ) -> Expression {
+        if let Some(reason) = unsupported(&method, &arguments) {
+            self.diagnostics.push(Diagnostic::error(format!(
+                "{} is not supported in {}, the call is left as it is",
+                reason, self.scopes.method
+            )));
+            return Expression::new(ExpressionKind::Call {
+                callee: Box::new(callee),
+                arguments,
+            });
+        }
+        if matches!(
+            method,
+            StringMethod::Compare | StringMethod::StaticEquals | StringMethod::MemberEquals
+        ) {
+            return self.lower_comparison(method, callee, arguments, types);
+        }
+        // java takes strings where C# also takes chars, s.Contains('a')
+        let text = |argument: Expression, index: usize| match &argument.kind {
+            ExpressionKind::Literal(Literal::Char(c)) => string_literal(&c.to_string()),
+            _ if types.get(index) == Some(&Some(Type::Char)) => {
+                static_call("String", "valueOf", vec![argument])
+            }
+            _ => argument,
+        };
+        let target = match callee.kind {
+            ExpressionKind::MemberAccess { target, .. } => Some(*target),
+            _ => None,
+        };
+        // s.PadLeft(5) reads s twice, Get().PadLeft(5) pads the value of a lambda parameter
+        if let (StringMethod::Pad { .. }, Some(value)) = (&method, &target)
+            && !is_pure(value)
+        {
+            let name = self.scopes.fresh_name("text");
+            let parameter = Expression::new(ExpressionKind::Variable(name.clone()));
+            let padded = self.lower_string_member(
+                method,
+                Expression::new(ExpressionKind::MemberAccess {
+                    target: Box::new(parameter),
+                    name: "Pad".to_string(),
+                }),
+                arguments,
+                types,
+            );
+            let lambda = Expression::new(ExpressionKind::Lambda {
+                parameters: vec![Variable {
+                    typ: Type::String,
+                    name,
+                    implicit: true,
+                    constant: false,
+                }],
+                body: LambdaBody::Expression(Box::new(padded)),
+            });
+            let value = static_call("Optional", "of", vec![target.expect("Expected a string")]);
+            return method_call(method_call(value, "map", vec![lambda]), "get", Vec::new());
+        }
+        let target = || target.clone().expect("Expected a string");
+        match method {
+            // s.Contains('a') becomes s.contains("a"), s.IndexOf('a') keeps its char
+            StringMethod::Renamed(name @ ("contains" | "startsWith" | "endsWith")) => {
+                let arguments = arguments.into_iter().enumerate();
+                let arguments = arguments.map(|(index, argument)| text(argument, index));
+                method_call(target(), name, arguments.collect())
+            }
+            StringMethod::Renamed(name) => method_call(target(), name, arguments),
+            StringMethod::Invariant(name) => {
+                let root = Expression::new(ExpressionKind::MemberAccess {
+                    target: Box::new(Expression::new(ExpressionKind::Variable(
+                        "Locale".to_string(),
+                    ))),
+                    name: "ROOT".to_string(),
+                });
+                method_call(target(), name, vec![root])
+            }
+            // java 11 removes the same whitespace as C#, trim() also removes the control chars
+            StringMethod::Trim(name) => match (name, self.java_version >= 11) {
+                ("Trim", true) => method_call(target(), "strip", Vec::new()),
+                ("TrimStart", true) => method_call(target(), "stripLeading", Vec::new()),
+                ("TrimEnd", true) => method_call(target(), "stripTrailing", Vec::new()),
+                ("Trim", false) => method_call(target(), "trim", Vec::new()),
+                (name, false) => {
+                    let pattern = match name {
+                        "TrimStart" => "^\\s+",
+                        _ => "\\s+$",
+                    };
+                    let arguments = vec![string_literal(pattern), string_literal("")];
+                    method_call(target(), "replaceAll", arguments)
+                }
+                _ => unreachable!(),
+            },
+            // java takes the end of the substring instead of its length
+            StringMethod::Substring => {
+                let mut arguments = arguments.into_iter();
+                let start = arguments.next().expect("Substring without a start");
+                // Substring(Next(), 2) becomes substring(next()).substring(0, 2)
+                if !is_pure(&start)
+                    && let Some(length) = arguments.next()
+                {
+                    let rest = method_call(target(), "substring", vec![start]);
+                    let zero = Expression::new(ExpressionKind::Literal(Literal::Int(0)));
+                    return method_call(rest, "substring", vec![zero, length]);
+                }
+                let mut substring = vec![start.clone()];
+                if let Some(length) = arguments.next() {
+                    substring.push(match (&start.kind, &length.kind) {
+                        (
+                            ExpressionKind::Literal(Literal::Int(start)),
+                            ExpressionKind::Literal(Literal::Int(length)),
+                        ) => Expression::new(ExpressionKind::Literal(Literal::Int(start + length))),
+                        (ExpressionKind::Literal(Literal::Int(0)), _) => length,
+                        _ => Expression::new(ExpressionKind::BinaryExpression {
+                            left: Box::new(start),
+                            operator: BinaryOperator::Add,
+                            right: Box::new(length),
+                        }),
+                    });
+                }
+                method_call(target(), "substring", substring)
+            }
+            StringMethod::Split => self.lower_split(target(), arguments, types),
+            // s.PadLeft(5, '0') adds the missing zeros in front of s
+            StringMethod::Pad { left } => {
+                let mut arguments = arguments.into_iter();
+                let width = arguments.next().expect("PadLeft without a width");
+                let fill = arguments
+                    .next()
+                    .map(|fill| text(fill, 1))
+                    .unwrap_or_else(|| string_literal(" "));
+                let missing = static_call(
+                    "Math",
+                    "max",
+                    vec![
+                        Expression::new(ExpressionKind::Literal(Literal::Int(0))),
+                        Expression::new(ExpressionKind::BinaryExpression {
+                            left: Box::new(width),
+                            operator: BinaryOperator::Sub,
+                            right: Box::new(method_call(target(), "length", Vec::new())),
+                        }),
+                    ],
+                );
+                let padding = match self.java_version >= 11 {
+                    true => method_call(fill, "repeat", vec![missing]),
+                    false => {
+                        let copies = static_call("Collections", "nCopies", vec![missing, fill]);
+                        static_call("String", "join", vec![string_literal(""), copies])
+                    }
+                };
+                let (left, right) = match left {
+                    true => (padding, target()),
+                    false => (target(), padding),
+                };
+                parenthesized(Expression::new(ExpressionKind::BinaryExpression {
+                    left: Box::new(left),
+                    operator: BinaryOperator::Add,
+                    right: Box::new(right),
+                }))
+            }
+            // string.IsNullOrEmpty(s) becomes s == null || s.isEmpty(),
+            // string.IsNullOrEmpty(Get()) is Objects.toString(get(), "").isEmpty()
+            StringMethod::IsNullOrEmpty | StringMethod::IsNullOrWhiteSpace => {
+                let value = arguments.into_iter().next().expect("Expected a string");
+                if !is_pure(&value) {
+                    let text = static_call("Objects", "toString", vec![value, string_literal("")]);
+                    return match (method, self.java_version >= 11) {
+                        (StringMethod::IsNullOrEmpty, _) => {
+                            method_call(text, "isEmpty", Vec::new())
+                        }
+                        (_, true) => method_call(text, "isBlank", Vec::new()),
+                        (_, false) => {
+                            let trimmed = method_call(text, "trim", Vec::new());
+                            method_call(trimmed, "isEmpty", Vec::new())
+                        }
+                    };
+                }
+                let empty = match (method, self.java_version >= 11) {
+                    (StringMethod::IsNullOrEmpty, _) => {
+                        method_call(value.clone(), "isEmpty", Vec::new())
+                    }
+                    (_, true) => method_call(value.clone(), "isBlank", Vec::new()),
+                    (_, false) => {
+                        let trimmed = method_call(value.clone(), "trim", Vec::new());
+                        method_call(trimmed, "isEmpty", Vec::new())
+                    }
+                };
+                let null = Expression::new(ExpressionKind::BinaryExpression {
+                    left: Box::new(value),
+                    operator: BinaryOperator::Eq,
+                    right: Box::new(Expression::new(ExpressionKind::Literal(Literal::Null))),
+                });
+                parenthesized(Expression::new(ExpressionKind::BinaryExpression {
+                    left: Box::new(null),
+                    operator: BinaryOperator::Or,
+                    right: Box::new(empty),
+                }))
+            }
+            StringMethod::Join => {
+                let mut arguments = arguments.into_iter();
+                let separator = text(arguments.next().expect("Join without a separator"), 0);
+                join(separator, arguments.collect(), &types[1..])
+            }
+            // string.Concat(a, b) is a + b, string.Concat(xs) joins xs
+            StringMethod::Concat => match (arguments.as_slice(), types) {
+                ([_], [Some(typ)]) if is_sequence(typ) => {
+                    join(string_literal(""), arguments, types)
+                }
+                _ => {
+                    let strings = types.iter().take(2).any(|typ| *typ == Some(Type::String));
+                    let mut arguments = arguments.into_iter();
+                    let first = match strings {
+                        true => arguments.next().expect("Concat without strings"),
+                        false => string_literal(""),
+                    };
+                    let concatenation = arguments.fold(first, |left, right| {
+                        Expression::new(ExpressionKind::BinaryExpression {
+                            left: Box::new(left),
+                            operator: BinaryOperator::Add,
+                            right: Box::new(right),
+                        })
+                    });
+                    parenthesized(concatenation)
+                }
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Splits a string on the text of its separators, java splits on a regex, so
+    /// s.Split(',') becomes s.split(Pattern.quote(","), -1), the -1 keeps the
+    /// empty strings at the end like C# does
+    fn lower_split(
+        &self,
+        target: Expression,
+        arguments: Vec<Expression>,
+        types: &[Option<Type>],
+    ) -> Expression {
+        let mut separators = Vec::new();
+        let mut limit = Expression::new(ExpressionKind::Literal(Literal::Int(-1)));
+        let mut remove_empty = false;
+        for (argument, typ) in arguments.into_iter().zip(types) {
+            match (argument.path().as_deref(), typ) {
+                (Some("StringSplitOptions.RemoveEmptyEntries"), _) => remove_empty = true,
+                (Some("StringSplitOptions.None"), _) => {}
+                // the number of parts, the same as the limit of java
+                (_, Some(Type::Int)) => limit = argument,
+                (_, Some(Type::Char)) => {
+                    let separator = match &argument.kind {
+                        ExpressionKind::Literal(Literal::Char(c)) => string_literal(&c.to_string()),
+                        _ => static_call("String", "valueOf", vec![argument]),
+                    };
+                    separators.push(static_call("Pattern", "quote", vec![separator]));
+                }
+                _ => separators.push(static_call("Pattern", "quote", vec![argument])),
+            }
+        }
+        // s.Split() splits on every whitespace
+        let pattern = separators
+            .into_iter()
+            .reduce(|left, right| {
+                Expression::new(ExpressionKind::BinaryExpression {
+                    left: Box::new(Expression::new(ExpressionKind::BinaryExpression {
+                        left: Box::new(left),
+                        operator: BinaryOperator::Add,
+                        right: Box::new(string_literal("|")),
+                    })),
+                    operator: BinaryOperator::Add,
+                    right: Box::new(right),
+                })
+            })
+            .unwrap_or_else(|| string_literal("\\s"));
+        let split = method_call(target, "split", vec![pattern, limit]);
+        if !remove_empty {
+            return split;
+        }
+        // Arrays.stream(parts).filter(part -> !part.isEmpty()).toArray(String[]::new)
+        let part = Expression::new(ExpressionKind::Variable("part".to_string()));
+        let filter = Expression::new(ExpressionKind::Lambda {
+            parameters: vec![Variable {
+                typ: Type::String,
+                name: "part".to_string(),
+                implicit: true,
+                constant: false,
+            }],
+            body: LambdaBody::Expression(Box::new(Expression::new(
+                ExpressionKind::PrefixUnaryExpression {
+                    operator: UnaryOperator::Not,
+                    right: Box::new(method_call(part, "isEmpty", Vec::new())),
+                },
+            ))),
+        });
+        let parts = static_call("Arrays", "stream", vec![split]);
+        let parts = method_call(parts, "filter", vec![filter]);
+        let array = Expression::new(ExpressionKind::MethodReference {
+            target: Box::new(Expression::new(ExpressionKind::Variable(
+                "String[]".to_string(),
+            ))),
+            name: "new".to_string(),
+        });
+        method_call(parts, "toArray", vec![array])
+    }
+
+    /// Rewrites a string comparison whose arguments are already lowered. The comparison
+    /// kind comes last, StringComparison.OrdinalIgnoreCase or a bool, a bool that is not
+    /// a literal chooses the method when the program runs
+    fn lower_comparison(
+        &mut self,
+        method: StringMethod,
+        callee: Expression,
+        mut arguments: Vec<Expression>,
+        types: &[Option<Type>],
+    ) -> Expression {
+        let comparison = match (&method, arguments.len()) {
+            (StringMethod::MemberEquals, 2) | (_, 3) => arguments.pop(),
+            _ => None,
+        };
+        let ordinal = callee.path().is_some_and(|path| path.ends_with("Ordinal"))
+            || comparison.as_ref().is_some_and(|comparison| {
+                comparison
+                    .path()
+                    .is_some_and(|path| path.starts_with("StringComparison.Ordinal"))
+            });
+        if let StringMethod::Compare = method {
+            let culture = match ordinal {
+                true => "",
+                false => " and compares the char codes instead of using the culture",
+            };
+            self.diagnostics.push(Diagnostic::warning(format!(
+                "string.Compare in {} becomes compareTo, which throws a NullPointerException for a null string{}",
+                self.scopes.method, culture
+            )));
+        }
+        let Some(comparison) = comparison else {
+            return comparison_call(&method, callee, arguments, false);
+        };
+        match ignores_case(&comparison) {
+            Some(ignore_case) => comparison_call(&method, callee, arguments, ignore_case),
+            // string.Compare(a, b, ignoreCase)
+            None if types.last() == Some(&Some(Type::Bool)) => {
+                let ignoring = comparison_call(&method, callee.clone(), arguments.clone(), true);
+                let exact = comparison_call(&method, callee, arguments, false);
+                Expression::new(ExpressionKind::ParenthesizedExpression(Box::new(
+                    Expression::new(ExpressionKind::ConditionalExpression {
+                        condition: Box::new(comparison),
+                        consequence: Box::new(ignoring),
+                        alternative: Box::new(exact),
+                    }),
+                )))
+            }
+            None => {
+                self.diagnostics.push(Diagnostic::error(format!(
+                    "the StringComparison of a string comparison in {} has to be written out, such as StringComparison.OrdinalIgnoreCase",
+                    self.scopes.method
+                )));
+                comparison_call(&method, callee, arguments, false)
+            }
+        }
+    }
+}
+
+/// Helper function that creates the java comparison of two strings
+fn comparison_call(
+    method: &StringMethod,
+    callee: Expression,
+    arguments: Vec<Expression>,
+    ignore_case: bool,
+) -> Expression {
+    match method {
+        // string.Compare(a, b) becomes a.compareTo(b)
+        StringMethod::Compare => {
+            let (left, right) = pair(arguments);
+            let method = if ignore_case {
+                "compareToIgnoreCase"
+            } else {
+                "compareTo"
+            };
+            method_call(left, method, vec![right])
+        }
+        // string.Equals(a, b) becomes Objects.equals(a, b), the ignore case
+        // comparison is a.equalsIgnoreCase(b)
+        StringMethod::StaticEquals if !ignore_case => static_call("Objects", "equals", arguments),
+        StringMethod::StaticEquals => {
+            let (left, right) = pair(arguments);
+            method_call(left, "equalsIgnoreCase", vec![right])
+        }
+        StringMethod::MemberEquals => {
+            let ExpressionKind::MemberAccess { target, .. } = callee.kind else {
+                panic!("Expected a member access");
+            };
+            let method = if ignore_case {
+                "equalsIgnoreCase"
+            } else {
+                "equals"
+            };
+            method_call(*target, method, arguments)
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Helper function that tells why a string call cannot be translated, the characters
+/// of s.Trim('x') or the StringSplitOptions.TrimEntries of a Split
+fn unsupported(method: &StringMethod, arguments: &[Expression]) -> Option<String> {
+    match method {
+        // string.Compare(a, 0, b, 0, 5) compares parts, string.Compare(a, b, true, culture)
+        StringMethod::Compare if arguments.len() > 3 => Some(format!(
+            "`string.Compare` with {} arguments",
+            arguments.len()
+        )),
+        StringMethod::Trim(name) if !arguments.is_empty() => {
+            Some(format!("`{}` with the characters to remove", name))
+        }
+        StringMethod::Split => arguments.iter().find_map(|argument| {
+            let supported = matches!(
+                argument.path().as_deref(),
+                Some("StringSplitOptions.None" | "StringSplitOptions.RemoveEmptyEntries")
+            );
+            (!supported && mentions_split_options(argument))
+                .then(|| "`Split` with these StringSplitOptions".to_string())
+        }),
+        _ => None,
+    }
+}
+
+/// Helper function that tells whether an argument is made of StringSplitOptions,
+/// RemoveEmptyEntries | TrimEntries too
+fn mentions_split_options(argument: &Expression) -> bool {
+    match &argument.kind {
+        ExpressionKind::BinaryExpression { left, right, .. } => {
+            mentions_split_options(left) || mentions_split_options(right)
+        }
+        ExpressionKind::ParenthesizedExpression(inner) => mentions_split_options(inner),
+        _ => argument
+            .path()
+            .is_some_and(|path| path.starts_with("StringSplitOptions.")),
+    }
+}
+
+/// Helper function that tells whether a StringComparison ignores the case,
+/// string.Compare(a, b, true) also does. None when it is only known at run time
+fn ignores_case(comparison: &Expression) -> Option<bool> {
+    match &comparison.kind {
+        ExpressionKind::Literal(Literal::Bool(ignore_case)) => Some(*ignore_case),
+        _ => comparison
+            .path()
+            .filter(|path| path.starts_with("StringComparison."))
+            .map(|path| path.ends_with("IgnoreCase")),
+    }
+}
+
+/// Helper function that splits the two compared strings
+fn pair(arguments: Vec<Expression>) -> (Expression, Expression) {
+    let mut arguments = arguments.into_iter();
+    match (arguments.next(), arguments.next()) {
+        (Some(left), Some(right)) => (left, right),
+        _ => panic!("Expected two strings to compare"),
+    }
+}
+
+/// Helper function that puts a ! in front of a call, for a != b
+fn negated(expr: Expression, negate: bool) -> Expression {
+    if negate {
+        Expression::new(ExpressionKind::PrefixUnaryExpression {
+            operator: UnaryOperator::Not,
+            right: Box::new(expr),
+        })
+    } else {
+        expr
+    }
+}
+
+/// Helper function that joins strings with a separator, String.join(", ", xs), the sequences
+/// of other types go through a stream, xs.stream().map(String::valueOf)
+fn join(separator: Expression, arguments: Vec<Expression>, types: &[Option<Type>]) -> Expression {
+    let is_string = |typ: &Option<Type>| matches!(typ, Some(Type::String) | None);
+    match (arguments.as_slice(), types) {
+        ([_], [Some(typ)]) if is_sequence(typ) => {
+            let sequence = arguments.into_iter().next().expect("Expected a sequence");
+            match element_type(typ) {
+                Some(Type::String) => static_call("String", "join", vec![separator, sequence]),
+                _ => {
+                    let value_of = Expression::new(ExpressionKind::MethodReference {
+                        target: Box::new(Expression::new(ExpressionKind::Variable(
+                            "String".to_string(),
+                        ))),
+                        name: "valueOf".to_string(),
+                    });
+                    let strings = method_call(stream(sequence, typ), "map", vec![value_of]);
+                    let joining = static_call("Collectors", "joining", vec![separator]);
+                    method_call(strings, "collect", vec![joining])
+                }
+            }
+        }
+        // string.Join(", ", a, b, c)
+        _ => {
+            let mut strings = vec![separator];
+            for (argument, typ) in arguments.into_iter().zip(types) {
+                strings.push(match is_string(typ) {
+                    true => argument,
+                    false => static_call("String", "valueOf", vec![argument]),
+                });
+            }
+            static_call("String", "join", strings)
+        }
+    }
+}
+
+/// Helper function that checks if a type is a sequence, the strings are not
+fn is_sequence(typ: &Type) -> bool {
+    element_type(typ).is_some()
+}
+
+/// Helper function that creates a string literal
+fn string_literal(text: &str) -> Expression {
+    Expression::new(ExpressionKind::Literal(Literal::String(text.to_string())))
+}
+
+/// Helper function that keeps a rewritten call together, !(s == null || s.isEmpty())
+fn parenthesized(expr: Expression) -> Expression {
+    Expression::new(ExpressionKind::ParenthesizedExpression(Box::new(expr)))
+}
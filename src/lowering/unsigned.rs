@@ -0,0 +1,483 @@
+use crate::ast::*;
+
+use super::{
+    Lowering, Scopes, hoist_target, lower_children, lower_methods, lower_statement_children,
+    promote, static_call, take_expression,
+};
+
+/// Emulates the C# unsigned integers on top of the java signed ones.
+/// Addition, subtraction and multiplication already give the same bits,
+/// division, comparison, shifting and printing go through the unsigned
+/// helpers of Integer and Long, byte and ushort values are masked when read
+pub fn lower_unsigned(program: &mut Program) {
+    let mut pass = UnsignedLowering::default();
+    lower_methods(&mut pass, program);
+}
+
+#[derive(Default)]
+struct UnsignedLowering {
+    scopes: Scopes,
+}
+
+impl Lowering for UnsignedLowering {
+    fn scopes(&mut self) -> &mut Scopes {
+        &mut self.scopes
+    }
+
+    fn lower_expression(&mut self, expr: &mut Expression) {
+        match &mut expr.kind {
+            ExpressionKind::BinaryExpression { left, right, .. } => {
+                // the types are computed before the children are rewritten
+                let (left_type, right_type) = self.scopes.operand_types(left, right);
+                lower_children(self, expr);
+                lower_binary(expr, left_type, right_type);
+            }
+            ExpressionKind::PrefixUnaryExpression { operator, right } => {
+                let typ = self.scopes.type_of(right);
+                let operator = operator.clone();
+                lower_children(self, expr);
+                if let ExpressionKind::PrefixUnaryExpression { right, .. } = &mut expr.kind {
+                    match operator {
+                        // -u is a long in C#
+                        UnaryOperator::Neg if typ == Some(Type::UInt) => {
+                            widen(right, &Type::UInt, &Type::Long)
+                        }
+                        UnaryOperator::Neg | UnaryOperator::Plus | UnaryOperator::BitNot => {
+                            mask(right, typ.as_ref())
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            ExpressionKind::CastExpression { typ, value } => {
+                let value_type = self.scopes.type_of(value);
+                let typ = typ.clone();
+                lower_children(self, expr);
+                if let (
+                    Expression {
+                        kind: ExpressionKind::CastExpression { value, .. },
+                        ..
+                    },
+                    Some(value_type),
+                ) = (&mut *expr, value_type)
+                {
+                    widen(value, &value_type, &typ);
+                }
+            }
+            ExpressionKind::Call { callee, arguments } => {
+                let path = callee.path();
+                // x.CompareTo(y)
+                let compared = match (&**callee, arguments.as_slice()) {
+                    (
+                        Expression {
+                            kind: ExpressionKind::MemberAccess { target, name },
+                            ..
+                        },
+                        [argument],
+                    ) if name == "CompareTo" => self
+                        .scopes
+                        .type_of(target)
+                        .filter(|typ| unsigned_class(typ).is_some() || unsigned_mask(typ).is_some())
+                        .map(|typ| {
+                            let argument_type = self.scopes.type_of(argument);
+                            (typ, argument_type, self.scopes.constant_value(argument))
+                        }),
+                    _ => None,
+                };
+                // x.ToString()
+                let target_type = match &callee.kind {
+                    ExpressionKind::MemberAccess { target, name } if name == "ToString" => {
+                        self.scopes.type_of(target)
+                    }
+                    _ => None,
+                };
+                // Console.WriteLine(x)
+                let argument_type = match arguments.as_slice() {
+                    [argument] => self.scopes.type_of(argument),
+                    _ => None,
+                };
+                // Take(u) with a long parameter
+                let passed: Vec<_> = match self.scopes.called_method(callee, arguments) {
+                    Some(method) => arguments
+                        .iter()
+                        .zip(&method.parameters)
+                        .map(|(argument, parameter)| {
+                            (
+                                self.scopes.type_of(argument),
+                                self.scopes.constant_value(argument),
+                                parameter.variable.typ.clone(),
+                            )
+                        })
+                        .collect(),
+                    None => Vec::new(),
+                };
+                lower_children(self, expr);
+                if let ExpressionKind::Call { arguments, .. } = &mut expr.kind {
+                    for (argument, (from, constant, to)) in arguments.iter_mut().zip(passed) {
+                        convert(argument, from.as_ref(), constant, &to);
+                    }
+                }
+
+                if let Some((typ, argument_type, constant)) = compared
+                    && let ExpressionKind::Call { callee, arguments } = &mut expr.kind
+                    && let ExpressionKind::MemberAccess { target, .. } = &mut callee.kind
+                {
+                    let target = take_expression(target);
+                    let argument = arguments.pop().expect("Expected CompareTo argument");
+                    *expr = compare(target, argument, &typ, argument_type.as_ref(), constant);
+                } else if let Some(target_type) = target_type
+                    && let ExpressionKind::Call { callee, .. } = &mut expr.kind
+                    && let ExpressionKind::MemberAccess { target, .. } = &mut callee.kind
+                {
+                    let mut target = take_expression(target);
+                    if to_unsigned_string(&mut target, &target_type) {
+                        *expr = target;
+                    } else {
+                        **callee = Expression::new(ExpressionKind::MemberAccess {
+                            target: Box::new(target),
+                            name: "ToString".to_string(),
+                        });
+                    }
+                } else if matches!(path.as_deref(), Some("Console.WriteLine" | "Console.Write"))
+                    && let Some(argument_type) = argument_type
+                    && let ExpressionKind::Call { arguments, .. } = &mut expr.kind
+                {
+                    to_unsigned_string(&mut arguments[0], &argument_type);
+                }
+            }
+            ExpressionKind::InterpolatedString(parts) => {
+                let types: Vec<Option<Type>> = parts
+                    .iter()
+                    .map(|part| match part {
+                        InterpolationPart::Value { value, .. } => self.scopes.type_of(value),
+                        InterpolationPart::Text(_) => None,
+                    })
+                    .collect();
+                lower_children(self, expr);
+
+                if let ExpressionKind::InterpolatedString(parts) = &mut expr.kind {
+                    for (part, typ) in parts.iter_mut().zip(types) {
+                        let (InterpolationPart::Value { value, format, .. }, Some(typ)) =
+                            (part, typ)
+                        else {
+                            continue;
+                        };
+                        match typ {
+                            // a long keeps the value and works with every number format
+                            Type::UInt => widen(value, &Type::UInt, &Type::Long),
+                            Type::ULong if format.is_none() => {
+                                to_unsigned_string(value, &typ);
+                            }
+                            _ => mask(value, Some(&typ)),
+                        }
+                    }
+                }
+            }
+            _ => lower_children(self, expr),
+        }
+    }
+
+    fn lower_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::VariableDeclaration {
+                variable,
+                value: Some(value),
+            } => {
+                let value_type = self.scopes.type_of(value);
+                let constant = self.scopes.constant_value(value);
+                let typ = variable.typ.clone();
+                lower_statement_children(self, statement);
+                if let Statement::VariableDeclaration {
+                    value: Some(value), ..
+                } = statement
+                {
+                    convert(value, value_type.as_ref(), constant, &typ);
+                }
+            }
+            Statement::Assignment {
+                target,
+                operator,
+                value,
+            } => {
+                let target_type = self.scopes.type_of(target);
+                let (_, value_type) = self.scopes.operand_types(target, value);
+                let constant = self.scopes.constant_value(value);
+                let operator = operator.clone();
+                // h /= 3 reads the target, Get().U /= 3 keeps Get() in a variable
+                let rewritten = matches!(
+                    operator,
+                    Some(BinaryOperator::Div | BinaryOperator::Mod | BinaryOperator::RightShift)
+                ) && target_type.as_ref().and_then(unsigned_class).is_some();
+                if rewritten && hoist_target(&mut self.scopes, statement) {
+                    return lower_statement_children(self, statement);
+                }
+                lower_statement_children(self, statement);
+
+                let Statement::Assignment {
+                    target,
+                    operator: assignment_operator,
+                    value,
+                } = statement
+                else {
+                    return;
+                };
+                let Some(target_type) = target_type else {
+                    return;
+                };
+                match operator {
+                    // h /= 3 becomes h = Integer.divideUnsigned(h, 3)
+                    Some(
+                        op @ (BinaryOperator::Div
+                        | BinaryOperator::Mod
+                        | BinaryOperator::RightShift),
+                    ) if unsigned_class(&target_type).is_some() => {
+                        let mut binary = Expression::new(ExpressionKind::BinaryExpression {
+                            left: Box::new(target.clone()),
+                            operator: op,
+                            right: Box::new(take_expression(value)),
+                        });
+                        lower_binary(&mut binary, Some(target_type), value_type);
+                        *value = binary;
+                        *assignment_operator = None;
+                    }
+                    _ => convert(value, value_type.as_ref(), constant, &target_type),
+                }
+            }
+            Statement::Return(Some(value)) => {
+                let value_type = self.scopes.type_of(value);
+                let constant = self.scopes.constant_value(value);
+                let return_type = self.scopes.return_type.clone();
+                lower_statement_children(self, statement);
+                if let (Statement::Return(Some(value)), Some(return_type)) =
+                    (statement, return_type)
+                {
+                    convert(value, value_type.as_ref(), constant, &return_type);
+                }
+            }
+            _ => lower_statement_children(self, statement),
+        }
+    }
+}
+
+/// Rewrites a binary expression whose children are already lowered,
+/// using the C# types of the children
+fn lower_binary(expr: &mut Expression, left_type: Option<Type>, right_type: Option<Type>) {
+    let ExpressionKind::BinaryExpression {
+        left,
+        operator,
+        right,
+    } = &mut expr.kind
+    else {
+        return;
+    };
+
+    // string concatenation prints the unsigned value
+    if *operator == BinaryOperator::Add
+        && (left_type == Some(Type::String) || right_type == Some(Type::String))
+    {
+        for (side, typ) in [(&mut **left, &left_type), (&mut **right, &right_type)] {
+            match typ {
+                Some(typ) if unsigned_class(typ).is_some() => {
+                    to_unsigned_string(side, typ);
+                }
+                _ => mask(side, typ.as_ref()),
+            }
+        }
+        return;
+    }
+
+    // byte and ushort are promoted to int, without the sign
+    mask(left, left_type.as_ref());
+    mask(right, right_type.as_ref());
+
+    let (Some(left_type), Some(right_type)) = (left_type, right_type) else {
+        return;
+    };
+
+    // shifts only look at the left side
+    if *operator == BinaryOperator::RightShift {
+        if unsigned_class(&left_type).is_some() {
+            *operator = BinaryOperator::UnsignedRightShift;
+        }
+        return;
+    }
+
+    let Some(promoted) = promote(&left_type, &right_type) else {
+        return;
+    };
+    // uint mixed with a signed int is computed as a long, with an ulong as an ulong
+    // and with a double as a double, the bytes and ushorts are already masked
+    for (side, typ) in [(&mut **left, &left_type), (&mut **right, &right_type)] {
+        if matches!(typ, Type::UInt | Type::ULong) {
+            widen(side, typ, &promoted);
+        }
+    }
+    let Some(class) = unsigned_class(&promoted) else {
+        return;
+    };
+
+    let comparison = match operator {
+        BinaryOperator::Lt | BinaryOperator::Gt | BinaryOperator::Le | BinaryOperator::Ge => {
+            Some(operator.clone())
+        }
+        _ => None,
+    };
+    let method = match operator {
+        BinaryOperator::Div => "divideUnsigned",
+        BinaryOperator::Mod => "remainderUnsigned",
+        _ if comparison.is_some() => "compareUnsigned",
+        _ => return,
+    };
+
+    let call = static_call(
+        class,
+        method,
+        vec![take_expression(left), take_expression(right)],
+    );
+    *expr = match comparison {
+        // a < b becomes Integer.compareUnsigned(a, b) < 0
+        Some(operator) => Expression::new(ExpressionKind::BinaryExpression {
+            left: Box::new(call),
+            operator,
+            right: Box::new(Expression::new(ExpressionKind::Literal(Literal::Int(0)))),
+        }),
+        None => call,
+    };
+}
+
+/// Helper function that obtains the java class with the unsigned helpers
+fn unsigned_class(typ: &Type) -> Option<&'static str> {
+    match typ {
+        Type::UInt => Some("Integer"),
+        Type::ULong => Some("Long"),
+        _ => None,
+    }
+}
+
+/// Compares two unsigned values like CompareTo, the uint and ulong ones with
+/// Integer.compareUnsigned(a, b) and Long.compareUnsigned(a, b). C# gives the
+/// difference of two bytes or ushorts, (a & 255) - (b & 255)
+fn compare(
+    mut target: Expression,
+    mut argument: Expression,
+    typ: &Type,
+    argument_type: Option<&Type>,
+    constant: Option<i64>,
+) -> Expression {
+    // u.CompareTo(b) with a byte b compares it as an uint
+    convert(&mut argument, argument_type, constant, typ);
+    match unsigned_class(typ) {
+        Some(class) => static_call(class, "compareUnsigned", vec![target, argument]),
+        None => {
+            mask(&mut target, Some(typ));
+            mask(&mut argument, Some(typ));
+            Expression::new(ExpressionKind::ParenthesizedExpression(Box::new(
+                Expression::new(ExpressionKind::BinaryExpression {
+                    left: Box::new(target),
+                    operator: BinaryOperator::Sub,
+                    right: Box::new(argument),
+                }),
+            )))
+        }
+    }
+}
+
+/// Helper function that obtains the mask which removes the sign of a small unsigned type
+fn unsigned_mask(typ: &Type) -> Option<i32> {
+    match typ {
+        Type::Byte => Some(0xFF),
+        Type::UShort => Some(0xFFFF),
+        _ => None,
+    }
+}
+
+/// Masks a byte or ushort value, so it is read without the sign, (b & 255)
+pub(super) fn mask(expr: &mut Expression, typ: Option<&Type>) {
+    let Some(mask) = typ.and_then(unsigned_mask) else {
+        return;
+    };
+    let value = take_expression(expr);
+    *expr = Expression::new(ExpressionKind::ParenthesizedExpression(Box::new(masked(
+        value, mask,
+    ))));
+}
+
+/// Helper function that creates the value & mask expression
+fn masked(value: Expression, mask: i32) -> Expression {
+    Expression::new(ExpressionKind::BinaryExpression {
+        left: Box::new(value),
+        operator: BinaryOperator::BitAnd,
+        right: Box::new(Expression::new(ExpressionKind::Literal(Literal::Int(mask)))),
+    })
+}
+
+/// Turns an unsigned value into a wider type, without the sign extension java would do
+pub(super) fn widen(expr: &mut Expression, from: &Type, to: &Type) {
+    let wider = matches!(
+        to,
+        Type::Long | Type::ULong | Type::Float | Type::Double | Type::Decimal
+    );
+    match from {
+        Type::UInt if wider => {
+            let value = take_expression(expr);
+            *expr = static_call("Integer", "toUnsignedLong", vec![value]);
+        }
+        // java only converts the signed longs, the digits of the ulong are read instead
+        Type::ULong if matches!(to, Type::Float | Type::Double) => {
+            let digits = static_call("Long", "toUnsignedString", vec![take_expression(expr)]);
+            *expr = match to {
+                Type::Float => static_call("Float", "parseFloat", vec![digits]),
+                _ => static_call("Double", "parseDouble", vec![digits]),
+            };
+        }
+        Type::Byte | Type::UShort if wider || matches!(to, Type::Int | Type::UInt) => {
+            mask(expr, Some(from))
+        }
+        _ => {}
+    }
+}
+
+/// Converts a value stored into a variable of the given type, widening unsigned
+/// values and casting the constants that do not fit the signed java type
+fn convert(expr: &mut Expression, from: Option<&Type>, constant: Option<i64>, to: &Type) {
+    // byte b = 200 or byte b = K needs a cast, since java bytes stop at 127
+    let limit = match to {
+        Type::Byte => Some(i8::MAX as i64),
+        Type::UShort => Some(i16::MAX as i64),
+        _ => None,
+    };
+    if let (Some(n), Some(limit)) = (constant, limit)
+        && from == Some(&Type::Int)
+    {
+        if n > limit {
+            let value = take_expression(expr);
+            let value = match value.kind {
+                ExpressionKind::Literal(_) | ExpressionKind::Variable(_) => value,
+                _ => Expression::new(ExpressionKind::ParenthesizedExpression(Box::new(value))),
+            };
+            *expr = Expression::new(ExpressionKind::CastExpression {
+                typ: to.clone(),
+                value: Box::new(value),
+            });
+        }
+        return;
+    }
+    if let Some(from) = from {
+        widen(expr, from, to);
+    }
+}
+
+/// Turns an unsigned value into its string, returns false if the type is not unsigned
+pub(super) fn to_unsigned_string(expr: &mut Expression, typ: &Type) -> bool {
+    if let Some(class) = unsigned_class(typ) {
+        let value = take_expression(expr);
+        *expr = static_call(class, "toUnsignedString", vec![value]);
+        return true;
+    }
+    if let Some(mask) = unsigned_mask(typ) {
+        let value = take_expression(expr);
+        *expr = static_call("Integer", "toString", vec![masked(value, mask)]);
+        return true;
+    }
+    false
+}
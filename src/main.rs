@@ -4,9 +4,10 @@ use std::{
 };
 
 // use rust_jcs_transpiler::parser_cs::find_everything;
-use rust_jcs_transpiler::{builder_java::*, generator_java::*};
+use rust_jcs_transpiler::{
+    builder_java::*, generator_java::*, mappings::Mappings, options::Options,
+};
 use tree_sitter::Parser;
-use tree_sitter_c_sharp;
 
 fn main() {
     let mut input = File::open("input.cs").expect("Input file not present!");
@@ -15,6 +16,21 @@ fn main() {
         .read_to_string(&mut input_string)
         .expect("Failed to read file");
     let code = input_string.as_str();
+    // the compiler switches, --checked works like /checked in C#
+    let args: Vec<String> = std::env::args().collect();
+    let options = Options {
+        checked: args
+            .iter()
+            .any(|arg| arg == "--checked" || arg == "/checked"),
+        nullable_annotations: args.iter().any(|arg| arg == "--nullable-annotations"),
+        // --java=8 writes the inferred types instead of var
+        java_version: args
+            .iter()
+            .find_map(|arg| arg.strip_prefix("--java="))
+            .map(|version| version.parse().expect("Invalid java version"))
+            .unwrap_or(Options::default().java_version),
+        mappings: project_mappings(&args),
+    };
     // create a new parser
     let mut parser = Parser::new();
     // set the programming language
@@ -30,12 +46,38 @@ fn main() {
     // find_everything(tree.root_node(), code, 0);
 
     // build the program using our parser
-    let program = build_program(tree.root_node(), code);
+    let program = build_program_with_options(tree.root_node(), code, &options);
     // println!("program? {:#?}", program);
+    // the problems found do not stop the translation
+    for diagnostic in &program.diagnostics {
+        eprintln!("{}", diagnostic);
+    }
     // build the program based on the ast
-    let java_code = JavaGenerator::generate(&program);
+    let java_code = JavaGenerator::generate_with_options(&program, &options);
     let mut output = File::create("output.java").expect("Failed to create output file");
     output
         .write_all(java_code.as_bytes())
         .expect("Failed to write to file");
 }
+
+/// Reads the mappings of the project, --mappings=FILE or the mappings.toml
+/// next to input.cs, they are added to the builtin ones
+fn project_mappings(args: &[String]) -> Mappings {
+    let mut mappings = Mappings::builtin();
+    let path = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--mappings="))
+        .map(str::to_string)
+        .or_else(|| {
+            std::path::Path::new("mappings.toml")
+                .exists()
+                .then(|| "mappings.toml".to_string())
+        });
+    if let Some(path) = path {
+        let text = std::fs::read_to_string(&path).expect("Failed to read the mappings file");
+        let project = Mappings::parse(&text)
+            .unwrap_or_else(|error| panic!("Invalid mappings file {}: {}", path, error));
+        mappings.extend(project);
+    }
+    mappings
+}
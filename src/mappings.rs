@@ -0,0 +1,237 @@
+use serde::Deserialize;
+
+use crate::{ast::Type, parser_cs::match_cs_type};
+
+// the mappings of the .NET library, a project can add its own
+const DEFAULT_MAPPINGS: &str = include_str!("mappings.toml");
+
+/// The java code of the .NET members, read from a mappings file,
+/// see mappings.toml for the format
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Mappings {
+    #[serde(default)]
+    pub methods: Vec<MethodMapping>,
+    #[serde(default)]
+    pub properties: Vec<PropertyMapping>,
+    #[serde(default)]
+    pub helpers: Vec<HelperClass>,
+}
+
+/// A call and the java code it becomes, Console.WriteLine(x) is System.out.println(x)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MethodMapping {
+    pub csharp: String, // the class and method, Console.WriteLine
+    pub java: String,   // the template, System.out.println({args})
+    #[serde(default)]
+    pub imports: Vec<String>, // the java classes used by the template
+    #[serde(default)]
+    pub printed: bool, // the arguments are printed, true is written True
+    pub input: Option<String>, // the java code reading the input line, scanner.nextInt()
+    pub arguments: Option<usize>, // only the calls with this many arguments
+    pub types: Option<Vec<String>>, // only the calls with these argument types, "double|float"
+    pub since: Option<u32>, // the java release the template needs, Math.absExact is 15
+    #[serde(default)]
+    pub helpers: Vec<String>, // the helper classes the template calls, DotNetMath
+}
+
+impl MethodMapping {
+    /// Finds the first placeholder of the template a call with this many arguments
+    /// does not fill, the {1} of AuditLog.write({1}, {0}) for Audit.Log(x)
+    pub fn missing_argument(&self, count: usize) -> Option<usize> {
+        template_parts(&self.java)
+            .ok()?
+            .into_iter()
+            .find_map(|part| match part {
+                TemplatePart::Argument { index, .. } if index >= count => Some(index),
+                _ => None,
+            })
+    }
+}
+
+/// A property and the java code it becomes, Environment.NewLine is System.lineSeparator()
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PropertyMapping {
+    pub csharp: String,
+    pub java: String,
+    #[serde(default)]
+    pub imports: Vec<String>,
+}
+
+/// A java class written after the classes of the program, for the .NET methods
+/// java has no expression for, DotNetMath.truncate(x)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HelperClass {
+    pub name: String, // the class, DotNetMath
+    pub java: String, // its code
+    #[serde(default)]
+    pub imports: Vec<String>,
+}
+
+/// A piece of a template, the text between the placeholders or a placeholder
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplatePart {
+    Text(String),
+    Argument { index: usize, trim: bool }, // {0} or {0|trim}
+    Arguments,                             // {args}
+}
+
+impl Mappings {
+    /// The mappings of the .NET library shipped with the transpiler
+    pub fn builtin() -> Mappings {
+        Mappings::parse(DEFAULT_MAPPINGS).expect("Invalid builtin mappings")
+    }
+
+    /// Reads a mappings file, the templates are checked right away
+    pub fn parse(text: &str) -> Result<Mappings, String> {
+        let mappings: Mappings = toml::from_str(text).map_err(|error| error.to_string())?;
+        for mapping in &mappings.methods {
+            template_parts(&mapping.java)
+                .map_err(|error| format!("{}: {}", mapping.csharp, error))?;
+            // a mapping for one argument cannot use {1}
+            let count = mapping
+                .types
+                .as_ref()
+                .map(|types| types.len())
+                .or(mapping.arguments);
+            if let Some(count) = count
+                && let Some(index) = mapping.missing_argument(count)
+            {
+                return Err(format!(
+                    "{}: the template uses `{{{}}}`, the calls have {}",
+                    mapping.csharp,
+                    index,
+                    match count {
+                        1 => "1 argument".to_string(),
+                        n => format!("{} arguments", n),
+                    }
+                ));
+            }
+            let types = mapping.types.iter().flatten();
+            if let Some(typ) = types
+                .flat_map(|types| types.split('|'))
+                .find(|typ| match_cs_type(typ) == Type::Unknown)
+            {
+                return Err(format!("{}: unknown type `{}`", mapping.csharp, typ));
+            }
+        }
+        Ok(mappings)
+    }
+
+    /// Adds the mappings of a project, a method replaces the ones with the same name,
+    /// number of arguments and types, a property or a helper the one with the same name
+    pub fn extend(&mut self, other: Mappings) {
+        for mapping in other.methods {
+            self.methods.retain(|m| {
+                m.csharp != mapping.csharp
+                    || m.arguments != mapping.arguments
+                    || m.types != mapping.types
+            });
+            self.methods.push(mapping);
+        }
+        for mapping in other.properties {
+            self.properties.retain(|m| m.csharp != mapping.csharp);
+            self.properties.push(mapping);
+        }
+        for helper in other.helpers {
+            self.helpers.retain(|h| h.name != helper.name);
+            self.helpers.push(helper);
+        }
+    }
+
+    /// Finds the mapping of a call from the C# types of its arguments, the ones for
+    /// these types come first, then the ones for the number of arguments, then the rest.
+    /// The templates needing a newer java than the targeted one are left out
+    pub fn method(
+        &self,
+        path: &str,
+        types: &[Option<Type>],
+        java_version: u32,
+    ) -> Option<&MethodMapping> {
+        let candidates = || {
+            self.methods.iter().filter(move |m| {
+                m.csharp == path && m.since.is_none_or(|since| since <= java_version)
+            })
+        };
+        candidates()
+            .find(|m| {
+                m.types
+                    .as_ref()
+                    .is_some_and(|expected| types_match(expected, types))
+            })
+            .or_else(|| {
+                candidates().find(|m| m.types.is_none() && m.arguments == Some(types.len()))
+            })
+            .or_else(|| candidates().find(|m| m.types.is_none() && m.arguments.is_none()))
+    }
+
+    /// Finds the mapping of a property
+    pub fn property(&self, path: &str) -> Option<&PropertyMapping> {
+        self.properties.iter().find(|m| m.csharp == path)
+    }
+
+    /// Finds a helper class by name
+    pub fn helper(&self, name: &str) -> Option<&HelperClass> {
+        self.helpers.iter().find(|h| h.name == name)
+    }
+}
+
+/// Helper function that checks the types of the arguments of a call against the
+/// ones of a mapping, "double|float" accepts both
+fn types_match(expected: &[String], types: &[Option<Type>]) -> bool {
+    expected.len() == types.len()
+        && expected.iter().zip(types).all(|(expected, typ)| {
+            expected
+                .split('|')
+                .any(|expected| typ.as_ref() == Some(&match_cs_type(expected)))
+        })
+}
+
+/// Splits a template into its text and its placeholders,
+/// Integer.parseInt({0|trim}) is Integer.parseInt(, the trimmed first argument and )
+pub fn template_parts(template: &str) -> Result<Vec<TemplatePart>, String> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            parts.push(TemplatePart::Text(rest[..start].to_string()));
+        }
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed placeholder in `{}`", template))?;
+        let placeholder = &rest[start + 1..start + end];
+        let part = match placeholder.split_once('|') {
+            None if placeholder == "args" => TemplatePart::Arguments,
+            None => TemplatePart::Argument {
+                index: placeholder_index(placeholder, template)?,
+                trim: false,
+            },
+            Some((index, "trim")) => TemplatePart::Argument {
+                index: placeholder_index(index, template)?,
+                trim: true,
+            },
+            Some((_, filter)) => {
+                return Err(format!("unknown filter `{}` in `{}`", filter, template));
+            }
+        };
+        parts.push(part);
+        rest = &rest[start + end + 1..];
+    }
+    if !rest.is_empty() {
+        parts.push(TemplatePart::Text(rest.to_string()));
+    }
+    Ok(parts)
+}
+
+/// Helper function that reads the number of an argument placeholder, the 1 of {1}
+fn placeholder_index(placeholder: &str, template: &str) -> Result<usize, String> {
+    placeholder.parse().map_err(|_| {
+        format!(
+            "unknown placeholder `{{{}}}` in `{}`",
+            placeholder, template
+        )
+    })
+}
@@ -0,0 +1,21 @@
+use crate::mappings::Mappings;
+
+/// The settings of a translation, they work like the switches of the C# compiler
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub checked: bool, // /checked, the whole file throws on integer overflow
+    pub nullable_annotations: bool, // string? becomes @Nullable String, instead of just String
+    pub java_version: u32, // the targeted java release, var needs at least 10
+    pub mappings: Mappings, // the java code of the .NET members, with the ones of the project
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            checked: false,
+            nullable_annotations: false,
+            java_version: 17,
+            mappings: Mappings::builtin(),
+        }
+    }
+}
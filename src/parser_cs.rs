@@ -13,13 +13,16 @@ pub fn find_classes(node: Node, source: &str, classes: &mut Vec<Class>) {
         {
             // obtain the name and methods
             let name = source[name_node.byte_range()].to_string();
-            // extract methods here
+            // extract fields and methods here
+            let mut fields = Vec::new();
             let mut methods = Vec::new();
             let mut uses_input: bool = false;
+            find_fields(node, source, &mut fields, &mut uses_input);
             find_methods(node, source, &mut methods, &mut uses_input);
             // add to the classes vector
             classes.push(Class {
                 name,
+                fields,
                 methods,
                 uses_input,
             });
@@ -33,6 +36,34 @@ pub fn find_classes(node: Node, source: &str, classes: &mut Vec<Class>) {
     }
 }
 
+/// Recursively find all the delegate types of the given code, the ones declared
+/// inside a class are found too
+pub fn find_delegates(node: Node, source: &str, delegates: &mut Vec<Delegate>) {
+    if node.kind() == "delegate_declaration" {
+        let name_node = node
+            .child_by_field_name("name")
+            .expect("Expected delegate name");
+        let type_node = node
+            .child_by_field_name("type")
+            .expect("Expected delegate return type");
+        let parameters_node = node
+            .child_by_field_name("parameters")
+            .expect("Expected delegate parameters");
+        // a delegate has no body, it cannot read the input
+        let mut uses_input = false;
+        delegates.push(Delegate {
+            name: source[name_node.byte_range()].to_string(),
+            return_type: extract_type(type_node, source),
+            parameters: match_cs_parameters(parameters_node, source, &mut uses_input),
+        });
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        find_delegates(child, source, delegates);
+    }
+}
+
 /// Recursively find all the methods of a given class
 pub fn find_methods(node: Node, source: &str, methods: &mut Vec<Method>, uses_input: &mut bool) {
     // extract method definition
@@ -51,29 +82,17 @@ pub fn find_methods(node: Node, source: &str, methods: &mut Vec<Method>, uses_in
 
         // extract function details
         let name_node = node
-            .children(&mut cursor)
-            .find(|n| n.kind() == "identifier")
-            .expect("Expected variable declaration");
+            .child_by_field_name("name")
+            .expect("Expected method name");
+        // the return type can be predefined or a class
         let type_node = node
-            .children(&mut cursor)
-            .find(|n| n.kind() == "predefined_type")
-            .expect("Expected variable declaration");
+            .child_by_field_name("returns")
+            .expect("Expected method return type");
 
         // obtain parameters
-        let mut parameters_raw: Vec<&str> = Vec::new();
         let parameters_node = node
-            .children(&mut cursor)
-            .find(|n| n.kind() == "parameter_list")
-            .expect("Expected variable declaration");
-        // iterate through the parameters_node's children
-        for i in 0..parameters_node.child_count() {
-            let child = parameters_node.child(i as u32).unwrap();
-            // if this is a parameter node, extract the data
-            if child.kind() == "parameter" {
-                // save the parameter in this vector
-                parameters_raw.push(&source[child.byte_range()]);
-            }
-        }
+            .child_by_field_name("parameters")
+            .expect("Expected parameter list");
 
         // obtain the body node
         let body_node = node
@@ -81,61 +100,22 @@ pub fn find_methods(node: Node, source: &str, methods: &mut Vec<Method>, uses_in
             .find(|n| n.kind() == "block")
             .expect("Expected variable declaration");
 
-        // search statements inside body
-        let mut body_statements: Vec<Statement> = Vec::new();
-        for i in 0..body_node.child_count() {
-            let child = body_node.child(i as u32).unwrap();
-            if child.kind() == "local_declaration_statement" {
-                // this is a variable declaration
-                body_statements.push(extract_var(child, source, uses_input));
-            }
-            if child.kind() == "if_statement" {
-                // extract the if statement
-                body_statements.push(extract_if(child, source, uses_input));
-            }
-            if child.kind() == "for_statement" {
-                // extract the for statement
-                body_statements.push(extract_for(child, source, uses_input));
-            }
-            if child.kind() == "while_statement" {
-                // extract the while statement
-                body_statements.push(extract_while(child, source, uses_input));
-            }
-            if child.kind() == "return_statement" {
-                // extract the return statement
-                body_statements.push(extract_return(child, source, uses_input));
-            }
-            if child.kind() == "expression_statement" {
-                // this can be anything
-                let expr = child.child(0).unwrap();
-                match expr.kind() {
-                    // a special case is the assignment expression (a = a + b)
-                    "assignment_expression" => {
-                        body_statements.push(extract_assignment(expr, source, uses_input))
-                    }
-                    // otherwise, treat it as a generic expression
-                    _ => {
-                        body_statements.push(Statement::Expression(extract_expression(
-                            child, source, uses_input,
-                        )));
-                    }
-                }
-            }
-        }
+        // search statements inside body, it is parsed like any other block
+        let body_statements = extract_block(body_node, source, uses_input);
 
         // parse the modifiers
         let modifiers = match_cs_modifiers(modifiers_raw);
         // extract the name
         let name = source[name_node.byte_range()].to_string();
         // extract the return type
-        let return_type = source[type_node.byte_range()].to_string();
+        let return_type = extract_type(type_node, source);
         // parse the parameters
-        let parameters = match_cs_parameters(parameters_raw);
+        let parameters = match_cs_parameters(parameters_node, source, uses_input);
 
         // add to the methods vector
         methods.push(Method {
             name,
-            return_type: match_cs_type(&return_type),
+            return_type,
             modifiers,
             parameters,
             body: body_statements,
@@ -149,8 +129,18 @@ pub fn find_methods(node: Node, source: &str, methods: &mut Vec<Method>, uses_in
     }
 }
 
-/// This function parses the variable_declaration statement
+/// This function parses the variable_declaration statement, the first variable
+/// of int a = 1, b = 2 in a for initializer
 pub fn extract_var(node: Node, source: &str, uses_input: &mut bool) -> Statement {
+    extract_vars(node, source, uses_input)
+        .into_iter()
+        .next()
+        .expect("Expected declarator")
+}
+
+/// This function parses a variable declaration into one statement per variable,
+/// int a = 1, b = 2 declares a and b
+pub fn extract_vars(node: Node, source: &str, uses_input: &mut bool) -> Vec<Statement> {
     let mut cursor = node.walk();
     let declaration_node = if node.kind() != "variable_declaration" {
         node.children(&mut cursor)
@@ -160,24 +150,50 @@ pub fn extract_var(node: Node, source: &str, uses_input: &mut bool) -> Statement
         node
     };
 
-    // get type
-    cursor = declaration_node.walk();
+    // get type, predefined or a class
     let type_node = declaration_node
-        .children(&mut cursor)
-        .find(|n| n.kind() == "predefined_type")
+        .child_by_field_name("type")
         .expect("Expected type");
-    let typ = match_cs_type(&source[type_node.byte_range()]);
+    // var is inferred later, by the semantic analysis
+    let implicit = type_node.kind() == "implicit_type";
+    // const int K = 5, the modifier comes before the declaration
+    cursor = node.walk();
+    let constant = node
+        .children(&mut cursor)
+        .any(|n| n.kind() == "modifier" && &source[n.byte_range()] == "const");
+    let typ = extract_type(type_node, source);
 
-    // get declarator
+    // get the declarators
     cursor = declaration_node.walk();
-    let declarator_node = declaration_node
+    declaration_node
         .children(&mut cursor)
-        .find(|n| n.kind() == "variable_declarator")
-        .expect("Expected declarator");
+        .filter(|n| n.kind() == "variable_declarator")
+        .map(|declarator_node| {
+            let (variable, value) = extract_declarator(
+                declarator_node,
+                typ.clone(),
+                implicit,
+                constant,
+                source,
+                uses_input,
+            );
+            Statement::VariableDeclaration { variable, value }
+        })
+        .collect()
+}
 
+/// This function parses a single declared variable, the x = 5 part of int x = 5
+pub fn extract_declarator(
+    node: Node,
+    typ: Type,
+    implicit: bool,
+    constant: bool,
+    source: &str,
+    uses_input: &mut bool,
+) -> (Variable, Option<Expression>) {
     // get variable name
-    cursor = declarator_node.walk();
-    let identifier_node = declarator_node
+    let mut cursor = node.walk();
+    let identifier_node = node
         .children(&mut cursor)
         .find(|n| n.kind() == "identifier")
         .expect("Expected identifier");
@@ -185,27 +201,72 @@ pub fn extract_var(node: Node, source: &str, uses_input: &mut bool) -> Statement
 
     // find initializer (everything after `=`)
     let mut value: Option<Expression> = None;
-    for child in declarator_node.children(&mut cursor) {
-        match child.kind() {
-            "integer_literal" | "real_literal" | "string_literal" | "boolean_literal" => {
-                value = Some(extract_expression(child, source, uses_input));
-                break;
-            }
-            "binary_expression"
-            | "prefix_unary_expression"
-            | "postfix_unary_expression"
-            | "invocation_expression"
-            | "member_access_expression" => {
-                value = Some(extract_expression(child, source, uses_input));
-                break;
-            }
-            _ => {}
+    // skip the name, it is an identifier as well
+    for child in node.children(&mut cursor).skip(1) {
+        if is_expression_kind(child.kind()) {
+            value = Some(extract_expression(child, source, uses_input));
+            break;
         }
     }
 
-    Statement::VariableDeclaration {
-        variable: Variable { typ, name },
-        value,
+    let variable = Variable {
+        typ,
+        name,
+        implicit,
+        constant,
+    };
+    (variable, value)
+}
+
+/// This function finds the fields of a class, int a, b = 2; gives two fields
+pub fn find_fields(node: Node, source: &str, fields: &mut Vec<Field>, uses_input: &mut bool) {
+    let Some(body_node) = node.child_by_field_name("body") else {
+        return;
+    };
+    let mut cursor = body_node.walk();
+    for child in body_node.children(&mut cursor) {
+        // an event is a field with the event keyword
+        if child.kind() != "field_declaration" && child.kind() != "event_field_declaration" {
+            continue;
+        }
+
+        // the modifiers come first, then the declaration
+        let mut modifiers_raw: Vec<&str> = Vec::new();
+        let mut declaration_node = None;
+        let mut field_cursor = child.walk();
+        for field_child in child.children(&mut field_cursor) {
+            match field_child.kind() {
+                "modifier" | "event" => modifiers_raw.push(&source[field_child.byte_range()]),
+                "variable_declaration" => declaration_node = Some(field_child),
+                _ => {}
+            }
+        }
+        let declaration_node = declaration_node.expect("Expected field declaration");
+        let type_node = declaration_node
+            .child_by_field_name("type")
+            .expect("Expected field type");
+        let typ = extract_type(type_node, source);
+
+        // every declarator is a separate field
+        let mut declarator_cursor = declaration_node.walk();
+        for declarator_node in declaration_node
+            .children(&mut declarator_cursor)
+            .filter(|n| n.kind() == "variable_declarator")
+        {
+            let (variable, value) = extract_declarator(
+                declarator_node,
+                typ.clone(),
+                false,
+                modifiers_raw.contains(&"const"),
+                source,
+                uses_input,
+            );
+            fields.push(Field {
+                modifiers: match_cs_modifiers(modifiers_raw.clone()),
+                variable,
+                value,
+            });
+        }
     }
 }
 
@@ -221,17 +282,31 @@ pub fn extract_assignment(node: Node, source: &str, uses_input: &mut bool) -> St
         .child_by_field_name("right")
         .expect("assignment missing right");
 
-    // left node should always be an identifier
-    let target = match left_node.kind() {
-        "identifier" => source[left_node.byte_range()].to_string(),
-        _ => panic!("Unsupported assignment target"),
+    // extract the operator, = or a compound one such as +=
+    let operator_node = node
+        .child_by_field_name("operator")
+        .expect("assignment missing operator");
+    let operator_text = &source[operator_node.byte_range()];
+    let operator = match operator_text {
+        "=" => None,
+        _ => Some(
+            match_cs_binary_operator(operator_text.trim_end_matches('='))
+                .expect("Unknown assignment operator"),
+        ),
     };
 
+    // left node can be an identifier or a member access (this.x)
+    let target = extract_expression(left_node, source, uses_input);
+
     // the right of the identifier is an expression, extract it
     let value = extract_expression(right_node, source, uses_input);
 
     // return the statement
-    Statement::Assignment { target, value }
+    Statement::Assignment {
+        target,
+        operator,
+        value,
+    }
 }
 
 /// This function parses if expressions
@@ -276,30 +351,31 @@ pub fn extract_if(node: Node, source: &str, uses_input: &mut bool) -> Statement
 
 /// This function parses for statements
 fn extract_for(node: Node, source: &str, uses_input: &mut bool) -> Statement {
-    // the for nodes are always in the same order, initializer, condition, increment, body
-    let mut cursor = node.walk();
-    let children: Vec<Node> = node.named_children(&mut cursor).collect();
-
-    let initializer = children.get(0).map(|n| {
+    // initializer, condition and increment are all optional, find them by their field
+    let initializer = node.child_by_field_name("initializer").map(|n| {
         Box::new(match n.kind() {
-            "variable_declaration" => extract_var(*n, source, uses_input),
-            "assignment_expression" => extract_assignment(*n, source, uses_input),
+            "variable_declaration" => extract_var(n, source, uses_input),
+            "assignment_expression" => extract_assignment(n, source, uses_input),
             _ => panic!("Unsupported for initializer: {}", n.kind()),
         })
     });
 
-    let condition = children
-        .get(1)
-        .map(|n| extract_expression(*n, source, uses_input));
+    let condition = node
+        .child_by_field_name("condition")
+        .map(|n| extract_expression(n, source, uses_input));
 
-    let increment = children.get(2).map(|n| {
-        Box::new(Statement::Expression(extract_expression(
-            *n, source, uses_input,
-        )))
+    // the increment can be an assignment, such as i += 2
+    let increment = node.child_by_field_name("update").map(|n| {
+        Box::new(match n.kind() {
+            "assignment_expression" => extract_assignment(n, source, uses_input),
+            _ => Statement::Expression(extract_expression(n, source, uses_input)),
+        })
     });
 
-    let body_node = children.get(3).expect("for loop missing body");
-    let body = extract_block(*body_node, source, uses_input);
+    let body_node = node
+        .child_by_field_name("body")
+        .expect("for loop missing body");
+    let body = extract_block(body_node, source, uses_input);
 
     Statement::For {
         initializer,
@@ -339,20 +415,9 @@ pub fn extract_return(node: Node, source: &str, uses_input: &mut bool) -> Statem
     let mut cursor = node.walk();
 
     for child in node.children(&mut cursor) {
-        match child.kind() {
-            // if we find an expression, extract it
-            "binary_expression"
-            | "prefix_unary_expression"
-            | "postfix_unary_expression"
-            | "invocation_expression"
-            | "identifier"
-            | "integer_literal"
-            | "real_literal"
-            | "boolean_literal"
-            | "string_literal" => {
-                return Statement::Return(Some(extract_expression(child, source, uses_input)));
-            }
-            _ => {}
+        // if we find an expression, extract it
+        if is_expression_kind(child.kind()) {
+            return Statement::Return(Some(extract_expression(child, source, uses_input)));
         }
     }
 
@@ -360,52 +425,203 @@ pub fn extract_return(node: Node, source: &str, uses_input: &mut bool) -> Statem
     Statement::Return(None)
 }
 
+/// This function parses the throw statements, throw new OverflowException()
+pub fn extract_throw(node: Node, source: &str, uses_input: &mut bool) -> Statement {
+    let mut cursor = node.walk();
+    let exception = node
+        .children(&mut cursor)
+        .find(|child| is_expression_kind(child.kind()))
+        // throw; rethrows the exception of a catch, and catch is not supported
+        .expect("Unsupported throw without an exception");
+    Statement::Throw(extract_expression(exception, source, uses_input))
+}
+
+/// Helper function that tells whether a node kind can be parsed by extract_expression
+pub fn is_expression_kind(kind: &str) -> bool {
+    matches!(
+        kind,
+        "integer_literal"
+            | "real_literal"
+            | "string_literal"
+            | "verbatim_string_literal"
+            | "raw_string_literal"
+            | "character_literal"
+            | "boolean_literal"
+            | "null_literal"
+            | "this"
+            | "base"
+            | "identifier"
+            | "binary_expression"
+            | "prefix_unary_expression"
+            | "postfix_unary_expression"
+            | "invocation_expression"
+            | "member_access_expression"
+            | "conditional_access_expression"
+            | "element_access_expression"
+            | "query_expression"
+            | "object_creation_expression"
+            | "anonymous_object_creation_expression"
+            | "checked_expression"
+            | "parenthesized_expression"
+            | "conditional_expression"
+            | "cast_expression"
+            | "is_expression"
+            | "as_expression"
+            | "typeof_expression"
+            | "interpolated_string_expression"
+            | "lambda_expression"
+            | "anonymous_method_expression"
+    )
+}
+
 pub fn extract_expression(node: Node, source: &str, uses_input: &mut bool) -> Expression {
     // call different functions depending of the node kind
     match node.kind() {
         "argument" => {
-            let expr_node = node.named_child(0).unwrap();
-            extract_expression(expr_node, source, uses_input)
+            // the value is the last child, after the name of a named argument,
+            // it is not a named node for F(this)
+            let expr_node = (node.child_count() as u32)
+                .checked_sub(1)
+                .and_then(|last| node.child(last))
+                .expect("Expected argument value");
+            let mut value = extract_expression(expr_node, source, uses_input);
+            // Swap(ref a, ref b), int.TryParse(s, out var n)
+            let mut cursor = node.walk();
+            let modifier = node.children(&mut cursor).find_map(|n| match n.kind() {
+                "ref" => Some(ParameterModifier::Ref),
+                "out" => Some(ParameterModifier::Out),
+                "in" => Some(ParameterModifier::In),
+                _ => None,
+            });
+            if let Some(modifier) = modifier {
+                value = Expression::new(ExpressionKind::RefArgument {
+                    modifier,
+                    value: Box::new(value),
+                });
+            }
+            match node.child_by_field_name("name") {
+                // Print(text: "hi")
+                Some(name_node) => Expression::new(ExpressionKind::NamedArgument {
+                    name: source[name_node.byte_range()].to_string(),
+                    value: Box::new(value),
+                }),
+                None => value,
+            }
+        }
+        // the int n of out int n
+        "declaration_expression" => {
+            let type_node = node.child_by_field_name("type").expect("Expected type");
+            let name_node = node.child_by_field_name("name").expect("Expected name");
+            Expression::new(ExpressionKind::DeclarationExpression(Variable {
+                typ: extract_type(type_node, source),
+                name: source[name_node.byte_range()].to_string(),
+                implicit: type_node.kind() == "implicit_type",
+                constant: false,
+            }))
+        }
+        "lambda_expression" | "anonymous_method_expression" => {
+            extract_lambda(node, source, uses_input)
         }
+        "query_expression" => extract_query(node, source, uses_input),
         "expression_statement" => extract_expression(node.child(0).unwrap(), source, uses_input),
         "binary_expression" => extract_binary_expression(node, source, uses_input),
         "prefix_unary_expression" => extract_unary_expression(node, source, true, uses_input),
         "postfix_unary_expression" => extract_unary_expression(node, source, false, uses_input),
         "invocation_expression" => extract_call_expression(node, source, uses_input),
-
-        // parse as i32
-        "integer_literal" => Expression::Literal(Literal::Int(
-            source[node.byte_range()].parse::<i32>().unwrap(),
-        )),
-
-        // here we can parse either as float (f32) or double (f64)
-        "real_literal" => {
-            let mut s = source[node.byte_range()].to_string();
-            if s.ends_with("f") {
-                s.pop();
-                Expression::Literal(Literal::Float(s.parse::<f32>().unwrap()))
-            } else if s.ends_with("d") {
-                s.pop();
-                Expression::Literal(Literal::Double(s.parse::<f64>().unwrap()))
+        "member_access_expression" => extract_member_access(node, source, uses_input),
+        "conditional_access_expression" => extract_conditional_access(node, source, uses_input),
+        "element_access_expression" => extract_element_access(node, source, uses_input),
+        "object_creation_expression" => extract_object_creation(node, source, uses_input),
+        "anonymous_object_creation_expression" => {
+            extract_anonymous_object(node, source, uses_input)
+        }
+        "checked_expression" => extract_checked_expression(node, source, uses_input),
+        "this" => Expression::new(ExpressionKind::This),
+        "base" => Expression::new(ExpressionKind::Base),
+        "parenthesized_expression" => {
+            Expression::new(ExpressionKind::ParenthesizedExpression(Box::new(
+                extract_expression(node.named_child(0).unwrap(), source, uses_input),
+            )))
+        }
+        "conditional_expression" => extract_conditional_expression(node, source, uses_input),
+        "cast_expression" => {
+            // (type) value
+            let type_node = node
+                .child_by_field_name("type")
+                .expect("cast_expression missing type");
+            let value_node = node
+                .child_by_field_name("value")
+                .expect("cast_expression missing value");
+            Expression::new(ExpressionKind::CastExpression {
+                typ: extract_type(type_node, source),
+                value: Box::new(extract_expression(value_node, source, uses_input)),
+            })
+        }
+        "is_expression" | "as_expression" => {
+            // both have the same structure, value is/as type
+            let left_node = node
+                .child_by_field_name("left")
+                .expect("is/as expression missing left");
+            let type_node = node
+                .child_by_field_name("right")
+                .expect("is/as expression missing type");
+            let left = Box::new(extract_expression(left_node, source, uses_input));
+            let typ = extract_type(type_node, source);
+            if node.kind() == "is_expression" {
+                Expression::new(ExpressionKind::IsExpression { left, typ })
             } else {
-                Expression::Literal(Literal::Float(s.parse::<f32>().unwrap()))
+                Expression::new(ExpressionKind::AsExpression { left, typ })
             }
         }
+        "interpolated_string_expression" => extract_interpolated_string(node, source, uses_input),
+        "typeof_expression" => {
+            let type_node = node
+                .child_by_field_name("type")
+                .expect("typeof_expression missing type");
+            Expression::new(ExpressionKind::TypeOfExpression(extract_type(
+                type_node, source,
+            )))
+        }
+
+        // hexadecimal, binary, underscores and suffixes are handled by the helper
+        "integer_literal" => Expression::new(ExpressionKind::Literal(parse_integer_literal(
+            &source[node.byte_range()],
+        ))),
+
+        // float, double or decimal, depending on the suffix
+        "real_literal" => Expression::new(ExpressionKind::Literal(parse_real_literal(
+            &source[node.byte_range()],
+        ))),
+
+        "null_literal" => Expression::new(ExpressionKind::Literal(Literal::Null)),
 
         // here we can have "true" or "false"
         "boolean_literal" => {
             let s = &source[node.byte_range()];
             if s == "true" {
-                Expression::Literal(Literal::Bool(true))
+                Expression::new(ExpressionKind::Literal(Literal::Bool(true)))
             } else {
-                Expression::Literal(Literal::Bool(false))
+                Expression::new(ExpressionKind::Literal(Literal::Bool(false)))
             }
         }
-        "string_literal" => {
-            Expression::Literal(Literal::String(source[node.byte_range()].to_string()))
+        "string_literal" | "verbatim_string_literal" | "raw_string_literal" => Expression::new(
+            ExpressionKind::Literal(extract_string_literal(node, source)),
+        ),
+        // a single character, possibly escaped
+        "character_literal" => {
+            let value = decode_string_literal(node, source);
+            Expression::new(ExpressionKind::Literal(Literal::Char(
+                value.chars().next().unwrap_or_default(),
+            )))
         }
         // identifier expression, use the variable's name
-        "identifier" => Expression::Variable(source[node.byte_range()].to_string()),
+        "identifier" => Expression::new(ExpressionKind::Variable(
+            source[node.byte_range()].to_string(),
+        )),
+        // a type used as a value, such as the `int` in `int.Parse`
+        "predefined_type" => Expression::new(ExpressionKind::Variable(
+            source[node.byte_range()].to_string(),
+        )),
         _ => panic!("Unsupported expression: {}", node.kind()),
     }
 }
@@ -432,29 +648,41 @@ pub fn extract_binary_expression(node: Node, source: &str, uses_input: &mut bool
     // extract the right expression, put it in a Box
     let right = Box::new(extract_expression(right_node, source, uses_input));
 
-    // parse the operator accordingly
-    let operator = match &source[operator_node.byte_range()] {
-        "+" => BinaryOperator::Add,
-        "-" => BinaryOperator::Sub,
-        "*" => BinaryOperator::Mul,
-        "/" => BinaryOperator::Div,
-        "==" => BinaryOperator::Eq,
-        "!=" => BinaryOperator::Ne,
-        "<" => BinaryOperator::Lt,
-        ">" => BinaryOperator::Gt,
-        "<=" => BinaryOperator::Le,
-        ">=" => BinaryOperator::Ge,
-        "&&" => BinaryOperator::And,
-        "||" => BinaryOperator::Or,
-        _ => panic!("Unknown operator"),
-    };
+    // parse the operator using the helper
+    let operator =
+        match_cs_binary_operator(&source[operator_node.byte_range()]).expect("Unknown operator");
 
     // return the binary expression
-    Expression::BinaryExpression {
+    Expression::new(ExpressionKind::BinaryExpression {
         left,
         operator,
         right,
-    }
+    })
+}
+
+/// This function parses conditional (ternary) expressions
+pub fn extract_conditional_expression(
+    node: Node,
+    source: &str,
+    uses_input: &mut bool,
+) -> Expression {
+    // condition ? consequence : alternative
+    let condition_node = node
+        .child_by_field_name("condition")
+        .expect("conditional_expression missing condition");
+    let consequence_node = node
+        .child_by_field_name("consequence")
+        .expect("conditional_expression missing consequence");
+    let alternative_node = node
+        .child_by_field_name("alternative")
+        .expect("conditional_expression missing alternative");
+
+    // every part is a generic expression
+    Expression::new(ExpressionKind::ConditionalExpression {
+        condition: Box::new(extract_expression(condition_node, source, uses_input)),
+        consequence: Box::new(extract_expression(consequence_node, source, uses_input)),
+        alternative: Box::new(extract_expression(alternative_node, source, uses_input)),
+    })
 }
 
 /// This function parses unary expressions
@@ -479,14 +707,27 @@ pub fn extract_unary_expression(
             let operator = match &source[operator_node.byte_range()] {
                 "!" => UnaryOperator::Not,
                 "-" => UnaryOperator::Neg,
+                "+" => UnaryOperator::Plus,
+                "~" => UnaryOperator::BitNot,
+                "++" => UnaryOperator::UAdd,
+                "--" => UnaryOperator::USub,
                 _ => panic!("Unsupported unary operator"),
             };
 
+            // -2147483648 is an int and -9223372036854775808 a long, C# reads the
+            // minus and the literal as one value, the literal alone does not fit
+            if operator == UnaryOperator::Neg
+                && operand_node.kind() == "integer_literal"
+                && let Some(value) = negative_boundary(&source[operand_node.byte_range()])
+            {
+                return Expression::new(ExpressionKind::Literal(value));
+            }
+
             // parse the right of the expression
             let right = Box::new(extract_expression(operand_node, source, uses_input));
 
             // return the unary expression
-            Expression::PrefixUnaryExpression { operator, right }
+            Expression::new(ExpressionKind::PrefixUnaryExpression { operator, right })
         }
         false => {
             let mut cursor = node.walk();
@@ -509,7 +750,7 @@ pub fn extract_unary_expression(
             let left = Box::new(extract_expression(operand_node, source, uses_input));
 
             // return the unary expression
-            Expression::PostfixUnaryExpression { left, operator }
+            Expression::new(ExpressionKind::PostfixUnaryExpression { left, operator })
         }
     }
 }
@@ -521,34 +762,580 @@ pub fn extract_call_expression(node: Node, source: &str, uses_input: &mut bool)
         .child_by_field_name("function")
         .expect("invocation_expression missing function");
 
-    // obtain the name using the helper
-    let function = extract_function_name(function_node, source);
-    if matches!(
-        function.as_str(),
-        "Console.ReadLine" | "int.Parse" | "double.Parse" | "bool.Parse"
-    ) {
+    // the callee is a generic expression, a name or a member access
+    let callee = Box::new(extract_expression(function_node, source, uses_input));
+    if callee.path().as_deref() == Some("Console.ReadLine") {
         *uses_input = true;
     }
     // arguments
-    let mut arguments = Vec::new();
     let args_node = node
         .child_by_field_name("arguments")
         .expect("invocation_expression missing arguments");
+    let mut arguments = extract_arguments(args_node, source, uses_input);
+
+    // composite formats, such as Console.WriteLine("{0} + {1}", a, b),
+    // are turned into interpolated strings
+    let path = callee.path();
+    if matches!(
+        path.as_deref(),
+        Some("Console.WriteLine" | "Console.Write" | "string.Format" | "String.Format")
+    ) && arguments.len() > 1
+        && let Expression {
+            kind: ExpressionKind::Literal(Literal::String(format)),
+            ..
+        } = &arguments[0]
+        && let Some(parts) = parse_composite_format(format, &arguments[1..])
+    {
+        let interpolated = Expression::new(ExpressionKind::InterpolatedString(parts));
+        // string.Format becomes the string itself
+        if path.as_deref().is_some_and(|p| p.ends_with(".Format")) {
+            return interpolated;
+        }
+        arguments = vec![interpolated];
+    }
+
+    // return the call expression
+    Expression::new(ExpressionKind::Call { callee, arguments })
+}
+
+/// This function parses checked { } and unchecked { } blocks
+pub fn extract_checked(node: Node, source: &str, uses_input: &mut bool) -> Statement {
+    // the first child is the keyword
+    let checked = node.child(0).expect("Expected checked keyword").kind() == "checked";
+    let mut cursor = node.walk();
+    let block_node = node
+        .children(&mut cursor)
+        .find(|n| n.kind() == "block")
+        .expect("Expected checked block");
+
+    Statement::Checked {
+        checked,
+        body: extract_block(block_node, source, uses_input),
+    }
+}
+
+/// This function parses a try with one catch, try { ... } catch (OverflowException e) { ... }.
+/// A catch without a variable gets its name in the lowering, catch { } catches any Exception
+pub fn extract_try(node: Node, source: &str, uses_input: &mut bool) -> Statement {
+    let body_node = node
+        .child_by_field_name("body")
+        .expect("Expected try block");
+    let mut cursor = node.walk();
+    let clauses: Vec<Node> = node
+        .children(&mut cursor)
+        .filter(|n| matches!(n.kind(), "catch_clause" | "finally_clause"))
+        .collect();
+    let [clause] = clauses.as_slice() else {
+        panic!("Unsupported try statement, only a try with a single catch is translated");
+    };
+    if clause.kind() != "catch_clause" {
+        panic!("Unsupported try statement, only a try with a single catch is translated");
+    }
+
+    let mut cursor = clause.walk();
+    let mut exception = Variable {
+        typ: Type::Named("Exception".to_string()),
+        name: String::new(),
+        implicit: false,
+        constant: false,
+    };
+    let mut handler = Vec::new();
+    for child in clause.children(&mut cursor) {
+        match child.kind() {
+            "catch_declaration" => {
+                let type_node = child
+                    .child_by_field_name("type")
+                    .expect("Expected catch type");
+                exception.typ = extract_type(type_node, source);
+                if let Some(name) = child.child_by_field_name("name") {
+                    exception.name = source[name.byte_range()].to_string();
+                }
+            }
+            "catch_filter_clause" => panic!("Unsupported catch filter"),
+            "block" => handler = extract_block(child, source, uses_input),
+            _ => {}
+        }
+    }
+
+    Statement::TryCatch {
+        body: extract_block(body_node, source, uses_input),
+        exception,
+        handler,
+    }
+}
+
+/// This function parses checked(...) and unchecked(...) expressions
+pub fn extract_checked_expression(node: Node, source: &str, uses_input: &mut bool) -> Expression {
+    let checked = node.child(0).expect("Expected checked keyword").kind() == "checked";
+    // the value is the only named child
+    let value_node = node.named_child(0).expect("Expected checked value");
+
+    Expression::new(ExpressionKind::CheckedExpression {
+        checked,
+        value: Box::new(extract_expression(value_node, source, uses_input)),
+    })
+}
+
+/// This function parses object creations, such as new Person("Ana", 7)
+pub fn extract_object_creation(node: Node, source: &str, uses_input: &mut bool) -> Expression {
+    let type_node = node
+        .child_by_field_name("type")
+        .expect("object_creation_expression missing type");
+
+    // the arguments are optional, new Person { ... } has none
+    let arguments = node
+        .child_by_field_name("arguments")
+        .map(|n| extract_arguments(n, source, uses_input))
+        .unwrap_or_default();
+
+    let creation = Expression::new(ExpressionKind::ObjectCreation {
+        typ: extract_type(type_node, source),
+        arguments,
+    });
+    match node.child_by_field_name("initializer") {
+        Some(initializer) => {
+            extract_collection_initializer(creation, initializer, source, uses_input)
+        }
+        None => creation,
+    }
+}
+
+/// This function parses an anonymous object, new { p.Name, Total = 5 }. A member
+/// without a name takes the one of its variable or member, the others get none
+fn extract_anonymous_object(node: Node, source: &str, uses_input: &mut bool) -> Expression {
+    let mut members = Vec::new();
+    let mut name = None;
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        match child.kind() {
+            "new" | "{" | "}" | "," | "=" => (),
+            // Total = 5, the name comes before the =
+            "identifier" if child.next_sibling().is_some_and(|next| next.kind() == "=") => {
+                name = Some(source[child.byte_range()].to_string());
+            }
+            _ => {
+                let value = extract_expression(child, source, uses_input);
+                let name = name.take().unwrap_or_else(|| match &value {
+                    Expression {
+                        kind: ExpressionKind::Variable(name),
+                        ..
+                    }
+                    | Expression {
+                        kind: ExpressionKind::MemberAccess { name, .. },
+                        ..
+                    } => name.clone(),
+                    _ => String::new(),
+                });
+                members.push((name, value));
+            }
+        }
+    }
+    Expression::new(ExpressionKind::AnonymousObject(members))
+}
+
+/// This function parses the elements of a collection initializer, new List<int> { 1, 2 },
+/// new Dictionary<string, int> { { "a", 1 } } or new Dictionary<string, int> { ["a"] = 1 }
+fn extract_collection_initializer(
+    creation: Expression,
+    node: Node,
+    source: &str,
+    uses_input: &mut bool,
+) -> Expression {
+    let mut elements = Vec::new();
+    let mut indexed = false;
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        match child.kind() {
+            // { "a", 1 } gives the arguments of Add
+            "initializer_expression" => {
+                let mut cursor = child.walk();
+                let arguments = child
+                    .named_children(&mut cursor)
+                    .map(|argument| extract_expression(argument, source, uses_input))
+                    .collect();
+                elements.push(arguments);
+            }
+            // ["a"] = 1 writes through the indexer, Name = "Ana" sets a property
+            "assignment_expression" => {
+                let left = child.child_by_field_name("left").expect("Expected left");
+                let right = child.child_by_field_name("right").expect("Expected right");
+                if left.kind() != "element_binding_expression" {
+                    panic!("Unsupported object initializer");
+                }
+                let index = left
+                    .named_child(0)
+                    .expect("Expected an index in the initializer");
+                elements.push(vec![
+                    extract_expression(index, source, uses_input),
+                    extract_expression(right, source, uses_input),
+                ]);
+                indexed = true;
+            }
+            _ => elements.push(vec![extract_expression(child, source, uses_input)]),
+        }
+    }
+
+    Expression::new(ExpressionKind::CollectionInitializer {
+        creation: Box::new(creation),
+        elements,
+        indexed,
+    })
+}
 
-    let mut cursor = args_node.walk();
+/// This function parses the arguments of a call or an object creation
+fn extract_arguments(node: Node, source: &str, uses_input: &mut bool) -> Vec<Expression> {
+    let mut arguments = Vec::new();
+    let mut cursor = node.walk();
     // iterate through the children
-    for child in args_node.children(&mut cursor) {
+    for child in node.children(&mut cursor) {
         if child.kind() == "argument" {
             // parse the whole argument recursively
             arguments.push(extract_expression(child, source, uses_input));
         }
     }
+    arguments
+}
 
-    // return the call expression
-    Expression::Call {
-        function,
-        arguments,
+/// This function parses member accesses, such as this.x or GetList().First()
+pub fn extract_member_access(node: Node, source: &str, uses_input: &mut bool) -> Expression {
+    // the accessed object, can be any expression
+    let target_node = node
+        .child_by_field_name("expression")
+        .expect("member access missing expression");
+
+    // the member's name
+    let name_node = node
+        .child_by_field_name("name")
+        .expect("member access missing name");
+
+    Expression::new(ExpressionKind::MemberAccess {
+        target: Box::new(extract_expression(target_node, source, uses_input)),
+        name: source[name_node.byte_range()].to_string(),
+    })
+}
+
+/// This function parses element accesses, such as xs[i] or ages["Ann"]
+pub fn extract_element_access(node: Node, source: &str, uses_input: &mut bool) -> Expression {
+    let target_node = node
+        .child_by_field_name("expression")
+        .expect("element access missing expression");
+    let subscript_node = node
+        .child_by_field_name("subscript")
+        .expect("element access missing subscript");
+
+    // a single index, xs[i, j] is a multidimensional array
+    let mut arguments = extract_arguments(subscript_node, source, uses_input);
+    if arguments.len() != 1 {
+        panic!("Only the accesses with a single index are supported");
     }
+
+    Expression::new(ExpressionKind::ElementAccess {
+        target: Box::new(extract_expression(target_node, source, uses_input)),
+        index: Box::new(arguments.remove(0)),
+    })
+}
+
+/// This function parses conditional accesses, such as Changed?.Invoke(),
+/// only a single member can follow the ?.
+pub fn extract_conditional_access(node: Node, source: &str, uses_input: &mut bool) -> Expression {
+    let target_node = node
+        .child_by_field_name("condition")
+        .expect("conditional access missing condition");
+
+    // ?.Name is a member binding, ?[0] is not supported
+    let mut cursor = node.walk();
+    let binding_node = node
+        .children(&mut cursor)
+        .find(|child| child.kind() == "member_binding_expression")
+        .expect("Only the ?. member accesses are supported");
+    let name_node = binding_node
+        .child_by_field_name("name")
+        .expect("member binding missing name");
+
+    Expression::new(ExpressionKind::ConditionalAccess {
+        target: Box::new(extract_expression(target_node, source, uses_input)),
+        name: source[name_node.byte_range()].to_string(),
+    })
+}
+
+/// This function parses interpolated strings, such as $"Hello {name}"
+pub fn extract_interpolated_string(node: Node, source: &str, uses_input: &mut bool) -> Expression {
+    let mut cursor = node.walk();
+    // verbatim strings start with $@ or @$
+    let verbatim = node
+        .child(0)
+        .is_some_and(|n| source[n.byte_range()].contains('@'));
+    // raw strings use at least three quotes and have no escapes at all
+    let raw = node
+        .children(&mut cursor)
+        .any(|n| n.kind() == "interpolation_quote" && n.byte_range().len() >= 3);
+
+    let mut parts = Vec::new();
+    for child in node.children(&mut cursor) {
+        match child.kind() {
+            "string_content" => {
+                let mut text = source[child.byte_range()].to_string();
+                if !raw {
+                    // doubled braces are escaped braces
+                    text = text.replace("{{", "{").replace("}}", "}");
+                }
+                if verbatim {
+                    // doubled quotes are escaped quotes
+                    text = text.replace("\"\"", "\"");
+                }
+                push_interpolation_text(&mut parts, &text);
+            }
+            "escape_sequence" => {
+                let text = decode_escape_sequence(&source[child.byte_range()]);
+                push_interpolation_text(&mut parts, &text);
+            }
+            "interpolation" => {
+                parts.push(extract_interpolation(child, source, uses_input));
+            }
+            _ => {}
+        }
+    }
+
+    if raw {
+        dedent_raw_interpolation(&mut parts);
+    }
+
+    Expression::new(ExpressionKind::InterpolatedString(parts))
+}
+
+/// Helper function that de-indents multi-line raw interpolated strings,
+/// just like dedent_raw_string does for the ones without values
+fn dedent_raw_interpolation(parts: &mut Vec<InterpolationPart>) {
+    // the text must start with a line break and end with the closing quotes' indentation
+    let Some(InterpolationPart::Text(first)) = parts.first() else {
+        return;
+    };
+    if !first.starts_with('\n') {
+        return;
+    }
+    let Some(InterpolationPart::Text(last)) = parts.last() else {
+        return;
+    };
+    let Some(i) = last.rfind('\n') else {
+        return;
+    };
+    let indent = last[i + 1..].to_string();
+    if !indent.chars().all(char::is_whitespace) {
+        return;
+    }
+
+    // drop the last line
+    if let Some(InterpolationPart::Text(last)) = parts.last_mut() {
+        last.truncate(i);
+    }
+    // remove the indentation after every line break, then the first line break
+    for part in parts.iter_mut() {
+        if let InterpolationPart::Text(text) = part {
+            *text = text.replace(&format!("\n{}", indent), "\n");
+        }
+    }
+    if let Some(InterpolationPart::Text(first)) = parts.first_mut() {
+        first.remove(0);
+    }
+    parts.retain(|part| !matches!(part, InterpolationPart::Text(text) if text.is_empty()));
+}
+
+/// This function parses a single {value,alignment:format} hole of an interpolated string
+fn extract_interpolation(node: Node, source: &str, uses_input: &mut bool) -> InterpolationPart {
+    let mut value = None;
+    let mut alignment = None;
+    let mut format = None;
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        let text = &source[child.byte_range()];
+        match child.kind() {
+            "interpolation_brace" => {}
+            // , followed by a constant
+            "interpolation_alignment_clause" => {
+                alignment = text[1..].trim().parse::<i32>().ok();
+            }
+            // : followed by the format specifier
+            "interpolation_format_clause" => {
+                format = Some(text[1..].to_string());
+            }
+            _ => value = Some(extract_expression(child, source, uses_input)),
+        }
+    }
+
+    InterpolationPart::Value {
+        value: value.expect("interpolation missing value"),
+        alignment,
+        format,
+    }
+}
+
+/// Helper function that parses a .NET composite format string,
+/// the {index,alignment:format} items are replaced by the matching arguments.
+/// Returns None if the format refers to missing arguments or is malformed
+pub fn parse_composite_format(
+    format: &str,
+    arguments: &[Expression],
+) -> Option<Vec<InterpolationPart>> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            // {{ and }} are escaped braces
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                // read the whole format item
+                let mut item = String::new();
+                loop {
+                    match chars.next()? {
+                        '}' => break,
+                        c => item.push(c),
+                    }
+                }
+                // split it into index, alignment and format
+                let (head, format) = match item.split_once(':') {
+                    Some((head, format)) => (head, Some(format.to_string())),
+                    None => (item.as_str(), None),
+                };
+                let (index, alignment) = match head.split_once(',') {
+                    Some((index, alignment)) => (index, Some(alignment.trim().parse().ok()?)),
+                    None => (head, None),
+                };
+                // a repeated index repeats the argument
+                let value = arguments.get(index.trim().parse::<usize>().ok()?)?.clone();
+
+                push_interpolation_text(&mut parts, &text);
+                text.clear();
+                parts.push(InterpolationPart::Value {
+                    value,
+                    alignment,
+                    format,
+                });
+            }
+            '}' => return None,
+            _ => text.push(c),
+        }
+    }
+    push_interpolation_text(&mut parts, &text);
+
+    Some(parts)
+}
+
+/// Helper function that appends text to an interpolated string,
+/// merging it with the previous text part
+fn push_interpolation_text(parts: &mut Vec<InterpolationPart>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    if let Some(InterpolationPart::Text(last)) = parts.last_mut() {
+        last.push_str(text);
+    } else {
+        parts.push(InterpolationPart::Text(text.to_string()));
+    }
+}
+
+/// This function parses the string literals, regular, verbatim and raw
+pub fn extract_string_literal(node: Node, source: &str) -> Literal {
+    let value = decode_string_literal(node, source);
+    // the u8 suffix turns the string into a byte sequence
+    if source[node.byte_range()].ends_with("u8") {
+        Literal::Utf8String(value)
+    } else {
+        Literal::String(value)
+    }
+}
+
+/// Helper function that obtains the value of a string or character literal
+pub fn decode_string_literal(node: Node, source: &str) -> String {
+    let text = &source[node.byte_range()];
+    match node.kind() {
+        // @"C:\path", only "" is escaped
+        "verbatim_string_literal" => {
+            let text = text.strip_suffix("u8").unwrap_or(text);
+            text[2..text.len() - 1].replace("\"\"", "\"")
+        }
+        // """raw""", nothing is escaped, multi-line ones are de-indented
+        "raw_string_literal" => {
+            let mut cursor = node.walk();
+            let content = node
+                .children(&mut cursor)
+                .find(|n| n.kind() == "raw_string_content")
+                .map(|n| &source[n.byte_range()])
+                .unwrap_or_default();
+            dedent_raw_string(content)
+        }
+        // regular strings and characters, made of content and escape sequences
+        _ => {
+            let mut out = String::new();
+            let mut cursor = node.walk();
+            for child in node.children(&mut cursor) {
+                match child.kind() {
+                    "string_literal_content" | "character_literal_content" => {
+                        out.push_str(&source[child.byte_range()])
+                    }
+                    "escape_sequence" => {
+                        out.push_str(&decode_escape_sequence(&source[child.byte_range()]))
+                    }
+                    _ => {}
+                }
+            }
+            out
+        }
+    }
+}
+
+/// Helper function for multi-line raw strings, the first and last line breaks are dropped
+/// and the indentation of the closing quotes is removed from every line
+fn dedent_raw_string(content: &str) -> String {
+    // single line raw strings are taken as they are
+    let Some(body) = content.strip_prefix('\n') else {
+        return content.to_string();
+    };
+    let body = body.strip_prefix('\r').unwrap_or(body);
+    let (body, indent) = match body.rfind('\n') {
+        Some(i) => (&body[..i], &body[i + 1..]),
+        None => ("", body),
+    };
+    body.trim_end_matches('\r')
+        .split('\n')
+        .map(|line| line.strip_prefix(indent).unwrap_or(line.trim_start()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Helper function that turns a C# escape sequence, such as \n or \u0041, into its value
+pub fn decode_escape_sequence(escape: &str) -> String {
+    let mut chars = escape.chars();
+    // skip the backslash
+    chars.next();
+    let code = match chars.next() {
+        Some('n') => return "\n".to_string(),
+        Some('t') => return "\t".to_string(),
+        Some('r') => return "\r".to_string(),
+        Some('0') => return "\0".to_string(),
+        Some('a') => return "\u{7}".to_string(),
+        Some('b') => return "\u{8}".to_string(),
+        Some('f') => return "\u{c}".to_string(),
+        Some('v') => return "\u{b}".to_string(),
+        Some('e') => return "\u{1b}".to_string(),
+        // \uXXXX, \UXXXXXXXX and \xH[H][H][H] are hexadecimal codes
+        Some('u' | 'U' | 'x') => u32::from_str_radix(chars.as_str(), 16).ok(),
+        // \\, \" and \' are the character itself
+        Some(c) => return c.to_string(),
+        None => None,
+    };
+    code.and_then(char::from_u32)
+        .map(|c| c.to_string())
+        .unwrap_or_default()
 }
 
 /// This function parses blocks of code
@@ -562,14 +1349,18 @@ fn extract_block(block_node: Node, source: &str, uses_input: &mut bool) -> Vec<S
         // based on the kind, extract the statement accordingly
         match child.kind() {
             "local_declaration_statement" => {
-                statements.push(extract_var(child, source, uses_input));
+                // this is a variable declaration, of one or more variables
+                statements.extend(extract_vars(child, source, uses_input));
             }
             "expression_statement" => {
+                // this can be anything
                 let expr = child.child(0).unwrap();
                 match expr.kind() {
+                    // a special case is the assignment expression (a = a + b)
                     "assignment_expression" => {
                         statements.push(extract_assignment(expr, source, uses_input))
                     }
+                    // otherwise, treat it as a generic expression
                     _ => {
                         statements.push(Statement::Expression(extract_expression(
                             child, source, uses_input,
@@ -586,6 +1377,22 @@ fn extract_block(block_node: Node, source: &str, uses_input: &mut bool) -> Vec<S
             "for_statement" => {
                 statements.push(extract_for(child, source, uses_input));
             }
+            "return_statement" => {
+                statements.push(extract_return(child, source, uses_input));
+            }
+            "throw_statement" => {
+                statements.push(extract_throw(child, source, uses_input));
+            }
+            "checked_statement" => {
+                statements.push(extract_checked(child, source, uses_input));
+            }
+            "try_statement" => {
+                statements.push(extract_try(child, source, uses_input));
+            }
+            // a nested block keeps its variables to itself
+            "block" => {
+                statements.push(Statement::Block(extract_block(child, source, uses_input)));
+            }
             _ => {}
         }
     }
@@ -598,14 +1405,506 @@ pub fn match_cs_type(s: &str) -> Type {
     match s {
         "void" => Type::Void,
         "int" => Type::Int,
+        "long" => Type::Long,
+        "short" => Type::Short,
+        "byte" => Type::Byte,
+        "sbyte" => Type::SByte,
+        "uint" => Type::UInt,
+        "ulong" => Type::ULong,
+        "ushort" => Type::UShort,
+        "decimal" => Type::Decimal,
         "bool" => Type::Bool,
         "string" => Type::String,
+        "char" => Type::Char,
         "float" => Type::Float,
         "double" => Type::Double,
         _ => Type::Unknown,
     }
 }
 
+/// Helper function for parsing integer literals, such as 42, 0xFF, 0b1010, 1_000 or 5UL.
+/// The type follows the C# rules: the first of int, uint, long, ulong that fits the value,
+/// restricted by the suffix
+pub fn parse_integer_literal(text: &str) -> Literal {
+    let text = text.replace('_', "").to_ascii_lowercase();
+    // the suffix is made of u and l, in any order
+    let digits = text.trim_end_matches(['u', 'l']);
+    let suffix = &text[digits.len()..];
+
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16)
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        u64::from_str_radix(binary, 2)
+    } else {
+        digits.parse::<u64>()
+    }
+    .expect("Invalid integer literal");
+
+    let unsigned = suffix.contains('u');
+    let long = suffix.contains('l');
+    if !unsigned && !long && value <= i32::MAX as u64 {
+        Literal::Int(value as i32)
+    } else if !long && value <= u32::MAX as u64 {
+        Literal::UInt(value as u32)
+    } else if !unsigned && value <= i64::MAX as u64 {
+        Literal::Long(value as i64)
+    } else {
+        Literal::ULong(value)
+    }
+}
+
+/// Helper function for the decimal literals which only fit their type once negated,
+/// 2147483648 and 9223372036854775808 without a suffix, or with an L for the second
+fn negative_boundary(text: &str) -> Option<Literal> {
+    let text = text.replace('_', "").to_ascii_lowercase();
+    match text.as_str() {
+        "2147483648" => Some(Literal::Int(i32::MIN)),
+        "9223372036854775808" | "9223372036854775808l" => Some(Literal::Long(i64::MIN)),
+        _ => None,
+    }
+}
+
+/// Helper function for parsing real literals, such as 3.14, 2.5f, 1e10 or 1.5m.
+/// Just like in C#, the ones without a suffix are doubles
+pub fn parse_real_literal(text: &str) -> Literal {
+    match text.chars().last() {
+        Some('f' | 'F') => Literal::Float(text.to_string()),
+        // decimals become BigDecimal strings, underscores are not allowed there
+        Some('m' | 'M') => Literal::Decimal(text[..text.len() - 1].replace('_', "")),
+        // the double spelling is valid java, with or without the d suffix
+        _ => Literal::Double(text.to_string()),
+    }
+}
+
+/// This function parses a lambda, x => x * 2 or (a, b) => { return a + b; }.
+/// The anonymous methods, delegate (int x) { ... }, are lambdas with a block
+pub fn extract_lambda(node: Node, source: &str, uses_input: &mut bool) -> Expression {
+    let parameters = match node.child_by_field_name("parameters") {
+        // x => x * 2 has a single parameter, without parentheses
+        Some(parameter) if parameter.kind() == "implicit_parameter" => vec![Variable {
+            typ: Type::Unknown,
+            name: source[parameter.byte_range()].to_string(),
+            implicit: true,
+            constant: false,
+        }],
+        Some(parameters) => match_cs_parameters(parameters, source, uses_input)
+            .into_iter()
+            .map(|parameter| parameter.variable)
+            .collect(),
+        // delegate { ... } takes any parameters
+        None => Vec::new(),
+    };
+
+    let mut cursor = node.walk();
+    let body_node = node
+        .child_by_field_name("body")
+        .or_else(|| node.children(&mut cursor).find(|n| n.kind() == "block"));
+    let body_node = body_node.expect("Expected lambda body");
+    let body = if body_node.kind() == "block" {
+        LambdaBody::Block(extract_block(body_node, source, uses_input))
+    } else {
+        LambdaBody::Expression(Box::new(extract_expression(body_node, source, uses_input)))
+    };
+    Expression::new(ExpressionKind::Lambda { parameters, body })
+}
+
+/// This function parses a query, from x in xs where x > 0 select x * 2, into the
+/// LINQ calls it stands for, xs.Where(x => x > 0).Select(x => x * 2)
+pub fn extract_query(node: Node, source: &str, uses_input: &mut bool) -> Expression {
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node.children(&mut cursor).collect();
+    let (variable, sequence) = extract_range(children[0], source, uses_input);
+    desugar_query(
+        &children[1..],
+        sequence,
+        variable,
+        Vec::new(),
+        source,
+        uses_input,
+    )
+}
+
+/// Helper function that turns the clauses of a query into calls on the sequence.
+/// A let, a join into, or a second from or a join followed by an orderby or a group
+/// pairs the range variable with the new one, like the transparent identifiers of C#:
+/// after let d = n * 2 the query goes on with n_d, where n is n_d.Key and d is n_d.Value.
+/// The other second froms and joins nest the rest of the query in a SelectMany,
+/// where both range variables are visible
+fn desugar_query(
+    clauses: &[Node],
+    mut sequence: Expression,
+    mut variable: String,
+    mut names: Vec<RangeName>,
+    source: &str,
+    uses_input: &mut bool,
+) -> Expression {
+    // the identity select is left out after another clause, like C# does
+    let mut applied = false;
+    let mut index = 0;
+    while index < clauses.len() {
+        let clause = clauses[index];
+        let mut cursor = clause.walk();
+        let parts: Vec<Node> = clause
+            .named_children(&mut cursor)
+            .filter(|part| part.kind() != "join_into_clause")
+            .collect();
+        let mut part = |index: usize| {
+            let mut value = extract_expression(parts[index], source, uses_input);
+            substitute_ranges(&mut value, &names);
+            value
+        };
+        match clause.kind() {
+            "where_clause" => {
+                let condition = part(0);
+                sequence = query_call(sequence, "Where", vec![range_lambda(&variable, condition)]);
+            }
+            // let d = n * 2 is Select(n => new KeyValuePair(n, n * 2))
+            "let_clause" => {
+                let value = part(1);
+                let name = source[parts[0].byte_range()].to_string();
+                sequence = query_call(
+                    sequence,
+                    "Select",
+                    vec![range_lambda(&variable, range_pair(&variable, value))],
+                );
+                variable = transparent(&variable, &mut names, &name);
+            }
+            "order_by_clause" => {
+                // orderby a descending, b is OrderByDescending(a).ThenBy(b)
+                let mut cursor = clause.walk();
+                let mut orderings: Vec<(Expression, bool)> = Vec::new();
+                for child in clause.children(&mut cursor) {
+                    match child.kind() {
+                        "descending" => orderings.last_mut().expect("Expected ordering").1 = true,
+                        "orderby" | "ascending" | "," => {}
+                        _ => {
+                            let mut key = extract_expression(child, source, uses_input);
+                            substitute_ranges(&mut key, &names);
+                            orderings.push((key, false));
+                        }
+                    }
+                }
+                for (position, (key, descending)) in orderings.into_iter().enumerate() {
+                    let method = match (position, descending) {
+                        (0, false) => "OrderBy",
+                        (0, true) => "OrderByDescending",
+                        (_, false) => "ThenBy",
+                        (_, true) => "ThenByDescending",
+                    };
+                    sequence = query_call(sequence, method, vec![range_lambda(&variable, key)]);
+                }
+            }
+            "select_clause" => {
+                let value = part(0);
+                let identity = matches!(&value, Expression { kind: ExpressionKind::Variable(name), .. } if *name == variable);
+                if !(identity && applied) {
+                    sequence = query_call(sequence, "Select", vec![range_lambda(&variable, value)]);
+                }
+            }
+            // group p.Name by p.Age, the element is left out when it is p itself
+            "group_clause" => {
+                let element = part(0);
+                let key = part(1);
+                let mut arguments = vec![range_lambda(&variable, key)];
+                if !matches!(&element, Expression { kind: ExpressionKind::Variable(name), .. } if *name == variable)
+                {
+                    arguments.push(range_lambda(&variable, element));
+                }
+                sequence = query_call(sequence, "GroupBy", arguments);
+            }
+            // select ... into g continues the query on the results
+            "into" => {
+                variable = source[clauses[index + 1].byte_range()].to_string();
+                names.clear();
+                applied = false;
+                index += 2;
+                continue;
+            }
+            "from_clause" | "join_clause" => {
+                let (inner_variable, inner_sequence) = if clause.kind() == "from_clause" {
+                    let (name, mut inner) = extract_range(clause, source, uses_input);
+                    substitute_ranges(&mut inner, &names);
+                    (name, inner)
+                } else {
+                    // join b in ys on a.Id equals b.Id keeps the b with the same key
+                    let name = source[parts[parts.len() - 4].byte_range()].to_string();
+                    let inner = part(parts.len() - 3);
+                    let outer_key = part(parts.len() - 2);
+                    let inner_key = part(parts.len() - 1);
+                    // the keys are compared with Equals, like C# does
+                    let condition = query_call(
+                        Expression::new(ExpressionKind::Variable("Object".to_string())),
+                        "Equals",
+                        vec![outer_key, inner_key],
+                    );
+                    let filter = range_lambda(&name, condition);
+                    (name, query_call(inner, "Where", vec![filter]))
+                };
+
+                // join ... into os pairs every element with the list of the matching ones
+                let mut cursor = clause.walk();
+                if let Some(into) = clause
+                    .named_children(&mut cursor)
+                    .find(|child| child.kind() == "join_into_clause")
+                {
+                    let mut cursor = into.walk();
+                    let name = into
+                        .named_children(&mut cursor)
+                        .find(|child| child.kind() == "identifier")
+                        .map(|child| source[child.byte_range()].to_string())
+                        .expect("join into missing name");
+                    let group = query_call(inner_sequence, "ToList", Vec::new());
+                    sequence = query_call(
+                        sequence,
+                        "Select",
+                        vec![range_lambda(&variable, range_pair(&variable, group))],
+                    );
+                    variable = transparent(&variable, &mut names, &name);
+                    applied = true;
+                    index += 1;
+                    continue;
+                }
+
+                // the rest of the query, up to a continuation, runs for every element
+                let end = clauses[index..]
+                    .iter()
+                    .position(|clause| clause.kind() == "into")
+                    .map_or(clauses.len(), |position| index + position);
+                // the orderings and groups need both variables in one sequence
+                if clauses[index + 1..end]
+                    .iter()
+                    .any(|clause| matches!(clause.kind(), "order_by_clause" | "group_clause"))
+                {
+                    let pairs = query_call(
+                        inner_sequence,
+                        "Select",
+                        vec![range_lambda(
+                            &inner_variable,
+                            range_pair(
+                                &variable,
+                                Expression::new(ExpressionKind::Variable(inner_variable.clone())),
+                            ),
+                        )],
+                    );
+                    sequence =
+                        query_call(sequence, "SelectMany", vec![range_lambda(&variable, pairs)]);
+                    variable = transparent(&variable, &mut names, &inner_variable);
+                    applied = true;
+                    index += 1;
+                    continue;
+                }
+                let inner = desugar_query(
+                    &clauses[index + 1..end],
+                    inner_sequence,
+                    inner_variable,
+                    names.clone(),
+                    source,
+                    uses_input,
+                );
+                sequence = query_call(sequence, "SelectMany", vec![range_lambda(&variable, inner)]);
+                index = end;
+                applied = true;
+                continue;
+            }
+            _ => {}
+        }
+        applied = true;
+        index += 1;
+    }
+    sequence
+}
+
+// a range variable kept in the pairs of a query, n is the n_d.Key of the pair n_d
+#[derive(Clone)]
+struct RangeName {
+    name: String,             // the range variable, n
+    root: String,             // the lambda parameter holding it, n_d
+    steps: Vec<&'static str>, // the members leading to it, Key
+}
+
+/// Helper function that pairs the range variable of a query with a new one, n with d
+/// becomes n_d, and returns the name of the pair
+fn transparent(variable: &str, names: &mut Vec<RangeName>, name: &str) -> String {
+    let pair = format!("{}_{}", variable, name);
+    // a pair already holds its range variables
+    if !names.iter().any(|range| range.root == variable) {
+        names.push(RangeName {
+            name: variable.to_string(),
+            root: variable.to_string(),
+            steps: Vec::new(),
+        });
+    }
+    for range in names.iter_mut().filter(|range| range.root == variable) {
+        range.root = pair.clone();
+        range.steps.insert(0, "Key");
+    }
+    names.push(RangeName {
+        name: name.to_string(),
+        root: pair.clone(),
+        steps: vec!["Value"],
+    });
+    pair
+}
+
+/// Helper function that makes the pair of the range variable and a new value
+fn range_pair(variable: &str, value: Expression) -> Expression {
+    // the type arguments come from the values
+    Expression::new(ExpressionKind::ObjectCreation {
+        typ: Type::Generic("KeyValuePair".to_string(), Vec::new()),
+        arguments: vec![
+            Expression::new(ExpressionKind::Variable(variable.to_string())),
+            value,
+        ],
+    })
+}
+
+/// Helper function for parsing the range variable of a from clause and its sequence
+fn extract_range(node: Node, source: &str, uses_input: &mut bool) -> (String, Expression) {
+    let name_node = node
+        .child_by_field_name("name")
+        .expect("from clause missing name");
+    let mut cursor = node.walk();
+    let sequence_node = node
+        .named_children(&mut cursor)
+        .last()
+        .expect("from clause missing sequence");
+    (
+        source[name_node.byte_range()].to_string(),
+        extract_expression(sequence_node, source, uses_input),
+    )
+}
+
+/// Helper function that calls a LINQ method on a sequence
+fn query_call(sequence: Expression, method: &str, arguments: Vec<Expression>) -> Expression {
+    Expression::new(ExpressionKind::Call {
+        callee: Box::new(Expression::new(ExpressionKind::MemberAccess {
+            target: Box::new(sequence),
+            name: method.to_string(),
+        })),
+        arguments,
+    })
+}
+
+/// Helper function that makes a clause of a query a lambda of its range variable, x => x > 0
+fn range_lambda(variable: &str, body: Expression) -> Expression {
+    Expression::new(ExpressionKind::Lambda {
+        parameters: vec![Variable {
+            typ: Type::Unknown,
+            name: variable.to_string(),
+            implicit: true,
+            constant: false,
+        }],
+        body: LambdaBody::Expression(Box::new(body)),
+    })
+}
+
+/// Helper function that replaces the range variables kept in pairs by their members, n_d.Key
+fn substitute_ranges(expr: &mut Expression, names: &[RangeName]) {
+    if let ExpressionKind::Variable(name) = &mut expr.kind
+        && let Some(range) = names.iter().rev().find(|range| range.name == *name)
+    {
+        *expr = range.steps.iter().fold(
+            Expression::new(ExpressionKind::Variable(range.root.clone())),
+            |target, step| {
+                Expression::new(ExpressionKind::MemberAccess {
+                    target: Box::new(target),
+                    name: step.to_string(),
+                })
+            },
+        );
+        return;
+    }
+    for child in expr.children_mut() {
+        substitute_ranges(child, names);
+    }
+}
+
+/// Helper function for parsing a type node, predefined or user defined
+pub fn extract_type(node: Node, source: &str) -> Type {
+    match node.kind() {
+        "predefined_type" => match_cs_type(&source[node.byte_range()]),
+        // an EventHandler is the Action<object, EventArgs> called with the sender
+        "identifier" | "qualified_name"
+            if matches!(
+                &source[node.byte_range()],
+                "EventHandler" | "System.EventHandler"
+            ) =>
+        {
+            Type::Generic(
+                "Action".to_string(),
+                vec![Type::Unknown, Type::Named("EventArgs".to_string())],
+            )
+        }
+        // class names, such as Person or System.String
+        "identifier" | "qualified_name" => Type::Named(source[node.byte_range()].to_string()),
+        // Func<int, bool>
+        "generic_name" => {
+            let mut cursor = node.walk();
+            let children: Vec<Node> = node.named_children(&mut cursor).collect();
+            let name = source[children[0].byte_range()].to_string();
+            let mut cursor = node.walk();
+            let mut arguments: Vec<Type> = children
+                .iter()
+                .find(|n| n.kind() == "type_argument_list")
+                .map(|list| {
+                    list.named_children(&mut cursor)
+                        .map(|argument| extract_type(argument, source))
+                        .collect()
+                })
+                .unwrap_or_default();
+            // EventHandler<T> is the Action<object, T>
+            if name == "EventHandler" {
+                arguments.insert(0, Type::Unknown);
+                return Type::Generic("Action".to_string(), arguments);
+            }
+            Type::Generic(name, arguments)
+        }
+        // int? or string?
+        "nullable_type" => {
+            let inner = node
+                .child_by_field_name("type")
+                .expect("Expected nullable type");
+            Type::Nullable(Box::new(extract_type(inner, source)))
+        }
+        // int[]
+        "array_type" => {
+            let inner = node
+                .child_by_field_name("type")
+                .expect("Expected array element type");
+            Type::Array(Box::new(extract_type(inner, source)))
+        }
+        _ => Type::Unknown,
+    }
+}
+
+/// Helper function for parsing binary operators
+pub fn match_cs_binary_operator(s: &str) -> Option<BinaryOperator> {
+    let operator = match s {
+        "+" => BinaryOperator::Add,
+        "-" => BinaryOperator::Sub,
+        "*" => BinaryOperator::Mul,
+        "/" => BinaryOperator::Div,
+        "%" => BinaryOperator::Mod,
+        "==" => BinaryOperator::Eq,
+        "!=" => BinaryOperator::Ne,
+        "<" => BinaryOperator::Lt,
+        ">" => BinaryOperator::Gt,
+        "<=" => BinaryOperator::Le,
+        ">=" => BinaryOperator::Ge,
+        "&&" => BinaryOperator::And,
+        "||" => BinaryOperator::Or,
+        "&" => BinaryOperator::BitAnd,
+        "|" => BinaryOperator::BitOr,
+        "^" => BinaryOperator::BitXor,
+        "<<" => BinaryOperator::LeftShift,
+        ">>" => BinaryOperator::RightShift,
+        ">>>" => BinaryOperator::UnsignedRightShift,
+        "??" => BinaryOperator::Coalesce,
+        _ => return None,
+    };
+    Some(operator)
+}
+
 /// Helper function for parsing function modifiers
 pub fn match_cs_modifiers(modifiers: Vec<&str>) -> Vec<Modifier> {
     let mut out: Vec<Modifier> = Vec::new();
@@ -614,6 +1913,10 @@ pub fn match_cs_modifiers(modifiers: Vec<&str>) -> Vec<Modifier> {
             "public" => out.push(Modifier::Public),
             "private" => out.push(Modifier::Private),
             "static" => out.push(Modifier::Static),
+            "protected" => out.push(Modifier::Protected),
+            "const" => out.push(Modifier::Const),
+            "readonly" => out.push(Modifier::Readonly),
+            "event" => out.push(Modifier::Event),
             _ => out.push(Modifier::Unknown),
         }
     }
@@ -621,41 +1924,78 @@ pub fn match_cs_modifiers(modifiers: Vec<&str>) -> Vec<Modifier> {
 }
 
 /// Helper function for parsing variables
-pub fn match_cs_parameters(parameters: Vec<&str>) -> Vec<Variable> {
-    let mut out: Vec<Variable> = Vec::new();
-    for parameter in parameters {
-        let param_raw = parameter.split_once(" ").unwrap();
-        let typ = match_cs_type(param_raw.0);
-        out.push(Variable {
-            typ,
-            name: param_raw.1.to_string(),
-        })
+pub fn match_cs_parameters(node: Node, source: &str, uses_input: &mut bool) -> Vec<Parameter> {
+    let mut out: Vec<Parameter> = Vec::new();
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node.children(&mut cursor).collect();
+    for (i, child) in children.iter().enumerate() {
+        match child.kind() {
+            "parameter" => out.push(extract_parameter(*child, source, uses_input)),
+            // params int[] xs is not wrapped in a parameter node, its type
+            // and name follow the params keyword
+            "params" => {
+                let type_node = children.get(i + 1).expect("Expected params parameter type");
+                let name_node = children.get(i + 2).expect("Expected params parameter name");
+                out.push(Parameter {
+                    variable: Variable {
+                        typ: extract_type(*type_node, source),
+                        name: source[name_node.byte_range()].to_string(),
+                        implicit: false,
+                        constant: false,
+                    },
+                    modifiers: Vec::new(),
+                    default: None,
+                    params: true,
+                });
+            }
+            _ => {}
+        }
     }
     out
 }
 
-/// This is a helper function that extracts function names
-fn extract_function_name(node: Node, source: &str) -> String {
-    match node.kind() {
-        // simple function or type like `int.Parse`
-        "identifier" | "predefined_type" => source[node.byte_range()].to_string(),
-
-        // compound function (Console.WriteLine, int.Parse, etc)
-        "member_access_expression" => {
-            let left = node
-                .child_by_field_name("expression")
-                .expect("member access missing expression");
-            let right = node
-                .child_by_field_name("name")
-                .expect("member access missing name");
-            format!(
-                "{}.{}",
-                extract_function_name(left, source),
-                extract_function_name(right, source)
-            )
+/// This function parses a single parameter, with its modifiers and default value.
+/// The attributes, such as [In], are skipped
+fn extract_parameter(node: Node, source: &str, uses_input: &mut bool) -> Parameter {
+    // the parameters of a lambda can leave out their type, (a, b) => a + b
+    let type_node = node.child_by_field_name("type");
+    let name_node = node
+        .child_by_field_name("name")
+        .expect("Expected parameter name");
+
+    let mut modifiers = Vec::new();
+    let mut default = None;
+    let mut cursor = node.walk();
+    let mut after_equals = false;
+    for child in node.children(&mut cursor) {
+        match child.kind() {
+            "modifier" => modifiers.push(match &source[child.byte_range()] {
+                "ref" => ParameterModifier::Ref,
+                "out" => ParameterModifier::Out,
+                "in" => ParameterModifier::In,
+                "this" => ParameterModifier::This,
+                _ => ParameterModifier::Unknown,
+            }),
+            "=" => after_equals = true,
+            // the default value comes right after the =
+            _ if after_equals && child.is_named() => {
+                default = Some(extract_expression(child, source, uses_input));
+                after_equals = false;
+            }
+            _ => {}
         }
+    }
 
-        _ => panic!("Unsupported function node: {}", node.kind()),
+    Parameter {
+        variable: Variable {
+            typ: type_node.map_or(Type::Unknown, |type_node| extract_type(type_node, source)),
+            name: source[name_node.byte_range()].to_string(),
+            implicit: type_node.is_none(),
+            constant: false,
+        },
+        modifiers,
+        default,
+        params: false,
     }
 }
 
@@ -682,7 +2022,7 @@ pub fn find_everything(node: Node, source: &str, indent: usize) {
         " ".repeat(indent),
         node.kind(),
         node.field_name_for_child(0),
-        source[node.byte_range()].to_string()
+        &source[node.byte_range()]
     );
     for child in node.children(&mut cursor) {
         find_everything(child, source, indent + 1);
@@ -0,0 +1,274 @@
+use crate::ast::*;
+
+/// A .NET collection and the java one standing in for it
+pub struct Collection {
+    pub name: &'static str,                            // the C# name, List
+    pub interface: &'static str, // the java type of the variables, java.util.List
+    pub class: &'static str,     // the java class it is created with, java.util.ArrayList
+    pub indexer: Option<(&'static str, &'static str)>, // the java methods reading and writing xs[i]
+    pub members: &'static [Member],
+}
+
+/// A member of a collection and the java method it becomes
+pub struct Member {
+    pub name: &'static str, // the C# member, Count
+    pub java: &'static str, // the java method, size
+    pub property: bool,     // xs.Count is read without parentheses, xs.size() in java
+    pub boxed: bool, // the argument is boxed, list.remove(5) would take 5 as an index in java
+    pub result: MemberType, // the C# type it gives
+}
+
+/// The C# type of a member, given the type arguments of its collection
+#[derive(Clone, Copy)]
+pub enum MemberType {
+    Void,
+    Bool,
+    Int,
+    Argument(usize), // a type argument, the T of Stack<T>.Pop()
+    Sequence(usize), // a sequence of a type argument, the keys of a dictionary
+}
+
+const fn property(name: &'static str, java: &'static str, result: MemberType) -> Member {
+    Member {
+        name,
+        java,
+        property: true,
+        boxed: false,
+        result,
+    }
+}
+
+const fn method(name: &'static str, java: &'static str, result: MemberType) -> Member {
+    Member {
+        name,
+        java,
+        property: false,
+        boxed: false,
+        result,
+    }
+}
+
+const fn boxed(name: &'static str, java: &'static str, result: MemberType) -> Member {
+    Member {
+        name,
+        java,
+        property: false,
+        boxed: true,
+        result,
+    }
+}
+
+const LIST: &[Member] = &[
+    property("Count", "size", MemberType::Int),
+    method("Add", "add", MemberType::Void),
+    method("AddRange", "addAll", MemberType::Void),
+    method("Insert", "add", MemberType::Void),
+    boxed("Remove", "remove", MemberType::Bool),
+    method("RemoveAt", "remove", MemberType::Void),
+    method("Contains", "contains", MemberType::Bool),
+    method("IndexOf", "indexOf", MemberType::Int),
+    method("Clear", "clear", MemberType::Void),
+];
+
+const DICTIONARY: &[Member] = &[
+    property("Count", "size", MemberType::Int),
+    property("Keys", "keySet", MemberType::Sequence(0)),
+    property("Values", "values", MemberType::Sequence(1)),
+    // merge takes the function called for a key that is already there, which throws
+    method("Add", "merge", MemberType::Void),
+    method("Remove", "remove", MemberType::Bool),
+    method("ContainsKey", "containsKey", MemberType::Bool),
+    method("ContainsValue", "containsValue", MemberType::Bool),
+    method("Clear", "clear", MemberType::Void),
+];
+
+const SET: &[Member] = &[
+    property("Count", "size", MemberType::Int),
+    method("Add", "add", MemberType::Bool),
+    method("Remove", "remove", MemberType::Bool),
+    method("Contains", "contains", MemberType::Bool),
+    method("UnionWith", "addAll", MemberType::Void),
+    method("IntersectWith", "retainAll", MemberType::Void),
+    method("ExceptWith", "removeAll", MemberType::Void),
+    method("Clear", "clear", MemberType::Void),
+];
+
+// the java methods that throw on an empty queue, like the C# ones
+const QUEUE: &[Member] = &[
+    property("Count", "size", MemberType::Int),
+    method("Enqueue", "add", MemberType::Void),
+    method("Dequeue", "remove", MemberType::Argument(0)),
+    method("Peek", "element", MemberType::Argument(0)),
+    method("Contains", "contains", MemberType::Bool),
+    method("Clear", "clear", MemberType::Void),
+];
+
+// a deque pushes to its front, element() is the top
+const STACK: &[Member] = &[
+    property("Count", "size", MemberType::Int),
+    method("Push", "push", MemberType::Void),
+    method("Pop", "pop", MemberType::Argument(0)),
+    method("Peek", "element", MemberType::Argument(0)),
+    method("Contains", "contains", MemberType::Bool),
+    method("Clear", "clear", MemberType::Void),
+];
+
+// a pair of a key and a value, the java entries have no setters either
+const PAIR: &[Member] = &[
+    property("Key", "getKey", MemberType::Argument(0)),
+    property("Value", "getValue", MemberType::Argument(1)),
+];
+
+// the dictionaries and sets keep the order of insertion, like the C# ones do in practice
+const COLLECTIONS: &[Collection] = &[
+    Collection {
+        name: "List",
+        interface: "java.util.List",
+        class: "java.util.ArrayList",
+        indexer: Some(("get", "set")),
+        members: LIST,
+    },
+    Collection {
+        name: "IList",
+        interface: "java.util.List",
+        class: "java.util.ArrayList",
+        indexer: Some(("get", "set")),
+        members: LIST,
+    },
+    Collection {
+        name: "Dictionary",
+        interface: "java.util.Map",
+        class: "java.util.LinkedHashMap",
+        indexer: Some(("get", "put")),
+        members: DICTIONARY,
+    },
+    Collection {
+        name: "IDictionary",
+        interface: "java.util.Map",
+        class: "java.util.LinkedHashMap",
+        indexer: Some(("get", "put")),
+        members: DICTIONARY,
+    },
+    Collection {
+        name: "SortedDictionary",
+        interface: "java.util.Map",
+        class: "java.util.TreeMap",
+        indexer: Some(("get", "put")),
+        members: DICTIONARY,
+    },
+    Collection {
+        name: "HashSet",
+        interface: "java.util.Set",
+        class: "java.util.LinkedHashSet",
+        indexer: None,
+        members: SET,
+    },
+    Collection {
+        name: "ISet",
+        interface: "java.util.Set",
+        class: "java.util.LinkedHashSet",
+        indexer: None,
+        members: SET,
+    },
+    Collection {
+        name: "SortedSet",
+        interface: "java.util.Set",
+        class: "java.util.TreeSet",
+        indexer: None,
+        members: SET,
+    },
+    Collection {
+        name: "Queue",
+        interface: "java.util.Queue",
+        class: "java.util.ArrayDeque",
+        indexer: None,
+        members: QUEUE,
+    },
+    Collection {
+        name: "Stack",
+        interface: "java.util.Deque",
+        class: "java.util.ArrayDeque",
+        indexer: None,
+        members: STACK,
+    },
+    Collection {
+        name: "KeyValuePair",
+        interface: "java.util.Map.Entry",
+        class: "java.util.AbstractMap.SimpleImmutableEntry",
+        indexer: None,
+        members: PAIR,
+    },
+];
+
+/// Finds the collection of a C# generic type name, such as List
+pub fn collection(name: &str) -> Option<&'static Collection> {
+    COLLECTIONS
+        .iter()
+        .find(|collection| collection.name == name)
+}
+
+/// Finds the collection of a type and its type arguments, the Dictionary
+/// and [string, int] of Dictionary<string, int>
+pub fn collection_type(typ: &Type) -> Option<(&'static Collection, &[Type])> {
+    match typ {
+        Type::Generic(name, arguments) => Some((collection(name)?, arguments.as_slice())),
+        _ => None,
+    }
+}
+
+/// Finds a member of a collection, a property or a method
+pub fn collection_member(
+    collection: &Collection,
+    name: &str,
+    property: bool,
+) -> Option<&'static Member> {
+    collection
+        .members
+        .iter()
+        .find(|member| member.name == name && member.property == property)
+}
+
+/// Finds the C# type of the java method a member became, the generator types the
+/// lowered program, where list.Contains(x) prints True as list.contains(x).
+/// The reads of the indexer give an element, ages.get(name)
+pub fn collection_java_type(
+    collection: &Collection,
+    java: &str,
+    arguments: &[Type],
+) -> Option<Type> {
+    if collection.indexer.is_some_and(|(get, _)| get == java) || java == "computeIfAbsent" {
+        return arguments.last().cloned();
+    }
+    let member = collection
+        .members
+        .iter()
+        .find(|member| member.java == java)?;
+    member_type(member.result, arguments)
+}
+
+/// Computes the C# type of a member of a collection from its type arguments
+pub fn member_type(result: MemberType, arguments: &[Type]) -> Option<Type> {
+    match result {
+        MemberType::Void => Some(Type::Void),
+        MemberType::Bool => Some(Type::Bool),
+        MemberType::Int => Some(Type::Int),
+        MemberType::Argument(index) => arguments.get(index).cloned(),
+        MemberType::Sequence(index) => Some(Type::Generic(
+            "ICollection".to_string(),
+            vec![arguments.get(index)?.clone()],
+        )),
+    }
+}
+
+/// Finds the java package of a collection class, java.util.ArrayList for ArrayList
+pub fn collection_import(class: &str) -> Option<&'static str> {
+    COLLECTIONS
+        .iter()
+        .flat_map(|collection| [collection.interface, collection.class])
+        .find(|path| path.rsplit('.').next() == Some(class))
+}
+
+/// Helper function that gives the simple name of a java class, ArrayList for java.util.ArrayList
+pub fn simple_name(path: &str) -> &str {
+    path.rsplit('.').next().unwrap_or(path)
+}
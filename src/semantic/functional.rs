@@ -0,0 +1,58 @@
+use crate::ast::*;
+
+/// A delegate type seen as a function, Func<int, bool> takes an int and returns a bool
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionType {
+    pub parameters: Vec<Type>,
+    pub return_type: Type,
+}
+
+/// Obtains the function a delegate type stands for, one of Func, Action and
+/// Predicate or a declared delegate. None for the other types
+pub fn function_type(typ: &Type, delegates: &[Delegate]) -> Option<FunctionType> {
+    let function = |parameters: &[Type], return_type: Type| FunctionType {
+        parameters: parameters.to_vec(),
+        return_type,
+    };
+    match typ {
+        // the return type of a Func comes last, Func<int, bool>
+        Type::Generic(name, arguments) => match name.as_str() {
+            "Func" => {
+                let (return_type, parameters) = arguments.split_last()?;
+                Some(function(parameters, return_type.clone()))
+            }
+            "Action" => Some(function(arguments, Type::Void)),
+            "Predicate" => Some(function(arguments, Type::Bool)),
+            _ => None,
+        },
+        Type::Named(name) if name == "Action" => Some(function(&[], Type::Void)),
+        Type::Named(name) => delegates
+            .iter()
+            .find(|delegate| delegate.name == *name)
+            .map(|delegate| {
+                let parameters: Vec<Type> = delegate
+                    .parameters
+                    .iter()
+                    .map(|parameter| parameter.variable.typ.clone())
+                    .collect();
+                function(&parameters, delegate.return_type.clone())
+            }),
+        _ => None,
+    }
+}
+
+/// Obtains the method of the java interface standing in for a delegate type,
+/// apply for a Function, run for a Runnable. The declared delegates get invoke
+pub fn functional_method(typ: &Type) -> &'static str {
+    match typ {
+        Type::Generic(name, arguments) => match (name.as_str(), arguments.len()) {
+            ("Func", 1) => "get",
+            ("Func", _) => "apply",
+            ("Action", _) => "accept",
+            ("Predicate", _) => "test",
+            _ => "invoke",
+        },
+        Type::Named(name) if name == "Action" => "run",
+        _ => "invoke",
+    }
+}
@@ -0,0 +1,215 @@
+use crate::ast::*;
+use crate::semantic::functional::function_type;
+use crate::semantic::scopes::{Scopes, class_fields, class_methods};
+
+/// Infers the types of the var locals from their values, the scopes
+/// act as the symbol table, so a later var can use an earlier one.
+/// The out var of a call takes the type of its parameter, and so do the
+/// parameters of a lambda, x in Func<int, int> f = x => x * 2 is an int
+pub fn infer_types(program: &mut Program) {
+    let mut inference = TypeInference::default();
+    inference.scopes.delegates = program.delegates.clone();
+    inference.scopes.fields = class_fields(program);
+    for class in &mut program.classes {
+        inference.scopes.methods = class_methods(class);
+        inference.scopes.class = class.name.clone();
+
+        // the fields are visible in every method
+        inference.scopes.push();
+        inference.scopes.return_type = None;
+        for field in &mut class.fields {
+            // an initializer is inferred just like the value of a local
+            let mut declaration = Statement::VariableDeclaration {
+                variable: field.variable.clone(),
+                value: field.value.take(),
+            };
+            inference.infer_statement(&mut declaration);
+            if let Statement::VariableDeclaration { value, .. } = declaration {
+                field.value = value;
+            }
+        }
+        for method in &mut class.methods {
+            inference.scopes.return_type = Some(method.return_type.clone());
+            inference.scopes.push();
+            for parameter in &method.parameters {
+                inference
+                    .scopes
+                    .declare(&parameter.variable.name, parameter.variable.typ.clone());
+            }
+            inference.infer_block(&mut method.body);
+            inference.scopes.pop();
+        }
+        inference.scopes.pop();
+    }
+}
+
+#[derive(Default)]
+struct TypeInference {
+    scopes: Scopes,
+}
+
+impl TypeInference {
+    fn infer_block(&mut self, statements: &mut [Statement]) {
+        self.scopes.push();
+        for statement in statements {
+            self.infer_statement(statement);
+        }
+        self.scopes.pop();
+    }
+
+    fn infer_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::VariableDeclaration { variable, value } => {
+                // var x = 5 takes the type of its value, an int
+                if let Some(value) = value {
+                    if variable.implicit
+                        && let Some(typ) = self.scopes.expression_type(value)
+                    {
+                        variable.typ = typ;
+                    }
+                    self.type_lambda(value, &variable.typ);
+                }
+                let constant = self.scopes.declared_constant(variable, value.as_ref());
+                if let Some(value) = value {
+                    self.infer_expression(value);
+                }
+                // the variable is visible after its declaration
+                self.scopes.declare(&variable.name, variable.typ.clone());
+                if let Some(constant) = constant {
+                    self.scopes.declare_constant(&variable.name, constant);
+                }
+            }
+            Statement::Assignment {
+                target,
+                operator,
+                value,
+            } => {
+                if operator.is_none()
+                    && let Some(typ) = self.scopes.expression_type(target)
+                {
+                    self.type_lambda(value, &typ);
+                }
+                self.infer_expression(target);
+                self.infer_expression(value);
+            }
+            Statement::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                self.infer_expression(condition);
+                self.infer_block(then_body);
+                self.infer_block(else_body);
+            }
+            Statement::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                // the initializer's variable is only visible inside the loop
+                self.scopes.push();
+                if let Some(initializer) = initializer {
+                    self.infer_statement(initializer);
+                }
+                if let Some(condition) = condition {
+                    self.infer_expression(condition);
+                }
+                if let Some(increment) = increment {
+                    self.infer_statement(increment);
+                }
+                self.infer_block(body);
+                self.scopes.pop();
+            }
+            Statement::While { condition, body } => {
+                self.infer_expression(condition);
+                self.infer_block(body);
+            }
+            Statement::Checked { body, .. } | Statement::Block(body) => self.infer_block(body),
+            Statement::TryCatch {
+                body,
+                exception,
+                handler,
+            } => {
+                self.infer_block(body);
+                self.scopes.push();
+                self.scopes.declare(&exception.name, exception.typ.clone());
+                self.infer_block(handler);
+                self.scopes.pop();
+            }
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    if let Some(typ) = self.scopes.return_type.clone() {
+                        self.type_lambda(value, &typ);
+                    }
+                    self.infer_expression(value);
+                }
+            }
+            Statement::Expression(expr) | Statement::Throw(expr) => self.infer_expression(expr),
+        }
+    }
+
+    fn infer_expression(&mut self, expr: &mut Expression) {
+        match &mut expr.kind {
+            // Apply(x => x * 2, 5) takes the parameter type of Apply, the callee
+            // goes first, the lambdas of xs.Select(...).Where(...) need the Select typed
+            ExpressionKind::Call { callee, arguments } => {
+                self.infer_expression(callee);
+                for (index, argument) in arguments.iter_mut().enumerate() {
+                    if let Some(typ) = self.scopes.parameter_type(callee, index) {
+                        self.type_lambda(argument, &typ);
+                    }
+                    self.infer_expression(argument);
+                }
+                // int.TryParse(s, out var n) declares the int n
+                for (index, argument) in arguments.iter_mut().enumerate() {
+                    if let ExpressionKind::RefArgument { value, .. } = &mut argument.kind
+                        && let ExpressionKind::DeclarationExpression(variable) = &mut value.kind
+                    {
+                        if variable.implicit
+                            && let Some(typ) = self.scopes.parameter_type(callee, index)
+                        {
+                            variable.typ = typ;
+                        }
+                        self.scopes.declare(&variable.name, variable.typ.clone());
+                    }
+                }
+            }
+            // the parameters are only visible in the lambda
+            ExpressionKind::Lambda { parameters, body } => {
+                self.scopes.push();
+                for parameter in parameters.iter() {
+                    self.scopes.declare(&parameter.name, parameter.typ.clone());
+                }
+                // the returns of a block are those of the lambda, not of the method
+                let return_type = self.scopes.return_type.take();
+                match body {
+                    LambdaBody::Expression(body) => self.infer_expression(body),
+                    LambdaBody::Block(statements) => self.infer_block(statements),
+                }
+                self.scopes.return_type = return_type;
+                self.scopes.pop();
+            }
+            _ => {
+                for child in expr.children_mut() {
+                    self.infer_expression(child);
+                }
+            }
+        }
+    }
+
+    /// Gives the parameters of a lambda the types of the delegate it becomes
+    fn type_lambda(&self, value: &mut Expression, typ: &Type) {
+        let ExpressionKind::Lambda { parameters, .. } = &mut value.kind else {
+            return;
+        };
+        let Some(function) = function_type(typ, &self.scopes.delegates) else {
+            return;
+        };
+        for (parameter, typ) in parameters.iter_mut().zip(function.parameters) {
+            if parameter.typ == Type::Unknown {
+                parameter.typ = typ;
+            }
+        }
+    }
+}
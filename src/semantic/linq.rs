@@ -0,0 +1,132 @@
+use crate::ast::*;
+use crate::semantic::collections::collection;
+
+/// Obtains the type of the elements of a sequence, the int of List<int>,
+/// IEnumerable<int> or int[]. None for the types LINQ does not work on
+pub fn element_type(typ: &Type) -> Option<Type> {
+    match typ {
+        Type::Array(element) => Some((**element).clone()),
+        Type::Generic(name, arguments) => match (name.as_str(), arguments.as_slice()) {
+            (
+                "IEnumerable"
+                | "ICollection"
+                | "IReadOnlyCollection"
+                | "IReadOnlyList"
+                | "IOrderedEnumerable",
+                [element],
+            ) => Some(element.clone()),
+            // the lists, sets, queues and stacks
+            (name, [element]) if collection(name).is_some() => Some(element.clone()),
+            // a group is the sequence of its elements
+            ("IGrouping", [_, element]) => Some(element.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Checks if a LINQ method is deferred, xs.Where(...) only runs once it is used
+pub fn is_deferred(method: &str) -> bool {
+    matches!(
+        method,
+        "Where"
+            | "Select"
+            | "SelectMany"
+            | "OrderBy"
+            | "OrderByDescending"
+            | "ThenBy"
+            | "ThenByDescending"
+            | "GroupBy"
+            | "Distinct"
+    )
+}
+
+/// Checks if a method is one of the LINQ methods we translate
+pub fn is_linq(method: &str) -> bool {
+    is_deferred(method)
+        || matches!(
+            method,
+            "Any"
+                | "All"
+                | "First"
+                | "FirstOrDefault"
+                | "Sum"
+                | "Average"
+                | "Max"
+                | "Min"
+                | "Count"
+                | "ToList"
+                | "ToDictionary"
+        )
+}
+
+/// Obtains the delegate type a LINQ method takes, Func<int, bool> for the
+/// predicate of Where on a sequence of ints. The results of the other
+/// lambdas are not known, they are Unknown
+pub fn linq_parameter_type(method: &str, element: &Type, index: usize) -> Option<Type> {
+    let function = |result: Type| Type::Generic("Func".to_string(), vec![element.clone(), result]);
+    match (method, index) {
+        ("Where" | "Any" | "All" | "First" | "FirstOrDefault" | "Count", 0) => {
+            Some(function(Type::Bool))
+        }
+        (
+            "Select" | "SelectMany" | "OrderBy" | "OrderByDescending" | "ThenBy"
+            | "ThenByDescending" | "GroupBy" | "Sum" | "Average" | "Max" | "Min",
+            0,
+        )
+        | ("ToDictionary", 0)
+        | ("ToDictionary" | "GroupBy", 1) => Some(function(Type::Unknown)),
+        _ => None,
+    }
+}
+
+/// Computes the type returned by a LINQ method, given the type of the elements
+/// and the results of its lambdas, x.Name in Select(x => x.Name)
+pub fn linq_return_type(method: &str, element: &Type, results: &[Option<Type>]) -> Option<Type> {
+    let sequence = |element: Type| Type::Generic("IEnumerable".to_string(), vec![element]);
+    let result = |index: usize| results.get(index).cloned().flatten();
+    match method {
+        "Where" | "OrderBy" | "OrderByDescending" | "ThenBy" | "ThenByDescending" | "Distinct" => {
+            Some(sequence(element.clone()))
+        }
+        "Select" => Some(sequence(result(0).unwrap_or(Type::Unknown))),
+        // the lambda gives a sequence, its elements are flattened
+        "SelectMany" => Some(sequence(
+            result(0)
+                .as_ref()
+                .and_then(element_type)
+                .unwrap_or(Type::Unknown),
+        )),
+        "GroupBy" => Some(sequence(Type::Generic(
+            "IGrouping".to_string(),
+            vec![
+                result(0).unwrap_or(Type::Unknown),
+                result(1).unwrap_or(element.clone()),
+            ],
+        ))),
+        "Any" | "All" => Some(Type::Bool),
+        "First" | "FirstOrDefault" => Some(element.clone()),
+        "Count" => Some(Type::Int),
+        // Sum() adds the elements, Sum(x => x.Price) the prices
+        "Sum" if results.is_empty() => Some(element.clone()),
+        "Sum" => result(0),
+        "Max" | "Min" if results.is_empty() => Some(element.clone()),
+        "Max" | "Min" => result(0),
+        // the average of integers is a double
+        "Average" => match results.first().cloned().unwrap_or(Some(element.clone()))? {
+            Type::Float => Some(Type::Float),
+            Type::Decimal => Some(Type::Decimal),
+            Type::Unknown => None,
+            _ => Some(Type::Double),
+        },
+        "ToList" => Some(Type::Generic("List".to_string(), vec![element.clone()])),
+        "ToDictionary" => Some(Type::Generic(
+            "Dictionary".to_string(),
+            vec![
+                result(0).unwrap_or(Type::Unknown),
+                result(1).unwrap_or(element.clone()),
+            ],
+        )),
+        _ => None,
+    }
+}
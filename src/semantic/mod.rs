@@ -0,0 +1,26 @@
+use crate::ast::*;
+
+pub mod collections;
+pub mod functional;
+mod infer;
+pub mod linq;
+pub mod overloads;
+mod resolve;
+pub mod scopes;
+pub mod strings;
+pub mod symbols;
+pub mod typecheck;
+
+pub use symbols::SymbolTable;
+pub use typecheck::TypeTable;
+
+/// Runs the semantic analysis on the parsed program, before it is lowered,
+/// so the lowering passes see every type
+pub fn analyze_program(program: &mut Program) {
+    infer::infer_types(program);
+    resolve::resolve_names(program);
+    // the type table is computed again by every lowering pass and by the generator,
+    // the lowering changes the expressions
+    let (_, diagnostics) = typecheck::check_types(program);
+    program.diagnostics.extend(diagnostics);
+}
@@ -0,0 +1,340 @@
+use crate::ast::*;
+
+use super::typecheck::{cs_type_name, is_convertible};
+
+/// A method as seen by its callers, the parameters and the return type
+#[derive(Debug, Clone)]
+pub struct Signature {
+    pub name: String,
+    pub parameters: Vec<Parameter>,
+    pub return_type: Type,
+    pub is_static: bool,
+}
+
+impl Signature {
+    pub fn of(method: &Method) -> Self {
+        Signature {
+            name: method.name.clone(),
+            parameters: method.parameters.clone(),
+            return_type: method.return_type.clone(),
+            is_static: method
+                .modifiers
+                .iter()
+                .any(|modifier| matches!(modifier, Modifier::Static)),
+        }
+    }
+
+    /// The parameter types, in order
+    pub fn types(&self) -> Vec<Type> {
+        self.parameters
+            .iter()
+            .map(|parameter| parameter.variable.typ.clone())
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Signature {
+    // F(int, long), the way C# writes it
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parameters: Vec<String> = self
+            .parameters
+            .iter()
+            .map(|parameter| {
+                let typ = cs_type_name(&parameter.variable.typ);
+                if parameter.params {
+                    format!("params {}", typ)
+                } else {
+                    typ
+                }
+            })
+            .collect();
+        write!(f, "{}({})", self.name, parameters.join(", "))
+    }
+}
+
+/// The outcome of an overload resolution, the indexes are those of the candidates
+#[derive(Debug, PartialEq)]
+pub enum Overload {
+    Resolved(usize),
+    Ambiguous(Vec<usize>),
+    Inapplicable,
+}
+
+/// How the arguments of a call go to the parameters of a candidate
+#[derive(Debug)]
+pub struct Binding {
+    pub positions: Vec<usize>, // the parameter each argument goes to
+    pub types: Vec<Type>,      // the type each argument is converted to
+    pub expanded: bool,        // the params arguments are passed one by one
+    pub defaults: bool,        // some optional parameters are left out
+}
+
+/// Binds the arguments of a call to the parameters of a candidate, the positional ones
+/// in order and the named ones by name. In the expanded form the arguments left
+/// go to the params array, one element each. None if the call does not fit
+pub fn bind(signature: &Signature, arguments: &[Expression], expanded: bool) -> Option<Binding> {
+    let parameters = &signature.parameters;
+    if expanded && !parameters.last().is_some_and(|parameter| parameter.params) {
+        return None;
+    }
+    let last = parameters.len().checked_sub(1);
+    let mut binding = Binding {
+        positions: Vec::new(),
+        types: Vec::new(),
+        expanded,
+        defaults: false,
+    };
+    for (j, argument) in arguments.iter().enumerate() {
+        let position = match &argument.kind {
+            ExpressionKind::NamedArgument { name, .. } => parameters
+                .iter()
+                .position(|parameter| parameter.variable.name == *name)?,
+            _ if expanded => j.min(last?),
+            _ => j,
+        };
+        let parameter = parameters.get(position)?;
+        let is_element = expanded && Some(position) == last;
+        if binding.positions.contains(&position) && !is_element {
+            return None;
+        }
+        let typ = match &parameter.variable.typ {
+            Type::Array(element) if is_element => (**element).clone(),
+            typ => typ.clone(),
+        };
+        binding.positions.push(position);
+        binding.types.push(typ);
+    }
+    // the parameters left out need a default value, the params array can stay empty
+    for (i, parameter) in parameters.iter().enumerate() {
+        if binding.positions.contains(&i) || (expanded && parameter.params) {
+            continue;
+        }
+        parameter.default.as_ref()?;
+        binding.defaults = true;
+    }
+    Some(binding)
+}
+
+/// Picks the overload C# calls for the given arguments: the applicable candidate
+/// whose parameters are better conversions of the arguments than those of every other.
+/// The expanded form of params is only tried when the normal one does not fit
+pub fn resolve_overload(
+    candidates: &[Signature],
+    arguments: &[Expression],
+    types: &[Type],
+) -> Overload {
+    let applies = |binding: &Binding| {
+        (0..arguments.len())
+            .all(|j| cs_implicit(argument_value(&arguments[j]), &types[j], &binding.types[j]))
+    };
+    let bindings: Vec<Option<Binding>> = candidates
+        .iter()
+        .map(|candidate| {
+            bind(candidate, arguments, false)
+                .filter(applies)
+                .or_else(|| bind(candidate, arguments, true).filter(applies))
+        })
+        .collect();
+    let applicable: Vec<usize> = (0..candidates.len())
+        .filter(|&i| bindings[i].is_some())
+        .collect();
+
+    // f(a) is better than g(a) if no argument converts better to g
+    // and at least one converts better to f. Otherwise the normal form wins
+    // over the expanded one, and the call without default values wins
+    let is_better = |a: usize, b: usize| {
+        let (Some(a), Some(b)) = (&bindings[a], &bindings[b]) else {
+            return false;
+        };
+        let converts_better = |first: &Binding, second: &Binding| {
+            (0..arguments.len()).any(|j| {
+                let argument = argument_value(&arguments[j]);
+                better_conversion(argument, &types[j], &first.types[j], &second.types[j])
+            })
+        };
+        match (converts_better(a, b), converts_better(b, a)) {
+            (true, false) => true,
+            (false, false) if a.types == b.types => {
+                (!a.expanded && b.expanded) || (!a.defaults && b.defaults)
+            }
+            _ => false,
+        }
+    };
+    pick(&applicable, is_better)
+}
+
+/// Picks the overload java would call for the same arguments, once they are translated.
+/// Java tries the candidates without boxing first, then with boxing, then the varargs
+/// methods, and takes the most specific one. The unsigned types are the signed ones
+/// of the same size there. The optional parameters are overloads in java
+pub fn java_overload(
+    candidates: &[Signature],
+    arguments: &[Expression],
+    types: &[Type],
+) -> Overload {
+    let types: Vec<Type> = types.iter().map(java_like).collect();
+
+    for (boxing, expanded) in [(false, false), (true, false), (true, true)] {
+        let bindings: Vec<Option<Vec<Type>>> = candidates
+            .iter()
+            .map(|candidate| {
+                let binding = bind(candidate, arguments, expanded)?;
+                let parameters: Vec<Type> = binding.types.iter().map(java_like).collect();
+                (0..arguments.len())
+                    .all(|j| java_invocable(&arguments[j], &types[j], &parameters[j], boxing))
+                    .then_some(parameters)
+            })
+            .collect();
+        let applicable: Vec<usize> = (0..candidates.len())
+            .filter(|&i| bindings[i].is_some())
+            .collect();
+        if applicable.is_empty() {
+            continue;
+        }
+        // f is more specific than g if every parameter of f widens to the one of g
+        let is_more_specific = |a: usize, b: usize| match (&bindings[a], &bindings[b]) {
+            (Some(a), Some(b)) => a != b && (0..arguments.len()).all(|j| java_widens(&a[j], &b[j])),
+            _ => false,
+        };
+        return pick(&applicable, is_more_specific);
+    }
+    Overload::Inapplicable
+}
+
+/// Tells whether two overloads become the same method in java, f(int) and f(uint) do
+pub fn same_java_signature(first: &Signature, second: &Signature) -> bool {
+    let java_parameters =
+        |signature: &Signature| signature.types().iter().map(java_like).collect::<Vec<_>>();
+    first.name == second.name && java_parameters(first) == java_parameters(second)
+}
+
+/// Helper function that obtains the passed value of an argument, the 5 of x: 5
+pub fn argument_value(argument: &Expression) -> &Expression {
+    match &argument.kind {
+        ExpressionKind::NamedArgument { value, .. } => value,
+        _ => argument,
+    }
+}
+
+/// Helper function that keeps the candidate better than all the other applicable ones
+fn pick(applicable: &[usize], is_better: impl Fn(usize, usize) -> bool) -> Overload {
+    let best: Vec<usize> = applicable
+        .iter()
+        .copied()
+        .filter(|&a| applicable.iter().all(|&b| a == b || is_better(a, b)))
+        .collect();
+    match (best.as_slice(), applicable) {
+        ([best], _) => Overload::Resolved(*best),
+        (_, []) => Overload::Inapplicable,
+        _ => Overload::Ambiguous(applicable.to_vec()),
+    }
+}
+
+/// Helper function that tells whether an argument converts implicitly to a parameter in C#.
+/// The unknown type is object, which takes anything, null goes to any reference type
+fn cs_implicit(argument: &Expression, from: &Type, to: &Type) -> bool {
+    if is_null(argument) {
+        return is_reference(to);
+    }
+    *to == Type::Unknown || is_convertible(argument, from, to)
+}
+
+/// Helper function that tells whether converting an argument to the first type
+/// is better than converting it to the second, following the C# rules
+fn better_conversion(argument: &Expression, from: &Type, first: &Type, second: &Type) -> bool {
+    if first == second {
+        return false;
+    }
+    if !is_null(argument) {
+        // the exact match wins
+        if from == first {
+            return true;
+        }
+        if from == second {
+            return false;
+        }
+    }
+    // then the more specific type, int is better than long as long takes every int
+    let implicit = |from: &Type, to: &Type| {
+        *to == Type::Unknown || is_convertible(&Expression::new(ExpressionKind::This), from, to)
+    };
+    if implicit(first, second) && !implicit(second, first) {
+        return true;
+    }
+    // and the signed types win over the unsigned ones
+    use Type::*;
+    matches!(
+        (first, second),
+        (SByte, Byte | UShort | UInt | ULong)
+            | (Short, UShort | UInt | ULong)
+            | (Int, UInt | ULong)
+            | (Long, ULong)
+    )
+}
+
+/// Helper function that tells whether an argument of a java type can be passed
+/// to a parameter, with or without boxing
+fn java_invocable(argument: &Expression, from: &Type, to: &Type, boxing: bool) -> bool {
+    if is_null(argument) {
+        return is_reference(to);
+    }
+    if java_widens(from, to) {
+        return true;
+    }
+    if !boxing {
+        return false;
+    }
+    match (from, to) {
+        // Integer takes an int, and Object takes anything
+        (_, Type::Unknown) => true,
+        (Type::Nullable(inner), _) => java_widens(inner, to),
+        (_, Type::Nullable(inner)) => from == &**inner,
+        _ => false,
+    }
+}
+
+/// Helper function for the java widening conversions, a long takes an int,
+/// Object takes any reference
+fn java_widens(from: &Type, to: &Type) -> bool {
+    use Type::*;
+    if from == to || (*to == Unknown && is_reference(from)) {
+        return true;
+    }
+    let wider: &[Type] = match from {
+        SByte => &[Short, Int, Long, Float, Double],
+        Short | Char => &[Int, Long, Float, Double],
+        Int => &[Long, Float, Double],
+        Long => &[Float, Double],
+        Float => &[Double],
+        _ => &[],
+    };
+    wider.contains(to)
+}
+
+/// Helper function that obtains the java counterpart of a C# type, as a C# type.
+/// The unsigned types keep their size, int? is the Integer wrapper
+fn java_like(typ: &Type) -> Type {
+    match typ {
+        Type::UInt => Type::Int,
+        Type::ULong => Type::Long,
+        Type::UShort => Type::Short,
+        Type::Byte => Type::SByte,
+        Type::Decimal => Type::Named("BigDecimal".to_string()),
+        Type::Nullable(inner) => Type::Nullable(Box::new(java_like(inner))),
+        Type::Array(element) => Type::Array(Box::new(java_like(element))),
+        _ => typ.clone(),
+    }
+}
+
+/// Helper function that tells whether a type can hold a null
+fn is_reference(typ: &Type) -> bool {
+    matches!(
+        typ,
+        Type::String | Type::Named(_) | Type::Nullable(_) | Type::Array(_) | Type::Unknown
+    )
+}
+
+/// Helper function that checks for the null literal
+fn is_null(expr: &Expression) -> bool {
+    matches!(&expr.kind, ExpressionKind::Literal(Literal::Null))
+}
@@ -0,0 +1,296 @@
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+use crate::parser_cs::match_cs_type;
+
+use super::symbols::{Reference, Resolution, ScopeKind, SymbolKind, SymbolTable};
+
+/// Links every name of the program to its declaration, class by class, method by
+/// method and block by block. Undefined names and locals hiding other locals are reported
+pub fn resolve_names(program: &mut Program) {
+    let mut resolver = Resolver::default();
+    let root = resolver.push(ScopeKind::Program);
+
+    // the classes can be used before their declaration
+    for class in &program.classes {
+        resolver.table.declare(
+            root,
+            &class.name,
+            SymbolKind::Class,
+            Type::Named(class.name.clone()),
+        );
+    }
+    for class in &mut program.classes {
+        resolver.resolve_class(class);
+    }
+
+    program.symbols = resolver.table;
+    program.diagnostics.extend(resolver.diagnostics);
+}
+
+#[derive(Default)]
+struct Resolver {
+    table: SymbolTable,
+    diagnostics: Vec<Diagnostic>,
+    current: usize, // the innermost scope
+    method: String, // the method being resolved, for the messages
+}
+
+impl Resolver {
+    /// Opens a scope inside the current one
+    fn push(&mut self, kind: ScopeKind) -> usize {
+        let parent = (!self.table.scopes.is_empty()).then_some(self.current);
+        self.current = self.table.add_scope(kind, parent);
+        self.current
+    }
+
+    /// Goes back to the parent scope
+    fn pop(&mut self) {
+        if let Some(parent) = self.table.scopes[self.current].parent {
+            self.current = parent;
+        }
+    }
+
+    fn resolve_class(&mut self, class: &mut Class) {
+        self.push(ScopeKind::Class(class.name.clone()));
+        // fields and methods are visible in the whole class
+        for field in &class.fields {
+            self.table.declare(
+                self.current,
+                &field.variable.name,
+                SymbolKind::Field,
+                field.variable.typ.clone(),
+            );
+        }
+        for method in &class.methods {
+            self.table.declare(
+                self.current,
+                &method.name,
+                SymbolKind::Method,
+                method.return_type.clone(),
+            );
+        }
+
+        self.method = class.name.clone();
+        for field in &mut class.fields {
+            if let Some(value) = &mut field.value {
+                self.resolve_expression(value);
+            }
+        }
+        for method in &mut class.methods {
+            self.method = method.name.clone();
+            self.push(ScopeKind::Method(method.name.clone()));
+            for parameter in &mut method.parameters {
+                // the default values are constants, they cannot use the other parameters
+                if let Some(default) = &mut parameter.default {
+                    self.resolve_expression(default);
+                }
+                self.declare(&parameter.variable, SymbolKind::Parameter);
+            }
+            self.resolve_block(&mut method.body);
+            self.pop();
+        }
+        self.pop();
+    }
+
+    fn resolve_block(&mut self, statements: &mut [Statement]) {
+        self.push(ScopeKind::Block);
+        for statement in statements {
+            self.resolve_statement(statement);
+        }
+        self.pop();
+    }
+
+    fn resolve_statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::VariableDeclaration { variable, value } => {
+                // int x = x + 1 cannot use itself
+                if let Some(value) = value {
+                    self.resolve_expression(value);
+                }
+                self.declare(variable, SymbolKind::Local);
+            }
+            Statement::Assignment { target, value, .. } => {
+                self.resolve_expression(target);
+                self.resolve_expression(value);
+            }
+            Statement::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                self.resolve_expression(condition);
+                self.resolve_block(then_body);
+                self.resolve_block(else_body);
+            }
+            Statement::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                // the initializer's variable is only visible inside the loop
+                self.push(ScopeKind::Block);
+                if let Some(initializer) = initializer {
+                    self.resolve_statement(initializer);
+                }
+                if let Some(condition) = condition {
+                    self.resolve_expression(condition);
+                }
+                if let Some(increment) = increment {
+                    self.resolve_statement(increment);
+                }
+                self.resolve_block(body);
+                self.pop();
+            }
+            Statement::While { condition, body } => {
+                self.resolve_expression(condition);
+                self.resolve_block(body);
+            }
+            Statement::Checked { body, .. } | Statement::Block(body) => self.resolve_block(body),
+            Statement::TryCatch {
+                body,
+                exception,
+                handler,
+            } => {
+                self.resolve_block(body);
+                // the exception is only visible in the handler
+                self.push(ScopeKind::Block);
+                // catch (OverflowException) has no variable
+                if !exception.name.is_empty() {
+                    self.declare(exception, SymbolKind::Local);
+                }
+                self.resolve_block(handler);
+                self.pop();
+            }
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    self.resolve_expression(value);
+                }
+            }
+            Statement::Expression(expr) | Statement::Throw(expr) => self.resolve_expression(expr),
+        }
+    }
+
+    fn resolve_expression(&mut self, expr: &mut Expression) {
+        match &mut expr.kind {
+            ExpressionKind::Variable(_) => self.resolve_name(expr, false),
+            // out _ discards the value, unless a variable is called _
+            ExpressionKind::RefArgument {
+                modifier: ParameterModifier::Out,
+                value,
+            } if matches!(&value.kind, ExpressionKind::Variable(name) if name == "_")
+                && self.table.lookup(self.current, "_").is_none() => {}
+            // out var _ and out int _ discard it too
+            ExpressionKind::DeclarationExpression(variable) if variable.name == "_" => {}
+            // out var n declares n in the enclosing block
+            ExpressionKind::DeclarationExpression(variable) => {
+                let variable = variable.clone();
+                self.declare(&variable, SymbolKind::Local);
+            }
+            // the parameters of a lambda cannot hide the locals around it either
+            ExpressionKind::Lambda { parameters, body } => {
+                self.push(ScopeKind::Lambda);
+                for parameter in parameters.iter() {
+                    self.declare(parameter, SymbolKind::Parameter);
+                }
+                match body {
+                    LambdaBody::Expression(body) => self.resolve_expression(body),
+                    LambdaBody::Block(statements) => self.resolve_block(statements),
+                }
+                self.pop();
+            }
+            // Compute(5) has to be a method of the class
+            ExpressionKind::Call { callee, arguments } => {
+                if let ExpressionKind::Variable(_) = &callee.kind {
+                    self.resolve_name(callee, true);
+                } else {
+                    self.resolve_expression(callee);
+                }
+                for argument in arguments {
+                    self.resolve_expression(argument);
+                }
+            }
+            _ => {
+                for child in expr.children_mut() {
+                    self.resolve_expression(child);
+                }
+            }
+        }
+    }
+
+    /// Declares a parameter or a local. C# does not let a local hide another
+    /// local or parameter of the method, and neither does java
+    fn declare(&mut self, variable: &Variable, kind: SymbolKind) {
+        if let Some(id) = self.table.lookup(self.current, &variable.name)
+            && matches!(
+                self.table.symbols[id].kind,
+                SymbolKind::Local | SymbolKind::Parameter
+            )
+        {
+            self.diagnostics.push(Diagnostic::error(format!(
+                "`{}` is already declared in an enclosing scope of {}",
+                variable.name, self.method
+            )));
+        } else if self.declared_inside(&variable.name) {
+            // a local is visible in its whole block, also before its declaration
+            self.diagnostics.push(Diagnostic::error(format!(
+                "`{}` cannot be declared in {}, a nested scope before it already declares it",
+                variable.name, self.method
+            )));
+        }
+        self.table
+            .declare(self.current, &variable.name, kind, variable.typ.clone());
+    }
+
+    /// Checks whether a scope nested in the current one declares a local or parameter
+    fn declared_inside(&self, name: &str) -> bool {
+        let scopes = &self.table.scopes;
+        (self.current + 1..scopes.len()).any(|id| {
+            let mut parent = scopes[id].parent;
+            while let Some(scope) = parent
+                && scope > self.current
+            {
+                parent = scopes[scope].parent;
+            }
+            parent == Some(self.current)
+                && scopes[id].symbols.get(name).is_some_and(|symbol| {
+                    matches!(
+                        self.table.symbols[*symbol].kind,
+                        SymbolKind::Local | SymbolKind::Parameter
+                    )
+                })
+        })
+    }
+
+    /// Links a used name to its declaration
+    fn resolve_name(&mut self, expr: &Expression, is_call: bool) {
+        let ExpressionKind::Variable(name) = &expr.kind else {
+            return;
+        };
+        let resolution = match self.table.lookup(self.current, name) {
+            Some(id) => Resolution::Symbol(id),
+            // capitalized names are taken as library classes, such as Console or Math,
+            // so are the predefined types, such as int in int.Parse
+            None if !is_call && is_library_name(name) => Resolution::Library,
+            None => {
+                let what = if is_call { "method" } else { "name" };
+                self.diagnostics.push(Diagnostic::error(format!(
+                    "undefined {} `{}` in {}",
+                    what, name, self.method
+                )));
+                Resolution::Undefined
+            }
+        };
+        let reference = Reference {
+            name: name.to_string(),
+            scope: self.current,
+            resolution,
+        };
+        self.table.add_reference(expr, reference);
+    }
+}
+
+/// Helper function that tells whether a name that is not declared belongs to the library
+fn is_library_name(name: &str) -> bool {
+    match_cs_type(name) != Type::Unknown || name.starts_with(|c: char| c.is_ascii_uppercase())
+}
@@ -0,0 +1,730 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ast::*,
+    parser_cs::match_cs_type,
+    semantic::{
+        collections::{collection_java_type, collection_member, collection_type, member_type},
+        functional::{function_type, functional_method},
+        linq::{element_type, is_linq, linq_parameter_type, linq_return_type},
+        overloads::{Overload, Signature, resolve_overload},
+        strings::{string_method_type, string_static_type},
+        symbols::{Resolution, Symbol, SymbolKind, SymbolTable},
+        typecheck::TypeTable,
+    },
+};
+
+/// The variable types visible at some point of a method, one map per block
+#[derive(Default, Clone)]
+pub struct Scopes {
+    scopes: Vec<HashMap<String, Type>>,
+    constants: Vec<HashMap<String, i64>>, // the values of the integer constants, by block
+    pub return_type: Option<Type>,        // return type of the method being walked
+    pub methods: HashMap<String, Vec<Signature>>, // the methods of the class, by name
+    pub method: String,                   // the method being walked, for the messages
+    pub class: String,                    // the class being walked
+    pub delegates: Vec<Delegate>,         // the delegate types of the program
+    pub fields: HashMap<String, HashMap<String, Type>>, // the field types of every class
+    pub names: HashSet<String>, // every name declared in the method, the created variables avoid them
+    pub symbols: SymbolTable,   // what the names of the C# code refer to, from the name resolution
+    pub types: TypeTable,       // the types of the checked program, for the lowering
+}
+
+impl Scopes {
+    /// Opens a new block
+    pub fn push(&mut self) {
+        self.scopes.push(HashMap::new());
+        self.constants.push(HashMap::new());
+    }
+
+    /// Closes the innermost block, forgetting its variables
+    pub fn pop(&mut self) {
+        self.scopes.pop();
+        self.constants.pop();
+    }
+
+    /// Declares a variable in the innermost block
+    pub fn declare(&mut self, name: &str, typ: Type) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), typ);
+        }
+    }
+
+    /// Computes the value of a variable declared with const, const int K = 5,
+    /// only the integer ones are kept
+    pub fn declared_constant(
+        &self,
+        variable: &Variable,
+        value: Option<&Expression>,
+    ) -> Option<i64> {
+        value
+            .filter(|_| variable.constant && is_integral(&variable.typ))
+            .and_then(|value| self.constant_value(value))
+    }
+
+    /// Remembers the value of a constant of the innermost block, once it is declared
+    pub fn declare_constant(&mut self, name: &str, value: i64) {
+        if let Some(scope) = self.constants.last_mut() {
+            scope.insert(name.to_string(), value);
+        }
+    }
+
+    /// Computes the value of an integer constant expression, such as -1 or K * 2
+    /// with a const K, None if it is not a constant
+    pub fn constant_value(&self, expr: &Expression) -> Option<i64> {
+        match &expr.kind {
+            ExpressionKind::Literal(Literal::Int(n)) => Some(*n as i64),
+            ExpressionKind::Literal(Literal::Long(n)) => Some(*n),
+            ExpressionKind::Literal(Literal::UInt(n)) => Some(*n as i64),
+            ExpressionKind::Literal(Literal::ULong(n)) => i64::try_from(*n).ok(),
+            // the innermost variable of that name, if it is a constant
+            ExpressionKind::Variable(name) => {
+                let depth = self
+                    .scopes
+                    .iter()
+                    .rposition(|scope| scope.contains_key(name))?;
+                self.constants.get(depth)?.get(name).copied()
+            }
+            ExpressionKind::ParenthesizedExpression(inner)
+            | ExpressionKind::CheckedExpression { value: inner, .. } => self.constant_value(inner),
+            ExpressionKind::PrefixUnaryExpression { operator, right } => {
+                let value = self.constant_value(right)?;
+                match operator {
+                    UnaryOperator::Neg => value.checked_neg(),
+                    UnaryOperator::Plus => Some(value),
+                    _ => None,
+                }
+            }
+            // (byte)K is a constant when the value fits
+            ExpressionKind::CastExpression { typ, value } if is_integral(typ) => {
+                let value = self.constant_value(value)?;
+                fits(value, typ).then_some(value)
+            }
+            ExpressionKind::BinaryExpression {
+                left,
+                operator,
+                right,
+            } => {
+                let (left, right) = (self.constant_value(left)?, self.constant_value(right)?);
+                match operator {
+                    BinaryOperator::Add => left.checked_add(right),
+                    BinaryOperator::Sub => left.checked_sub(right),
+                    BinaryOperator::Mul => left.checked_mul(right),
+                    BinaryOperator::Div => left.checked_div(right),
+                    BinaryOperator::Mod => left.checked_rem(right),
+                    BinaryOperator::BitAnd => Some(left & right),
+                    BinaryOperator::BitOr => Some(left | right),
+                    BinaryOperator::BitXor => Some(left ^ right),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Finds the declaration a name of the C# code refers to. None for the names
+    /// the lowering created, moved or replaced, the passes then go by the scopes
+    pub fn declaration(&self, expr: &Expression) -> Option<&Symbol> {
+        self.symbols.declaration(self.symbols.reference(expr)?)
+    }
+
+    /// Checks if a name is a class, of the program or of the library, the Math of Math.Abs
+    pub fn is_class(&self, expr: &Expression) -> bool {
+        match self
+            .symbols
+            .reference(expr)
+            .map(|reference| &reference.resolution)
+        {
+            Some(Resolution::Library) => true,
+            Some(Resolution::Symbol(id)) => self.symbols.symbols[*id].kind == SymbolKind::Class,
+            // the locals and fields are in the scopes
+            _ => {
+                matches!(&expr.kind, ExpressionKind::Variable(name) if self.lookup(name).is_none())
+            }
+        }
+    }
+
+    /// Checks whether an expression is a string, a string? is one too
+    pub fn is_string(&self, expr: &Expression) -> bool {
+        self.type_of(expr).as_ref().map(underlying_type) == Some(&Type::String)
+    }
+
+    /// Returns the type of an expression from the table of the checked program,
+    /// an expression created after the check has its type computed
+    pub fn type_of(&self, expr: &Expression) -> Option<Type> {
+        match self.types.get(expr) {
+            Some(typ) => Some(typ.clone()),
+            None => self.expression_type(expr),
+        }
+    }
+
+    /// Finds the type of a variable, starting from the innermost block
+    pub fn lookup(&self, name: &str) -> Option<&Type> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    /// Computes the C# type of an expression, None if it cannot be known
+    pub fn expression_type(&self, expr: &Expression) -> Option<Type> {
+        match &expr.kind {
+            ExpressionKind::Literal(lit) => Some(literal_type(lit)),
+            ExpressionKind::Variable(name) => self.lookup(name).cloned(),
+            ExpressionKind::ParenthesizedExpression(inner)
+            | ExpressionKind::CheckedExpression { value: inner, .. }
+            | ExpressionKind::NamedArgument { value: inner, .. }
+            | ExpressionKind::RefArgument { value: inner, .. } => self.expression_type(inner),
+            ExpressionKind::DeclarationExpression(variable) => Some(variable.typ.clone()),
+            ExpressionKind::CastExpression { typ, .. }
+            | ExpressionKind::AsExpression { typ, .. } => Some(typ.clone()),
+            ExpressionKind::IsExpression { .. } => Some(Type::Bool),
+            ExpressionKind::InterpolatedString(_) => Some(Type::String),
+            ExpressionKind::ConditionalExpression {
+                consequence,
+                alternative,
+                ..
+            } => {
+                // x == null ? null : x.Length is an int?, like x?.Length
+                let null = |expr: &Expression| {
+                    matches!(&expr.kind, ExpressionKind::Literal(Literal::Null))
+                };
+                if null(consequence) || null(alternative) {
+                    let value = if null(consequence) {
+                        alternative
+                    } else {
+                        consequence
+                    };
+                    return self.expression_type(value).map(|typ| match typ {
+                        Type::String
+                        | Type::Named(_)
+                        | Type::Generic(..)
+                        | Type::Array(_)
+                        | Type::Unknown => typ,
+                        typ => lift(typ, true),
+                    });
+                }
+                let consequence = self.expression_type(consequence);
+                let alternative = self.expression_type(alternative);
+                match (consequence, alternative) {
+                    // x > 3 ? 1 : 2.5 is a double, both sides take the wider type
+                    (Some(left), Some(right)) if left != right => {
+                        promote(&left, &right).or(Some(left))
+                    }
+                    (left, right) => left.or(right),
+                }
+            }
+            ExpressionKind::PrefixUnaryExpression { operator, right } => {
+                let typ = self.expression_type(right)?;
+                let nullable = matches!(typ, Type::Nullable(_));
+                let typ = underlying_type(&typ).clone();
+                let result = match operator {
+                    UnaryOperator::Not => Some(Type::Bool),
+                    UnaryOperator::UAdd | UnaryOperator::USub => Some(typ),
+                    // negating an uint gives a long
+                    UnaryOperator::Neg if typ == Type::UInt => Some(Type::Long),
+                    // ~x keeps an uint, only the small types become int
+                    _ => promote(&typ, &typ),
+                };
+                // lifted operators, -x is an int? for an int? x
+                result.map(|typ| lift(typ, nullable))
+            }
+            ExpressionKind::PostfixUnaryExpression { left, .. } => self.expression_type(left),
+            ExpressionKind::BinaryExpression {
+                left,
+                operator,
+                right,
+            } => {
+                let (left_type, right_type) = self.operand_types(left, right);
+                match operator {
+                    BinaryOperator::Eq
+                    | BinaryOperator::Ne
+                    | BinaryOperator::Lt
+                    | BinaryOperator::Gt
+                    | BinaryOperator::Le
+                    | BinaryOperator::Ge
+                    | BinaryOperator::And
+                    | BinaryOperator::Or => Some(Type::Bool),
+                    // the type of a shift is the type of its left side
+                    BinaryOperator::LeftShift
+                    | BinaryOperator::RightShift
+                    | BinaryOperator::UnsignedRightShift => {
+                        let left_type = left_type?;
+                        promote(&left_type, &left_type)
+                    }
+                    // int? ?? int is an int, the value is no longer null
+                    BinaryOperator::Coalesce => match (left_type?, right_type) {
+                        (Type::Nullable(inner), Some(right_type))
+                            if !matches!(right_type, Type::Nullable(_)) && *inner != right_type =>
+                        {
+                            promote(&inner, &right_type).or(Some(*inner))
+                        }
+                        (Type::Nullable(inner), Some(right_type)) if *inner == right_type => {
+                            Some(*inner)
+                        }
+                        (left_type, _) => Some(left_type),
+                    },
+                    // adding anything to a string is a concatenation
+                    BinaryOperator::Add
+                        if left_type == Some(Type::String) || right_type == Some(Type::String) =>
+                    {
+                        Some(Type::String)
+                    }
+                    // logical operators on booleans
+                    BinaryOperator::BitAnd | BinaryOperator::BitOr | BinaryOperator::BitXor
+                        if left_type == Some(Type::Bool) =>
+                    {
+                        Some(Type::Bool)
+                    }
+                    _ => {
+                        let (left_type, right_type) = (left_type?, right_type?);
+                        let nullable = matches!(left_type, Type::Nullable(_))
+                            || matches!(right_type, Type::Nullable(_));
+                        let typ =
+                            promote(underlying_type(&left_type), underlying_type(&right_type))?;
+                        // lifted operators, int? + int is an int?
+                        Some(lift(typ, nullable))
+                    }
+                }
+            }
+            // the limits of the numbers, int.MaxValue
+            ExpressionKind::MemberAccess { target, name }
+                if let Some(typ) = constant_type(target, name) =>
+            {
+                Some(typ)
+            }
+            // the members of a nullable value, x.Value and x.HasValue
+            ExpressionKind::MemberAccess { target, name } => {
+                match (self.expression_type(target)?, name.as_str()) {
+                    (Type::Nullable(inner), "Length") if *inner == Type::String => Some(Type::Int),
+                    (Type::Nullable(inner), "Value") => Some(*inner),
+                    (Type::Nullable(_), "HasValue") => Some(Type::Bool),
+                    (Type::Array(_) | Type::String, "Length") => Some(Type::Int),
+                    // the value kept in a holder, x.value
+                    (Type::Holder(inner), "value") => Some(*inner),
+                    // the fields of our classes, p.Name
+                    (Type::Named(class), name) => self.fields.get(&class)?.get(name).cloned(),
+                    // the properties of the collections, xs.Count
+                    (typ, name) => {
+                        let (collection, arguments) = collection_type(&typ)?;
+                        let member = collection_member(collection, name, true)?;
+                        member_type(member.result, arguments)
+                    }
+                }
+            }
+            ExpressionKind::Call { callee, arguments } => self.call_type(callee, arguments),
+            // xs[i] gives an element, ages["Ann"] the value of a key
+            ExpressionKind::ElementAccess { target, .. } => match self.expression_type(target)? {
+                Type::Array(element) => Some(*element),
+                _ if self.is_string(target) => Some(Type::Char),
+                typ => {
+                    let (collection, arguments) = collection_type(&typ)?;
+                    collection.indexer?;
+                    arguments.last().cloned()
+                }
+            },
+            // the pairs of a query take the types of their values
+            ExpressionKind::ObjectCreation {
+                typ: Type::Generic(name, types),
+                arguments,
+            } if name == "KeyValuePair" && types.is_empty() => Some(Type::Generic(
+                name.clone(),
+                arguments
+                    .iter()
+                    .map(|argument| self.expression_type(argument).unwrap_or(Type::Unknown))
+                    .collect(),
+            )),
+            ExpressionKind::ObjectCreation { typ, .. } => Some(typ.clone()),
+            ExpressionKind::CollectionInitializer { creation, .. } => {
+                self.expression_type(creation)
+            }
+            ExpressionKind::Assignment { target, .. } => self.expression_type(target),
+            _ => None,
+        }
+    }
+
+    /// Computes the return type of the library calls we know about
+    fn call_type(&self, callee: &Expression, arguments: &[Expression]) -> Option<Type> {
+        // f(x) and f.Invoke(x) on a delegate, f.apply(x) once lowered
+        let delegate = match &callee.kind {
+            ExpressionKind::MemberAccess { target, name } => self
+                .expression_type(target)
+                .filter(|typ| name == "Invoke" || name == functional_method(typ)),
+            _ => self.expression_type(callee),
+        };
+        if let Some(function) = delegate.and_then(|typ| function_type(&typ, &self.delegates)) {
+            return Some(function.return_type);
+        }
+        // the LINQ methods of a sequence, xs.Where(x => x > 0)
+        if let ExpressionKind::MemberAccess { target, name } = &callee.kind
+            && is_linq(name)
+            && let Some(element) = self.expression_type(target).as_ref().and_then(element_type)
+        {
+            let results: Vec<Option<Type>> = arguments
+                .iter()
+                .map(|argument| self.lambda_type(argument, &element))
+                .collect();
+            return linq_return_type(name, &element, &results);
+        }
+        // the methods of the collections, stack.Pop()
+        if let ExpressionKind::MemberAccess { target, name } = &callee.kind
+            && let Some(typ) = self.expression_type(target)
+            && let Some((collection, type_arguments)) = collection_type(&typ)
+            && let Some(member) = collection_member(collection, name, false)
+        {
+            return member_type(member.result, type_arguments);
+        }
+        // and the java methods they became, list.contains(x)
+        if let ExpressionKind::MemberAccess { target, name } = &callee.kind
+            && let Some(typ) = self.expression_type(target)
+            && let Some((collection, type_arguments)) = collection_type(&typ)
+            && let Some(typ) = collection_java_type(collection, name, type_arguments)
+        {
+            return Some(typ);
+        }
+        // the methods of a string, s.Substring(1)
+        if let ExpressionKind::MemberAccess { target, name } = &callee.kind
+            && self.is_string(target)
+            && let Some(typ) = string_method_type(name)
+        {
+            return Some(typ);
+        }
+        // x.ToString() is always a string
+        if let ExpressionKind::MemberAccess { name, .. } = &callee.kind
+            && name == "ToString"
+        {
+            return Some(Type::String);
+        }
+        // a.Equals(b) and a.CompareTo(b), on any object
+        if let ExpressionKind::MemberAccess { name, .. } = &callee.kind {
+            match name.as_str() {
+                "Equals" | "TryGetValue" => return Some(Type::Bool),
+                "CompareTo" => return Some(Type::Int),
+                _ => {}
+            }
+        }
+        // x.GetValueOrDefault() is never null
+        if let ExpressionKind::MemberAccess { target, name } = &callee.kind
+            && name == "GetValueOrDefault"
+            && let Some(Type::Nullable(inner)) = self.expression_type(target)
+        {
+            return Some(*inner);
+        }
+
+        let argument_type = |index: usize| self.expression_type(arguments.get(index)?);
+        match callee.path()?.as_str() {
+            "Console.ReadLine" => Some(Type::String),
+            "int.Parse" => Some(Type::Int),
+            "long.Parse" => Some(Type::Long),
+            "double.Parse" => Some(Type::Double),
+            "float.Parse" => Some(Type::Float),
+            "decimal.Parse" => Some(Type::Decimal),
+            "bool.Parse" => Some(Type::Bool),
+            path if try_parse_type(path).is_some() => Some(Type::Bool),
+            "string.Compare"
+            | "String.Compare"
+            | "string.CompareOrdinal"
+            | "String.CompareOrdinal" => Some(Type::Int),
+            // a == b on strings, once lowered
+            "string.Equals" | "String.Equals" | "Objects.equals" => Some(Type::Bool),
+            path if string_static_type(path).is_some() => string_static_type(path),
+            "Math.Abs" | "Math.Clamp" | "decimal.Round" => argument_type(0),
+            "Math.Pow" | "Math.Sqrt" => Some(Type::Double),
+            "Math.Sign" => Some(Type::Int),
+            "Convert.ToInt32" => Some(Type::Int),
+            "Convert.ToInt64" => Some(Type::Long),
+            "Convert.ToInt16" => Some(Type::Short),
+            "Convert.ToUInt16" => Some(Type::UShort),
+            "Convert.ToByte" => Some(Type::Byte),
+            "Convert.ToSByte" => Some(Type::SByte),
+            "Convert.ToUInt32" => Some(Type::UInt),
+            "Convert.ToUInt64" => Some(Type::ULong),
+            "Convert.ToDouble" => Some(Type::Double),
+            "Convert.ToBoolean" => Some(Type::Bool),
+            "Convert.ToString" => Some(Type::String),
+            "char.ToUpper" | "char.ToLower" => Some(Type::Char),
+            "double.IsNaN" | "double.IsInfinity" => Some(Type::Bool),
+            path if path.starts_with("char.Is") => Some(Type::Bool),
+            "Math.Max" | "Math.Min" => promote(&argument_type(0)?, &argument_type(1)?),
+            // the checked arithmetic, once lowered
+            "Math.addExact" | "Math.subtractExact" | "Math.multiplyExact" => {
+                promote(&argument_type(0)?, &argument_type(1)?)
+            }
+            "Math.negateExact" | "Math.incrementExact" | "Math.decrementExact" => argument_type(0),
+            "Math.toIntExact" => Some(Type::Int),
+            "Integer.toUnsignedLong" => Some(Type::Long),
+            // only the decimal overloads keep the type, the rest work on doubles
+            "Math.Round" | "Math.Floor" | "Math.Ceiling" | "Math.Truncate" => {
+                match argument_type(0)? {
+                    Type::Decimal => Some(Type::Decimal),
+                    _ => Some(Type::Double),
+                }
+            }
+            // the methods of the class, Compute(5) or this.Compute(5)
+            _ => self
+                .called_method(callee, arguments)
+                .map(|method| method.return_type.clone()),
+        }
+    }
+
+    /// Finds the method of the class a call runs, Compute(5) or this.Compute(5),
+    /// the overload C# picks for an overloaded one
+    pub fn called_method(
+        &self,
+        callee: &Expression,
+        arguments: &[Expression],
+    ) -> Option<&Signature> {
+        let path = callee.path()?;
+        let name = path.strip_prefix("this.").unwrap_or(&path);
+        let candidates = self.methods.get(name)?;
+        if let [method] = candidates.as_slice() {
+            return Some(method);
+        }
+        let types: Vec<Type> = arguments
+            .iter()
+            .map(|argument| self.expression_type(argument))
+            .collect::<Option<_>>()?;
+        match resolve_overload(candidates, arguments, &types) {
+            Overload::Resolved(index) => Some(&candidates[index]),
+            _ => None,
+        }
+    }
+
+    /// Finds the type of the parameter a call passes an argument to, the int of
+    /// int.TryParse(s, out n), or a parameter of a method of the class
+    pub fn parameter_type(&self, callee: &Expression, index: usize) -> Option<Type> {
+        // the lambdas of xs.Where(x => x > 0) take the elements of xs
+        if let ExpressionKind::MemberAccess { target, name } = &callee.kind
+            && is_linq(name)
+            && let Some(element) = self.expression_type(target).as_ref().and_then(element_type)
+        {
+            return linq_parameter_type(name, &element, index);
+        }
+        // the out value of ages.TryGetValue(name, out var age)
+        if let ExpressionKind::MemberAccess { target, name } = &callee.kind
+            && name == "TryGetValue"
+            && let Some(Type::Generic(_, arguments)) = self.expression_type(target)
+            && let [_, value] = arguments.as_slice()
+        {
+            return (index == 1).then(|| value.clone());
+        }
+        let path = callee.path()?;
+        if let Some(typ) = try_parse_type(&path) {
+            return (index == 1).then_some(typ);
+        }
+        let name = path.strip_prefix("this.").unwrap_or(&path);
+        let candidates = self.methods.get(name)?;
+        let parameter = candidates.first()?.parameters.get(index)?;
+        Some(parameter.variable.typ.clone())
+    }
+
+    /// Computes the type of the value of a lambda whose parameters have the given type,
+    /// the int of x => x * 2 for an int x. None for a block or a method group
+    pub fn lambda_type(&self, lambda: &Expression, parameter: &Type) -> Option<Type> {
+        let ExpressionKind::Lambda {
+            parameters,
+            body: LambdaBody::Expression(body),
+        } = &lambda.kind
+        else {
+            return None;
+        };
+        let mut scopes = self.clone();
+        scopes.push();
+        for variable in parameters {
+            scopes.declare(&variable.name, parameter.clone());
+        }
+        scopes.expression_type(body)
+    }
+
+    /// Computes the types of the two sides of a binary expression. Just like in C#,
+    /// a non negative int constant takes the type of an unsigned other side
+    pub fn operand_types(
+        &self,
+        left: &Expression,
+        right: &Expression,
+    ) -> (Option<Type>, Option<Type>) {
+        let left_type = self.expression_type(left);
+        let right_type = self.expression_type(right);
+        let is_constant = |expr: &Expression| matches!(&expr.kind, ExpressionKind::Literal(Literal::Int(n)) if *n >= 0);
+        let is_unsigned = |typ: &Option<Type>| matches!(typ, Some(Type::UInt | Type::ULong));
+
+        if is_constant(left) && is_unsigned(&right_type) {
+            (right_type.clone(), right_type)
+        } else if is_constant(right) && is_unsigned(&left_type) {
+            (left_type.clone(), left_type)
+        } else {
+            (left_type, right_type)
+        }
+    }
+}
+
+/// Helper function that obtains the C# type of a literal
+pub fn literal_type(lit: &Literal) -> Type {
+    match lit {
+        Literal::Int(_) => Type::Int,
+        Literal::Long(_) => Type::Long,
+        Literal::UInt(_) => Type::UInt,
+        Literal::ULong(_) => Type::ULong,
+        Literal::Bool(_) => Type::Bool,
+        Literal::String(_) => Type::String,
+        Literal::Utf8String(_) => Type::Unknown,
+        Literal::Char(_) => Type::Char,
+        Literal::Float(_) => Type::Float,
+        Literal::Double(_) => Type::Double,
+        Literal::Decimal(_) => Type::Decimal,
+        // null fits any nullable type
+        Literal::Null => Type::Unknown,
+    }
+}
+
+/// Helper function that obtains the type of a constant of a number type,
+/// int for int.MaxValue and double for double.NaN
+fn constant_type(target: &Expression, name: &str) -> Option<Type> {
+    let ExpressionKind::Variable(typ) = &target.kind else {
+        return None;
+    };
+    match (match_cs_type(typ), name) {
+        (Type::Unknown | Type::Void | Type::String | Type::Bool, _) => None,
+        (typ, "MaxValue" | "MinValue") => Some(typ),
+        (
+            typ @ (Type::Double | Type::Float),
+            "Epsilon" | "NaN" | "PositiveInfinity" | "NegativeInfinity",
+        ) => Some(typ),
+        _ => None,
+    }
+}
+
+/// Helper function that obtains the type a TryParse reads, int for int.TryParse
+pub fn try_parse_type(path: &str) -> Option<Type> {
+    match path {
+        "int.TryParse" => Some(Type::Int),
+        "long.TryParse" => Some(Type::Long),
+        "double.TryParse" => Some(Type::Double),
+        "decimal.TryParse" => Some(Type::Decimal),
+        _ => None,
+    }
+}
+
+/// Helper function that obtains the value type of a nullable type, int for int?
+pub fn underlying_type(typ: &Type) -> &Type {
+    match typ {
+        Type::Nullable(inner) => inner,
+        _ => typ,
+    }
+}
+
+/// Helper function that tells whether a type is one of the integer types
+pub fn is_integral(typ: &Type) -> bool {
+    matches!(
+        typ,
+        Type::Int
+            | Type::Long
+            | Type::Short
+            | Type::Byte
+            | Type::SByte
+            | Type::UInt
+            | Type::ULong
+            | Type::UShort
+    )
+}
+
+/// Helper function that tells whether an integer constant fits an integer type
+pub fn fits(value: i64, typ: &Type) -> bool {
+    match typ {
+        Type::SByte => i8::try_from(value).is_ok(),
+        Type::Byte => u8::try_from(value).is_ok(),
+        Type::Short => i16::try_from(value).is_ok(),
+        Type::UShort => u16::try_from(value).is_ok(),
+        Type::Int => i32::try_from(value).is_ok(),
+        Type::UInt => u32::try_from(value).is_ok(),
+        Type::Long => true,
+        Type::ULong => u64::try_from(value).is_ok(),
+        _ => false,
+    }
+}
+
+/// Helper function that makes a type nullable, if the operation was lifted
+fn lift(typ: Type, nullable: bool) -> Type {
+    if nullable && !matches!(typ, Type::Nullable(_)) {
+        Type::Nullable(Box::new(typ))
+    } else {
+        typ
+    }
+}
+
+/// Helper function that applies the C# binary numeric promotions,
+/// returns the type both operands are converted to, None if they are not numbers
+pub fn promote(left: &Type, right: &Type) -> Option<Type> {
+    let numeric = |typ: &Type| {
+        matches!(
+            typ,
+            Type::Int
+                | Type::Long
+                | Type::Short
+                | Type::Byte
+                | Type::SByte
+                | Type::UInt
+                | Type::ULong
+                | Type::UShort
+                | Type::Char
+                | Type::Float
+                | Type::Double
+                | Type::Decimal
+        )
+    };
+    if !numeric(left) || !numeric(right) {
+        return None;
+    }
+
+    let either = |typ: Type| *left == typ || *right == typ;
+    // the signed types that fit in an int
+    let small_signed = |typ: &Type| matches!(typ, Type::Int | Type::Short | Type::SByte);
+
+    let typ = if either(Type::Decimal) {
+        Type::Decimal
+    } else if either(Type::Double) {
+        Type::Double
+    } else if either(Type::Float) {
+        Type::Float
+    } else if either(Type::ULong) {
+        Type::ULong
+    } else if either(Type::Long) {
+        Type::Long
+    } else if (*left == Type::UInt && small_signed(right))
+        || (*right == Type::UInt && small_signed(left))
+    {
+        // uint and int have no common 32 bit type
+        Type::Long
+    } else if either(Type::UInt) {
+        Type::UInt
+    } else {
+        // byte, short, char and the rest become int
+        Type::Int
+    };
+    Some(typ)
+}
+
+/// Helper function that groups the methods of a class by name, the overloads together
+pub fn class_methods(class: &Class) -> HashMap<String, Vec<Signature>> {
+    let mut methods: HashMap<String, Vec<Signature>> = HashMap::new();
+    for method in &class.methods {
+        methods
+            .entry(method.name.clone())
+            .or_default()
+            .push(Signature::of(method));
+    }
+    methods
+}
+
+/// Helper function that collects the field types of every class, by class name
+pub fn class_fields(program: &Program) -> HashMap<String, HashMap<String, Type>> {
+    program
+        .classes
+        .iter()
+        .map(|class| {
+            let fields = class
+                .fields
+                .iter()
+                .map(|field| (field.variable.name.clone(), field.variable.typ.clone()))
+                .collect();
+            (class.name.clone(), fields)
+        })
+        .collect()
+}
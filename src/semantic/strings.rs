@@ -0,0 +1,58 @@
+use crate::ast::*;
+
+/// Obtains the C# type of a method of a string, the int of s.IndexOf("a").
+/// The java methods they become are also known, the generator types the
+/// lowered program, where s.Contains("a") prints True as s.contains("a").
+/// None for the methods we do not know
+pub fn string_method_type(method: &str) -> Option<Type> {
+    match method {
+        "Substring" | "ToUpper" | "ToLower" | "ToUpperInvariant" | "ToLowerInvariant" | "Trim"
+        | "TrimStart" | "TrimEnd" | "Replace" | "PadLeft" | "PadRight" => Some(Type::String),
+        "Contains" | "StartsWith" | "EndsWith" => Some(Type::Bool),
+        "IndexOf" | "LastIndexOf" => Some(Type::Int),
+        "Split" => Some(Type::Array(Box::new(Type::String))),
+        "ToCharArray" => Some(Type::Array(Box::new(Type::Char))),
+        // the java methods
+        "substring" | "toUpperCase" | "toLowerCase" | "strip" | "stripLeading"
+        | "stripTrailing" | "trim" | "replace" | "replaceAll" | "repeat" => Some(Type::String),
+        "contains" | "startsWith" | "endsWith" | "isEmpty" | "isBlank" | "equalsIgnoreCase" => {
+            Some(Type::Bool)
+        }
+        "length" | "indexOf" | "lastIndexOf" | "compareToIgnoreCase" => Some(Type::Int),
+        "charAt" => Some(Type::Char),
+        "split" => Some(Type::Array(Box::new(Type::String))),
+        "toCharArray" => Some(Type::Array(Box::new(Type::Char))),
+        _ => None,
+    }
+}
+
+/// Obtains the C# type of a static method of string, string.Join gives a string
+pub fn string_static_type(path: &str) -> Option<Type> {
+    let method = path
+        .strip_prefix("string.")
+        .or_else(|| path.strip_prefix("String."))?;
+    match method {
+        "IsNullOrEmpty" | "IsNullOrWhiteSpace" => Some(Type::Bool),
+        "Join" | "Concat" => Some(Type::String),
+        _ => None,
+    }
+}
+
+/// Checks whether a .NET format specifier has a java.util.Formatter counterpart,
+/// a letter with an optional precision such as F2, or a custom format such as 0.00.
+/// The others, such as the currency C, are printed as is
+pub fn is_supported_format(format: &str) -> bool {
+    let format = format.trim();
+    let mut chars = format.chars();
+    let kind = chars.next();
+    let precision = chars.as_str();
+    let has_precision = precision.is_empty() || precision.parse::<usize>().is_ok();
+    match kind {
+        None => true,
+        Some('G' | 'g') => precision.is_empty(),
+        Some('D' | 'd' | 'X' | 'x' | 'F' | 'f' | 'N' | 'n' | 'E' | 'e' | 'P' | 'p') => {
+            has_precision
+        }
+        Some(_) => format.chars().all(|c| "0#.,".contains(c)),
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use crate::ast::{Expression, ExpressionKind, NodeId, Type};
+
+/// What a declared name is
+#[derive(Debug, Clone, PartialEq)]
+pub enum SymbolKind {
+    Class,
+    Field,
+    Method,
+    Parameter,
+    Local,
+}
+
+/// A declared name, with the scope it was declared in
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub typ: Type, // the type of a variable, the return type of a method
+    pub scope: usize,
+}
+
+/// What introduced a scope
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScopeKind {
+    Program,        // the classes of the file
+    Class(String),  // fields and methods
+    Method(String), // parameters
+    Block,          // locals
+    Lambda,         // the parameters of a lambda
+}
+
+#[derive(Debug, Clone)]
+pub struct Scope {
+    pub kind: ScopeKind,
+    pub parent: Option<usize>,
+    pub symbols: HashMap<String, usize>, // the names declared directly in this scope
+}
+
+/// What a used name refers to
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    Symbol(usize), // a declaration of the program
+    Library,       // a library class, such as Console or Math
+    Undefined,
+}
+
+/// A use of a name, in the order they appear in the code
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub name: String,
+    pub scope: usize, // the scope the name is used in
+    pub resolution: Resolution,
+}
+
+/// The scopes of the program, with every declaration and every use of a name.
+/// The uses are also found by the node of their expression, like the TypeTable
+#[derive(Debug, Default, Clone)]
+pub struct SymbolTable {
+    pub scopes: Vec<Scope>,
+    pub symbols: Vec<Symbol>,
+    pub references: Vec<Reference>,
+    resolutions: HashMap<NodeId, usize>, // the reference of every name, by its node
+}
+
+impl SymbolTable {
+    /// Creates a new scope inside the parent one, returns its id
+    pub fn add_scope(&mut self, kind: ScopeKind, parent: Option<usize>) -> usize {
+        self.scopes.push(Scope {
+            kind,
+            parent,
+            symbols: HashMap::new(),
+        });
+        self.scopes.len() - 1
+    }
+
+    /// Declares a name in a scope, returns the id of the symbol
+    pub fn declare(&mut self, scope: usize, name: &str, kind: SymbolKind, typ: Type) -> usize {
+        let id = self.symbols.len();
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            kind,
+            typ,
+            scope,
+        });
+        self.scopes[scope].symbols.insert(name.to_string(), id);
+        id
+    }
+
+    /// Finds the declaration of a name visible from a scope, starting from the innermost one
+    pub fn lookup(&self, scope: usize, name: &str) -> Option<usize> {
+        let mut current = Some(scope);
+        while let Some(id) = current {
+            if let Some(symbol) = self.scopes[id].symbols.get(name) {
+                return Some(*symbol);
+            }
+            current = self.scopes[id].parent;
+        }
+        None
+    }
+
+    /// Records the use of a name, the expression is the variable or the called name
+    pub fn add_reference(&mut self, expr: &Expression, reference: Reference) {
+        self.resolutions.insert(expr.id, self.references.len());
+        self.references.push(reference);
+    }
+
+    /// Returns the use of a name of the resolved program. The lowering keeps the
+    /// node of a moved name, but it can rename it, so the name has to be the same one
+    pub fn reference(&self, expr: &Expression) -> Option<&Reference> {
+        let ExpressionKind::Variable(name) = &expr.kind else {
+            return None;
+        };
+        let id = self.resolutions.get(&expr.id)?;
+        let reference = &self.references[*id];
+        (reference.name == *name).then_some(reference)
+    }
+
+    /// Returns the declaration a reference was linked to, if it is part of the program
+    pub fn declaration(&self, reference: &Reference) -> Option<&Symbol> {
+        match reference.resolution {
+            Resolution::Symbol(id) => Some(&self.symbols[id]),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,545 @@
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+use crate::semantic::scopes::{
+    Scopes, class_fields, class_methods, fits, is_integral, promote, underlying_type,
+};
+use crate::semantic::strings::is_supported_format;
+
+/// The C# type of every expression of a program whose type is known, found
+/// by the node of the expression. The expressions the lowering creates after
+/// the check are not in it
+#[derive(Debug, Default, Clone)]
+pub struct TypeTable {
+    types: HashMap<NodeId, Type>,
+}
+
+impl TypeTable {
+    /// Returns the type of an expression of the checked program
+    pub fn get(&self, expr: &Expression) -> Option<&Type> {
+        self.types.get(&expr.id)
+    }
+}
+
+/// Computes the type of every expression and reports the type errors of the C# code
+pub fn check_types(program: &Program) -> (TypeTable, Vec<Diagnostic>) {
+    let mut checker = TypeChecker::default();
+    checker.scopes.delegates = program.delegates.clone();
+    checker.scopes.fields = class_fields(program);
+    for class in &program.classes {
+        checker.scopes.methods = class_methods(class);
+        checker.scopes.class = class.name.clone();
+
+        // the fields are visible in every method
+        checker.scopes.push();
+        checker.scopes.return_type = None;
+        checker.method = class.name.clone();
+        for field in &class.fields {
+            checker.check_declaration(&field.variable, field.value.as_ref());
+        }
+        for method in &class.methods {
+            checker.method = method.name.clone();
+            checker.scopes.return_type = Some(method.return_type.clone());
+            checker.scopes.push();
+            for parameter in &method.parameters {
+                checker
+                    .scopes
+                    .declare(&parameter.variable.name, parameter.variable.typ.clone());
+            }
+            checker.check_block(&method.body);
+            checker.scopes.pop();
+        }
+        checker.scopes.pop();
+    }
+    (checker.table, checker.diagnostics)
+}
+
+#[derive(Default)]
+struct TypeChecker {
+    scopes: Scopes,
+    table: TypeTable,
+    diagnostics: Vec<Diagnostic>,
+    method: String, // the method being checked, for the messages
+}
+
+impl TypeChecker {
+    fn check_block(&mut self, statements: &[Statement]) {
+        self.scopes.push();
+        for statement in statements {
+            self.check_statement(statement);
+        }
+        self.scopes.pop();
+    }
+
+    fn check_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::VariableDeclaration { variable, value } => {
+                self.check_declaration(variable, value.as_ref())
+            }
+            Statement::Assignment {
+                target,
+                operator,
+                value,
+            } => {
+                self.check_expression(target);
+                self.check_expression(value);
+                // x += 1 is checked like x + 1
+                if operator.is_none()
+                    && let Some(target_type) = self.scopes.expression_type(target)
+                {
+                    self.check_conversion(value, &target_type);
+                }
+            }
+            Statement::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                self.check_condition(condition);
+                self.check_block(then_body);
+                self.check_block(else_body);
+            }
+            Statement::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                self.scopes.push();
+                if let Some(initializer) = initializer {
+                    self.check_statement(initializer);
+                }
+                if let Some(condition) = condition {
+                    self.check_condition(condition);
+                }
+                if let Some(increment) = increment {
+                    self.check_statement(increment);
+                }
+                self.check_block(body);
+                self.scopes.pop();
+            }
+            Statement::While { condition, body } => {
+                self.check_condition(condition);
+                self.check_block(body);
+            }
+            Statement::Checked { body, .. } | Statement::Block(body) => self.check_block(body),
+            Statement::TryCatch {
+                body,
+                exception,
+                handler,
+            } => {
+                self.check_block(body);
+                self.scopes.push();
+                self.scopes.declare(&exception.name, exception.typ.clone());
+                self.check_block(handler);
+                self.scopes.pop();
+            }
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    self.check_expression(value);
+                    if let Some(return_type) = self.scopes.return_type.clone() {
+                        self.check_conversion(value, &return_type);
+                    }
+                }
+            }
+            Statement::Expression(expr) | Statement::Throw(expr) => self.check_expression(expr),
+        }
+    }
+
+    fn check_declaration(&mut self, variable: &Variable, value: Option<&Expression>) {
+        if let Some(value) = value {
+            self.check_expression(value);
+            self.check_conversion(value, &variable.typ);
+        }
+        self.scopes.declare(&variable.name, variable.typ.clone());
+        // byte b = K fits when the const K does
+        if let Some(constant) = self.scopes.declared_constant(variable, value) {
+            self.scopes.declare_constant(&variable.name, constant);
+        }
+    }
+
+    /// Checks the condition of an if, for or while
+    fn check_condition(&mut self, condition: &Expression) {
+        self.check_expression(condition);
+        if let Some(typ) = self.scopes.expression_type(condition)
+            && is_known(&typ)
+            && typ != Type::Bool
+        {
+            self.error(format!(
+                "the condition has the type `{}` instead of `bool`",
+                cs_type_name(&typ)
+            ));
+        }
+    }
+
+    /// Computes and stores the type of an expression and of its children
+    fn check_expression(&mut self, expr: &Expression) {
+        match &expr.kind {
+            ExpressionKind::BinaryExpression {
+                left,
+                operator,
+                right,
+            } => self.check_operands(left, operator, right),
+            ExpressionKind::PrefixUnaryExpression { operator, right } => {
+                let typ = self.scopes.expression_type(right);
+                let valid = match (operator, typ.as_ref().map(underlying_type)) {
+                    (_, None | Some(Type::Unknown | Type::Named(_))) => true,
+                    (UnaryOperator::Not, Some(typ)) => *typ == Type::Bool,
+                    // ~ flips bits, there are none for a double or a decimal
+                    (UnaryOperator::BitNot, Some(typ)) => {
+                        promote(typ, &Type::Int).is_some_and(|typ| is_integral(&typ))
+                    }
+                    (_, Some(typ)) => promote(typ, &Type::Int).is_some(),
+                };
+                if !valid && let Some(typ) = typ {
+                    self.error(format!(
+                        "the operator `{}` cannot be applied to `{}`",
+                        cs_unary_operator(operator),
+                        cs_type_name(&typ)
+                    ));
+                }
+            }
+            // the parameters are only visible in the lambda
+            ExpressionKind::Lambda { parameters, body } => {
+                self.scopes.push();
+                for parameter in parameters {
+                    self.scopes.declare(&parameter.name, parameter.typ.clone());
+                }
+                let return_type = self.scopes.return_type.take();
+                match body {
+                    LambdaBody::Expression(body) => self.check_expression(body),
+                    LambdaBody::Block(statements) => self.check_block(statements),
+                }
+                self.scopes.return_type = return_type;
+                self.scopes.pop();
+                return;
+            }
+            // int.TryParse(s, out var n) declares n
+            ExpressionKind::DeclarationExpression(variable) => {
+                self.scopes.declare(&variable.name, variable.typ.clone())
+            }
+            // int.Parse works on strings
+            ExpressionKind::Call { callee, arguments } => {
+                // the parser turns the composite formats it can read into interpolated strings
+                if let Some(path) = callee.path()
+                    && matches!(
+                        path.as_str(),
+                        "Console.WriteLine" | "Console.Write" | "string.Format" | "String.Format"
+                    )
+                    && let [format, rest @ ..] = arguments.as_slice()
+                    && !rest.is_empty()
+                    && let ExpressionKind::Literal(Literal::String(format)) = &format.kind
+                {
+                    self.diagnostics.push(Diagnostic::warning(format!(
+                        "the format {:?} of {} in {} does not match its {}, C# throws a FormatException",
+                        format,
+                        path,
+                        self.method,
+                        match rest.len() {
+                            1 => "1 argument".to_string(),
+                            n => format!("{} arguments", n),
+                        }
+                    )));
+                }
+                if let Some(path) = callee.path()
+                    && path.ends_with(".Parse")
+                    && let [argument] = arguments.as_slice()
+                    && let Some(typ) = self.scopes.expression_type(argument)
+                    && is_known(&typ)
+                    && *underlying_type(&typ) != Type::String
+                {
+                    self.error(format!(
+                        "{} expects a `string`, not `{}`",
+                        path,
+                        cs_type_name(&typ)
+                    ));
+                }
+            }
+            // $"{price:C}" has no java counterpart
+            ExpressionKind::InterpolatedString(parts) => {
+                for part in parts {
+                    if let InterpolationPart::Value {
+                        format: Some(format),
+                        ..
+                    } = part
+                        && !is_supported_format(format)
+                    {
+                        self.diagnostics.push(Diagnostic::warning(format!(
+                            "the format `{}` in {} has no java counterpart, the value is printed as is",
+                            format, self.method
+                        )));
+                    }
+                }
+            }
+            // new { p.Name } becomes an anonymous java class
+            ExpressionKind::AnonymousObject(members) => {
+                if members.iter().any(|(name, _)| name.is_empty()) {
+                    self.error("the members of an anonymous object need a name".to_string());
+                }
+                self.diagnostics.push(Diagnostic::warning(format!(
+                    "the anonymous object in {} becomes an anonymous java class, \
+                     it is compared by reference and its members can only be read through `var`",
+                    self.method
+                )));
+            }
+            _ => {}
+        }
+
+        if let Some(typ) = self.scopes.expression_type(expr) {
+            self.table.types.insert(expr.id, typ);
+        }
+        for child in children(expr) {
+            self.check_expression(child);
+        }
+    }
+
+    /// Checks that the operands of a binary expression fit the operator
+    fn check_operands(&mut self, left: &Expression, operator: &BinaryOperator, right: &Expression) {
+        let (Some(left_type), Some(right_type)) = self.scopes.operand_types(left, right) else {
+            return;
+        };
+        if !is_known(&left_type) || !is_known(&right_type) {
+            return;
+        }
+        let (left_value, right_value) = (underlying_type(&left_type), underlying_type(&right_type));
+        let is_string = |typ: &Type| *typ == Type::String;
+        let both_bool = *left_value == Type::Bool && *right_value == Type::Bool;
+        let numeric = promote(left_value, right_value).is_some();
+        let integral = promote(left_value, right_value).is_some_and(|typ| is_integral(&typ));
+
+        let valid = match operator {
+            BinaryOperator::Add => numeric || is_string(left_value) || is_string(right_value),
+            BinaryOperator::Eq | BinaryOperator::Ne => {
+                numeric || both_bool || left_value == right_value
+            }
+            BinaryOperator::And | BinaryOperator::Or => both_bool,
+            // the left side can be null, a nullable value or a string
+            BinaryOperator::Coalesce => {
+                matches!(left_type, Type::Nullable(_)) || is_string(&left_type)
+            }
+            BinaryOperator::BitAnd | BinaryOperator::BitOr | BinaryOperator::BitXor => {
+                integral || both_bool
+            }
+            // the count of a shift is an int, 1 << n
+            BinaryOperator::LeftShift
+            | BinaryOperator::RightShift
+            | BinaryOperator::UnsignedRightShift => {
+                is_integral(left_value) && is_integral(right_value)
+            }
+            _ => numeric,
+        };
+        if !valid {
+            self.error(format!(
+                "the operator `{}` cannot be applied to `{}` and `{}`",
+                cs_binary_operator(operator),
+                cs_type_name(&left_type),
+                cs_type_name(&right_type)
+            ));
+        }
+    }
+
+    /// Checks that a value can be stored in a variable of the given type
+    fn check_conversion(&mut self, value: &Expression, typ: &Type) {
+        let Some(value_type) = self.scopes.expression_type(value) else {
+            return;
+        };
+        let constant = self.scopes.constant_value(value);
+        if !is_known(&value_type) || !is_known(typ) || converts(constant, &value_type, typ) {
+            return;
+        }
+        self.error(format!(
+            "cannot convert `{}` to `{}`",
+            cs_type_name(&value_type),
+            cs_type_name(typ)
+        ));
+    }
+
+    fn error(&mut self, message: String) {
+        self.diagnostics
+            .push(Diagnostic::error(format!("{} in {}", message, self.method)));
+    }
+}
+
+/// Helper function that returns the sub-expressions, like children_mut does
+pub(crate) fn children(expr: &Expression) -> Vec<&Expression> {
+    match &expr.kind {
+        ExpressionKind::BinaryExpression { left, right, .. } => vec![left, right],
+        ExpressionKind::PrefixUnaryExpression { right, .. } => vec![right],
+        ExpressionKind::PostfixUnaryExpression { left, .. } => vec![left],
+        ExpressionKind::Call { callee, arguments } => {
+            let mut children = vec![&**callee];
+            children.extend(arguments.iter());
+            children
+        }
+        ExpressionKind::MemberAccess { target, .. }
+        | ExpressionKind::ConditionalAccess { target, .. }
+        | ExpressionKind::MethodReference { target, .. } => vec![target],
+        ExpressionKind::ElementAccess { target, index } => vec![target, index],
+        ExpressionKind::Assignment { target, value } => vec![target, value],
+        ExpressionKind::Lambda { body, .. } => match body {
+            LambdaBody::Expression(body) => vec![body],
+            LambdaBody::Block(_) => Vec::new(),
+        },
+        ExpressionKind::ObjectCreation { arguments, .. } => arguments.iter().collect(),
+        ExpressionKind::CollectionInitializer {
+            creation, elements, ..
+        } => {
+            let mut children = vec![&**creation];
+            children.extend(elements.iter().flatten());
+            children
+        }
+        ExpressionKind::AnonymousObject(members) => {
+            members.iter().map(|(_, value)| value).collect()
+        }
+        ExpressionKind::ParenthesizedExpression(inner) => vec![inner],
+        ExpressionKind::ConditionalExpression {
+            condition,
+            consequence,
+            alternative,
+        } => vec![condition, consequence, alternative],
+        ExpressionKind::CastExpression { value, .. }
+        | ExpressionKind::CheckedExpression { value, .. }
+        | ExpressionKind::NamedArgument { value, .. }
+        | ExpressionKind::RefArgument { value, .. } => vec![value],
+        ExpressionKind::IsExpression { left, .. } | ExpressionKind::AsExpression { left, .. } => {
+            vec![left]
+        }
+        ExpressionKind::InterpolatedString(parts) => parts
+            .iter()
+            .filter_map(|part| match part {
+                InterpolationPart::Value { value, .. } => Some(value),
+                InterpolationPart::Text(_) => None,
+            })
+            .collect(),
+        ExpressionKind::Literal(_)
+        | ExpressionKind::Variable(_)
+        | ExpressionKind::This
+        | ExpressionKind::Base
+        | ExpressionKind::TypeOfExpression(_)
+        | ExpressionKind::DeclarationExpression(_) => Vec::new(),
+    }
+}
+
+/// Helper function that tells whether the checker knows enough about a type,
+/// the classes are not checked
+fn is_known(typ: &Type) -> bool {
+    !matches!(
+        underlying_type(typ),
+        Type::Unknown | Type::Named(_) | Type::Generic(..)
+    )
+}
+
+/// Helper function that applies the C# implicit conversions
+pub(super) fn is_convertible(value: &Expression, from: &Type, to: &Type) -> bool {
+    converts(Scopes::default().constant_value(value), from, to)
+}
+
+/// Same as is_convertible, for a value whose constant is already computed,
+/// the constants can use the const variables
+fn converts(constant: Option<i64>, from: &Type, to: &Type) -> bool {
+    if from == to {
+        return true;
+    }
+    // an int? converts to the nullable types its int converts to
+    if let (Type::Nullable(from), Type::Nullable(to)) = (from, to) {
+        return converts(constant, from, to);
+    }
+    // an int? can hold an int, and anything an int can hold
+    if let Type::Nullable(inner) = to {
+        return converts(constant, from, inner);
+    }
+
+    use Type::*;
+    // an int constant fits any integer type wide enough for it, a long one an ulong
+    if let Some(n) = constant
+        && is_integral(to)
+        && fits(n, to)
+        && (*from == Int || (*from == Long && *to == ULong))
+    {
+        return true;
+    }
+
+    let wider: &[Type] = match from {
+        SByte => &[Short, Int, Long, Float, Double, Decimal],
+        Byte => &[
+            Short, UShort, Int, UInt, Long, ULong, Float, Double, Decimal,
+        ],
+        Short => &[Int, Long, Float, Double, Decimal],
+        UShort | Char => &[Int, UInt, Long, ULong, Float, Double, Decimal],
+        Int => &[Long, Float, Double, Decimal],
+        UInt => &[Long, ULong, Float, Double, Decimal],
+        Long | ULong => &[Float, Double, Decimal],
+        Float => &[Double],
+        _ => &[],
+    };
+    wider.contains(to) || (*from == Char && *to == UShort)
+}
+
+/// Helper function that writes a type the way C# does, for the messages
+pub fn cs_type_name(typ: &Type) -> String {
+    match typ {
+        Type::Void => "void".to_string(),
+        Type::Int => "int".to_string(),
+        Type::Long => "long".to_string(),
+        Type::Short => "short".to_string(),
+        Type::Byte => "byte".to_string(),
+        Type::SByte => "sbyte".to_string(),
+        Type::UInt => "uint".to_string(),
+        Type::ULong => "ulong".to_string(),
+        Type::UShort => "ushort".to_string(),
+        Type::Decimal => "decimal".to_string(),
+        Type::Bool => "bool".to_string(),
+        Type::String => "string".to_string(),
+        Type::Char => "char".to_string(),
+        Type::Float => "float".to_string(),
+        Type::Double => "double".to_string(),
+        Type::Named(name) => name.clone(),
+        Type::Nullable(inner) => format!("{}?", cs_type_name(inner)),
+        Type::Array(element) => format!("{}[]", cs_type_name(element)),
+        Type::Holder(inner) => format!("ref {}", cs_type_name(inner)),
+        Type::Generic(name, arguments) => {
+            let arguments: Vec<String> = arguments.iter().map(cs_type_name).collect();
+            format!("{}<{}>", name, arguments.join(", "))
+        }
+        Type::Unknown => "object".to_string(),
+    }
+}
+
+/// Helper function that writes a binary operator the way C# does, for the messages
+fn cs_binary_operator(operator: &BinaryOperator) -> &'static str {
+    match operator {
+        BinaryOperator::Add => "+",
+        BinaryOperator::Sub => "-",
+        BinaryOperator::Mul => "*",
+        BinaryOperator::Div => "/",
+        BinaryOperator::Mod => "%",
+        BinaryOperator::Eq => "==",
+        BinaryOperator::Ne => "!=",
+        BinaryOperator::Lt => "<",
+        BinaryOperator::Gt => ">",
+        BinaryOperator::Le => "<=",
+        BinaryOperator::Ge => ">=",
+        BinaryOperator::And => "&&",
+        BinaryOperator::Or => "||",
+        BinaryOperator::BitAnd => "&",
+        BinaryOperator::BitOr => "|",
+        BinaryOperator::BitXor => "^",
+        BinaryOperator::LeftShift => "<<",
+        BinaryOperator::RightShift => ">>",
+        BinaryOperator::UnsignedRightShift => ">>>",
+        BinaryOperator::Coalesce => "??",
+    }
+}
+
+/// Helper function that writes a unary operator the way C# does, for the messages
+fn cs_unary_operator(operator: &UnaryOperator) -> &'static str {
+    match operator {
+        UnaryOperator::Not => "!",
+        UnaryOperator::Neg => "-",
+        UnaryOperator::Plus => "+",
+        UnaryOperator::BitNot => "~",
+        UnaryOperator::UAdd => "++",
+        UnaryOperator::USub => "--",
+    }
+}
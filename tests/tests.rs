@@ -1,6 +1,17 @@
 #[cfg(test)]
 mod tests {
-    use rust_jcs_transpiler::{builder_java::build_program, generator_java::JavaGenerator};
+    use rust_jcs_transpiler::{
+        ast::{ExpressionKind, Program, Statement, Type},
+        builder_java::build_program_with_options,
+        diagnostics::Diagnostic,
+        generator_java::JavaGenerator,
+        mappings::Mappings,
+        options::Options,
+        semantic::{
+            symbols::{Resolution, SymbolKind},
+            typecheck::check_types,
+        },
+    };
     use tree_sitter::Parser;
 
     #[test]
@@ -201,8 +212,3985 @@ class Program {
 "#;
         assert_eq!(expected_code, generate_code(input_code));
     }
+    #[test]
+    fn test_code_conditional_and_casts() {
+        let input_code = r#"class Program {
+    static object Describe(int a) {
+        return a > 5 ? "big" : "small";
+    }
+    public static void Main() {
+        double d = 7.5d;
+        int a = (int)d;
+        int b = a > 5 ? a : (a + 1) * 2;
+        object boxed = Describe(b);
+        bool isText = boxed is string;
+        string s = boxed as string;
+        string t = Describe(a) as string;
+        Console.WriteLine(typeof(Program));
+    }
+}
+"#;
+
+        let expected_code = r#"import java.util.Optional;
+class Program {
+    static Object describe(int a) {
+        return a > 5 ? "big" : "small";
+    }
+    public static void main(String[] args) {
+        double d = 7.5d;
+        int a = (int) d;
+        int b = a > 5 ? a : (a + 1) * 2;
+        Object boxed = describe(b);
+        boolean isText = boxed instanceof String;
+        String s = (boxed instanceof String ? (String) boxed : null);
+        String t = Optional.ofNullable(describe(a)).filter(String.class::isInstance).map(String.class::cast).orElse(null);
+        System.out.println(Program.class);
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_member_access() {
+        let input_code = r#"class Program {
+    public void Reset() {
+        this.Count = 0;
+        base.Reset();
+    }
+    public static void Main() {
+        string name = GetList().First().Name;
+        int length = name.Length;
+        Console.WriteLine(Helper.Format(name));
+    }
+}
+"#;
+
+        let expected_code = r#"class Program {
+    public void reset() {
+        this.Count = 0;
+        super.reset();
+    }
+    public static void main(String[] args) {
+        String name = getList().first().Name;
+        int length = name.length();
+        System.out.println(Helper.format(name));
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_string_formatting() {
+        let input_code = r#"class Program {
+    public static void Main() {
+        string name = "Ana";
+        int age = 7;
+        double total = 12.5;
+        Console.WriteLine($"Hello {name}, you are {age} {{years}}");
+        Console.WriteLine($"{age}{age + 1}");
+        string s = $"Age: {age:D3}, total: {total,8:F2}, 50% off: {total:N0}";
+        Console.WriteLine("{0} + {1} = {2,5}", age, total, age + total);
+        string hex = string.Format("{0:X} {1:x4}", age, age);
+        string padded = $"{age:00000} {total:00.00} {age,8:000} {age:#,##0}";
+    }
+}
+"#;
+
+        let expected_code = r#"class Program {
+    public static void main(String[] args) {
+        String name = "Ana";
+        int age = 7;
+        double total = 12.5;
+        System.out.println("Hello " + name + ", you are " + age + " {years}");
+        System.out.println("" + age + (age + 1));
+        String s = String.format("Age: %03d, total: %8.2f, 50%% off: %,.0f", age, (double) total, (double) total);
+        System.out.println(String.format("%s + %s = %5s", age, total, (age + total)));
+        String hex = String.format("%X %04x", age, age);
+        String padded = String.format("%05d %05.2f %8s %,d", age, (double) total, String.format("%03d", age), age);
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_string_literals() {
+        let input_code = r#"class Program {
+    public static void Main() {
+        string path = @"C:\temp\""new""";
+        string tab = "a\tb\u0041";
+        string raw = """She said "hi" """;
+        string poem = """
+            Roses are red,
+              violets are blue
+            """;
+        char quote = '\'';
+        char letter = 'x';
+        Console.WriteLine($@"{path}\{letter}");
+    }
+}
+"#;
+
+        let expected_code = r#"class Program {
+    public static void main(String[] args) {
+        String path = "C:\\temp\\\"new\"";
+        String tab = "a\tbA";
+        String raw = "She said \"hi\" ";
+        String poem = """
+Roses are red,
+  violets are blue\
+""";
+        char quote = '\'';
+        char letter = 'x';
+        System.out.println(path + "\\" + letter);
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_numeric_types() {
+        let input_code = r#"class Program {
+    public static long Widen(short s, byte b) {
+        return s + b;
+    }
+    public static void Main() {
+        long big = 5000000000;
+        long small = 5L;
+        int mask = 0xFF;
+        int bits = 0b1010_1010;
+        int million = 1_000_000;
+        uint hash = 0xFFFFFFFFu;
+        ulong full = 18446744073709551615UL;
+        ushort port = 8080;
+        sbyte delta = -1;
+        decimal price;
+    }
+}
+"#;
+
+        let expected_code = r#"import java.math.BigDecimal;
+class Program {
+    public static long widen(short s, byte b) {
+        return s + (b & 255);
+    }
+    public static void main(String[] args) {
+        long big = 5000000000L;
+        long small = 5L;
+        int mask = 255;
+        int bits = 170;
+        int million = 1000000;
+        int hash = 0xFFFFFFFF;
+        long full = 0xFFFFFFFFFFFFFFFFL;
+        short port = 8080;
+        byte delta = -1;
+        BigDecimal price;
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_real_literals() {
+        let input_code = r#"class Program {
+    public static void Main() {
+        double pi = 3.14;
+        double one = 1.0;
+        double big = 1e10;
+        double tiny = 2.5E-3d;
+        float ratio = 5f;
+        float half = .5F;
+        decimal price = 19.99m;
+    }
+}
+"#;
+
+        let expected_code = r#"import java.math.BigDecimal;
+class Program {
+    public static void main(String[] args) {
+        double pi = 3.14;
+        double one = 1.0;
+        double big = 1e10;
+        double tiny = 2.5E-3d;
+        float ratio = 5f;
+        float half = .5F;
+        BigDecimal price = new BigDecimal("19.99");
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_unsigned_emulation() {
+        let input_code = r#"class Program {
+    public static uint Hash(uint h, byte b) {
+        h ^= b;
+        h *= 16777619u;
+        h /= 3;
+        return h >> 4;
+    }
+    public static void Main() {
+        uint a = 4000000000;
+        uint b = 7;
+        bool bigger = a > b;
+        uint half = a / 2;
+        ulong rest = 18446744073709551615UL % 10;
+        long wide = a;
+        int sign = -5;
+        long mixed = a + sign;
+        byte low = 200;
+        int sum = low + 1;
+        ushort port = 65535;
+        Console.WriteLine(a);
+        Console.WriteLine("a = " + a + ", low = " + low);
+        string text = rest.ToString();
+        Console.WriteLine($"{a} {port}");
+        int order = a.CompareTo(b) + rest.CompareTo(3UL) + low.CompareTo((byte) 7);
+        int min = -2147483648;
+        long longMin = -9223372036854775808;
+        Console.WriteLine(order + min + longMin);
+    }
+}
+"#;
+
+        let expected_code = r#"class Program {
+    public static int hash(int h, byte b) {
+        h ^= (b & 255);
+        h *= 16777619;
+        h = Integer.divideUnsigned(h, 3);
+        return h >>> 4;
+    }
+    public static void main(String[] args) {
+        int a = 0xEE6B2800;
+        int b = 7;
+        boolean bigger = Integer.compareUnsigned(a, b) > 0;
+        int half = Integer.divideUnsigned(a, 2);
+        long rest = Long.remainderUnsigned(0xFFFFFFFFFFFFFFFFL, 10);
+        long wide = Integer.toUnsignedLong(a);
+        int sign = -5;
+        long mixed = Integer.toUnsignedLong(a) + sign;
+        byte low = (byte) 200;
+        int sum = (low & 255) + 1;
+        short port = (short) 65535;
+        System.out.println(Integer.toUnsignedString(a));
+        System.out.println("a = " + Integer.toUnsignedString(a) + ", low = " + (low & 255));
+        String text = Long.toUnsignedString(rest);
+        System.out.println(Integer.toUnsignedLong(a) + " " + (port & 65535));
+        int order = Integer.compareUnsigned(a, b) + Long.compareUnsigned(rest, 3L) + ((low & 255) - ((byte) 7 & 255));
+        int min = -2147483648;
+        long longMin = -9223372036854775808L;
+        System.out.println(order + min + longMin);
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_decimal_arithmetic() {
+        let input_code = r#"using System;
+
+class Program
+{
+    static decimal Total(decimal price, int quantity)
+    {
+        decimal total = price * quantity;
+        if (total > 100)
+        {
+            total -= 5.5m;
+        }
+        return total;
+    }
+
+    static void Main()
+    {
+        decimal price = 19.99m;
+        decimal tax = price * 0.19m;
+        decimal share = 10m / 3;
+        decimal rounded = Math.Round(tax, 2);
+        decimal away = Math.Round(2.5m, MidpointRounding.AwayFromZero);
+        decimal parsed = decimal.Parse("12.50");
+        int whole = (int) price;
+        decimal count = 0;
+        count++;
+        decimal before = count++;
+        decimal after = --count;
+        Console.WriteLine(before + after);
+        Console.WriteLine(Total(price, 7));
+        Console.WriteLine(share.ToString());
+        Console.WriteLine(price == parsed);
+        Console.WriteLine(-rounded + Math.Abs(away));
+        Console.WriteLine(whole);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.math.BigDecimal;
+import java.math.MathContext;
+import java.math.RoundingMode;
+class Program {
+    static BigDecimal total(BigDecimal price, int quantity) {
+        BigDecimal total = price.multiply(BigDecimal.valueOf(quantity));
+        if (total.compareTo(BigDecimal.valueOf(100)) > 0) {
+            total = total.subtract(new BigDecimal("5.5"));
+        }
+        return total;
+    }
+    static void main(String[] args) {
+        BigDecimal price = new BigDecimal("19.99");
+        BigDecimal tax = price.multiply(new BigDecimal("0.19"));
+        BigDecimal share = new BigDecimal("10").divide(BigDecimal.valueOf(3), new MathContext(29, RoundingMode.HALF_EVEN));
+        BigDecimal rounded = tax.setScale(2, RoundingMode.HALF_EVEN);
+        BigDecimal away = new BigDecimal("2.5").setScale(0, RoundingMode.HALF_UP);
+        BigDecimal parsed = new BigDecimal("12.50");
+        int whole = price.intValue();
+        BigDecimal count = BigDecimal.ZERO;
+        count = count.add(BigDecimal.ONE);
+        BigDecimal before = (count = count.add(BigDecimal.ONE)).subtract(BigDecimal.ONE);
+        BigDecimal after = (count = count.subtract(BigDecimal.ONE));
+        System.out.println(before.add(after));
+        System.out.println(total(price, 7));
+        System.out.println(share.toPlainString());
+        System.out.println((price.compareTo(parsed) == 0) ? "True" : "False");
+        System.out.println(rounded.negate().add(away.abs()));
+        System.out.println(whole);
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_checked_arithmetic() {
+        let input_code = r#"class Program
+{
+    static int Factorial(int n)
+    {
+        int result = 1;
+        for (int i = 2; i <= n; i++)
+        {
+            checked
+            {
+                result *= i;
+            }
+        }
+        return result;
+    }
+
+    static void Main()
+    {
+        int a = 2000000000;
+        long big = 5000000000L;
+        int sum = checked(a + a);
+        int narrow = checked((int) big);
+        int wrapped = unchecked(a * 3);
+        checked
+        {
+            long total = big * 2 + a;
+            int negated = -a;
+            a++;
+            unchecked
+            {
+                a += 1;
+            }
+        }
+        Console.WriteLine(Factorial(20) + sum);
+    }
+}
+"#;
+
+        let expected_code = r#"class Program {
+    static int factorial(int n) {
+        int result = 1;
+        for (int i = 2; i <= n; i++) {
+            {
+                result = Math.multiplyExact(result, i);
+            }
+        }
+        return result;
+    }
+    static void main(String[] args) {
+        int a = 2000000000;
+        long big = 5000000000L;
+        int sum = Math.addExact(a, a);
+        int narrow = Math.toIntExact(big);
+        int wrapped = (a * 3);
+        {
+            long total = Math.addExact(Math.multiplyExact(big, 2), a);
+            int negated = Math.negateExact(a);
+            a = Math.incrementExact(a);
+            {
+                a += 1;
+            }
+        }
+        System.out.println(factorial(20) + sum);
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_checked_option() {
+        let input_code = r#"class Program {
+    static long Sum(long a, int b) {
+        long total = a + b;
+        total -= 1;
+        return unchecked(total * 2);
+    }
+}
+"#;
+
+        let expected_code = r#"class Program {
+    static long sum(long a, int b) {
+        long total = Math.addExact(a, b);
+        total = Math.subtractExact(total, 1);
+        return (total * 2);
+    }
+}
+"#;
+
+        let options = Options {
+            checked: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            expected_code,
+            generate_code_with_options(input_code, &options)
+        );
+    }
+
+    #[test]
+    fn test_code_nullable_types() {
+        let input_code = r#"class Program
+{
+    static int? Half(int? value)
+    {
+        if (!value.HasValue)
+        {
+            return null;
+        }
+        return value / 2;
+    }
+
+    static void Main()
+    {
+        int? a = 5;
+        int? b = null;
+        long? big = 7;
+        bool? flag = true;
+        string? name = null;
+        int? sum = a + b;
+        int? negated = -a;
+        bool same = a == b;
+        bool five = a == 5;
+        bool less = a < 10;
+        int plain = a.Value;
+        int safe = b.GetValueOrDefault();
+        long fallback = big.GetValueOrDefault(3);
+        if (a.HasValue && flag.Value)
+        {
+            Console.WriteLine("a = " + a + ", b = " + b);
+        }
+        Console.WriteLine(Half(a));
+    }
+}
+"#;
+
+        let expected_code = r#"import java.util.Objects;
+class Program {
+    static Integer half(Integer value) {
+        if (!(value != null)) {
+            return null;
+        }
+        return (value == null ? null : value / 2);
+    }
+    static void main(String[] args) {
+        Integer a = 5;
+        Integer b = null;
+        Long big = (long) 7;
+        Boolean flag = true;
+        String name = null;
+        Integer sum = (a == null || b == null ? null : a + b);
+        Integer negated = (a == null ? null : -a);
+        boolean same = Objects.equals(a, b);
+        boolean five = (a != null && a == 5);
+        boolean less = (a != null && a < 10);
+        int plain = a;
+        int safe = Objects.requireNonNullElse(b, 0);
+        long fallback = Objects.requireNonNullElse(big, (long) 3);
+        if (a != null && flag) {
+            System.out.println("a = " + Objects.toString(a, "") + ", b = " + Objects.toString(b, ""));
+        }
+        System.out.println(half(a));
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_nullable_annotations() {
+        let input_code = r#"class Program {
+    static string? Find(string? key, int? limit) {
+        string? found = null;
+        return found;
+    }
+}
+"#;
+
+        let expected_code = r#"import org.jspecify.annotations.Nullable;
+class Program {
+    static @Nullable String find(@Nullable String key, @Nullable Integer limit) {
+        @Nullable String found = null;
+        return found;
+    }
+}
+"#;
+
+        let options = Options {
+            nullable_annotations: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            expected_code,
+            generate_code_with_options(input_code, &options)
+        );
+    }
+
+    #[test]
+    fn test_code_var_inference() {
+        let input_code = r#"class Program
+{
+    static double Average(int a, int b)
+    {
+        return (a + b) / 2.0;
+    }
+
+    static void Main()
+    {
+        var count = 3;
+        var big = 3000000000;
+        var price = 9.99m;
+        var name = "Ana";
+        var ratio = Average(count, 4);
+        var total = price * count;
+        var label = $"{name}: {total}";
+        var maybe = count > 2 ? (int?) count : null;
+        var scale = count > 2 ? 1 : 2.5;
+        for (var i = 0; i < count; i++)
+        {
+            var square = i * i;
+            Console.WriteLine(square);
+        }
+        Console.WriteLine(label + ratio + big);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.math.BigDecimal;
+class Program {
+    static double average(int a, int b) {
+        return (a + b) / 2.0;
+    }
+    static void main(String[] args) {
+        var count = 3;
+        var big = 0xB2D05E00;
+        var price = new BigDecimal("9.99");
+        var name = "Ana";
+        var ratio = average(count, 4);
+        var total = price.multiply(BigDecimal.valueOf(count));
+        var label = name + ": " + total;
+        var maybe = count > 2 ? (Integer) count : null;
+        var scale = count > 2 ? 1 : 2.5;
+        for (var i = 0; i < count; i++) {
+            var square = i * i;
+            System.out.println(square);
+        }
+        System.out.println(label + ratio + Integer.toUnsignedString(big));
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+
+        // older java has no var, the inferred types are written instead
+        let expected_code = r#"import java.math.BigDecimal;
+class Program {
+    static double average(int a, int b) {
+        return (a + b) / 2.0;
+    }
+    static void main(String[] args) {
+        int count = 3;
+        int big = 0xB2D05E00;
+        BigDecimal price = new BigDecimal("9.99");
+        String name = "Ana";
+        double ratio = average(count, 4);
+        BigDecimal total = price.multiply(BigDecimal.valueOf(count));
+        String label = name + ": " + total;
+        Integer maybe = count > 2 ? (Integer) count : null;
+        double scale = count > 2 ? 1 : 2.5;
+        for (int i = 0; i < count; i++) {
+            int square = i * i;
+            System.out.println(square);
+        }
+        System.out.println(label + ratio + Integer.toUnsignedString(big));
+    }
+}
+"#;
+
+        let options = Options {
+            java_version: 8,
+            ..Default::default()
+        };
+        assert_eq!(
+            expected_code,
+            generate_code_with_options(input_code, &options)
+        );
+    }
+
+    #[test]
+    fn test_code_name_resolution() {
+        let input_code = r#"class Counter {
+    private static int total = 0;
+    const int Limit = 10;
+
+    static int Add(int amount) {
+        int result = total + amount;
+        if (result > Limit) {
+            int amount = Limit;
+            result = amount;
+        }
+        total = result;
+        return Missing(result) + unknown;
+    }
+}
+"#;
+
+        let expected_code = r#"class Counter {
+    private static int total = 0;
+    static final int Limit = 10;
+    static int add(int amount) {
+        int result = total + amount;
+        if (result > Limit) {
+            int amount = Limit;
+            result = amount;
+        }
+        total = result;
+        return missing(result) + unknown;
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![
+                Diagnostic::error("`amount` is already declared in an enclosing scope of Add"),
+                Diagnostic::error("undefined method `Missing` in Add"),
+                Diagnostic::error("undefined name `unknown` in Add"),
+            ],
+            program.diagnostics
+        );
+
+        // total + amount uses the field and the parameter
+        let symbols = &program.symbols;
+        let kinds: Vec<_> = symbols.references[..2]
+            .iter()
+            .map(|reference| symbols.declaration(reference).map(|symbol| &symbol.kind))
+            .collect();
+        assert_eq!(
+            vec![Some(&SymbolKind::Field), Some(&SymbolKind::Parameter)],
+            kinds
+        );
+        let unknown = symbols
+            .references
+            .iter()
+            .find(|reference| reference.name == "unknown")
+            .unwrap();
+        assert_eq!(Resolution::Undefined, unknown.resolution);
+
+        // and by their expression, the unknown of the return
+        let body = &program.classes[0].methods[0].body;
+        let Some(Statement::Return(Some(value))) = body.last() else {
+            panic!("Expected the return of Add");
+        };
+        let ExpressionKind::BinaryExpression { right, .. } = &value.kind else {
+            panic!("Expected the sum of Add");
+        };
+        let reference = symbols.reference(right).unwrap();
+        assert_eq!("unknown", reference.name);
+        assert_eq!(Resolution::Undefined, reference.resolution);
+
+        // the tables find an expression by its node, wherever it is moved
+        let moved = Box::new(right.as_ref().clone());
+        assert_eq!(
+            Some("unknown"),
+            symbols
+                .reference(&moved)
+                .map(|reference| reference.name.as_str())
+        );
+        let Some(Statement::Assignment { value, .. }) = body.iter().rev().nth(1) else {
+            panic!("Expected the assignment of total");
+        };
+        let types = check_types(&program).0;
+        let moved = Box::new(value.clone());
+        assert_eq!(Some(&Type::Int), types.get(&moved));
+    }
+
+    #[test]
+    fn test_locals_declared_after_nested_scopes() {
+        let input_code = r#"class Program
+{
+    static void Main()
+    {
+        {
+            int z = 1;
+        }
+        int z = 2;
+        if (z > 1)
+        {
+            int y = 3;
+        }
+        for (int i = 0; i < 2; i++)
+        {
+            int w = i;
+        }
+        int w = 4;
+        System.Func<int, int> f = x => x + 1;
+        int x = 5;
+        {
+            int v = 1;
+        }
+        {
+            int v = 2;
+        }
+    }
+}
+"#;
+
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![
+                Diagnostic::error(
+                    "`z` cannot be declared in Main, a nested scope before it already declares it"
+                ),
+                Diagnostic::error(
+                    "`w` cannot be declared in Main, a nested scope before it already declares it"
+                ),
+                Diagnostic::error(
+                    "`x` cannot be declared in Main, a nested scope before it already declares it"
+                ),
+            ],
+            program.diagnostics
+        );
+    }
+
+    #[test]
+    fn test_code_type_checking() {
+        let input_code = r#"class Program {
+    static bool IsEven(int n) {
+        return n % 2 == 0;
+    }
+    static void Main() {
+        string line = " 42 ";
+        int n = int.Parse(line);
+        long big = long.Parse("9000000000");
+        int age = int.Parse(Console.ReadLine());
+        bool even = IsEven(n);
+        Console.WriteLine(even);
+        Console.WriteLine("even: " + even);
+        Console.WriteLine($"{n} is even: {IsEven(n)}");
+        Console.WriteLine(even.ToString());
+        int wrong = "text";
+        if (n) {
+            n = -line;
+        }
+        byte small = 300;
+        short fits = -5;
+        Console.WriteLine(n + age + big);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.util.Scanner;
+class Program {
+    static boolean isEven(int n) {
+        Scanner scanner = new Scanner(System.in);
+        return n % 2 == 0;
+    }
+    static void main(String[] args) {
+        Scanner scanner = new Scanner(System.in);
+        String line = " 42 ";
+        int n = Integer.parseInt(line.trim());
+        long big = Long.parseLong("9000000000");
+        int age = scanner.nextInt();
+        boolean even = isEven(n);
+        System.out.println(even ? "True" : "False");
+        System.out.println("even: " + (even ? "True" : "False"));
+        System.out.println(n + " is even: " + (isEven(n) ? "True" : "False"));
+        System.out.println((even ? "True" : "False"));
+        int wrong = "text";
+        if (n) {
+            n = -line;
+        }
+        byte small = (byte) 300;
+        short fits = -5;
+        System.out.println(n + age + big);
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![
+                Diagnostic::error("cannot convert `string` to `int` in Main"),
+                Diagnostic::error("the condition has the type `int` instead of `bool` in Main"),
+                Diagnostic::error("the operator `-` cannot be applied to `string` in Main"),
+                Diagnostic::error("cannot convert `int` to `byte` in Main"),
+            ],
+            program.diagnostics
+        );
+    }
+
+    #[test]
+    fn test_code_string_comparison() {
+        let input_code = r#"class Program {
+    static string Role(string name) {
+        if (name == "admin") {
+            return "root";
+        }
+        return name;
+    }
+    static void Main() {
+        string name = Console.ReadLine();
+        string role = Role(name);
+        if (role != "root" && name != null) {
+            Console.WriteLine("not an admin");
+        }
+        int order = string.Compare(name, "bob");
+        int loose = string.Compare(name, "Bob", true);
+        bool same = string.Equals(name, role);
+        bool similar = string.Equals(name, "ADMIN", StringComparison.OrdinalIgnoreCase);
+        bool exact = name.Equals(role, StringComparison.Ordinal);
+        int before = name.CompareTo(role);
+        bool ignore = name == role;
+        int either = string.Compare(name, role, ignore);
+        object boxed = name;
+        bool sameObject = boxed == "admin";
+        if (Settings.Load() == Settings.Saved()) {
+            Console.WriteLine(order + loose + before + either);
+        }
+    }
+}
+"#;
+
+        let expected_code = r#"import java.util.Objects;
+import java.util.Scanner;
+class Program {
+    static String role(String name) {
+        Scanner scanner = new Scanner(System.in);
+        if (Objects.equals(name, "admin")) {
+            return "root";
+        }
+        return name;
+    }
+    static void main(String[] args) {
+        Scanner scanner = new Scanner(System.in);
+        String name = scanner.nextLine();
+        String role = role(name);
+        if (!Objects.equals(role, "root") && name != null) {
+            System.out.println("not an admin");
+        }
+        int order = name.compareTo("bob");
+        int loose = name.compareToIgnoreCase("Bob");
+        boolean same = Objects.equals(name, role);
+        boolean similar = name.equalsIgnoreCase("ADMIN");
+        boolean exact = name.equals(role);
+        int before = name.compareTo(role);
+        boolean ignore = Objects.equals(name, role);
+        int either = (ignore ? name.compareToIgnoreCase(role) : name.compareTo(role));
+        Object boxed = name;
+        boolean sameObject = boxed == "admin";
+        if (Settings.load() == Settings.saved()) {
+            System.out.println(order + loose + before + either);
+        }
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+
+        // the library calls have unknown types
+        let compare = Diagnostic::warning(
+            "string.Compare in Main becomes compareTo, which throws a NullPointerException for a null string and compares the char codes instead of using the culture",
+        );
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![
+                compare.clone(),
+                compare.clone(),
+                compare,
+                Diagnostic::warning(
+                    "the operands of `==` have unknown types in Main, java compares them by reference"
+                ),
+            ],
+            program.diagnostics
+        );
+    }
+
+    #[test]
+    fn test_code_overload_resolution() {
+        let input_code = r#"class Printer {
+    static void Show(byte value) {
+        Console.WriteLine("byte " + value);
+    }
+    static void Show(long value) {
+        Console.WriteLine("long " + value);
+    }
+    static void Show(string text) {
+        Console.WriteLine("text " + text);
+    }
+    static void Pair(int a, long b) {
+        Console.WriteLine(a + b);
+    }
+    static void Pair(long a, int b) {
+        Console.WriteLine(a - b);
+    }
+    static void Scale(uint factor) {
+        Console.WriteLine(factor);
+    }
+    static void Scale(int factor) {
+        Console.WriteLine(-factor);
+    }
+    static void Code(ushort unit) {
+        Console.WriteLine("unit " + unit);
+    }
+    static void Code(int value) {
+        Console.WriteLine("value " + value);
+    }
+    static double Half(double x) {
+        return x / 2;
+    }
+    static decimal Half(decimal x) {
+        return x / 2;
+    }
+    static void Main() {
+        int count = 3;
+        uint big = 7u;
+        Show(200);
+        Show(count);
+        Show(big);
+        Show("hi");
+        Show(count + 1);
+        Pair(1, 2);
+        Pair(count, 2L);
+        Scale(4);
+        char letter = 'A';
+        Code(letter);
+        Code(letter + 1);
+        Console.WriteLine(Half(2.5m));
+    }
+}
+"#;
+
+        let expected_code = r#"import java.math.BigDecimal;
+import java.math.MathContext;
+import java.math.RoundingMode;
+class Printer {
+    static void show(byte value) {
+        System.out.println("byte " + (value & 255));
+    }
+    static void show(long value) {
+        System.out.println("long " + value);
+    }
+    static void show(String text) {
+        System.out.println("text " + text);
+    }
+    static void pair(int a, long b) {
+        System.out.println(a + b);
+    }
+    static void pair(long a, int b) {
+        System.out.println(a - b);
+    }
+    static void scale(int factor) {
+        System.out.println(Integer.toUnsignedString(factor));
+    }
+    static void scale(int factor) {
+        System.out.println(-factor);
+    }
+    static void code(short unit) {
+        System.out.println("unit " + (unit & 65535));
+    }
+    static void code(int value) {
+        System.out.println("value " + value);
+    }
+    static double half(double x) {
+        return x / 2;
+    }
+    static BigDecimal half(BigDecimal x) {
+        return x.divide(BigDecimal.valueOf(2), new MathContext(29, RoundingMode.HALF_EVEN));
+    }
+    static void main(String[] args) {
+        int count = 3;
+        int big = 7;
+        show((byte) 200);
+        show(count);
+        show(Integer.toUnsignedLong(big));
+        show("hi");
+        show(count + 1);
+        pair(1, 2);
+        pair(count, 2L);
+        scale(4);
+        char letter = 'A';
+        code((short) letter);
+        code(letter + 1);
+        System.out.println(half(new BigDecimal("2.5")));
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![
+                Diagnostic::error(
+                    "the overloads Scale(uint) and Scale(int) have the same java signature in Printer"
+                ),
+                Diagnostic::error(
+                    "the call to `Pair` is ambiguous between Pair(int, long) and Pair(long, int) in Main"
+                ),
+            ],
+            program.diagnostics
+        );
+    }
+
+    #[test]
+    fn test_code_optional_parameters() {
+        let input_code = r#"class Shop {
+    static decimal Price(decimal amount, int quantity = 1, decimal discount = 0.1m) {
+        return amount * quantity * (1 - discount);
+    }
+    static void Log(string message, string level = "info", bool timestamp = false) {
+        Console.WriteLine(level + ": " + message + " " + timestamp);
+    }
+    static void Log(string message) {
+        Console.WriteLine(message);
+    }
+    static int Sum(string label, params int[] values) {
+        Console.WriteLine(label + values.Length);
+        return values.Length;
+    }
+    static void Main() {
+        decimal single = Price(9.99m);
+        decimal bulk = Price(quantity: 10, amount: 4.5m);
+        decimal sale = Price(20m, discount: 0.25m);
+        Log("started");
+        Log("stopped", timestamp: true);
+        Log(level: "warn", message: "low stock");
+        Sum("none");
+        Sum("three", 1, 2, 3);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.math.BigDecimal;
+class Shop {
+    static BigDecimal price(BigDecimal amount, int quantity, BigDecimal discount) {
+        return amount.multiply(BigDecimal.valueOf(quantity)).multiply(BigDecimal.ONE.subtract(discount));
+    }
+    static BigDecimal price(BigDecimal amount) {
+        return price(amount, 1, new BigDecimal("0.1"));
+    }
+    static BigDecimal price(BigDecimal amount, int quantity) {
+        return price(amount, quantity, new BigDecimal("0.1"));
+    }
+    static void log(String message, String level, boolean timestamp) {
+        System.out.println(level + ": " + message + " " + (timestamp ? "True" : "False"));
+    }
+    static void log(String message, String level) {
+        log(message, level, false);
+    }
+    static void log(String message) {
+        System.out.println(message);
+    }
+    static int sum(String label, int... values) {
+        System.out.println(label + values.length);
+        return values.length;
+    }
+    static void main(String[] args) {
+        BigDecimal single = price(new BigDecimal("9.99"));
+        BigDecimal bulk = price(new BigDecimal("4.5"), 10);
+        BigDecimal sale = price(new BigDecimal("20"), 1, new BigDecimal("0.25"));
+        log("started");
+        log("stopped", "info", true);
+        log("low stock", "warn");
+        sum("none");
+        sum("three", 1, 2, 3);
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_ref_out_parameters() {
+        let input_code = r#"class Program {
+    static void Swap(ref int a, ref int b) {
+        int temp = a;
+        a = b;
+        b = temp;
+    }
+    static void Divide(int a, int b, out int quotient, out int remainder) {
+        quotient = a / b;
+        remainder = a % b;
+    }
+    static void Rename(ref string name) {
+        name = name + "!";
+    }
+    static void Main() {
+        int x = 1;
+        int y = 2;
+        Swap(ref x, ref y);
+        Console.WriteLine(x + " " + y);
+        Divide(17, 5, out int q, out var r);
+        Console.WriteLine(q + " " + r);
+        string name = "Ada";
+        Rename(ref name);
+        Console.WriteLine(name);
+        if (int.TryParse(Console.ReadLine(), out var number)) {
+            Console.WriteLine(number + 1);
+        }
+        long big;
+        long.TryParse("123", out big);
+        Console.WriteLine(big);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.util.Scanner;
+class Program {
+    static void swap(IntRef a, IntRef b) {
+        Scanner scanner = new Scanner(System.in);
+        int temp = a.value;
+        a.value = b.value;
+        b.value = temp;
+    }
+    static void divide(int a, int b, IntRef quotient, IntRef remainder) {
+        Scanner scanner = new Scanner(System.in);
+        quotient.value = a / b;
+        remainder.value = a % b;
+    }
+    static void rename(Ref<String> name) {
+        Scanner scanner = new Scanner(System.in);
+        name.value = name.value + "!";
+    }
+    static void main(String[] args) {
+        Scanner scanner = new Scanner(System.in);
+        IntRef x = new IntRef(1);
+        IntRef y = new IntRef(2);
+        swap(x, y);
+        System.out.println(x.value + " " + y.value);
+        IntRef q = new IntRef(0);
+        IntRef r = new IntRef(0);
+        divide(17, 5, q, r);
+        System.out.println(q.value + " " + r.value);
+        Ref<String> name = new Ref<String>("Ada");
+        rename(name);
+        System.out.println(name.value);
+        int number;
+        boolean numberParsed;
+        try {
+            number = Integer.parseInt(scanner.nextLine().trim());
+            numberParsed = true;
+        }
+        catch (NumberFormatException e) {
+            number = 0;
+            numberParsed = false;
+        }
+        if (numberParsed) {
+            System.out.println(number + 1);
+        }
+        long big;
+        boolean bigParsed;
+        try {
+            big = Long.parseLong("123".trim());
+            bigParsed = true;
+        }
+        catch (NumberFormatException e) {
+            big = 0L;
+            bigParsed = false;
+        }
+        System.out.println(big);
+    }
+}
+class IntRef {
+    int value;
+    IntRef(int value) {
+        this.value = value;
+    }
+}
+class Ref<T> {
+    T value;
+    Ref(T value) {
+        this.value = value;
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_lambdas_and_delegates() {
+        let input_code = r#"delegate int BinaryOp(int a, int b);
+
+class Calculator {
+    static int Square(int x) {
+        return x * x;
+    }
+    static int Apply(Func<int, int> f, int value) {
+        return f(value);
+    }
+    static int Combine(BinaryOp op, int a, int b) {
+        return op.Invoke(a, b);
+    }
+    static void Main() {
+        Func<int, int> twice = x => x * 2;
+        Func<int, int, int> add = (a, b) => a + b;
+        Func<int, int, int, int> sum3 = (a, b, c) => a + b + c;
+        Predicate<int> isEven = n => n % 2 == 0;
+        Action<string> print = Console.WriteLine;
+        Action hello = () => Console.WriteLine("hello");
+        Func<double, double> abs = Math.Abs;
+        BinaryOp multiply = delegate (int a, int b) { return a * b; };
+        int count = 0;
+        Action increment = () => {
+            count++;
+            Console.WriteLine("count " + count);
+        };
+        Console.WriteLine(twice(4));
+        Console.WriteLine(add.Invoke(2, 3));
+        Console.WriteLine(sum3(1, 2, 3));
+        Console.WriteLine(isEven(4));
+        Console.WriteLine(Apply(Square, 5));
+        Console.WriteLine(Combine(multiply, 6, 7));
+        print("done");
+        hello();
+        increment();
+        Console.WriteLine(abs(-2.5));
+    }
+}
+"#;
+
+        let expected_code = r#"import java.util.function.BiFunction;
+import java.util.function.Consumer;
+import java.util.function.Function;
+import java.util.function.Predicate;
+@FunctionalInterface
+interface BinaryOp {
+    int invoke(int a, int b);
+}
+class Calculator {
+    static int square(int x) {
+        return x * x;
+    }
+    static int apply(Function<Integer, Integer> f, int value) {
+        return f.apply(value);
+    }
+    static int combine(BinaryOp op, int a, int b) {
+        return op.invoke(a, b);
+    }
+    static void main(String[] args) {
+        Function<Integer, Integer> twice = x -> x * 2;
+        BiFunction<Integer, Integer, Integer> add = (a, b) -> a + b;
+        Function3<Integer, Integer, Integer, Integer> sum3 = (a, b, c) -> a + b + c;
+        Predicate<Integer> isEven = n -> n % 2 == 0;
+        Consumer<String> print = System.out::println;
+        Runnable hello = () -> System.out.println("hello");
+        Function<Double, Double> abs = Math::abs;
+        BinaryOp multiply = (a, b) -> {
+            return a * b;
+        };
+        IntRef count = new IntRef(0);
+        Runnable increment = () -> {
+            count.value++;
+            System.out.println("count " + count.value);
+        };
+        System.out.println(twice.apply(4));
+        System.out.println(add.apply(2, 3));
+        System.out.println(sum3.apply(1, 2, 3));
+        System.out.println(isEven.test(4) ? "True" : "False");
+        System.out.println(apply(Calculator::square, 5));
+        System.out.println(combine(multiply, 6, 7));
+        print.accept("done");
+        hello.run();
+        increment.run();
+        System.out.println(abs.apply(-2.5));
+    }
+}
+class IntRef {
+    int value;
+    IntRef(int value) {
+        this.value = value;
+    }
+}
+@FunctionalInterface
+interface Function3<T1, T2, T3, R> {
+    R apply(T1 t1, T2 t2, T3 t3);
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_events() {
+        let input_code = r#"using System;
+
+public delegate void PriceHandler(string item, decimal price);
+
+class Button
+{
+    public event Action<string> Clicked;
+    public event PriceHandler PriceChanged;
+
+    public void Click(string name)
+    {
+        Clicked?.Invoke(name);
+    }
+
+    public void ChangePrice(string item, decimal price)
+    {
+        if (PriceChanged != null)
+        {
+            PriceChanged(item, price);
+        }
+    }
+}
+
+class Program
+{
+    static void OnClick(string name)
+    {
+        Console.WriteLine("Clicked " + name);
+    }
+
+    static void Main()
+    {
+        Button button = new Button();
+        int clicks = 0;
+        Action<string> logger = name => Console.WriteLine("Log " + name);
+        button.Clicked += OnClick;
+        button.Clicked += logger;
+        button.Clicked += name => clicks++;
+        button.PriceChanged += (item, price) => Console.WriteLine(item + " " + price);
+        button.Click("ok");
+        button.ChangePrice("apple", 1.5m);
+        button.Clicked -= logger;
+        button.Clicked -= OnClick;
+        button.Click("again");
+        Button other = null;
+        other?.Click("none");
+        Console.WriteLine(clicks);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.math.BigDecimal;
+import java.util.ArrayList;
+import java.util.List;
+import java.util.function.Consumer;
+@FunctionalInterface
+interface PriceHandler {
+    void invoke(String item, BigDecimal price);
+}
+class Button {
+    private final List<Consumer<String>> clickedListeners = new ArrayList<>();
+    private final List<PriceHandler> priceChangedListeners = new ArrayList<>();
+    public void addClickedListener(Consumer<String> listener) {
+        clickedListeners.add(listener);
+    }
+    public void removeClickedListener(Consumer<String> listener) {
+        clickedListeners.remove(listener);
+    }
+    public void addPriceChangedListener(PriceHandler listener) {
+        priceChangedListeners.add(listener);
+    }
+    public void removePriceChangedListener(PriceHandler listener) {
+        priceChangedListeners.remove(listener);
+    }
+    public void click(String name) {
+        new ArrayList<>(clickedListeners).forEach(listener -> listener.accept(name));
+    }
+    public void changePrice(String item, BigDecimal price) {
+        if (!priceChangedListeners.isEmpty()) {
+            new ArrayList<>(priceChangedListeners).forEach(listener -> listener.invoke(item, price));
+        }
+    }
+}
+class Program {
+    private static final Consumer<String> onClickListener = Program::onClick;
+    static void onClick(String name) {
+        System.out.println("Clicked " + name);
+    }
+    static void main(String[] args) {
+        Button button = new Button();
+        IntRef clicks = new IntRef(0);
+        Consumer<String> logger = name -> System.out.println("Log " + name);
+        button.addClickedListener(onClickListener);
+        button.addClickedListener(logger);
+        button.addClickedListener(name -> clicks.value++);
+        button.addPriceChangedListener((item, price) -> System.out.println(item + " " + price));
+        button.click("ok");
+        button.changePrice("apple", new BigDecimal("1.5"));
+        button.removeClickedListener(logger);
+        button.removeClickedListener(onClickListener);
+        button.click("again");
+        Button other = null;
+        if (other != null) {
+            other.click("none");
+        }
+        System.out.println(clicks.value);
+    }
+}
+class IntRef {
+    int value;
+    IntRef(int value) {
+        this.value = value;
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_linq_methods() {
+        let input_code = r#"using System;
+using System.Collections.Generic;
+using System.Linq;
+
+class Report
+{
+    static IEnumerable<int> Positive(List<int> numbers)
+    {
+        return numbers.Where(n => n > 0);
+    }
+
+    static void Print(int[] values, List<decimal> prices)
+    {
+        var evens = values.Where(v => v % 2 == 0);
+        List<int> squares = values.Select(v => v * v).ToList();
+        List<string> words = values.OrderByDescending(v => v).ThenBy(v => v % 3).Select(v => "n" + v).ToList();
+        bool any = values.Any();
+        bool big = values.Any(v => v > 7);
+        bool small = values.All(v => v < 10);
+        int first = values.First();
+        int firstBig = values.FirstOrDefault(v => v > 100);
+        int total = values.Sum();
+        int doubled = values.Sum(v => v * 2);
+        decimal cost = prices.Sum();
+        int count = values.Where(v => v > 2).Distinct().Count();
+        int threes = values.Count(v => v == 3);
+        var groups = values.GroupBy(v => v % 2).Select(g => g.Key + ": " + g.Count()).ToList();
+        Dictionary<int, string> names = values.Distinct().ToDictionary(v => v, v => "n" + v);
+        Console.WriteLine(total + doubled + count + threes);
+        Console.WriteLine(any && big && small);
+        Console.WriteLine(first + firstBig);
+        Console.WriteLine(cost);
+        Console.WriteLine(words);
+        Console.WriteLine(groups);
+        Console.WriteLine(names.Count);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.math.BigDecimal;
+import java.util.Arrays;
+import java.util.Collection;
+import java.util.Comparator;
+import java.util.LinkedHashMap;
+import java.util.List;
+import java.util.Map;
+import java.util.stream.Collectors;
+class Report {
+    static Collection<Integer> positive(List<Integer> numbers) {
+        return numbers.stream().filter(n -> n > 0).collect(Collectors.toList());
+    }
+    static void print(int[] values, List<BigDecimal> prices) {
+        var evens = Arrays.stream(values).boxed().filter(v -> v % 2 == 0).collect(Collectors.toList());
+        List<Integer> squares = Arrays.stream(values).boxed().map(v -> v * v).collect(Collectors.toList());
+        List<String> words = Arrays.stream(values).boxed().sorted(Comparator.comparing(v -> v % 3)).sorted(Comparator.comparing(v -> v, Comparator.reverseOrder())).map(v -> "n" + v).collect(Collectors.toList());
+        boolean any = Arrays.stream(values).boxed().findAny().isPresent();
+        boolean big = Arrays.stream(values).boxed().anyMatch(v -> v > 7);
+        boolean small = Arrays.stream(values).boxed().allMatch(v -> v < 10);
+        int first = Arrays.stream(values).boxed().findFirst().orElseThrow();
+        int firstBig = Arrays.stream(values).boxed().filter(v -> v > 100).findFirst().orElse(0);
+        int total = Arrays.stream(values).boxed().mapToInt(Integer::intValue).reduce(0, Math::addExact);
+        int doubled = Arrays.stream(values).boxed().mapToInt(v -> v * 2).reduce(0, Math::addExact);
+        BigDecimal cost = prices.stream().reduce(BigDecimal.ZERO, BigDecimal::add);
+        int count = (int) Arrays.stream(values).boxed().filter(v -> v > 2).distinct().count();
+        int threes = (int) Arrays.stream(values).boxed().filter(v -> v == 3).count();
+        var groups = Arrays.stream(values).boxed().collect(Collectors.groupingBy(v -> v % 2, LinkedHashMap::new, Collectors.toList())).entrySet().stream().map(g -> g.getKey() + ": " + (int) g.getValue().stream().count()).collect(Collectors.toList());
+        Map<Integer, String> names = Arrays.stream(values).boxed().distinct().collect(Collectors.toMap(v -> v, v -> "n" + v, (a, b) -> {
+            throw new IllegalStateException("Duplicate key");
+        }, LinkedHashMap::new));
+        System.out.println(total + doubled + count + threes);
+        System.out.println((any && big && small) ? "True" : "False");
+        System.out.println(first + firstBig);
+        System.out.println(cost);
+        System.out.println(words);
+        System.out.println(groups);
+        System.out.println(names.size());
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_linq_queries() {
+        let input_code = r#"using System;
+using System.Collections.Generic;
+using System.Linq;
+
+class Person
+{
+    public string Name;
+    public int Age;
+    public List<string> Pets;
+}
+
+class Order
+{
+    public string Owner;
+    public decimal Total;
+}
+
+class Queries
+{
+    static void Run(List<Person> people, List<Order> orders, List<int> numbers)
+    {
+        var doubled = from n in numbers
+                      let d = n * 2
+                      where d > 3
+                      orderby d descending, n
+                      select d + 1;
+        List<string> adults = (from p in people where p.Age >= 18 orderby p.Name select p.Name).ToList();
+        var byAge = from p in people
+                    group p.Name by p.Age into ages
+                    select ages.Key + ": " + ages.Count();
+        var bought = from p in people
+                     join o in orders on p.Name equals o.Owner
+                     where o.Total > 10
+                     select p.Name + " " + o.Total;
+        var pets = from p in people
+                   from pet in p.Pets
+                   select p.Name + " has " + pet;
+        var owners = from p in people
+                     join o in orders on p.Name equals o.Owner into owned
+                     orderby owned.Count() descending
+                     select p.Name + " " + owned.Count();
+        var sorted = from p in people
+                     from pet in p.Pets
+                     orderby pet, p.Age
+                     select p.Name + " has " + pet;
+        int count = (from n in numbers where n > 0 select n).Count();
+        Console.WriteLine(count);
+        Console.WriteLine(adults);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.math.BigDecimal;
+import java.util.AbstractMap.SimpleImmutableEntry;
+import java.util.Comparator;
+import java.util.LinkedHashMap;
+import java.util.List;
+import java.util.Objects;
+import java.util.stream.Collectors;
+class Person {
+    public String Name;
+    public int Age;
+    public List<String> Pets;
+}
+class Order {
+    public String Owner;
+    public BigDecimal Total;
+}
+class Queries {
+    static void run(List<Person> people, List<Order> orders, List<Integer> numbers) {
+        var doubled = numbers.stream().map(n -> new SimpleImmutableEntry<>(n, n * 2)).filter(n_d -> n_d.getValue() > 3).sorted(Comparator.comparing(n_d -> n_d.getKey())).sorted(Comparator.comparing(n_d -> n_d.getValue(), Comparator.reverseOrder())).map(n_d -> n_d.getValue() + 1).collect(Collectors.toList());
+        List<String> adults = (people.stream().filter(p -> p.Age >= 18).sorted(Comparator.comparing(p -> p.Name)).map(p -> p.Name)).collect(Collectors.toList());
+        var byAge = people.stream().collect(Collectors.groupingBy(p -> p.Age, LinkedHashMap::new, Collectors.mapping(p -> p.Name, Collectors.toList()))).entrySet().stream().map(ages -> ages.getKey() + ": " + (int) ages.getValue().stream().count()).collect(Collectors.toList());
+        var bought = people.stream().flatMap(p -> orders.stream().filter(o -> Objects.equals(p.Name, o.Owner)).filter(o -> o.Total.compareTo(BigDecimal.TEN) > 0).map(o -> p.Name + " " + o.Total)).collect(Collectors.toList());
+        var pets = people.stream().flatMap(p -> p.Pets.stream().map(pet -> p.Name + " has " + pet)).collect(Collectors.toList());
+        var owners = people.stream().map(p -> new SimpleImmutableEntry<>(p, orders.stream().filter(o -> Objects.equals(p.Name, o.Owner)).collect(Collectors.toList()))).sorted(Comparator.comparing(p_owned -> (int) p_owned.getValue().stream().count(), Comparator.reverseOrder())).map(p_owned -> p_owned.getKey().Name + " " + (int) p_owned.getValue().stream().count()).collect(Collectors.toList());
+        var sorted = people.stream().flatMap(p -> p.Pets.stream().map(pet -> new SimpleImmutableEntry<>(p, pet))).sorted(Comparator.comparing(p_pet -> p_pet.getKey().Age)).sorted(Comparator.comparing(p_pet -> p_pet.getValue())).map(p_pet -> p_pet.getKey().Name + " has " + p_pet.getValue()).collect(Collectors.toList());
+        int count = (int) (numbers.stream().filter(n -> n > 0)).count();
+        System.out.println(count);
+        System.out.println(adults);
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_collections() {
+        let input_code = r#"using System;
+using System.Collections.Generic;
+
+class Inventory
+{
+    static void Main()
+    {
+        List<int> numbers = new List<int>();
+        numbers.Add(4);
+        numbers.Add(7);
+        numbers.Insert(0, 1);
+        numbers.Remove(7);
+        numbers[0] = numbers[1] * 2;
+        numbers[1] += 3;
+        Console.WriteLine(numbers.Count + " " + numbers.Contains(4));
+
+        Dictionary<string, int> stock = new Dictionary<string, int>();
+        stock["apple"] = 3;
+        stock.Add("pear", 5);
+        stock["apple"]++;
+        if (stock.ContainsKey("pear"))
+        {
+            stock.Remove("pear");
+        }
+        if (stock.TryGetValue("apple", out int apples))
+        {
+            Console.WriteLine(apples);
+        }
+        Console.WriteLine(stock.Count + stock["apple"]);
+
+        HashSet<string> seen = new HashSet<string>();
+        bool added = seen.Add("a");
+        Queue<string> tasks = new Queue<string>();
+        tasks.Enqueue("first");
+        string next = tasks.Dequeue();
+        Stack<int> undo = new Stack<int>();
+        undo.Push(1);
+        int top = undo.Peek() + undo.Pop();
+        Console.WriteLine(next + top + seen.Count + tasks.Count);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.util.ArrayDeque;
+import java.util.ArrayList;
+import java.util.Deque;
+import java.util.LinkedHashMap;
+import java.util.LinkedHashSet;
+import java.util.List;
+import java.util.Map;
+import java.util.NoSuchElementException;
+import java.util.Queue;
+import java.util.Set;
+class Inventory {
+    static void main(String[] args) {
+        List<Integer> numbers = new ArrayList<Integer>();
+        numbers.add(4);
+        numbers.add(7);
+        numbers.add(0, 1);
+        numbers.remove((Integer) 7);
+        numbers.set(0, numbers.get(1) * 2);
+        numbers.set(1, numbers.get(1) + 3);
+        System.out.println(numbers.size() + " " + (numbers.contains(4) ? "True" : "False"));
+        Map<String, Integer> stock = new LinkedHashMap<String, Integer>();
+        stock.put("apple", 3);
+        stock.merge("pear", 5, (a, b) -> {
+            throw new IllegalStateException("Duplicate key");
+        });
+        stock.put("apple", stock.computeIfAbsent("apple", key -> {
+            throw new NoSuchElementException("The given key '" + key + "' was not present in the dictionary.");
+        }) + 1);
+        if (stock.containsKey("pear")) {
+            stock.remove("pear");
+        }
+        boolean applesFound = stock.containsKey("apple");
+        int apples = stock.getOrDefault("apple", 0);
+        if (applesFound) {
+            System.out.println(apples);
+        }
+        System.out.println(stock.size() + stock.computeIfAbsent("apple", key -> {
+            throw new NoSuchElementException("The given key '" + key + "' was not present in the dictionary.");
+        }));
+        Set<String> seen = new LinkedHashSet<String>();
+        boolean added = seen.add("a");
+        Queue<String> tasks = new ArrayDeque<String>();
+        tasks.add("first");
+        String next = tasks.remove();
+        Deque<Integer> undo = new ArrayDeque<Integer>();
+        undo.push(1);
+        int top = undo.element() + undo.pop();
+        System.out.println(next + top + seen.size() + tasks.size());
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_project_mappings() {
+        let input_code = r#"class Program {
+    public static void Main() {
+        string first = "Ada";
+        string last = "Lovelace";
+        Audit.Log(first + " " + last, 3);
+        Console.Write(Text.Shout(first + last) + Environment.NewLine);
+        Console.WriteLine(int.Parse(" 42 ") > 40);
+        Console.WriteLine(Math.Abs(-2.5) + Math.Abs(-3));
+    }
+}
+"#;
+
+        // the mappings of an in-house library, added to the builtin ones
+        let project = r#"[[methods]]
+csharp = "Audit.Log"
+java = "AuditLog.write({1}, {0})"
+imports = ["com.acme.audit.AuditLog"]
+
+[[methods]]
+csharp = "Text.Shout"
+java = "{0}.toUpperCase()"
+
+# replaces the builtin int.Parse, which has no arguments or types either
+[[methods]]
+csharp = "int.Parse"
+java = "Numbers.parseInt({0})"
+imports = ["com.acme.text.Numbers"]
+
+# added next to the builtin Math.Abs, only for doubles
+[[methods]]
+csharp = "Math.Abs"
+java = "Numbers.abs({0})"
+types = ["double"]
+"#;
+
+        let expected_code = r#"import com.acme.audit.AuditLog;
+import com.acme.text.Numbers;
+class Program {
+    public static void main(String[] args) {
+        String first = "Ada";
+        String last = "Lovelace";
+        AuditLog.write(3, first + " " + last);
+        System.out.print((first + last).toUpperCase() + System.lineSeparator());
+        System.out.println((Numbers.parseInt(" 42 ") > 40) ? "True" : "False");
+        System.out.println(Numbers.abs(-2.5) + Math.absExact(-3));
+    }
+}
+"#;
+
+        let mut options = Options::default();
+        options
+            .mappings
+            .extend(Mappings::parse(project).expect("Invalid mappings"));
+        assert_eq!(
+            expected_code,
+            generate_code_with_options(input_code, &options)
+        );
+    }
+
+    #[test]
+    fn test_project_mappings_missing_arguments() {
+        let input_code = r#"class Program {
+    public static void Main() {
+        Audit.Log("started");
+        Audit.Log("stopped", 2);
+    }
+}
+"#;
+
+        let project = r#"[[methods]]
+csharp = "Audit.Log"
+java = "AuditLog.write({1}, {0})"
+"#;
+        let mut options = Options::default();
+        options
+            .mappings
+            .extend(Mappings::parse(project).expect("Invalid mappings"));
+        let program = build(input_code, &options);
+        assert_eq!(
+            vec![Diagnostic::error(
+                "the mapping of `Audit.Log` uses `{1}`, the call in Main has 1 argument"
+            )],
+            program.diagnostics
+        );
+
+        // a mapping for one argument is checked when it is read
+        let project = r#"[[methods]]
+csharp = "Audit.Log"
+java = "AuditLog.write({1}, {0})"
+arguments = 1
+"#;
+        assert_eq!(
+            Err("Audit.Log: the template uses `{1}`, the calls have 1 argument".to_string()),
+            Mappings::parse(project).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_code_string_methods() {
+        let input_code = r#"using System.Collections.Generic;
+
+class Program {
+    static string Describe(string name, List<int> scores) {
+        if (string.IsNullOrEmpty(name)) {
+            return "nobody";
+        }
+        string initial = name.Substring(0, 1).ToUpper();
+        string rest = name.Substring(1).ToLower();
+        return initial + rest + ": " + string.Join(", ", scores);
+    }
+
+    public static void Main() {
+        string line = "  ada,grace;;linus  ";
+        string trimmed = line.Trim();
+        string[] names = trimmed.Split(',', ';');
+        Console.WriteLine(names.Length);
+        Console.WriteLine(trimmed.Length);
+        Console.WriteLine(trimmed[0]);
+        Console.WriteLine(trimmed.Substring(4, 5));
+        Console.WriteLine(trimmed.Contains('g'));
+        Console.WriteLine(trimmed.IndexOf("grace"));
+        Console.WriteLine(trimmed.Replace(";", "|"));
+        Console.WriteLine(trimmed.StartsWith("ada"));
+        Console.WriteLine(string.Join("-", names));
+        Console.WriteLine(string.Concat("a", "b", "c"));
+        Console.WriteLine("7".PadLeft(3, '0') + "|" + "x".PadRight(3) + "|");
+        Console.WriteLine(string.IsNullOrWhiteSpace("   "));
+        string[] parts = "a,,b,".Split(',', StringSplitOptions.RemoveEmptyEntries);
+        Console.WriteLine(parts.Length);
+        Console.WriteLine("a.b.c".Split('.').Length);
+        List<int> scores = new List<int>();
+        scores.Add(3);
+        scores.Add(5);
+        Console.WriteLine(Describe("aDA", scores));
+        Console.WriteLine(string.Empty + "x".ToUpperInvariant());
+    }
+}
+"#;
+
+        let expected_code = r#"import java.util.ArrayList;
+import java.util.Arrays;
+import java.util.List;
+import java.util.Locale;
+import java.util.regex.Pattern;
+import java.util.stream.Collectors;
+class Program {
+    static String describe(String name, List<Integer> scores) {
+        if ((name == null || name.isEmpty())) {
+            return "nobody";
+        }
+        String initial = name.substring(0, 1).toUpperCase();
+        String rest = name.substring(1).toLowerCase();
+        return initial + rest + ": " + scores.stream().map(String::valueOf).collect(Collectors.joining(", "));
+    }
+    public static void main(String[] args) {
+        String line = "  ada,grace;;linus  ";
+        String trimmed = line.strip();
+        String[] names = trimmed.split(Pattern.quote(",") + "|" + Pattern.quote(";"), -1);
+        System.out.println(names.length);
+        System.out.println(trimmed.length());
+        System.out.println(trimmed.charAt(0));
+        System.out.println(trimmed.substring(4, 9));
+        System.out.println(trimmed.contains("g") ? "True" : "False");
+        System.out.println(trimmed.indexOf("grace"));
+        System.out.println(trimmed.replace(";", "|"));
+        System.out.println(trimmed.startsWith("ada") ? "True" : "False");
+        System.out.println(String.join("-", names));
+        System.out.println(("a" + "b" + "c"));
+        System.out.println(("0".repeat(Math.max(0, 3 - "7".length())) + "7") + "|" + ("x" + " ".repeat(Math.max(0, 3 - "x".length()))) + "|");
+        System.out.println(("   " == null || "   ".isBlank()) ? "True" : "False");
+        String[] parts = Arrays.stream("a,,b,".split(Pattern.quote(","), -1)).filter(part -> !part.isEmpty()).toArray(String[]::new);
+        System.out.println(parts.length);
+        System.out.println("a.b.c".split(Pattern.quote("."), -1).length);
+        List<Integer> scores = new ArrayList<Integer>();
+        scores.add(3);
+        scores.add(5);
+        System.out.println(describe("aDA", scores));
+        System.out.println("" + "x".toUpperCase(Locale.ROOT));
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_math_convert_char() {
+        let input_code = r#"class Program {
+    public static void Main() {
+        double price = 2.5;
+        int count = -7;
+        long big = 9000000000;
+        Console.WriteLine(Math.Round(price));
+        Console.WriteLine(Math.Round(3.5));
+        Console.WriteLine(Math.Round(2.345, 2));
+        Console.WriteLine(Math.Truncate(-price));
+        Console.WriteLine(Math.Max(count, 3) + Math.Abs(count));
+        Console.WriteLine(Math.Pow(2, 10) + Math.Floor(price) + Math.Ceiling(price));
+        Console.WriteLine(Math.Sign(count) + Math.Sign(-price));
+        Console.WriteLine(Convert.ToInt32(price));
+        Console.WriteLine(Convert.ToInt32(" 12 ") + Convert.ToInt32(true));
+        Console.WriteLine(Convert.ToInt64(big) + Convert.ToDouble(count));
+        Console.WriteLine(Convert.ToString(count > 0));
+        char c = '7';
+        Console.WriteLine(char.IsDigit(c) && !char.IsLetter(c));
+        Console.WriteLine(char.IsWhiteSpace(' '));
+        Console.WriteLine(char.ToUpper('q'));
+        Console.WriteLine(int.MaxValue);
+        Console.WriteLine(long.MinValue);
+        Console.WriteLine(double.MinValue < 0);
+        Console.WriteLine(double.IsNaN(double.NaN));
+        Console.WriteLine(Math.Round(-2.5, MidpointRounding.AwayFromZero));
+        Console.WriteLine(Convert.ToInt64(price * 1e9));
+        Console.WriteLine(uint.MaxValue + " " + ulong.MaxValue + " " + byte.MaxValue + " " + sbyte.MinValue);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.math.BigDecimal;
+import java.math.RoundingMode;
+class Program {
+    public static void main(String[] args) {
+        double price = 2.5;
+        int count = -7;
+        long big = 9000000000L;
+        System.out.println(Math.rint(price));
+        System.out.println(Math.rint(3.5));
+        System.out.println(DotNetMath.round(2.345, 2));
+        System.out.println(DotNetMath.truncate(-price));
+        System.out.println(Math.max(count, 3) + Math.absExact(count));
+        System.out.println(Math.pow(2, 10) + Math.floor(price) + Math.ceil(price));
+        System.out.println(Integer.signum(count) + (int) Math.signum(-price));
+        System.out.println(Math.toIntExact((long) Math.rint(price)));
+        System.out.println(Integer.parseInt("12") + (true ? 1 : 0));
+        System.out.println((long) big + (double) count);
+        System.out.println(((count > 0) ? "True" : "False"));
+        char c = '7';
+        System.out.println((Character.isDigit(c) && !Character.isLetter(c)) ? "True" : "False");
+        System.out.println((Character.isWhitespace(' ') || Character.isSpaceChar(' ')) ? "True" : "False");
+        System.out.println(Character.toUpperCase('q'));
+        System.out.println(Integer.MAX_VALUE);
+        System.out.println(Long.MIN_VALUE);
+        System.out.println((-Double.MAX_VALUE < 0) ? "True" : "False");
+        System.out.println(Double.isNaN(Double.NaN) ? "True" : "False");
+        System.out.println(DotNetMath.round(-2.5, RoundingMode.HALF_UP));
+        System.out.println(DotNetMath.toInt64(price * 1e9));
+        System.out.println(Integer.toUnsignedString(0xFFFFFFFF) + " " + Long.toUnsignedString(0xFFFFFFFFFFFFFFFFL) + " " + (((byte) 0xFF) & 255) + " " + Byte.MIN_VALUE);
+    }
+}
+class DotNetMath {
+    static double round(double value, RoundingMode mode) {
+        if (Double.isNaN(value) || Double.isInfinite(value)) {
+            return value;
+        }
+        return Math.copySign(new BigDecimal(value).setScale(0, mode).doubleValue(), value);
+    }
+    static double round(double value, int digits, RoundingMode mode) {
+        if (Math.abs(value) >= 1e16) {
+            return value;
+        }
+        double scale = Math.pow(10, digits);
+        return round(value * scale, mode) / scale;
+    }
+    static double round(double value, int digits) {
+        return round(value, digits, RoundingMode.HALF_EVEN);
+    }
+    static double truncate(double value) {
+        return value < 0 ? Math.ceil(value) : Math.floor(value);
+    }
+    static long toInt64(double value) {
+        double rounded = Math.rint(value);
+        if (!(rounded >= -0x1p63 && rounded < 0x1p63)) {
+            throw new ArithmeticException("Value was either too large or too small for an Int64.");
+        }
+        return (long) rounded;
+    }
+    static short toInt16(long value) {
+        if (value < Short.MIN_VALUE || value > Short.MAX_VALUE) {
+            throw new ArithmeticException("Value was either too large or too small for an Int16.");
+        }
+        return (short) value;
+    }
+    static short toUInt16(long value) {
+        if (value < 0 || value > 65535) {
+            throw new ArithmeticException("Value was either too large or too small for a UInt16.");
+        }
+        return (short) value;
+    }
+    static byte toByte(long value) {
+        if (value < 0 || value > 255) {
+            throw new ArithmeticException("Value was either too large or too small for an unsigned byte.");
+        }
+        return (byte) value;
+    }
+    static byte toSByte(long value) {
+        if (value < Byte.MIN_VALUE || value > Byte.MAX_VALUE) {
+            throw new ArithmeticException("Value was either too large or too small for a signed byte.");
+        }
+        return (byte) value;
+    }
+    static int toUInt32(long value) {
+        if (value < 0 || value > 0xFFFFFFFFL) {
+            throw new ArithmeticException("Value was either too large or too small for a UInt32.");
+        }
+        return (int) value;
+    }
+    static long toUInt64(long value) {
+        if (value < 0) {
+            throw new ArithmeticException("Value was either too large or too small for a UInt64.");
+        }
+        return value;
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_this_and_named_arguments() {
+        let input_code = r#"class Counter {
+    private int count = 0;
+    static int Next(int step) {
+        return step + 1;
+    }
+    static int Range(int low, int high) {
+        return high - low;
+    }
+    static void Show(Counter counter) {
+        Console.WriteLine(counter.count);
+    }
+    void Print() {
+        Show(this);
+        int width = Range(high: Next(5), low: Next(1));
+        int size = Range(high: 10, low: 2);
+        Console.WriteLine(width + size);
+    }
+}
+"#;
+
+        let expected_code = r#"class Counter {
+    private int count = 0;
+    static int next(int step) {
+        return step + 1;
+    }
+    static int range(int low, int high) {
+        return high - low;
+    }
+    static void show(Counter counter) {
+        System.out.println(counter.count);
+    }
+    void print() {
+        show(this);
+        int width = range(next(1), next(5));
+        int size = range(2, 10);
+        System.out.println(width + size);
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+
+        // Next(5) would run after Next(1) in java
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![Diagnostic::warning(
+                "the named arguments of `Range` in Print are evaluated in the order of the parameters, not in the written order"
+            )],
+            program.diagnostics
+        );
+    }
+
+    #[test]
+    fn test_code_this_values() {
+        let input_code = r#"class Builder {
+    private int size = 0;
+    Builder Grow(int amount) {
+        size = size + amount;
+        return this;
+    }
+    void Build() {
+        Builder current = this;
+        current.Grow(2).Grow(3);
+        Console.WriteLine(current.size);
+    }
+}
+"#;
+
+        let expected_code = r#"class Builder {
+    private int size = 0;
+    Builder grow(int amount) {
+        size = size + amount;
+        return this;
+    }
+    void build() {
+        Builder current = this;
+        current.grow(2).grow(3);
+        System.out.println(current.size);
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_nullable_calls() {
+        let input_code = r#"class Program {
+    static int? Next() {
+        return 4;
+    }
+    static void Main() {
+        int? a = 3;
+        int? g = Next() + 1;
+        int? h = a + 1;
+        double? dd = a;
+        long? ll = a;
+        bool same = Next() == 4;
+        int? n = -Next();
+        Console.WriteLine(g + " " + h + " " + dd + " " + ll + " " + same + " " + n);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.util.Objects;
+class Program {
+    static Integer next() {
+        return 4;
+    }
+    static void main(String[] args) {
+        Integer a = 3;
+        Integer g = next() + 1;
+        Integer h = (a == null ? null : a + 1);
+        Double dd = (a == null ? null : (double) a);
+        Long ll = (a == null ? null : (long) a);
+        boolean same = Objects.equals(next(), 4);
+        Integer n = -next();
+        System.out.println(Objects.toString(g, "") + " " + Objects.toString(h, "") + " " + Objects.toString(dd, "") + " " + Objects.toString(ll, "") + " " + (same ? "True" : "False") + " " + Objects.toString(n, ""));
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+
+        // Next() + 1 and -Next() would call Next() twice to check it
+        let program = build(input_code, &Options::default());
+        let unchecked = Diagnostic::warning(
+            "a nullable value computed by a call in Main is not checked for null, java throws a NullPointerException on null",
+        );
+        assert_eq!(vec![unchecked.clone(), unchecked], program.diagnostics);
+    }
+
+    #[test]
+    fn test_code_out_arguments_in_loops() {
+        let input_code = r#"class Program {
+    static int calls = 0;
+    static int Bump(ref int value) {
+        value = value + 1;
+        return value;
+    }
+    static void Main() {
+        int e = 0, total = 0;
+        bool kParsed = false;
+        string[] lines = "4,5,x".Split(",");
+        int i = 0;
+        int k;
+        while (int.TryParse(lines[i], out k)) {
+            total = total + k;
+            i++;
+        }
+        int j;
+        for (j = 0; int.TryParse(lines[j], out var n); j++) {
+            total = total + n;
+        }
+        if (int.TryParse("7", out k)) {
+            Bump(ref e);
+        }
+        Bump(ref calls);
+        Console.WriteLine(total + " " + e + " " + kParsed);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.util.regex.Pattern;
+class Program {
+    static int calls = 0;
+    static int bump(IntRef value) {
+        value.value = value.value + 1;
+        return value.value;
+    }
+    static void main(String[] args) {
+        IntRef e = new IntRef(0);
+        int total = 0;
+        boolean kParsed = false;
+        String[] lines = "4,5,x".split(Pattern.quote(","), -1);
+        int i = 0;
+        int k;
+        boolean kParsed2;
+        try {
+            k = Integer.parseInt(lines[i].trim());
+            kParsed2 = true;
+        }
+        catch (NumberFormatException e2) {
+            k = 0;
+            kParsed2 = false;
+        }
+        while (kParsed2) {
+            total = total + k;
+            i++;
+            try {
+                k = Integer.parseInt(lines[i].trim());
+                kParsed2 = true;
+            }
+            catch (NumberFormatException e2) {
+                k = 0;
+                kParsed2 = false;
+            }
+        }
+        int j;
+        j = 0;
+        int n;
+        boolean nParsed;
+        try {
+            n = Integer.parseInt(lines[j].trim());
+            nParsed = true;
+        }
+        catch (NumberFormatException e2) {
+            n = 0;
+            nParsed = false;
+        }
+        while (nParsed) {
+            total = total + n;
+            j++;
+            try {
+                n = Integer.parseInt(lines[j].trim());
+                nParsed = true;
+            }
+            catch (NumberFormatException e2) {
+                n = 0;
+                nParsed = false;
+            }
+        }
+        boolean kParsed3;
+        try {
+            k = Integer.parseInt("7".trim());
+            kParsed3 = true;
+        }
+        catch (NumberFormatException e2) {
+            k = 0;
+            kParsed3 = false;
+        }
+        if (kParsed3) {
+            bump(e);
+        }
+        IntRef callsRef = new IntRef(calls);
+        bump(callsRef);
+        calls = callsRef.value;
+        System.out.println(total + " " + e.value + " " + (kParsed ? "True" : "False"));
+    }
+}
+class IntRef {
+    int value;
+    IntRef(int value) {
+        this.value = value;
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+
+        // the local e is passed by ref, the field calls through a holder it is set back from
+        let program = build(input_code, &Options::default());
+        assert_eq!(Vec::<Diagnostic>::new(), program.diagnostics);
+    }
+
+    #[test]
+    fn test_code_string_calls_read_once() {
+        let input_code = r#"class Program {
+    static int calls = 0;
+    static string Get() {
+        calls++;
+        return "ab";
+    }
+    static int Start() {
+        calls++;
+        return 1;
+    }
+    static void Main() {
+        string text = "x";
+        string left = Get().PadLeft(5, '0');
+        string right = Get().PadRight(4);
+        bool empty = string.IsNullOrEmpty(Get());
+        bool blank = string.IsNullOrWhiteSpace(Get());
+        string part = "hello".Substring(Start(), 3);
+        string name = text.PadLeft(3);
+        string trimmed = text.Trim('x');
+        string[] parts = text.Split(',', StringSplitOptions.TrimEntries);
+        Console.WriteLine(left + right + empty + blank + part + name + calls);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.util.Objects;
+import java.util.Optional;
+class Program {
+    static int calls = 0;
+    static String get() {
+        calls++;
+        return "ab";
+    }
+    static int start() {
+        calls++;
+        return 1;
+    }
+    static void main(String[] args) {
+        String text = "x";
+        String left = Optional.of(get()).map(text2 -> ("0".repeat(Math.max(0, 5 - text2.length())) + text2)).get();
+        String right = Optional.of(get()).map(text2 -> (text2 + " ".repeat(Math.max(0, 4 - text2.length())))).get();
+        boolean empty = Objects.toString(get(), "").isEmpty();
+        boolean blank = Objects.toString(get(), "").isBlank();
+        String part = "hello".substring(start()).substring(0, 3);
+        String name = (" ".repeat(Math.max(0, 3 - text.length())) + text);
+        String trimmed = text.trim('x');
+        String[] parts = text.split(',', StringSplitOptions.TrimEntries);
+        System.out.println(left + right + (empty ? "True" : "False") + (blank ? "True" : "False") + part + name + calls);
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![
+                Diagnostic::error(
+                    "`Trim` with the characters to remove is not supported in Main, the call is left as it is"
+                ),
+                Diagnostic::error(
+                    "`Split` with these StringSplitOptions is not supported in Main, the call is left as it is"
+                ),
+            ],
+            program.diagnostics
+        );
+    }
+
+    #[test]
+    fn test_code_constants() {
+        let input_code = r#"class Program
+{
+    const int Limit = 200;
+    const long Big = 5;
+
+    static void Main()
+    {
+        const int K = 5;
+        const int Twice = K * 2 + 1;
+        byte kb = K;
+        byte limit = Limit;
+        sbyte small = -Twice;
+        ushort mask = Limit * 300;
+        int min = -2147483648;
+        long longMin = -9223372036854775808;
+        ulong fromLong = Big;
+        int fromBig = Big;
+        short tooBig = Limit * 1000;
+        int plain = 7;
+        byte notConstant = plain;
+        Console.WriteLine(kb + limit + small + mask + min + longMin);
+    }
+}
+"#;
+
+        let expected_code = r#"class Program {
+    static final int Limit = 200;
+    static final long Big = 5;
+    static void main(String[] args) {
+        final int K = 5;
+        final int Twice = K * 2 + 1;
+        byte kb = K;
+        byte limit = (byte) Limit;
+        byte small = -Twice;
+        short mask = (short) (Limit * 300);
+        int min = -2147483648;
+        long longMin = -9223372036854775808L;
+        long fromLong = Big;
+        int fromBig = Big;
+        short tooBig = Limit * 1000;
+        int plain = 7;
+        byte notConstant = plain;
+        System.out.println((kb & 255) + (limit & 255) + small + (mask & 65535) + min + longMin);
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+
+        // only the values which do not fit, or are not constants, are reported
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![
+                Diagnostic::error("cannot convert `long` to `int` in Main"),
+                Diagnostic::error("cannot convert `int` to `short` in Main"),
+                Diagnostic::error("cannot convert `int` to `byte` in Main"),
+            ],
+            program.diagnostics
+        );
+    }
+
+    #[test]
+    fn test_code_checked_casts() {
+        let input_code = r#"class Program
+{
+    static byte ToLevel(int percent)
+    {
+        if (percent > 100)
+        {
+            throw new OverflowException("percent above 100");
+        }
+        return checked((byte) (percent * 255 / 100));
+    }
+
+    static void Main()
+    {
+        int small = 100;
+        long big = 40000;
+        checked
+        {
+            short s = (short) small;
+            sbyte sb = (sbyte) (small + 20);
+            ushort us = (ushort) big;
+            Console.WriteLine(s + sb + us + ToLevel(50));
+        }
+        byte wrapped = (byte) big;
+        Console.WriteLine(wrapped);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.math.BigDecimal;
+import java.math.RoundingMode;
+class Program {
+    static byte toLevel(int percent) {
+        if (percent > 100) {
+            throw new ArithmeticException("percent above 100");
+        }
+        return DotNetMath.toByte(Math.multiplyExact(percent, 255) / 100);
+    }
+    static void main(String[] args) {
+        int small = 100;
+        long big = 40000;
+        {
+            short s = DotNetMath.toInt16(small);
+            byte sb = DotNetMath.toSByte(Math.addExact(small, 20));
+            short us = DotNetMath.toUInt16(big);
+            System.out.println(Math.addExact(Math.addExact(Math.addExact(s, sb), (us & 65535)), (toLevel(50) & 255)));
+        }
+        byte wrapped = (byte) big;
+        System.out.println(Integer.toString(wrapped & 255));
+    }
+}
+class DotNetMath {
+    static double round(double value, RoundingMode mode) {
+        if (Double.isNaN(value) || Double.isInfinite(value)) {
+            return value;
+        }
+        return Math.copySign(new BigDecimal(value).setScale(0, mode).doubleValue(), value);
+    }
+    static double round(double value, int digits, RoundingMode mode) {
+        if (Math.abs(value) >= 1e16) {
+            return value;
+        }
+        double scale = Math.pow(10, digits);
+        return round(value * scale, mode) / scale;
+    }
+    static double round(double value, int digits) {
+        return round(value, digits, RoundingMode.HALF_EVEN);
+    }
+    static double truncate(double value) {
+        return value < 0 ? Math.ceil(value) : Math.floor(value);
+    }
+    static long toInt64(double value) {
+        double rounded = Math.rint(value);
+        if (!(rounded >= -0x1p63 && rounded < 0x1p63)) {
+            throw new ArithmeticException("Value was either too large or too small for an Int64.");
+        }
+        return (long) rounded;
+    }
+    static short toInt16(long value) {
+        if (value < Short.MIN_VALUE || value > Short.MAX_VALUE) {
+            throw new ArithmeticException("Value was either too large or too small for an Int16.");
+        }
+        return (short) value;
+    }
+    static short toUInt16(long value) {
+        if (value < 0 || value > 65535) {
+            throw new ArithmeticException("Value was either too large or too small for a UInt16.");
+        }
+        return (short) value;
+    }
+    static byte toByte(long value) {
+        if (value < 0 || value > 255) {
+            throw new ArithmeticException("Value was either too large or too small for an unsigned byte.");
+        }
+        return (byte) value;
+    }
+    static byte toSByte(long value) {
+        if (value < Byte.MIN_VALUE || value > Byte.MAX_VALUE) {
+            throw new ArithmeticException("Value was either too large or too small for a signed byte.");
+        }
+        return (byte) value;
+    }
+    static int toUInt32(long value) {
+        if (value < 0 || value > 0xFFFFFFFFL) {
+            throw new ArithmeticException("Value was either too large or too small for a UInt32.");
+        }
+        return (int) value;
+    }
+    static long toUInt64(long value) {
+        if (value < 0) {
+            throw new ArithmeticException("Value was either too large or too small for a UInt64.");
+        }
+        return value;
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_event_handlers() {
+        let input_code = r#"using System;
+
+class Button
+{
+    public event EventHandler Clicked;
+    public event EventHandler<string> Renamed;
+    public string Label;
+
+    public void Click()
+    {
+        Clicked?.Invoke(this, EventArgs.Empty);
+        var handler = Renamed;
+        handler?.Invoke(this, "b");
+        if (handler != null)
+        {
+            handler(this, "c");
+        }
+    }
+
+    // the parameter hides the event
+    public void Forward(EventHandler Clicked)
+    {
+        Clicked(this, EventArgs.Empty);
+    }
+}
+
+class Program
+{
+    static void Main()
+    {
+        var b = new Button();
+        b.Clicked += (sender, e) => Console.WriteLine("clicked");
+        b.Renamed += (sender, name) => Console.WriteLine(name);
+        b.Click();
+        string s = null;
+        int? n = s?.Length;
+        Console.WriteLine(n == null);
+        s = "abc";
+        Console.WriteLine(s?.ToUpper());
+        var length = s?.Length;
+        Console.WriteLine(length + 1);
+        Console.WriteLine(b.Label?.Trim() == null);
+        Console.WriteLine(new Button().Label?.Length);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.util.ArrayList;
+import java.util.List;
+import java.util.function.BiConsumer;
+class Button {
+    private final List<BiConsumer<Object, Object>> clickedListeners = new ArrayList<>();
+    private final List<BiConsumer<Object, String>> renamedListeners = new ArrayList<>();
+    public String Label;
+    public void addClickedListener(BiConsumer<Object, Object> listener) {
+        clickedListeners.add(listener);
+    }
+    public void removeClickedListener(BiConsumer<Object, Object> listener) {
+        clickedListeners.remove(listener);
+    }
+    public void addRenamedListener(BiConsumer<Object, String> listener) {
+        renamedListeners.add(listener);
+    }
+    public void removeRenamedListener(BiConsumer<Object, String> listener) {
+        renamedListeners.remove(listener);
+    }
+    public void click() {
+        new ArrayList<>(clickedListeners).forEach(listener -> listener.accept(this, new Object()));
+        var handler = new ArrayList<>(renamedListeners);
+        handler.forEach(listener -> listener.accept(this, "b"));
+        if (!handler.isEmpty()) {
+            handler.forEach(listener -> listener.accept(this, "c"));
+        }
+    }
+    public void forward(BiConsumer<Object, Object> Clicked) {
+        Clicked.accept(this, new Object());
+    }
+}
+class Program {
+    static void main(String[] args) {
+        var b = new Button();
+        b.addClickedListener((sender, e) -> System.out.println("clicked"));
+        b.addRenamedListener((sender, name) -> System.out.println(name));
+        b.click();
+        String s = null;
+        Integer n = (s == null ? null : s.length());
+        System.out.println((n == null) ? "True" : "False");
+        s = "abc";
+        System.out.println((s == null ? null : s.toUpperCase()));
+        var length = (s == null ? null : s.length());
+        System.out.println(length + 1);
+        System.out.println(((b.Label == null ? null : b.Label.strip()) == null) ? "True" : "False");
+        System.out.println((new Button().Label == null ? null : new Button().Label.length()));
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+
+        // new Button() would be created twice
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![Diagnostic::error(
+                "the target of ?. is read twice in java, keep it in a variable in Main"
+            )],
+            program.diagnostics
+        );
+    }
+
+    #[test]
+    fn test_code_unsigned_widening() {
+        let input_code = r#"class Printer {
+    static void Show(byte value) {
+        Console.WriteLine("byte " + value);
+    }
+    static void Show(long value) {
+        Console.WriteLine("long " + value);
+    }
+    static void Show(string text) {
+        Console.WriteLine("text " + text);
+    }
+    static void Pair(int a, long b) {
+        Console.WriteLine(a + b);
+    }
+    static void Pair(long a, int b) {
+        Console.WriteLine(a - b);
+    }
+    static void Scale(uint factor) {
+        Console.WriteLine(factor);
+    }
+    static void Scale(int factor) {
+        Console.WriteLine(-factor);
+    }
+    static void Code(ushort unit) {
+        Console.WriteLine("unit " + unit);
+    }
+    static void Code(int value) {
+        Console.WriteLine("value " + value);
+    }
+    static double Half(double x) {
+        return x / 2;
+    }
+    static decimal Half(decimal x) {
+        return x / 2;
+    }
+    static void Main() {
+        int count = 3;
+        uint big = 7u;
+        Show(200);
+        Show(count);
+        Show(big);
+        Show("hi");
+        Show(count + 1);
+        Pair(1, 2);
+        Pair(count, 2L);
+        Scale(4);
+        char letter = 'A';
+        Code(letter);
+        Code(letter + 1);
+        Console.WriteLine(Half(2.5m));
+    }
+}
+"#;
+
+        let expected_code = r#"import java.math.BigDecimal;
+import java.math.MathContext;
+import java.math.RoundingMode;
+class Printer {
+    static void show(byte value) {
+        System.out.println("byte " + (value & 255));
+    }
+    static void show(long value) {
+        System.out.println("long " + value);
+    }
+    static void show(String text) {
+        System.out.println("text " + text);
+    }
+    static void pair(int a, long b) {
+        System.out.println(a + b);
+    }
+    static void pair(long a, int b) {
+        System.out.println(a - b);
+    }
+    static void scale(int factor) {
+        System.out.println(Integer.toUnsignedString(factor));
+    }
+    static void scale(int factor) {
+        System.out.println(-factor);
+    }
+    static void code(short unit) {
+        System.out.println("unit " + (unit & 65535));
+    }
+    static void code(int value) {
+        System.out.println("value " + value);
+    }
+    static double half(double x) {
+        return x / 2;
+    }
+    static BigDecimal half(BigDecimal x) {
+        return x.divide(BigDecimal.valueOf(2), new MathContext(29, RoundingMode.HALF_EVEN));
+    }
+    static void main(String[] args) {
+        int count = 3;
+        int big = 7;
+        show((byte) 200);
+        show(count);
+        show(Integer.toUnsignedLong(big));
+        show("hi");
+        show(count + 1);
+        pair(1, 2);
+        pair(count, 2L);
+        scale(4);
+        char letter = 'A';
+        code((short) letter);
+        code(letter + 1);
+        System.out.println(half(new BigDecimal("2.5")));
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_decimal_targets() {
+        let input_code = r#"using System;
+
+class Item
+{
+    public decimal Price;
+}
+
+class Program
+{
+    static Item item = new Item();
+    static int calls = 0;
+
+    static Item Get()
+    {
+        calls++;
+        return item;
+    }
+
+    static decimal Twice(decimal d)
+    {
+        return d * 2;
+    }
+
+    static void Main()
+    {
+        item.Price = 1.5m;
+        Get().Price++;
+        Get().Price += item.Price;
+        --Get().Price;
+        Console.WriteLine(calls);
+        Console.WriteLine(item.Price);
+        Console.WriteLine(Twice(5));
+        decimal a = 1234.5678m;
+        decimal rate = 0.125m;
+        Console.WriteLine($"{a:F2} {a:N1} {rate:P1} {a:0.0}");
+    }
+}
+"#;
+
+        let expected_code = r#"import java.math.BigDecimal;
+class Item {
+    public BigDecimal Price;
+}
+class Program {
+    static Item item = new Item();
+    static int calls = 0;
+    static Item get() {
+        calls++;
+        return item;
+    }
+    static BigDecimal twice(BigDecimal d) {
+        return d.multiply(BigDecimal.valueOf(2));
+    }
+    static void main(String[] args) {
+        item.Price = new BigDecimal("1.5");
+        {
+            var item2 = get();
+            item2.Price = item2.Price.add(BigDecimal.ONE);
+        }
+        {
+            var item3 = get();
+            item3.Price = item3.Price.add(item.Price);
+        }
+        {
+            var item4 = get();
+            item4.Price = item4.Price.subtract(BigDecimal.ONE);
+        }
+        System.out.println(calls);
+        System.out.println(item.Price);
+        System.out.println(twice(BigDecimal.valueOf(5)));
+        BigDecimal a = new BigDecimal("1234.5678");
+        BigDecimal rate = new BigDecimal("0.125");
+        System.out.println(String.format("%.2f %,.1f %.1f%% %.1f", a, a, rate.multiply(BigDecimal.valueOf(100)), a));
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_decimal_unsupported_operators() {
+        let input_code = r#"class Item {
+    public decimal Price;
+}
+
+class Program {
+    static Item Get() {
+        return new Item();
+    }
+
+    static void Main() {
+        decimal a = 1.5m;
+        decimal old = Get().Price++;
+        decimal flipped = ~a;
+        decimal shifted = a << 1;
+    }
+}"#;
+
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![
+                Diagnostic::error("the operator `~` cannot be applied to `decimal` in Main"),
+                Diagnostic::error(
+                    "the operator `<<` cannot be applied to `decimal` and `int` in Main"
+                ),
+                Diagnostic::error(
+                    "the decimal ++ inside an expression in Main can only change a variable, a field or an array element, put it in a statement of its own"
+                ),
+            ],
+            program.diagnostics
+        );
+    }
+
+    #[test]
+    fn test_code_checked_conversions() {
+        let input_code = r#"using System;
+
+class Box
+{
+    public int V;
+    public uint U;
+}
+
+class Program
+{
+    static Box box = new Box();
+    static int calls = 0;
+
+    static Box Get()
+    {
+        calls++;
+        return box;
+    }
+
+    static void Main()
+    {
+        int x = 5;
+        double d = 3.9;
+        uint u = 7;
+        byte small = 250;
+        checked
+        {
+            int y = x++;
+            int z = ++x;
+            int a = (int)d;
+            int b = (int)u;
+            short sh = 5;
+            sh += 1;
+            u--;
+            uint v = u + u;
+            Get().V += 1;
+            Get().V++;
+            Get().U = 10;
+            Get().U /= 3;
+            Console.WriteLine(x + y + z + a + b + sh);
+            Console.WriteLine(v);
+            Console.WriteLine(calls);
+            Console.WriteLine(box.V);
+            Console.WriteLine(box.U);
+        }
+        try
+        {
+            checked
+            {
+                small += 10;
+            }
+            Console.WriteLine("no overflow");
+        }
+        catch (OverflowException)
+        {
+            Console.WriteLine("byte overflow");
+        }
+        try
+        {
+            d = 1e20;
+            int big = checked((int)d);
+            Console.WriteLine(big);
+        }
+        catch (OverflowException e)
+        {
+            Console.WriteLine("double overflow");
+        }
+        try
+        {
+            uint zero = 0;
+            checked
+            {
+                zero--;
+            }
+            Console.WriteLine(zero);
+        }
+        catch (OverflowException)
+        {
+            Console.WriteLine("uint overflow");
+        }
+        try
+        {
+            uint huge = 3000000000;
+            int i = checked((int)huge);
+            Console.WriteLine(i);
+        }
+        catch (OverflowException)
+        {
+            Console.WriteLine("uint cast overflow");
+        }
+    }
+}
+"#;
+
+        let expected_code = r#"import java.math.BigDecimal;
+import java.math.RoundingMode;
+class Box {
+    public int V;
+    public int U;
+}
+class Program {
+    static Box box = new Box();
+    static int calls = 0;
+    static Box get() {
+        calls++;
+        return box;
+    }
+    static void main(String[] args) {
+        int x = 5;
+        double d = 3.9;
+        int u = 7;
+        byte small = (byte) 250;
+        {
+            int y = ((x = Math.incrementExact(x)) - 1);
+            int z = (x = Math.incrementExact(x));
+            int a = Math.toIntExact(DotNetMath.toInt64(DotNetMath.truncate(d)));
+            int b = Math.toIntExact(Integer.toUnsignedLong(u));
+            short sh = 5;
+            sh = DotNetMath.toInt16(Math.addExact(sh, 1));
+            u = DotNetMath.toUInt32(Integer.toUnsignedLong(u) - 1);
+            int v = DotNetMath.toUInt32(Math.addExact(Integer.toUnsignedLong(u), Integer.toUnsignedLong(u)));
+            {
+                var box3 = get();
+                box3.V = Math.addExact(box3.V, 1);
+            }
+            {
+                var box4 = get();
+                box4.V = Math.incrementExact(box4.V);
+            }
+            get().U = 10;
+            {
+                var box2 = get();
+                box2.U = Integer.divideUnsigned(box2.U, 3);
+            }
+            System.out.println(Math.addExact(Math.addExact(Math.addExact(Math.addExact(Math.addExact(x, y), z), a), b), sh));
+            System.out.println(Integer.toUnsignedString(v));
+            System.out.println(calls);
+            System.out.println(box.V);
+            System.out.println(Integer.toUnsignedString(box.U));
+        }
+        try {
+            {
+                small = DotNetMath.toByte(Math.addExact((small & 255), 10));
+            }
+            System.out.println("no overflow");
+        }
+        catch (ArithmeticException e2) {
+            System.out.println("byte overflow");
+        }
+        try {
+            d = 1e20;
+            int big = Math.toIntExact(DotNetMath.toInt64(DotNetMath.truncate(d)));
+            System.out.println(big);
+        }
+        catch (ArithmeticException e) {
+            System.out.println("double overflow");
+        }
+        try {
+            int zero = 0;
+            {
+                zero = DotNetMath.toUInt32(Integer.toUnsignedLong(zero) - 1);
+            }
+            System.out.println(Integer.toUnsignedString(zero));
+        }
+        catch (ArithmeticException e3) {
+            System.out.println("uint overflow");
+        }
+        try {
+            int huge = 0xB2D05E00;
+            int i = Math.toIntExact(Integer.toUnsignedLong(huge));
+            System.out.println(i);
+        }
+        catch (ArithmeticException e4) {
+            System.out.println("uint cast overflow");
+        }
+    }
+}
+class DotNetMath {
+    static double round(double value, RoundingMode mode) {
+        if (Double.isNaN(value) || Double.isInfinite(value)) {
+            return value;
+        }
+        return Math.copySign(new BigDecimal(value).setScale(0, mode).doubleValue(), value);
+    }
+    static double round(double value, int digits, RoundingMode mode) {
+        if (Math.abs(value) >= 1e16) {
+            return value;
+        }
+        double scale = Math.pow(10, digits);
+        return round(value * scale, mode) / scale;
+    }
+    static double round(double value, int digits) {
+        return round(value, digits, RoundingMode.HALF_EVEN);
+    }
+    static double truncate(double value) {
+        return value < 0 ? Math.ceil(value) : Math.floor(value);
+    }
+    static long toInt64(double value) {
+        double rounded = Math.rint(value);
+        if (!(rounded >= -0x1p63 && rounded < 0x1p63)) {
+            throw new ArithmeticException("Value was either too large or too small for an Int64.");
+        }
+        return (long) rounded;
+    }
+    static short toInt16(long value) {
+        if (value < Short.MIN_VALUE || value > Short.MAX_VALUE) {
+            throw new ArithmeticException("Value was either too large or too small for an Int16.");
+        }
+        return (short) value;
+    }
+    static short toUInt16(long value) {
+        if (value < 0 || value > 65535) {
+            throw new ArithmeticException("Value was either too large or too small for a UInt16.");
+        }
+        return (short) value;
+    }
+    static byte toByte(long value) {
+        if (value < 0 || value > 255) {
+            throw new ArithmeticException("Value was either too large or too small for an unsigned byte.");
+        }
+        return (byte) value;
+    }
+    static byte toSByte(long value) {
+        if (value < Byte.MIN_VALUE || value > Byte.MAX_VALUE) {
+            throw new ArithmeticException("Value was either too large or too small for a signed byte.");
+        }
+        return (byte) value;
+    }
+    static int toUInt32(long value) {
+        if (value < 0 || value > 0xFFFFFFFFL) {
+            throw new ArithmeticException("Value was either too large or too small for a UInt32.");
+        }
+        return (int) value;
+    }
+    static long toUInt64(long value) {
+        if (value < 0) {
+            throw new ArithmeticException("Value was either too large or too small for a UInt64.");
+        }
+        return value;
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_checked_unsupported_operations() {
+        let input_code = r#"class Program
+{
+    static int[] Values()
+    {
+        return new int[0];
+    }
+
+    static void Main()
+    {
+        ulong a = 5;
+        char c = 'a';
+        byte b = 1;
+        double d = 2.5;
+        checked
+        {
+            ulong sum = a + a;
+            c++;
+            int n = b++;
+            long l = (long)a;
+            b += d;
+        }
+    }
+}
+"#;
+
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![
+                Diagnostic::warning(
+                    "the `+` of two ulongs in Main is not checked for overflow, java wraps around"
+                ),
+                Diagnostic::warning(
+                    "the `++` of a `char` in Main is not checked for overflow, java wraps around"
+                ),
+                Diagnostic::warning(
+                    "the `++` of a `byte` inside an expression in Main is not checked for overflow, java wraps around"
+                ),
+                Diagnostic::warning(
+                    "the cast from `ulong` to `long` in Main is not checked for overflow, java wraps around"
+                ),
+                Diagnostic::warning(
+                    "the `+=` on a `byte` in Main is not checked for overflow, java wraps around"
+                ),
+            ],
+            program.diagnostics
+        );
+    }
+
+    #[test]
+    fn test_code_collection_initializers() {
+        let input_code = r#"using System;
+using System.Collections.Generic;
+
+class Program
+{
+    static int calls = 0;
+
+    static string Key()
+    {
+        calls++;
+        return "a";
+    }
+
+    static void Main()
+    {
+        List<int> xs = new List<int> { 1, 2, 3 };
+        List<long> big = new List<long>(10) { 1, 2 };
+        var set = new HashSet<string> { "x", "y" };
+        Dictionary<string, int> ages = new Dictionary<string, int> { ["a"] = 1, ["b"] = 2 };
+        var more = new Dictionary<string, long> { { "c", 3 }, { "d", 4 } };
+        Console.WriteLine(xs.Contains(2));
+        Console.WriteLine("has " + ages.ContainsKey("a"));
+        Console.WriteLine(set.Contains("z"));
+        Console.WriteLine(big[1] + more["d"]);
+        ages[Key()] += 5;
+        ages[Key()]++;
+        Console.WriteLine(ages["a"] + " " + calls);
+        try
+        {
+            ages.Add("a", 3);
+        }
+        catch (Exception e)
+        {
+            Console.WriteLine("duplicate");
+        }
+        try
+        {
+            Console.WriteLine(ages["z"]);
+        }
+        catch (KeyNotFoundException e)
+        {
+            Console.WriteLine("missing");
+        }
+    }
+}
+"#;
+
+        let expected_code = r#"import java.util.AbstractMap.SimpleImmutableEntry;
+import java.util.ArrayList;
+import java.util.Arrays;
+import java.util.LinkedHashMap;
+import java.util.LinkedHashSet;
+import java.util.List;
+import java.util.Map;
+import java.util.Map.Entry;
+import java.util.NoSuchElementException;
+import java.util.stream.Collectors;
+import java.util.stream.Stream;
+class Program {
+    static int calls = 0;
+    static String key() {
+        calls++;
+        return "a";
+    }
+    static void main(String[] args) {
+        List<Integer> xs = new ArrayList<Integer>(Arrays.asList(1, 2, 3));
+        List<Long> big = new ArrayList<Long>(Arrays.asList((long) 1, (long) 2));
+        var set = new LinkedHashSet<String>(Arrays.asList("x", "y"));
+        Map<String, Integer> ages = Stream.of(new SimpleImmutableEntry<>("a", 1), new SimpleImmutableEntry<>("b", 2)).collect(Collectors.toMap(Entry::getKey, Entry::getValue, (a, b) -> b, LinkedHashMap::new));
+        var more = Stream.of(new SimpleImmutableEntry<>("c", (long) 3), new SimpleImmutableEntry<>("d", (long) 4)).collect(Collectors.toMap(Entry::getKey, Entry::getValue, (a, b) -> {
+            throw new IllegalStateException("Duplicate key");
+        }, LinkedHashMap::new));
+        System.out.println(xs.contains(2) ? "True" : "False");
+        System.out.println("has " + (ages.containsKey("a") ? "True" : "False"));
+        System.out.println(set.contains("z") ? "True" : "False");
+        System.out.println(big.get(1) + more.computeIfAbsent("d", key -> {
+            throw new NoSuchElementException("The given key '" + key + "' was not present in the dictionary.");
+        }));
+        {
+            var index = key();
+            ages.put(index, ages.computeIfAbsent(index, key -> {
+                throw new NoSuchElementException("The given key '" + key + "' was not present in the dictionary.");
+            }) + 5);
+        }
+        {
+            var index2 = key();
+            ages.put(index2, ages.computeIfAbsent(index2, key -> {
+                throw new NoSuchElementException("The given key '" + key + "' was not present in the dictionary.");
+            }) + 1);
+        }
+        System.out.println(ages.computeIfAbsent("a", key -> {
+            throw new NoSuchElementException("The given key '" + key + "' was not present in the dictionary.");
+        }) + " " + calls);
+        try {
+            ages.merge("a", 3, (a, b) -> {
+                throw new IllegalStateException("Duplicate key");
+            });
+        }
+        catch (Exception e) {
+            System.out.println("duplicate");
+        }
+        try {
+            System.out.println(ages.computeIfAbsent("z", key -> {
+                throw new NoSuchElementException("The given key '" + key + "' was not present in the dictionary.");
+            }));
+        }
+        catch (NoSuchElementException e) {
+            System.out.println("missing");
+        }
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_unsupported_collection_initializers() {
+        let input_code = r#"using System.Collections.Generic;
+
+class Program
+{
+    static void Main()
+    {
+        List<int> xs = new List<int> { 1 };
+        List<int> copy = new List<int>(xs) { 2 };
+        Queue<int> queue = new Queue<int> { 3 };
+    }
+}
+"#;
+
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![
+                Diagnostic::error(
+                    "the collection initializer in Main cannot add to a copied collection, add its elements after creating it"
+                ),
+                Diagnostic::error(
+                    "the collection initializer of `Queue<int>` in Main is not supported"
+                ),
+            ],
+            program.diagnostics
+        );
+    }
+
+    #[test]
+    fn test_code_nullable_coalesce() {
+        let input_code = r#"using System;
+
+class Program
+{
+    static int calls = 0;
+
+    static int? Next()
+    {
+        calls++;
+        return calls > 1 ? 7 : null;
+    }
+
+    static int Fallback()
+    {
+        return 42;
+    }
+
+    static void Main()
+    {
+        int? a = null;
+        int? b = 5;
+        string name = null;
+        int x = a ?? 3;
+        int y = b ?? 3;
+        string shown = name ?? "none";
+        int first = Next() ?? Fallback();
+        int second = Next() ?? 0;
+        long wide = b ?? 10L;
+        Console.WriteLine(x + " " + y + " " + shown + " " + first + " " + second + " " + wide + " " + calls);
+        a++;
+        b++;
+        --a;
+        a += 2;
+        b += 2;
+        int? c = a++;
+        Console.WriteLine((a == null) + " " + b + " " + (c == null));
+        bool? flag = null;
+        bool? yes = true;
+        bool? no = false;
+        Console.WriteLine((flag & true) == null);
+        Console.WriteLine(flag & false);
+        Console.WriteLine(flag | true);
+        Console.WriteLine((flag | false) == null);
+        Console.WriteLine((flag ^ true) == null);
+        Console.WriteLine(yes & yes);
+        Console.WriteLine(no | yes);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.util.Objects;
+import java.util.Optional;
+class Program {
+    static int calls = 0;
+    static Integer next() {
+        calls++;
+        return calls > 1 ? 7 : null;
+    }
+    static int fallback() {
+        return 42;
+    }
+    static void main(String[] args) {
+        Integer a = null;
+        Integer b = 5;
+        String name = null;
+        int x = (a != null ? a : 3);
+        int y = (b != null ? b : 3);
+        String shown = (name != null ? name : "none");
+        int first = Optional.ofNullable(next()).orElseGet(() -> fallback());
+        int second = Optional.ofNullable(next()).orElse(0);
+        long wide = (b != null ? b : 10L);
+        System.out.println(x + " " + y + " " + shown + " " + first + " " + second + " " + wide + " " + calls);
+        if (a != null) {
+            a++;
+        }
+        if (b != null) {
+            b++;
+        }
+        if (a != null) {
+            --a;
+        }
+        a = (a == null ? null : a + 2);
+        b = (b == null ? null : b + 2);
+        Integer c = (a == null ? null : a++);
+        System.out.println(((a == null) ? "True" : "False") + " " + Objects.toString(b, "") + " " + ((c == null) ? "True" : "False"));
+        Boolean flag = null;
+        Boolean yes = true;
+        Boolean no = false;
+        System.out.println((((Objects.equals(flag, false) ? Boolean.FALSE : flag == null ? null : Boolean.TRUE)) == null) ? "True" : "False");
+        System.out.println(Boolean.FALSE);
+        System.out.println(Boolean.TRUE);
+        System.out.println((((Objects.equals(flag, true) ? Boolean.TRUE : flag == null ? null : Boolean.FALSE)) == null) ? "True" : "False");
+        System.out.println((((flag == null ? null : flag ^ true)) == null) ? "True" : "False");
+        System.out.println((Objects.equals(yes, false) || Objects.equals(yes, false) ? Boolean.FALSE : yes == null || yes == null ? null : Boolean.TRUE));
+        System.out.println((Objects.equals(no, true) || Objects.equals(yes, true) ? Boolean.TRUE : no == null || yes == null ? null : Boolean.FALSE));
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_nullable_unsupported_operands() {
+        let input_code = r#"class Program
+{
+    static bool? Maybe()
+    {
+        return null;
+    }
+
+    static void Main()
+    {
+        int count = 3;
+        int total = count ?? 0;
+        bool? both = Maybe() & true;
+    }
+}
+"#;
+
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![
+                Diagnostic::error("the operator `??` cannot be applied to `int` and `int` in Main"),
+                Diagnostic::warning(
+                    "a nullable value computed by a call in Main is not checked for null, java throws a NullPointerException on null"
+                ),
+            ],
+            program.diagnostics
+        );
+    }
+
+    #[test]
+    fn test_code_ref_fields_and_discards() {
+        let input_code = r#"using System;
+
+class Counter
+{
+    public int Hits;
+}
+
+class Program
+{
+    static int calls = 0;
+    static string[] words = "a,b".Split(",");
+
+    static bool Split(string text, out int head, out string rest)
+    {
+        head = text.Length;
+        rest = text.Substring(1);
+        return true;
+    }
+
+    static int Bump(ref int value)
+    {
+        value = value + 1;
+        return value;
+    }
+
+    static void Append(ref string text)
+    {
+        text = text + "!";
+    }
+
+    static void Main()
+    {
+        Split("abc", out _, out var tail);
+        Split("abcd", out int size, out _);
+        Split("ab", out var _, out string _);
+        Bump(ref calls);
+        int twice = Bump(ref calls) * 2;
+        Counter counter = new Counter();
+        Bump(ref counter.Hits);
+        Append(ref words[1]);
+        Console.WriteLine(tail + " " + size + " " + calls + " " + twice + " " + counter.Hits + " " + words[1]);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.util.regex.Pattern;
+class Counter {
+    public int Hits;
+}
+class Program {
+    static int calls = 0;
+    static String[] words = "a,b".split(Pattern.quote(","), -1);
+    static boolean split(String text, IntRef head, Ref<String> rest) {
+        head.value = text.length();
+        rest.value = text.substring(1);
+        return true;
+    }
+    static int bump(IntRef value) {
+        value.value = value.value + 1;
+        return value.value;
+    }
+    static void append(Ref<String> text) {
+        text.value = text.value + "!";
+    }
+    static void main(String[] args) {
+        Ref<String> tail = new Ref<String>(null);
+        split("abc", new IntRef(0), tail);
+        IntRef size = new IntRef(0);
+        split("abcd", size, new Ref<String>(null));
+        split("ab", new IntRef(0), new Ref<String>(null));
+        IntRef callsRef = new IntRef(calls);
+        bump(callsRef);
+        calls = callsRef.value;
+        IntRef callsRef2 = new IntRef(calls);
+        int twice = bump(callsRef2) * 2;
+        calls = callsRef2.value;
+        Counter counter = new Counter();
+        IntRef hitsRef = new IntRef(counter.Hits);
+        bump(hitsRef);
+        counter.Hits = hitsRef.value;
+        Ref<String> elementRef = new Ref<String>(words[1]);
+        append(elementRef);
+        words[1] = elementRef.value;
+        System.out.println(tail.value + " " + size.value + " " + calls + " " + twice + " " + counter.Hits + " " + words[1]);
+    }
+}
+class IntRef {
+    int value;
+    IntRef(int value) {
+        this.value = value;
+    }
+}
+class Ref<T> {
+    T value;
+    Ref(T value) {
+        this.value = value;
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_unsupported_ref_arguments() {
+        let input_code = r#"class Program
+{
+    static int calls = 0;
+
+    static int Bump(ref int value)
+    {
+        value = value + 1;
+        return value;
+    }
+
+    static int Next()
+    {
+        return 0;
+    }
+
+    static void Main()
+    {
+        int[] values = new int[0];
+        if (Bump(ref calls) > 1)
+        {
+            calls = 0;
+        }
+        Bump(ref values[Next()]);
+    }
+}
+"#;
+
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![
+                Diagnostic::error(
+                    "a field or an array element passed by ref in Main is set back after the statement, put the call in a statement of its own"
+                ),
+                Diagnostic::error(
+                    "the ref argument in Main can only be a variable, a field or an array element read without calls, outside of loop conditions and lambda expressions"
+                ),
+            ],
+            program.diagnostics
+        );
+    }
+
+    #[test]
+    fn test_unsupported_string_comparisons() {
+        let input_code = r#"using System;
+
+class Program
+{
+    static void Main()
+    {
+        string a = "apple";
+        StringComparison comparison = StringComparison.Ordinal;
+        int part = string.CompareOrdinal(a, "b");
+        int prefix = string.Compare(a, 0, "b", 0, 1);
+        bool same = a.Equals("b", comparison);
+    }
+}
+"#;
+
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![
+                Diagnostic::warning(
+                    "string.Compare in Main becomes compareTo, which throws a NullPointerException for a null string"
+                ),
+                Diagnostic::error(
+                    "`string.Compare` with 5 arguments is not supported in Main, the call is left as it is"
+                ),
+                Diagnostic::error(
+                    "the StringComparison of a string comparison in Main has to be written out, such as StringComparison.OrdinalIgnoreCase"
+                ),
+            ],
+            program.diagnostics
+        );
+    }
+
+    #[test]
+    fn test_code_nullable_string_members() {
+        let input_code = r#"using System;
+
+class Program
+{
+    static string? Find(string key)
+    {
+        return key == "" ? null : key;
+    }
+
+    static void Main()
+    {
+        string? s = "Hello";
+        Console.WriteLine(s.Length);
+        Console.WriteLine(s.ToUpper());
+        Console.WriteLine(s[1]);
+        Console.WriteLine(s.Substring(1, 2));
+        Console.WriteLine(s == "Hello");
+        Console.WriteLine(Find("abc").Length);
+        string? missing = null;
+        Console.WriteLine(missing + "!" + s.Trim());
+        Console.WriteLine(s.Split(',').Length);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.util.Objects;
+import java.util.regex.Pattern;
+class Program {
+    static String find(String key) {
+        return Objects.equals(key, "") ? null : key;
+    }
+    static void main(String[] args) {
+        String s = "Hello";
+        System.out.println(s.length());
+        System.out.println(s.toUpperCase());
+        System.out.println(s.charAt(1));
+        System.out.println(s.substring(1, 3));
+        System.out.println(Objects.equals(s, "Hello") ? "True" : "False");
+        System.out.println(find("abc").length());
+        String missing = null;
+        System.out.println(Objects.toString(missing, "") + "!" + s.strip());
+        System.out.println(s.split(Pattern.quote(","), -1).length);
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_code_linq_aggregates() {
+        let input_code = r#"using System;
+using System.Collections.Generic;
+using System.Linq;
+
+class Item
+{
+    public string Name;
+    public int Count;
+    public double Price;
+}
+
+class Program
+{
+    static void Main()
+    {
+        List<int> values = new List<int> { 4, 9, 2 };
+        List<string> names = new List<string> { "b", "c", "a" };
+        List<decimal> prices = new List<decimal> { 1.5m, 2.25m };
+        Console.WriteLine(values.Sum());
+        Console.WriteLine(values.Average());
+        Console.WriteLine(values.Max());
+        Console.WriteLine(values.Min(v => v * 2));
+        Console.WriteLine(values.Average(v => v * 1.5));
+        Console.WriteLine(names.Max());
+        Console.WriteLine(prices.Max());
+        List<int> big = new List<int> { int.MaxValue, 1 };
+        try
+        {
+            Console.WriteLine(big.Sum());
+        }
+        catch (OverflowException e)
+        {
+            Console.WriteLine("overflow");
+        }
+        List<int> none = new List<int>();
+        Console.WriteLine(none.Select(v => v.ToString()).Max() == null);
+        try
+        {
+            Console.WriteLine(none.Max());
+        }
+        catch (Exception e)
+        {
+            Console.WriteLine("empty");
+        }
+    }
+}
+"#;
+
+        let expected_code = r#"import java.math.BigDecimal;
+import java.util.ArrayList;
+import java.util.Arrays;
+import java.util.Comparator;
+import java.util.List;
+class Item {
+    public String Name;
+    public int Count;
+    public double Price;
+}
+class Program {
+    static void main(String[] args) {
+        List<Integer> values = new ArrayList<Integer>(Arrays.asList(4, 9, 2));
+        List<String> names = new ArrayList<String>(Arrays.asList("b", "c", "a"));
+        List<BigDecimal> prices = new ArrayList<BigDecimal>(Arrays.asList(new BigDecimal("1.5"), new BigDecimal("2.25")));
+        System.out.println(values.stream().mapToInt(Integer::intValue).reduce(0, Math::addExact));
+        System.out.println(values.stream().mapToInt(Integer::intValue).average().orElseThrow());
+        System.out.println(values.stream().mapToInt(Integer::intValue).max().orElseThrow());
+        System.out.println(values.stream().mapToInt(v -> v * 2).min().orElseThrow());
+        System.out.println(values.stream().mapToDouble(v -> v * 1.5).average().orElseThrow());
+        System.out.println(names.stream().max(Comparator.naturalOrder()).orElse(null));
+        System.out.println(prices.stream().max(Comparator.naturalOrder()).orElseThrow());
+        List<Integer> big = new ArrayList<Integer>(Arrays.asList(Integer.MAX_VALUE, 1));
+        try {
+            System.out.println(big.stream().mapToInt(Integer::intValue).reduce(0, Math::addExact));
+        }
+        catch (ArithmeticException e) {
+            System.out.println("overflow");
+        }
+        List<Integer> none = new ArrayList<Integer>();
+        System.out.println((none.stream().map(v -> v.toString()).max(Comparator.naturalOrder()).orElse(null) == null) ? "True" : "False");
+        try {
+            System.out.println(none.stream().mapToInt(Integer::intValue).max().orElseThrow());
+        }
+        catch (Exception e) {
+            System.out.println("empty");
+        }
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_unsupported_average() {
+        let input_code = r#"using System;
+using System.Collections.Generic;
+using System.Linq;
+
+class Program
+{
+    static void Main()
+    {
+        List<decimal> prices = new List<decimal> { 1.5m, 2.25m };
+        Console.WriteLine(prices.Average());
+    }
+}
+"#;
+
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![Diagnostic::error(
+                "the Average of decimals in Main is not supported"
+            )],
+            program.diagnostics
+        );
+    }
+
+    #[test]
+    fn test_code_anonymous_objects() {
+        let input_code = r#"using System;
+using System.Collections.Generic;
+using System.Linq;
+
+class Person
+{
+    public string Name;
+    public int Age;
+}
+
+class Program
+{
+    static void Main()
+    {
+        List<Person> people = new List<Person>();
+        Person ann = new Person();
+        ann.Name = "Ann";
+        ann.Age = 30;
+        people.Add(ann);
+        people.Add(new Person());
+        var adults = from p in people
+                     where p.Age >= 18
+                     select new { p.Name, Decade = p.Age / 10 };
+        var first = adults.First();
+        Console.WriteLine(first.Name + " " + first.Decade);
+        var point = new { X = 1, Y = 2 };
+        Console.WriteLine(point.X + point.Y);
+    }
+}
+"#;
+
+        let expected_code = r#"import java.util.ArrayList;
+import java.util.List;
+import java.util.stream.Collectors;
+class Person {
+    public String Name;
+    public int Age;
+}
+class Program {
+    static void main(String[] args) {
+        List<Person> people = new ArrayList<Person>();
+        Person ann = new Person();
+        ann.Name = "Ann";
+        ann.Age = 30;
+        people.add(ann);
+        people.add(new Person());
+        var adults = people.stream().filter(p -> p.Age >= 18).map(p -> new Object() { final String Name = p.Name; final int Decade = p.Age / 10; }).collect(Collectors.toList());
+        var first = adults.stream().findFirst().orElseThrow();
+        System.out.println(first.Name + " " + first.Decade);
+        var point = new Object() { final int X = 1; final int Y = 2; };
+        System.out.println(point.X + point.Y);
+    }
+}
+"#;
+
+        assert_eq!(expected_code, generate_code(input_code));
+    }
+
+    #[test]
+    fn test_anonymous_object_diagnostics() {
+        let input_code = r#"class Program
+{
+    static void Main()
+    {
+        var point = new { X = 1, 2 + 3 };
+    }
+}
+"#;
+
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![
+                Diagnostic::error("the members of an anonymous object need a name in Main"),
+                Diagnostic::warning(
+                    "the anonymous object in Main becomes an anonymous java class, it is compared by reference and its members can only be read through `var`"
+                ),
+            ],
+            program.diagnostics
+        );
+    }
+
+    #[test]
+    fn test_unsupported_string_formats() {
+        let input_code = r#"class Program {
+    static void Main() {
+        decimal price = 9.5m;
+        int count = 3;
+        Console.WriteLine($"{price:C} for {count:G5}");
+        string text = string.Format("{0} {1}", "a");
+        Console.WriteLine("{0} and {2}", count, price);
+    }
+}
+"#;
+
+        let program = build(input_code, &Options::default());
+        assert_eq!(
+            vec![
+                Diagnostic::warning(
+                    "the format `C` in Main has no java counterpart, the value is printed as is"
+                ),
+                Diagnostic::warning(
+                    "the format `G5` in Main has no java counterpart, the value is printed as is"
+                ),
+                Diagnostic::warning(
+                    "the format \"{0} {1}\" of string.Format in Main does not match its 1 argument, C# throws a FormatException"
+                ),
+                Diagnostic::warning(
+                    "the format \"{0} and {2}\" of Console.WriteLine in Main does not match its 2 arguments, C# throws a FormatException"
+                ),
+            ],
+            program.diagnostics
+        );
+    }
+
     /// This function works just like the main function
     fn generate_code(input_code: &str) -> String {
+        generate_code_with_options(input_code, &Options::default())
+    }
+
+    /// Same as generate_code, with the compiler switches
+    fn generate_code_with_options(input_code: &str, options: &Options) -> String {
+        let program = build(input_code, options);
+        JavaGenerator::generate_with_options(&program, options)
+    }
+
+    /// This function parses the code and builds the program, without generating it
+    fn build(input_code: &str, options: &Options) -> Program {
         let mut parser = Parser::new();
         let language = tree_sitter_c_sharp::LANGUAGE;
         parser
@@ -211,10 +4199,6 @@ class Program {
 
         let tree = parser.parse(input_code, None).unwrap();
 
-        let program = build_program(tree.root_node(), input_code);
-
-        let java_code = JavaGenerator::generate(&program);
-
-        java_code
+        build_program_with_options(tree.root_node(), input_code, options)
     }
 }